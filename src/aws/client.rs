@@ -45,14 +45,52 @@ fn fnv1a_hash(data: &str) -> u64 {
     hash
 }
 
-/// Generate a short hash based on machine hostname.
+/// File in the config directory holding this machine's random ID
+const MACHINE_ID_FILE: &str = "machine_id";
+
+/// Generate a short hash based on machine hostname, or on the user and a
+/// persisted machine ID when the hostname can't be read.
 /// Used to create unique AWS resource names per machine.
 pub fn machine_hash() -> String {
-    let hostname = hostname::get()
-        .map(|h| h.to_string_lossy().to_string())
-        .unwrap_or_else(|_| "unknown".to_string());
+    let identity = match hostname::get() {
+        Ok(hostname) => hostname.to_string_lossy().to_string(),
+        Err(_) => {
+            let user = std::env::var("USER")
+                .or_else(|_| std::env::var("USERNAME"))
+                .ok();
+            let machine_id = Settings::config_path()
+                .and_then(|path| load_or_create_machine_id(&path.with_file_name(MACHINE_ID_FILE)));
+            fallback_identity(user.as_deref(), machine_id.as_deref())
+        }
+    };
 
-    format!("{:08x}", fnv1a_hash(&hostname) & 0xFFFFFFFF)
+    format!("{:08x}", fnv1a_hash(&identity) & 0xFFFFFFFF)
+}
+
+/// What identifies the machine when its hostname can't be read, so such machines
+/// don't all share the hash of "unknown"
+fn fallback_identity(user: Option<&str>, machine_id: Option<&str>) -> String {
+    format!(
+        "unknown:{}:{}",
+        user.unwrap_or_default(),
+        machine_id.unwrap_or_default()
+    )
+}
+
+/// Read the machine ID at `path`, generating and saving one on first use. None if
+/// it can't be saved, since an ID that changes every run would rename resources.
+fn load_or_create_machine_id(path: &std::path::Path) -> Option<String> {
+    if let Ok(id) = std::fs::read_to_string(path) {
+        let id = id.trim();
+        if !id.is_empty() {
+            return Some(id.to_string());
+        }
+    }
+
+    let id = uuid::Uuid::new_v4().to_string();
+    std::fs::create_dir_all(path.parent()?).ok()?;
+    std::fs::write(path, &id).ok()?;
+    Some(id)
 }
 
 /// AWS client wrapper holding all service clients
@@ -63,26 +101,80 @@ pub struct AwsClients {
     pub iam: IamClient,
     pub region: String,
     pub account_id: String,
+    /// ARN of the caller identity the credentials resolve to
+    pub caller_arn: String,
+    /// Prefix for managed resource names and the `deployment` tag
+    pub resource_prefix: String,
 }
 
-impl AwsClients {
-    /// Create new AWS clients, using region from settings if configured
-    pub async fn new() -> Result<Self> {
-        // Check if settings has a region override
-        if let Ok(settings) = Settings::load() {
-            if let Some(ref region) = settings.region {
-                return Self::with_region(region).await;
-            }
+/// What to build AWS clients from: an explicit region and AWS profile, each
+/// falling back to the default provider chain when unset
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct ClientOptions {
+    region: Option<String>,
+    aws_profile: Option<String>,
+    resource_prefix: Option<String>,
+}
+
+impl ClientOptions {
+    /// Options from settings, with `region` (when given) taking precedence over
+    /// the configured region
+    fn from_settings(settings: &Settings, region: Option<&str>) -> Self {
+        Self {
+            region: region.map(String::from).or_else(|| settings.region.clone()),
+            aws_profile: settings.aws_profile.clone(),
+            resource_prefix: settings.resource_prefix.clone(),
         }
+    }
 
-        // Fall back to default configuration
-        Self::new_without_settings().await
+    fn config_loader(&self) -> aws_config::ConfigLoader {
+        let mut loader = aws_config::defaults(BehaviorVersion::latest());
+        // A named profile supplies both credentials and, unless overridden, the region
+        if let Some(ref profile) = self.aws_profile {
+            loader = loader.profile_name(profile);
+        }
+        if let Some(ref region) = self.region {
+            loader = loader.region(aws_config::Region::new(region.clone()));
+        }
+        loader
     }
+}
 
-    /// Create new AWS clients from default configuration (ignoring settings)
-    /// Used during config init to get the AWS default region
+impl AwsClients {
+    /// Create new AWS clients, using the region and AWS profile from settings if configured
+    pub async fn new() -> Result<Self> {
+        let settings = Settings::load().unwrap_or_default();
+        Self::connect(ClientOptions::from_settings(&settings, None)).await
+    }
+
+    /// Create new AWS clients from the configured AWS profile only, ignoring the
+    /// other settings. Used during config init to get the AWS default region
     pub async fn new_without_settings() -> Result<Self> {
-        let config = aws_config::defaults(BehaviorVersion::latest()).load().await;
+        let settings = Settings::load().unwrap_or_default();
+        Self::connect(ClientOptions {
+            aws_profile: settings.aws_profile,
+            ..Default::default()
+        })
+        .await
+    }
+
+    /// Create new AWS clients with a specific region
+    pub async fn with_region(region: &str) -> Result<Self> {
+        let settings = Settings::load().unwrap_or_default();
+        Self::connect(ClientOptions::from_settings(&settings, Some(region))).await
+    }
+
+    async fn connect(options: ClientOptions) -> Result<Self> {
+        // Checked before connecting so a bad prefix never names a resource
+        let resource_prefix = match options.resource_prefix {
+            Some(ref prefix) => {
+                Settings::validate_resource_prefix(prefix)?;
+                prefix.clone()
+            }
+            None => DEFAULT_RESOURCE_PREFIX.to_string(),
+        };
+
+        let config = options.config_loader().load().await;
 
         let region = config.region().map(|r| r.to_string()).ok_or_else(|| {
             Ec2CliError::AwsCredentials(
@@ -97,16 +189,19 @@ impl AwsClients {
         let sts = StsClient::new(&config);
 
         // Verify credentials by getting caller identity
-        let identity = sts
-            .get_caller_identity()
-            .send()
-            .await
-            .map_err(|e| Ec2CliError::AwsCredentials(format_credentials_error(&e)))?;
+        let identity = sts.get_caller_identity().send().await.map_err(|e| {
+            let message = format_credentials_error(&e);
+            Ec2CliError::AwsCredentials(match options.aws_profile {
+                Some(ref profile) => format!("{} (using AWS profile '{}')", message, profile),
+                None => message,
+            })
+        })?;
 
         let account_id = identity
             .account()
             .ok_or_else(|| Ec2CliError::AwsCredentials("No account ID in response".to_string()))?
             .to_string();
+        let caller_arn = identity.arn().unwrap_or_default().to_string();
 
         Ok(Self {
             ec2,
@@ -114,39 +209,8 @@ impl AwsClients {
             iam,
             region,
             account_id,
-        })
-    }
-
-    /// Create new AWS clients with a specific region
-    pub async fn with_region(region: &str) -> Result<Self> {
-        let config = aws_config::defaults(BehaviorVersion::latest())
-            .region(aws_config::Region::new(region.to_string()))
-            .load()
-            .await;
-
-        let ec2 = Ec2Client::new(&config);
-        let ssm = SsmClient::new(&config);
-        let iam = IamClient::new(&config);
-        let sts = StsClient::new(&config);
-
-        // Verify credentials
-        let identity = sts
-            .get_caller_identity()
-            .send()
-            .await
-            .map_err(|e| Ec2CliError::AwsCredentials(format_credentials_error(&e)))?;
-
-        let account_id = identity
-            .account()
-            .ok_or_else(|| Ec2CliError::AwsCredentials("No account ID in response".to_string()))?
-            .to_string();
-
-        Ok(Self {
-            ec2,
-            ssm,
-            iam,
-            region: region.to_string(),
-            account_id,
+            caller_arn,
+            resource_prefix,
         })
     }
 }
@@ -158,16 +222,35 @@ pub const MANAGED_TAG_VALUE: &str = "true";
 /// Tag used to store the ec2-cli instance name
 pub const NAME_TAG_KEY: &str = "ec2-cli:name";
 
+/// Custom tag identifying who launched a resource, used for ownership checks
+pub const OWNER_TAG_KEY: &str = "Username";
+
 /// Standard Name tag
 pub const AWS_NAME_TAG: &str = "Name";
 
-/// Hardcoded deployment identifier tag
+/// Deployment identifier tag, set to the resource prefix
 pub const DEPLOYMENT_TAG_KEY: &str = "deployment";
-pub const DEPLOYMENT_TAG_VALUE: &str = "ec2-cli";
+
+/// Resource prefix used unless settings choose another
+pub const DEFAULT_RESOURCE_PREFIX: &str = "ec2-cli";
+
+/// Tag keys set by ec2-cli on every resource, which custom tags may not override
+pub const RESERVED_TAG_KEYS: &[&str] = &[
+    MANAGED_TAG_KEY,
+    NAME_TAG_KEY,
+    AWS_NAME_TAG,
+    DEPLOYMENT_TAG_KEY,
+];
+
+/// Name of a managed resource: the instance (or other) name under `prefix`
+pub fn resource_name(prefix: &str, name: &str) -> String {
+    format!("{}-{}", prefix, name)
+}
 
 /// Create standard tags for a resource, including custom tags from settings
 pub fn create_tags(
     name: &str,
+    prefix: &str,
     custom_tags: &std::collections::HashMap<String, String>,
 ) -> Vec<aws_sdk_ec2::types::Tag> {
     let mut tags = vec![
@@ -181,11 +264,11 @@ pub fn create_tags(
             .build(),
         aws_sdk_ec2::types::Tag::builder()
             .key(AWS_NAME_TAG)
-            .value(format!("ec2-cli-{}", name))
+            .value(resource_name(prefix, name))
             .build(),
         aws_sdk_ec2::types::Tag::builder()
             .key(DEPLOYMENT_TAG_KEY)
-            .value(DEPLOYMENT_TAG_VALUE)
+            .value(prefix)
             .build(),
     ];
 
@@ -218,3 +301,80 @@ pub async fn get_default_vpc(clients: &AwsClients) -> Result<String> {
         .map(String::from)
         .ok_or(Ec2CliError::NoDefaultVpc)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_client_options_from_settings() {
+        let settings = Settings {
+            region: Some("us-west-2".to_string()),
+            aws_profile: Some("work".to_string()),
+            ..Default::default()
+        };
+
+        let options = ClientOptions::from_settings(&settings, None);
+        assert_eq!(options.aws_profile.as_deref(), Some("work"));
+        assert_eq!(options.region.as_deref(), Some("us-west-2"));
+
+        // An explicit region (e.g. an instance's) wins, but the profile still applies
+        let options = ClientOptions::from_settings(&settings, Some("eu-west-1"));
+        assert_eq!(options.aws_profile.as_deref(), Some("work"));
+        assert_eq!(options.region.as_deref(), Some("eu-west-1"));
+
+        // Without settings, everything comes from the default chain
+        assert_eq!(
+            ClientOptions::from_settings(&Settings::default(), None),
+            ClientOptions::default()
+        );
+    }
+
+    #[test]
+    fn test_resource_names_use_prefix() {
+        assert_eq!(resource_name(DEFAULT_RESOURCE_PREFIX, "dev"), "ec2-cli-dev");
+        assert_eq!(resource_name("team-a", "dev"), "team-a-dev");
+
+        let tags = create_tags("dev", "team-a", &Default::default());
+        let value = |key: &str| {
+            tags.iter()
+                .find(|tag| tag.key() == Some(key))
+                .and_then(|tag| tag.value())
+                .map(String::from)
+        };
+        assert_eq!(value(AWS_NAME_TAG).as_deref(), Some("team-a-dev"));
+        assert_eq!(value(DEPLOYMENT_TAG_KEY).as_deref(), Some("team-a"));
+        assert_eq!(value(NAME_TAG_KEY).as_deref(), Some("dev"));
+    }
+
+    #[test]
+    fn test_fallback_identity_differs_per_user_and_machine() {
+        let hash = |identity: String| fnv1a_hash(&identity) & 0xFFFFFFFF;
+        let alice = fallback_identity(Some("alice"), Some("id-1"));
+
+        assert_eq!(alice, fallback_identity(Some("alice"), Some("id-1")));
+        assert_ne!(
+            hash(alice.clone()),
+            hash(fallback_identity(Some("bob"), Some("id-1")))
+        );
+        assert_ne!(
+            hash(alice.clone()),
+            hash(fallback_identity(Some("alice"), Some("id-2")))
+        );
+        assert_ne!(hash(alice), hash("unknown".to_string()));
+    }
+
+    #[test]
+    fn test_machine_id_is_persisted() {
+        let dir = std::env::temp_dir().join(format!("ec2-cli-machine-id-{}", std::process::id()));
+        let path = dir.join(MACHINE_ID_FILE);
+
+        let first = load_or_create_machine_id(&path).unwrap();
+        assert_eq!(
+            load_or_create_machine_id(&path).as_deref(),
+            Some(first.as_str())
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
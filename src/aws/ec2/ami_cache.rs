@@ -0,0 +1,173 @@
+use chrono::{DateTime, Duration, Utc};
+use directories::ProjectDirs;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::future::Future;
+use std::path::PathBuf;
+
+use crate::Result;
+
+/// How long a resolved AMI ID stays valid (Canonical publishes roughly daily)
+const AMI_CACHE_TTL_HOURS: i64 = 12;
+
+/// Resolved AMI IDs keyed by region, AMI type, and architecture
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct AmiCache {
+    #[serde(default)]
+    entries: HashMap<String, CachedAmi>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CachedAmi {
+    ami_id: String,
+    resolved_at: DateTime<Utc>,
+}
+
+impl AmiCache {
+    /// Get the path to the cache file
+    fn path() -> Option<PathBuf> {
+        ProjectDirs::from("", "", "ec2-cli").map(|dirs| dirs.cache_dir().join("ami_cache.json"))
+    }
+
+    /// Load the cache, treating a missing or unreadable file as empty
+    pub fn load() -> Self {
+        Self::path()
+            .and_then(|path| std::fs::read_to_string(path).ok())
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    /// Save the cache to disk
+    pub fn save(&self) -> Result<()> {
+        let Some(path) = Self::path() else {
+            return Ok(());
+        };
+
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        std::fs::write(&path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Build the cache key for an AMI lookup
+    pub fn key(region: &str, ami_type: &str, architecture: &str) -> String {
+        format!("{}/{}/{}", region, ami_type, architecture)
+    }
+
+    /// Get a cached AMI ID if it was resolved within the TTL
+    fn get(&self, key: &str, now: DateTime<Utc>) -> Option<&str> {
+        self.entries
+            .get(key)
+            .filter(|entry| now - entry.resolved_at < Duration::hours(AMI_CACHE_TTL_HOURS))
+            .map(|entry| entry.ami_id.as_str())
+    }
+
+    /// Return the cached AMI ID for `key`, or call `fetch` and cache its result.
+    /// With `refresh`, the cached value is ignored.
+    pub async fn get_or_fetch<F, Fut>(
+        &mut self,
+        key: &str,
+        now: DateTime<Utc>,
+        refresh: bool,
+        fetch: F,
+    ) -> Result<String>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<String>>,
+    {
+        if !refresh {
+            if let Some(ami_id) = self.get(key, now) {
+                return Ok(ami_id.to_string());
+            }
+        }
+
+        let ami_id = fetch().await?;
+        self.entries.insert(
+            key.to_string(),
+            CachedAmi {
+                ami_id: ami_id.clone(),
+                resolved_at: now,
+            },
+        );
+        Ok(ami_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Ec2CliError;
+
+    #[tokio::test]
+    async fn test_cache_hit_within_ttl_skips_fetch() {
+        let mut cache = AmiCache::default();
+        let key = AmiCache::key("us-east-1", "ubuntu-24.04", "x86_64");
+        let now = Utc::now();
+
+        let first = cache
+            .get_or_fetch(&key, now, false, || async { Ok("ami-111".to_string()) })
+            .await
+            .unwrap();
+        assert_eq!(first, "ami-111");
+
+        let later = now + Duration::hours(AMI_CACHE_TTL_HOURS - 1);
+        let second = cache
+            .get_or_fetch(&key, later, false, || async {
+                Err(Ec2CliError::Other("fetch should not be called".to_string()))
+            })
+            .await
+            .unwrap();
+        assert_eq!(second, "ami-111");
+    }
+
+    #[tokio::test]
+    async fn test_cache_expired_refetches() {
+        let mut cache = AmiCache::default();
+        let key = AmiCache::key("us-east-1", "ubuntu-24.04", "x86_64");
+        let now = Utc::now();
+
+        cache
+            .get_or_fetch(&key, now, false, || async { Ok("ami-111".to_string()) })
+            .await
+            .unwrap();
+
+        let later = now + Duration::hours(AMI_CACHE_TTL_HOURS + 1);
+        let ami_id = cache
+            .get_or_fetch(&key, later, false, || async { Ok("ami-222".to_string()) })
+            .await
+            .unwrap();
+        assert_eq!(ami_id, "ami-222");
+    }
+
+    #[tokio::test]
+    async fn test_cache_refresh_bypasses_hit() {
+        let mut cache = AmiCache::default();
+        let key = AmiCache::key("us-east-1", "ubuntu-24.04", "x86_64");
+        let now = Utc::now();
+
+        cache
+            .get_or_fetch(&key, now, false, || async { Ok("ami-111".to_string()) })
+            .await
+            .unwrap();
+
+        let ami_id = cache
+            .get_or_fetch(&key, now, true, || async { Ok("ami-222".to_string()) })
+            .await
+            .unwrap();
+        assert_eq!(ami_id, "ami-222");
+    }
+
+    #[test]
+    fn test_cache_key_separates_region_and_architecture() {
+        assert_ne!(
+            AmiCache::key("us-east-1", "ubuntu-24.04", "x86_64"),
+            AmiCache::key("us-west-2", "ubuntu-24.04", "x86_64")
+        );
+        assert_ne!(
+            AmiCache::key("us-east-1", "ubuntu-24.04", "x86_64"),
+            AmiCache::key("us-east-1", "ubuntu-24.04", "arm64")
+        );
+    }
+}
@@ -1,18 +1,28 @@
 use std::collections::HashMap;
+use std::fmt;
+use std::future::Future;
 
+use aws_sdk_ec2::error::ProvideErrorMetadata;
 use aws_sdk_ec2::types::{
-    BlockDeviceMapping, EbsBlockDevice, Filter, HttpTokensState, InstanceMetadataEndpointState,
-    InstanceMetadataOptionsRequest, InstanceStateName, InstanceType as AwsInstanceType,
+    BlockDeviceMapping, EbsBlockDevice, Filter, HttpTokensState, Image, ImageState, Instance,
+    InstanceInterruptionBehavior, InstanceMarketOptionsRequest, InstanceMetadataEndpointState,
+    InstanceMetadataOptionsRequest, InstanceStateName, InstanceType as AwsInstanceType, MarketType,
+    SnapshotState, SpotInstanceType, SpotMarketOptions,
 };
 use uuid::Uuid;
 
-use crate::config::Settings;
-use crate::profile::Profile;
-use crate::ssh::SSM_PROXY_COMMAND;
+use crate::profile::{AmiConfig, Profile, RootVolumeConfig};
+use crate::ssh::ssm_proxy_command;
+use crate::user_data::{GIT_READY_MARKER, READY_MARKER};
 use crate::{Ec2CliError, Result};
 
-use super::super::client::{create_tags, AwsClients};
-use super::super::infrastructure::Infrastructure;
+use super::super::client::{
+    create_tags, resource_name, AwsClients, DEPLOYMENT_TAG_KEY, MANAGED_TAG_KEY, MANAGED_TAG_VALUE,
+    NAME_TAG_KEY, OWNER_TAG_KEY,
+};
+use super::super::infrastructure::{Infrastructure, IAM_POLL_INTERVAL, IAM_PROPAGATION_TIMEOUT};
+use super::super::retry::{poll_until, retry_while};
+use super::ami_cache::AmiCache;
 
 /// Create a per-instance security group
 pub async fn create_instance_security_group(
@@ -23,7 +33,7 @@ pub async fn create_instance_security_group(
 ) -> Result<String> {
     // Generate unique suffix for security group name
     let hash = &Uuid::new_v4().to_string()[..8];
-    let sg_name = format!("ec2-cli-{}-{}", instance_name, hash);
+    let sg_name = security_group_name(&clients.resource_prefix, instance_name, hash);
 
     let sg = clients
         .ec2
@@ -37,7 +47,11 @@ pub async fn create_instance_security_group(
         .tag_specifications(
             aws_sdk_ec2::types::TagSpecification::builder()
                 .resource_type(aws_sdk_ec2::types::ResourceType::SecurityGroup)
-                .set_tags(Some(create_tags(instance_name, custom_tags)))
+                .set_tags(Some(create_tags(
+                    instance_name,
+                    &clients.resource_prefix,
+                    custom_tags,
+                )))
                 .build(),
         )
         .send()
@@ -46,7 +60,7 @@ pub async fn create_instance_security_group(
 
     let security_group_id = sg
         .group_id()
-        .ok_or_else(|| Ec2CliError::Ec2("No security group ID returned".to_string()))?
+        .ok_or_else(|| Ec2CliError::Ec2("No security group ID returned".to_string(), None))?
         .to_string();
 
     // Security group has default egress rule (0.0.0.0/0) which is needed for SSM via internet
@@ -55,6 +69,11 @@ pub async fn create_instance_security_group(
     Ok(security_group_id)
 }
 
+/// Name for a new instance's security group; `suffix` keeps relaunches unique
+fn security_group_name(prefix: &str, instance_name: &str, suffix: &str) -> String {
+    format!("{}-{}", resource_name(prefix, instance_name), suffix)
+}
+
 /// Delete a security group
 pub async fn delete_security_group(clients: &AwsClients, security_group_id: &str) -> Result<()> {
     clients
@@ -68,7 +87,212 @@ pub async fn delete_security_group(clients: &AwsClients, security_group_id: &str
     Ok(())
 }
 
+/// EC2 error codes meaning a spot request can't be met right now
+const SPOT_UNAVAILABLE_CODES: &[&str] = &[
+    "InsufficientInstanceCapacity",
+    "SpotMaxPriceTooLow",
+    "MaxSpotInstanceCountExceeded",
+];
+
+/// Purchase option used to launch an instance
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LaunchMode {
+    OnDemand,
+    Spot,
+}
+
+impl LaunchMode {
+    /// Launch modes to try, in order
+    pub fn attempts(spot_fallback: bool) -> &'static [LaunchMode] {
+        if spot_fallback {
+            &[LaunchMode::Spot, LaunchMode::OnDemand]
+        } else {
+            &[LaunchMode::OnDemand]
+        }
+    }
+}
+
+impl fmt::Display for LaunchMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LaunchMode::OnDemand => write!(f, "on-demand"),
+            LaunchMode::Spot => write!(f, "spot"),
+        }
+    }
+}
+
+/// Check whether a launch failed because spot capacity or price wasn't available
+fn is_spot_unavailable(err: &Ec2CliError) -> bool {
+    match err {
+        Ec2CliError::Ec2(message, _) => SPOT_UNAVAILABLE_CODES
+            .iter()
+            .any(|code| message.starts_with(&format!("{}:", code))),
+        _ => false,
+    }
+}
+
+/// Check whether a launch failed because EC2 can't see the instance profile yet
+fn is_instance_profile_not_ready(err: &Ec2CliError) -> bool {
+    matches!(err, Ec2CliError::Ec2(message, _) if message.contains("Invalid IAM Instance Profile"))
+}
+
+/// The error code at the start of an EC2 error message ("Code: message")
+fn ec2_error_code(message: &str) -> Option<&str> {
+    message.split_once(':').map(|(code, _)| code.trim())
+}
+
+/// Name of the Service Quotas quota limiting vCPUs for an instance type. On-Demand
+/// and Spot instances are counted against separate quotas.
+fn vcpu_quota(instance_type: &str, mode: LaunchMode) -> &'static str {
+    let family = instance_type.split('.').next().unwrap_or_default();
+    let class = |prefix: &str| family.starts_with(prefix);
+    let (on_demand, spot) = if class("inf") {
+        (
+            "Running On-Demand Inf instances",
+            "All Inf Spot Instance Requests",
+        )
+    } else if class("dl") {
+        (
+            "Running On-Demand DL instances",
+            "All DL Spot Instance Requests",
+        )
+    } else if class("trn") {
+        (
+            "Running On-Demand Trn instances",
+            "All Trn Spot Instance Requests",
+        )
+    } else if class("hpc") {
+        // HPC instances aren't sold as Spot, so only the On-Demand quota applies
+        (
+            "Running On-Demand HPC instances",
+            "Running On-Demand HPC instances",
+        )
+    } else if class("f") {
+        (
+            "Running On-Demand F instances",
+            "All F Spot Instance Requests",
+        )
+    } else if class("g") || class("vt") {
+        (
+            "Running On-Demand G and VT instances",
+            "All G and VT Spot Instance Requests",
+        )
+    } else if class("p5") {
+        (
+            "Running On-Demand P instances",
+            "All P5 Spot Instance Requests",
+        )
+    } else if class("p") {
+        (
+            "Running On-Demand P instances",
+            "All P4, P3 and P2 Spot Instance Requests",
+        )
+    } else if class("x") {
+        (
+            "Running On-Demand X instances",
+            "All X Spot Instance Requests",
+        )
+    } else {
+        (
+            "Running On-Demand Standard (A, C, D, H, I, M, R, T, Z) instances",
+            "All Standard (A, C, D, H, I, M, R, T, Z) Spot Instance Requests",
+        )
+    };
+    match mode {
+        LaunchMode::OnDemand => on_demand,
+        LaunchMode::Spot => spot,
+    }
+}
+
+/// Turn a failed `run_instances` call into guidance for the errors first launches
+/// usually hit: account quotas, and AMIs or instance types the region doesn't
+/// offer. Other errors are returned as they are.
+fn explain_launch_error(
+    err: Ec2CliError,
+    instance_type: &str,
+    mode: LaunchMode,
+    region: &str,
+) -> Ec2CliError {
+    let Ec2CliError::Ec2(message, source) = err else {
+        return err;
+    };
+    let quotas_url = format!(
+        "https://{}.console.aws.amazon.com/servicequotas/home/services/ec2/quotas",
+        region
+    );
+
+    match ec2_error_code(&message) {
+        Some("VcpuLimitExceeded") => Ec2CliError::QuotaExceeded(format!(
+            "launching {} ({}) would go over this account's vCPU limit in {}. Destroy \
+             instances you no longer need, or request a higher '{}' quota at {}",
+            instance_type,
+            mode,
+            region,
+            vcpu_quota(instance_type, mode),
+            quotas_url
+        )),
+        Some("InstanceLimitExceeded") => Ec2CliError::QuotaExceeded(format!(
+            "this account can't run more {} instances in {}. Destroy instances you \
+             no longer need, or request a higher limit at {}",
+            instance_type, region, quotas_url
+        )),
+        Some("InvalidAMIID.NotFound" | "InvalidAMIID.Unavailable") => Ec2CliError::Ec2(
+            format!(
+                "the AMI isn't available in {}. AMI IDs are per region: run 'up \
+                 --refresh-ami', or set the profile's ami.id to an image from {} ({})",
+                region, region, message
+            ),
+            source,
+        ),
+        Some("Unsupported") => Ec2CliError::Ec2(
+            format!(
+                "{} isn't offered in this subnet's availability zone. Pick another \
+                 instance type, or set a subnet in another zone with 'config \
+                 set-subnet' ({})",
+                instance_type, message
+            ),
+            source,
+        ),
+        _ => Ec2CliError::Ec2(message, source),
+    }
+}
+
+/// Try each launch mode in turn until one succeeds.
+///
+/// Only spot capacity/price errors move on to the next mode; any other error is
+/// returned immediately. Returns the launch result and the mode that succeeded.
+pub async fn launch_with_fallback<T, F, Fut>(
+    modes: &[LaunchMode],
+    mut launch: F,
+) -> Result<(T, LaunchMode)>
+where
+    F: FnMut(LaunchMode) -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    let mut last_err = None;
+
+    for &mode in modes {
+        match launch(mode).await {
+            Ok(launched) => return Ok((launched, mode)),
+            Err(e) if mode == LaunchMode::Spot && is_spot_unavailable(&e) => last_err = Some(e),
+            Err(e) => return Err(e),
+        }
+    }
+
+    Err(last_err.unwrap_or_else(|| Ec2CliError::Ec2("No launch modes to try".to_string(), None)))
+}
+
+/// An instance returned by `run_instances`
+#[derive(Debug, Clone)]
+pub struct LaunchedInstance {
+    pub instance_id: String,
+    pub private_ip: Option<String>,
+    pub subnet_id: Option<String>,
+    pub availability_zone: Option<String>,
+}
+
 /// Launch a new EC2 instance
+#[allow(clippy::too_many_arguments)]
 pub async fn launch_instance(
     clients: &AwsClients,
     infra: &Infrastructure,
@@ -76,38 +300,18 @@ pub async fn launch_instance(
     profile: &Profile,
     name: &str,
     user_data: &str,
-) -> Result<String> {
-    // Load custom tags from settings
-    let custom_tags = Settings::load().map(|s| s.tags).unwrap_or_default();
-
+    custom_tags: &HashMap<String, String>,
+    refresh_ami: bool,
+    mode: LaunchMode,
+    encrypt: bool,
+) -> Result<LaunchedInstance> {
     // Look up AMI
-    let ami_id = lookup_ami(clients, profile).await?;
+    let ami_id = lookup_ami(clients, profile, refresh_ami).await?;
 
     // Parse instance type
     let instance_type = AwsInstanceType::from(profile.instance.instance_type.as_str());
 
-    // Create block device mapping with encryption always enabled
-    let root_volume = &profile.instance.storage.root_volume;
-    let mut ebs_builder = EbsBlockDevice::builder()
-        .volume_size(root_volume.size_gb as i32)
-        .volume_type(aws_sdk_ec2::types::VolumeType::from(
-            root_volume.volume_type.as_str(),
-        ))
-        .delete_on_termination(true)
-        .encrypted(true); // Always encrypt EBS volumes
-
-    if let Some(iops) = root_volume.iops {
-        ebs_builder = ebs_builder.iops(iops as i32);
-    }
-    if let Some(throughput) = root_volume.throughput {
-        ebs_builder = ebs_builder.throughput(throughput as i32);
-    }
-
-    // Ubuntu AMIs use /dev/sda1 as root device (unlike Amazon Linux which uses /dev/xvda)
-    let block_device = BlockDeviceMapping::builder()
-        .device_name("/dev/sda1")
-        .ebs(ebs_builder.build())
-        .build();
+    let block_device = root_block_device(&profile.instance.storage.root_volume, encrypt);
 
     // Encode user data
     let user_data_encoded = base64::Engine::encode(
@@ -115,8 +319,21 @@ pub async fn launch_instance(
         user_data.as_bytes(),
     );
 
+    // One-time spot request; the instance terminates if reclaimed
+    let market_options = (mode == LaunchMode::Spot).then(|| {
+        InstanceMarketOptionsRequest::builder()
+            .market_type(MarketType::Spot)
+            .spot_options(
+                SpotMarketOptions::builder()
+                    .spot_instance_type(SpotInstanceType::OneTime)
+                    .instance_interruption_behavior(InstanceInterruptionBehavior::Terminate)
+                    .build(),
+            )
+            .build()
+    });
+
     // Launch instance with IMDSv2 required (prevents SSRF credential theft)
-    let run_result = clients
+    let request = clients
         .ec2
         .run_instances()
         .image_id(&ami_id)
@@ -131,6 +348,7 @@ pub async fn launch_instance(
                 .build(),
         )
         .block_device_mappings(block_device)
+        .set_instance_market_options(market_options)
         .user_data(&user_data_encoded)
         .metadata_options(
             InstanceMetadataOptionsRequest::builder()
@@ -142,53 +360,138 @@ pub async fn launch_instance(
         .tag_specifications(
             aws_sdk_ec2::types::TagSpecification::builder()
                 .resource_type(aws_sdk_ec2::types::ResourceType::Instance)
-                .set_tags(Some(create_tags(name, &custom_tags)))
+                .set_tags(Some(create_tags(
+                    name,
+                    &clients.resource_prefix,
+                    custom_tags,
+                )))
                 .build(),
-        )
-        .send()
-        .await
-        .map_err(Ec2CliError::ec2)?;
+        );
+
+    // A just-created instance profile can take a little longer to reach EC2
+    let run_result = retry_while(
+        IAM_PROPAGATION_TIMEOUT,
+        IAM_POLL_INTERVAL,
+        is_instance_profile_not_ready,
+        || async { request.clone().send().await.map_err(Ec2CliError::ec2) },
+    )
+    .await
+    .map_err(|e| explain_launch_error(e, &profile.instance.instance_type, mode, &clients.region))?;
 
     let instance = run_result
         .instances()
         .first()
-        .ok_or_else(|| Ec2CliError::Ec2("No instance returned".to_string()))?;
+        .ok_or_else(|| Ec2CliError::Ec2("No instance returned".to_string(), None))?;
 
     let instance_id = instance
         .instance_id()
-        .ok_or_else(|| Ec2CliError::Ec2("No instance ID".to_string()))?
+        .ok_or_else(|| Ec2CliError::Ec2("No instance ID".to_string(), None))?
         .to_string();
 
-    Ok(instance_id)
+    Ok(LaunchedInstance {
+        instance_id,
+        private_ip: instance.private_ip_address().map(String::from),
+        subnet_id: instance.subnet_id().map(String::from),
+        availability_zone: instance
+            .placement()
+            .and_then(|p| p.availability_zone())
+            .map(String::from),
+    })
+}
+
+/// Root volume mapping, encrypted (with the profile's KMS key, if set) unless
+/// `encrypt` is false
+fn root_block_device(root_volume: &RootVolumeConfig, encrypt: bool) -> BlockDeviceMapping {
+    let mut ebs_builder = EbsBlockDevice::builder()
+        .volume_size(root_volume.size_gb as i32)
+        .volume_type(aws_sdk_ec2::types::VolumeType::from(
+            root_volume.volume_type.as_str(),
+        ))
+        .delete_on_termination(true)
+        .encrypted(encrypt);
+
+    if encrypt {
+        ebs_builder = ebs_builder.set_kms_key_id(root_volume.kms_key_id.clone());
+    }
+    if let Some(iops) = root_volume.iops {
+        ebs_builder = ebs_builder.iops(iops as i32);
+    }
+    if let Some(throughput) = root_volume.throughput {
+        ebs_builder = ebs_builder.throughput(throughput as i32);
+    }
+
+    // Ubuntu AMIs use /dev/sda1 as root device (unlike Amazon Linux which uses /dev/xvda)
+    BlockDeviceMapping::builder()
+        .device_name("/dev/sda1")
+        .ebs(ebs_builder.build())
+        .build()
 }
 
-/// Look up AMI ID based on profile configuration
-pub async fn lookup_ami(clients: &AwsClients, profile: &Profile) -> Result<String> {
+/// Look up AMI ID based on profile configuration.
+///
+/// Resolved IDs are cached per region, AMI type, and architecture; `refresh` bypasses the cache.
+pub async fn lookup_ami(clients: &AwsClients, profile: &Profile, refresh: bool) -> Result<String> {
     // If specific AMI ID is provided, use it
     if let Some(ref ami_id) = profile.instance.ami.id {
         return Ok(ami_id.clone());
     }
 
     let ami_config = &profile.instance.ami;
+    let key = AmiCache::key(
+        &clients.region,
+        &ami_config.ami_type,
+        &ami_config.architecture,
+    );
 
-    // Build filters based on AMI type (Ubuntu only)
-    let arch = match ami_config.architecture.as_str() {
-        "arm64" => "arm64",
-        _ => "amd64",
-    };
+    let mut cache = AmiCache::load();
+    let ami_id = cache
+        .get_or_fetch(&key, chrono::Utc::now(), refresh, || {
+            fetch_latest_ami(clients, ami_config)
+        })
+        .await?;
 
-    let (owner, name_pattern) = match ami_config.ami_type.as_str() {
-        "ubuntu-22.04" => (
-            "099720109477", // Canonical
-            format!("ubuntu/images/hvm-ssd/ubuntu-jammy-22.04-{}-server-*", arch),
-        ),
-        "ubuntu-24.04" => (
-            "099720109477", // Canonical
-            format!(
-                "ubuntu/images/hvm-ssd-gp3/ubuntu-noble-24.04-{}-server-*",
-                arch
-            ),
-        ),
+    // Caching is best-effort
+    let _ = cache.save();
+
+    Ok(ami_id)
+}
+
+/// Check that an AMI exists in the client's region and is available to launch
+pub async fn validate_ami_available(clients: &AwsClients, ami_id: &str) -> Result<()> {
+    let images = clients
+        .ec2
+        .describe_images()
+        .image_ids(ami_id)
+        .send()
+        .await
+        .map_err(Ec2CliError::ec2)?;
+
+    let image = images.images().first().ok_or_else(|| {
+        Ec2CliError::ResourceNotFound(format!("AMI {} not found in {}", ami_id, clients.region))
+    })?;
+
+    match image.state() {
+        Some(ImageState::Available) => Ok(()),
+        state => Err(Ec2CliError::ResourceNotFound(format!(
+            "AMI {} is not available (state: {})",
+            ami_id,
+            state.map(|s| s.as_str()).unwrap_or("unknown")
+        ))),
+    }
+}
+
+/// Canonical's AWS account, the owner of official Ubuntu images
+const CANONICAL_OWNER: &str = "099720109477";
+
+/// Image name patterns for an AMI type, in order of preference.
+///
+/// Canonical has changed the volume-type segment of image names between releases
+/// (`hvm-ssd` for 22.04, `hvm-ssd-gp3` for 24.04), so each version also tries the
+/// other spelling in case the naming drifts again.
+fn ami_name_patterns(ami_type: &str, arch: &str) -> Result<Vec<String>> {
+    let (codename, version) = match ami_type {
+        "ubuntu-22.04" => ("jammy", "22.04"),
+        "ubuntu-24.04" => ("noble", "24.04"),
         other => {
             return Err(Ec2CliError::ProfileValidation(format!(
                 "Unknown AMI type: {}. Supported: ubuntu-22.04, ubuntu-24.04",
@@ -197,33 +500,110 @@ pub async fn lookup_ami(clients: &AwsClients, profile: &Profile) -> Result<Strin
         }
     };
 
+    let volume_types: &[&str] = match ami_type {
+        "ubuntu-22.04" => &["hvm-ssd", "hvm-ssd-gp3"],
+        _ => &["hvm-ssd-gp3", "hvm-ssd"],
+    };
+
+    Ok(volume_types
+        .iter()
+        .map(|volume_type| {
+            format!(
+                "ubuntu/images/{}/ubuntu-{}-{}-{}-server-*",
+                volume_type, codename, version, arch
+            )
+        })
+        .collect())
+}
+
+/// Match an image name against a describe-images style pattern (`*` matches anything)
+fn matches_name_pattern(name: &str, pattern: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = name.strip_prefix(first) else {
+        return false;
+    };
+
+    let parts: Vec<&str> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else {
+        // No wildcard: the whole name must match
+        return rest.is_empty();
+    };
+
+    for part in middle {
+        match rest.find(part) {
+            Some(i) => rest = &rest[i + part.len()..],
+            None => return false,
+        }
+    }
+    rest.ends_with(last)
+}
+
+/// Pick the newest image matching the most preferred pattern that matches anything.
+///
+/// Returns the image ID and the pattern it matched.
+fn select_latest_image<'a>(images: &[Image], patterns: &'a [String]) -> Option<(String, &'a str)> {
+    patterns.iter().find_map(|pattern| {
+        images
+            .iter()
+            .filter(|image| {
+                image
+                    .name()
+                    .is_some_and(|name| matches_name_pattern(name, pattern))
+            })
+            .filter_map(|image| {
+                Some((image.creation_date().unwrap_or_default(), image.image_id()?))
+            })
+            .max_by(|a, b| a.0.cmp(b.0))
+            .map(|(_, image_id)| (image_id.to_string(), pattern.as_str()))
+    })
+}
+
+/// Query AWS for the latest AMI matching the configured type and architecture
+async fn fetch_latest_ami(clients: &AwsClients, ami_config: &AmiConfig) -> Result<String> {
+    // Build filters based on AMI type (Ubuntu only)
+    let arch = match ami_config.architecture.as_str() {
+        "arm64" => "arm64",
+        _ => "amd64",
+    };
+
+    let patterns = ami_name_patterns(&ami_config.ami_type, arch)?;
+
+    // Name filter values are ORed, so one request covers every pattern
     let images = clients
         .ec2
         .describe_images()
-        .owners(owner)
-        .filters(Filter::builder().name("name").values(&name_pattern).build())
+        .owners(CANONICAL_OWNER)
+        .filters(
+            Filter::builder()
+                .name("name")
+                .set_values(Some(patterns.clone()))
+                .build(),
+        )
         .filters(Filter::builder().name("state").values("available").build())
         .send()
         .await
         .map_err(Ec2CliError::ec2)?;
 
-    // Sort by creation date and get the latest
-    let mut images: Vec<_> = images.images().to_vec();
-    images.sort_by(|a, b| {
-        let a_date = a.creation_date().unwrap_or_default();
-        let b_date = b.creation_date().unwrap_or_default();
-        b_date.cmp(a_date) // Descending order
-    });
+    let (image_id, pattern) = select_latest_image(images.images(), &patterns).ok_or_else(|| {
+        Ec2CliError::ResourceNotFound(format!(
+            "No AMI found for {} ({}) owned by {}. Tried name patterns: {}. \
+                 Set ami.id in the profile to use a specific image",
+            ami_config.ami_type,
+            ami_config.architecture,
+            CANONICAL_OWNER,
+            patterns.join(", ")
+        ))
+    })?;
 
-    images
-        .first()
-        .and_then(|i| i.image_id().map(String::from))
-        .ok_or_else(|| {
-            Ec2CliError::ResourceNotFound(format!(
-                "No AMI found matching {} for {}",
-                ami_config.ami_type, ami_config.architecture
-            ))
-        })
+    if pattern != patterns[0] {
+        eprintln!(
+            "Note: no AMI matched {}, using fallback name pattern {}",
+            patterns[0], pattern
+        );
+    }
+
+    Ok(image_id)
 }
 
 /// Wait for instance to be running
@@ -277,60 +657,183 @@ pub async fn wait_for_ssm_ready(
             )));
         }
 
-        let filter = aws_sdk_ssm::types::InstanceInformationStringFilter::builder()
-            .key("InstanceIds")
-            .values(instance_id)
-            .build()
-            .map_err(|e| Ec2CliError::Ssm(e.to_string()))?;
+        if is_ssm_online(clients, instance_id).await? {
+            return Ok(());
+        }
+
+        tokio::time::sleep(tokio::time::Duration::from_secs(10)).await;
+    }
+}
+
+/// Whether the instance's SSM agent currently reports as online
+pub async fn is_ssm_online(clients: &AwsClients, instance_id: &str) -> Result<bool> {
+    let filter = aws_sdk_ssm::types::InstanceInformationStringFilter::builder()
+        .key("InstanceIds")
+        .values(instance_id)
+        .build()
+        .map_err(|e| Ec2CliError::Ssm(e.to_string(), Some(e.into())))?;
+
+    let info = clients
+        .ssm
+        .describe_instance_information()
+        .filters(filter)
+        .send()
+        .await
+        .map_err(Ec2CliError::ssm)?;
+
+    Ok(info
+        .instance_information_list()
+        .first()
+        .is_some_and(|i| i.ping_status() == Some(&aws_sdk_ssm::types::PingStatus::Online)))
+}
+
+/// SSM document that runs a shell script as root
+const RUN_SHELL_SCRIPT_DOCUMENT: &str = "AWS-RunShellScript";
+
+/// Interval between checks on a Run Command invocation
+const COMMAND_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(1);
 
-        let info = clients
+/// Result of a command run with SSM Run Command
+#[derive(Debug, Clone)]
+pub struct RemoteCommandOutput {
+    /// Exit status of the command (-1 if it never ran or timed out)
+    pub exit_code: i32,
+    /// Standard output; SSM truncates it after 24,000 characters
+    pub stdout: String,
+    /// Standard error; SSM truncates it after 8,000 characters
+    pub stderr: String,
+}
+
+/// Run a shell script on the instance as root with SSM Run Command, without an
+/// SSM session, and wait up to `timeout` for it to finish
+pub async fn run_remote_command(
+    clients: &AwsClients,
+    instance_id: &str,
+    script: &str,
+    timeout: std::time::Duration,
+) -> Result<RemoteCommandOutput> {
+    use aws_sdk_ssm::types::CommandInvocationStatus;
+
+    let sent = clients
+        .ssm
+        .send_command()
+        .instance_ids(instance_id)
+        .document_name(RUN_SHELL_SCRIPT_DOCUMENT)
+        .parameters("commands", vec![script.to_string()])
+        .parameters("executionTimeout", vec![timeout.as_secs().to_string()])
+        .send()
+        .await
+        .map_err(Ec2CliError::ssm)?;
+    let command_id = sent
+        .command()
+        .and_then(|c| c.command_id())
+        .ok_or_else(|| Ec2CliError::Ssm("No command ID returned".to_string(), None))?
+        .to_string();
+
+    let deadline = std::time::Instant::now() + timeout;
+    loop {
+        tokio::time::sleep(COMMAND_POLL_INTERVAL).await;
+
+        let result = clients
             .ssm
-            .describe_instance_information()
-            .filters(filter)
+            .get_command_invocation()
+            .command_id(&command_id)
+            .instance_id(instance_id)
             .send()
-            .await
-            .map_err(Ec2CliError::ssm)?;
-
-        if let Some(instance_info) = info.instance_information_list().first() {
-            if instance_info.ping_status() == Some(&aws_sdk_ssm::types::PingStatus::Online) {
-                return Ok(());
+            .await;
+        let invocation = match result {
+            Ok(invocation) => Some(invocation),
+            // The invocation can take a moment to show up after SendCommand
+            Err(e)
+                if e.as_service_error()
+                    .is_some_and(|se| se.is_invocation_does_not_exist()) =>
+            {
+                None
             }
+            Err(e) => return Err(Ec2CliError::ssm(e)),
+        };
+
+        let finished = invocation.filter(|i| {
+            !matches!(
+                i.status(),
+                None | Some(
+                    CommandInvocationStatus::Pending
+                        | CommandInvocationStatus::InProgress
+                        | CommandInvocationStatus::Delayed
+                )
+            )
+        });
+        if let Some(invocation) = finished {
+            return Ok(RemoteCommandOutput {
+                exit_code: invocation.response_code(),
+                stdout: invocation
+                    .standard_output_content()
+                    .unwrap_or_default()
+                    .to_string(),
+                stderr: invocation
+                    .standard_error_content()
+                    .unwrap_or_default()
+                    .to_string(),
+            });
         }
 
-        tokio::time::sleep(tokio::time::Duration::from_secs(10)).await;
+        if std::time::Instant::now() >= deadline {
+            return Err(Ec2CliError::Timeout(format!(
+                "Command {} on instance {} did not finish within {} seconds",
+                command_id,
+                instance_id,
+                timeout.as_secs()
+            )));
+        }
     }
 }
 
-/// Get instance state
-pub async fn get_instance_state(
-    clients: &AwsClients,
-    instance_id: &str,
-) -> Result<InstanceStateName> {
-    let result = clients
+/// Reboot an instance
+pub async fn reboot_instance(clients: &AwsClients, instance_id: &str) -> Result<()> {
+    clients
         .ec2
-        .describe_instances()
+        .reboot_instances()
         .instance_ids(instance_id)
         .send()
         .await
         .map_err(Ec2CliError::ec2)?;
 
-    let instance = result
-        .reservations()
-        .first()
-        .and_then(|r| r.instances().first())
-        .ok_or_else(|| Ec2CliError::InstanceNotFound(instance_id.to_string()))?;
+    Ok(())
+}
 
-    instance
-        .state()
-        .and_then(|s| s.name().cloned())
-        .ok_or_else(|| Ec2CliError::InstanceState("Unknown state".to_string()))
+/// Fetch the instance's serial console output, which EC2 keeps even when the
+/// instance never comes online. None if nothing has been captured yet.
+pub async fn get_console_output(clients: &AwsClients, instance_id: &str) -> Result<Option<String>> {
+    let result = clients
+        .ec2
+        .get_console_output()
+        .instance_id(instance_id)
+        .send()
+        .await
+        .map_err(Ec2CliError::ec2)?;
+
+    decode_console_output(result.output())
 }
 
-/// Terminate an instance
-pub async fn terminate_instance(clients: &AwsClients, instance_id: &str) -> Result<()> {
+/// Decode the base64 console output from `GetConsoleOutput`. Boot output can
+/// contain stray bytes, so invalid UTF-8 is replaced rather than rejected.
+fn decode_console_output(encoded: Option<&str>) -> Result<Option<String>> {
+    let Some(encoded) = encoded.filter(|e| !e.is_empty()) else {
+        return Ok(None);
+    };
+
+    let bytes = base64::Engine::decode(&base64::engine::general_purpose::STANDARD, encoded.trim())
+        .map_err(|e| {
+            Ec2CliError::Ec2(format!("Console output is not valid base64: {}", e), None)
+        })?;
+    Ok(Some(String::from_utf8_lossy(&bytes).into_owned()))
+}
+
+/// Stop an instance
+pub async fn stop_instance(clients: &AwsClients, instance_id: &str) -> Result<()> {
     clients
         .ec2
-        .terminate_instances()
+        .stop_instances()
         .instance_ids(instance_id)
         .send()
         .await
@@ -339,8 +842,21 @@ pub async fn terminate_instance(clients: &AwsClients, instance_id: &str) -> Resu
     Ok(())
 }
 
-/// Wait for instance to be terminated
-pub async fn wait_for_terminated(
+/// Start a stopped instance
+pub async fn start_instance(clients: &AwsClients, instance_id: &str) -> Result<()> {
+    clients
+        .ec2
+        .start_instances()
+        .instance_ids(instance_id)
+        .send()
+        .await
+        .map_err(Ec2CliError::ec2)?;
+
+    Ok(())
+}
+
+/// Wait for instance to be stopped
+pub async fn wait_for_stopped(
     clients: &AwsClients,
     instance_id: &str,
     timeout_secs: u64,
@@ -351,30 +867,19 @@ pub async fn wait_for_terminated(
     loop {
         if start.elapsed() > timeout {
             return Err(Ec2CliError::Timeout(format!(
-                "Instance {} did not terminate within {} seconds",
+                "Instance {} did not stop within {} seconds",
                 instance_id, timeout_secs
             )));
         }
 
-        // If instance is no longer found, treat it as terminated
-        let state = match get_instance_state(clients, instance_id).await {
-            Ok(s) => s,
-            Err(Ec2CliError::InstanceNotFound(_)) => return Ok(()),
-            Err(e) => return Err(e),
-        };
-
-        match state {
-            InstanceStateName::Terminated => return Ok(()),
-            // Valid intermediate states during termination
-            InstanceStateName::ShuttingDown
-            | InstanceStateName::Stopping
-            | InstanceStateName::Stopped
-            | InstanceStateName::Running => {
+        match get_instance_state(clients, instance_id).await? {
+            InstanceStateName::Stopped => return Ok(()),
+            InstanceStateName::Running | InstanceStateName::Stopping => {
                 tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
             }
             other => {
                 return Err(Ec2CliError::InstanceState(format!(
-                    "Instance {} in unexpected state during termination: {:?}",
+                    "Instance {} in unexpected state while stopping: {:?}",
                     instance_id, other
                 )));
             }
@@ -382,58 +887,1003 @@ pub async fn wait_for_terminated(
     }
 }
 
-/// Wait for the git repo marker file to exist on the instance
-/// This ensures the git bare repo is ready before returning from `up`
-pub async fn wait_for_git_ready(
+/// Change the instance type of a stopped instance
+pub async fn modify_instance_type(
+    clients: &AwsClients,
+    instance_id: &str,
+    instance_type: &str,
+) -> Result<()> {
+    clients
+        .ec2
+        .modify_instance_attribute()
+        .instance_id(instance_id)
+        .instance_type(
+            aws_sdk_ec2::types::AttributeValue::builder()
+                .value(instance_type)
+                .build(),
+        )
+        .send()
+        .await
+        .map_err(Ec2CliError::ec2)?;
+
+    Ok(())
+}
+
+/// Get the CPU architecture of an instance (e.g. "x86_64" or "arm64")
+pub async fn get_instance_architecture(clients: &AwsClients, instance_id: &str) -> Result<String> {
+    let result = clients
+        .ec2
+        .describe_instances()
+        .instance_ids(instance_id)
+        .send()
+        .await
+        .map_err(Ec2CliError::ec2)?;
+
+    result
+        .reservations()
+        .first()
+        .and_then(|r| r.instances().first())
+        .ok_or_else(|| Ec2CliError::InstanceNotFound(instance_id.to_string()))?
+        .architecture()
+        .map(|arch| arch.as_str().to_string())
+        .ok_or_else(|| Ec2CliError::InstanceState("Unknown architecture".to_string()))
+}
+
+/// Error code DescribeInstances returns for an instance ID EC2 doesn't know
+const INSTANCE_NOT_FOUND_CODE: &str = "InvalidInstanceID.NotFound";
+
+/// Get instance state
+pub async fn get_instance_state(
+    clients: &AwsClients,
+    instance_id: &str,
+) -> Result<InstanceStateName> {
+    let result = clients
+        .ec2
+        .describe_instances()
+        .instance_ids(instance_id)
+        .send()
+        .await
+        .map_err(|e| {
+            // Instances gone for a while are rejected rather than listed as terminated
+            if e.code() == Some(INSTANCE_NOT_FOUND_CODE) {
+                Ec2CliError::InstanceNotFound(instance_id.to_string())
+            } else {
+                Ec2CliError::ec2(e)
+            }
+        })?;
+
+    let instance = result
+        .reservations()
+        .first()
+        .and_then(|r| r.instances().first())
+        .ok_or_else(|| Ec2CliError::InstanceNotFound(instance_id.to_string()))?;
+
+    instance
+        .state()
+        .and_then(|s| s.name().cloned())
+        .ok_or_else(|| Ec2CliError::InstanceState("Unknown state".to_string()))
+}
+
+/// An ec2-cli managed instance as seen in AWS
+#[derive(Debug, Clone)]
+pub struct ManagedInstance {
+    pub instance_id: String,
+    /// Value of the ec2-cli:name tag, if present
+    pub name: Option<String>,
+    pub state: InstanceStateName,
+    pub security_group_id: Option<String>,
+    /// Value of the Username tag, if present
+    pub owner: Option<String>,
+    pub vpc_id: Option<String>,
+    pub subnet_id: Option<String>,
+}
+
+impl ManagedInstance {
+    /// Whether the instance is gone or on its way out
+    pub fn is_terminated(&self) -> bool {
+        matches!(
+            self.state,
+            InstanceStateName::Terminated | InstanceStateName::ShuttingDown
+        )
+    }
+
+    /// Whether the instance belongs to `owner` (any instance does when None)
+    pub fn is_owned_by(&self, owner: Option<&str>) -> bool {
+        owner.is_none_or(|owner| self.owner.as_deref() == Some(owner))
+    }
+}
+
+/// Find the live instance whose ec2-cli:name tag is `name` among `(region, instance)`
+/// pairs, which may span several regions.
+///
+/// Names are only unique within one state file, so AWS can hold two live instances
+/// with the same name (e.g. launched from different machines). That is an error
+/// listing every candidate, since a lookup by name can't tell which is meant.
+pub fn find_by_name_tag<'a>(
+    name: &str,
+    instances: impl IntoIterator<Item = (&'a str, &'a ManagedInstance)>,
+) -> Result<Option<(&'a str, &'a ManagedInstance)>> {
+    let mut matches: Vec<_> = instances
+        .into_iter()
+        .filter(|(_, i)| i.name.as_deref() == Some(name) && !i.is_terminated())
+        .collect();
+
+    match matches.len() {
+        0 => Ok(None),
+        1 => Ok(matches.pop()),
+        _ => {
+            matches.sort_by_key(|(region, i)| (*region, i.instance_id.as_str()));
+            let candidates: Vec<_> = matches
+                .iter()
+                .map(|(region, i)| format!("{} ({})", i.instance_id, region))
+                .collect();
+            Err(Ec2CliError::AmbiguousInstanceName(format!(
+                "'{}' is the name of several instances: {}",
+                name,
+                candidates.join(", ")
+            )))
+        }
+    }
+}
+
+/// List all instances tagged as managed by ec2-cli, under the configured resource
+/// prefix, in the client's region
+pub async fn list_managed_instances(clients: &AwsClients) -> Result<Vec<ManagedInstance>> {
+    let mut instances = Vec::new();
+    let mut next_token = None;
+
+    loop {
+        let result = clients
+            .ec2
+            .describe_instances()
+            .filters(
+                Filter::builder()
+                    .name(format!("tag:{}", MANAGED_TAG_KEY))
+                    .values(MANAGED_TAG_VALUE)
+                    .build(),
+            )
+            .filters(
+                Filter::builder()
+                    .name(format!("tag:{}", DEPLOYMENT_TAG_KEY))
+                    .values(&clients.resource_prefix)
+                    .build(),
+            )
+            .set_next_token(next_token)
+            .send()
+            .await
+            .map_err(Ec2CliError::ec2)?;
+
+        for instance in result.reservations().iter().flat_map(|r| r.instances()) {
+            let Some(instance_id) = instance.instance_id() else {
+                continue;
+            };
+
+            instances.push(ManagedInstance {
+                instance_id: instance_id.to_string(),
+                name: tag_value(instance, NAME_TAG_KEY),
+                state: instance
+                    .state()
+                    .and_then(|s| s.name().cloned())
+                    .unwrap_or_else(|| InstanceStateName::from("unknown")),
+                security_group_id: instance
+                    .security_groups()
+                    .first()
+                    .and_then(|g| g.group_id())
+                    .map(String::from),
+                owner: tag_value(instance, OWNER_TAG_KEY),
+                vpc_id: instance.vpc_id().map(String::from),
+                subnet_id: instance.subnet_id().map(String::from),
+            });
+        }
+
+        next_token = result.next_token().map(String::from);
+        if next_token.is_none() {
+            break;
+        }
+    }
+
+    Ok(instances)
+}
+
+/// Value of the tag `key` on an instance
+fn tag_value(instance: &Instance, key: &str) -> Option<String> {
+    instance
+        .tags()
+        .iter()
+        .find(|t| t.key() == Some(key))
+        .and_then(|t| t.value())
+        .map(String::from)
+}
+
+/// Get the owner (Username tag) of an instance
+pub async fn get_instance_owner(clients: &AwsClients, instance_id: &str) -> Result<Option<String>> {
+    let result = clients
+        .ec2
+        .describe_instances()
+        .instance_ids(instance_id)
+        .send()
+        .await
+        .map_err(Ec2CliError::ec2)?;
+
+    let instance = result
+        .reservations()
+        .first()
+        .and_then(|r| r.instances().first())
+        .ok_or_else(|| Ec2CliError::InstanceNotFound(instance_id.to_string()))?;
+
+    Ok(tag_value(instance, OWNER_TAG_KEY))
+}
+
+/// Terminate an instance
+pub async fn terminate_instance(clients: &AwsClients, instance_id: &str) -> Result<()> {
+    clients
+        .ec2
+        .terminate_instances()
+        .instance_ids(instance_id)
+        .send()
+        .await
+        .map_err(Ec2CliError::ec2)?;
+
+    Ok(())
+}
+
+/// Wait for instance to be terminated
+pub async fn wait_for_terminated(
+    clients: &AwsClients,
     instance_id: &str,
-    username: &str,
-    ssh_key_path: Option<&str>,
     timeout_secs: u64,
 ) -> Result<()> {
     let start = std::time::Instant::now();
     let timeout = std::time::Duration::from_secs(timeout_secs);
-    let target = format!("{}@{}", username, instance_id);
-    let marker_check = format!("test -f /home/{}/.ec2-cli-git-ready", username);
 
     loop {
         if start.elapsed() > timeout {
             return Err(Ec2CliError::Timeout(format!(
-                "Git repo setup did not complete within {} seconds",
-                timeout_secs
+                "Instance {} did not terminate within {} seconds",
+                instance_id, timeout_secs
             )));
         }
 
-        let mut cmd = tokio::process::Command::new("ssh");
+        // If instance is no longer found, treat it as terminated
+        let state = match get_instance_state(clients, instance_id).await {
+            Ok(s) => s,
+            Err(Ec2CliError::InstanceNotFound(_)) => return Ok(()),
+            Err(e) => return Err(e),
+        };
+
+        match state {
+            InstanceStateName::Terminated => return Ok(()),
+            // Valid intermediate states during termination
+            InstanceStateName::ShuttingDown
+            | InstanceStateName::Stopping
+            | InstanceStateName::Stopped
+            | InstanceStateName::Running => {
+                tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
+            }
+            other => {
+                return Err(Ec2CliError::InstanceState(format!(
+                    "Instance {} in unexpected state during termination: {:?}",
+                    instance_id, other
+                )));
+            }
+        }
+    }
+}
+
+/// Root EBS volume of an instance, from its block device mappings
+fn root_volume_id(instance: &Instance) -> Option<String> {
+    let root_device = instance.root_device_name()?;
+    instance
+        .block_device_mappings()
+        .iter()
+        .find(|mapping| mapping.device_name() == Some(root_device))
+        .and_then(|mapping| mapping.ebs())
+        .and_then(|ebs| ebs.volume_id())
+        .map(String::from)
+}
+
+/// Snapshot an instance's root volume, tagged as managed under the instance name.
+///
+/// Returns the snapshot ID. The snapshot is point-in-time as soon as it's created,
+/// so the instance can be terminated while it's still pending.
+pub async fn create_root_snapshot(
+    clients: &AwsClients,
+    instance_id: &str,
+    instance_name: &str,
+    custom_tags: &HashMap<String, String>,
+) -> Result<String> {
+    let result = clients
+        .ec2
+        .describe_instances()
+        .instance_ids(instance_id)
+        .send()
+        .await
+        .map_err(Ec2CliError::ec2)?;
+
+    let instance = result
+        .reservations()
+        .first()
+        .and_then(|r| r.instances().first())
+        .ok_or_else(|| Ec2CliError::InstanceNotFound(instance_id.to_string()))?;
+
+    let volume_id = root_volume_id(instance).ok_or_else(|| {
+        Ec2CliError::ResourceNotFound(format!("No root EBS volume on instance {}", instance_id))
+    })?;
+
+    let snapshot = clients
+        .ec2
+        .create_snapshot()
+        .volume_id(&volume_id)
+        .description(format!(
+            "Root volume of ec2-cli instance {} ({})",
+            instance_name, instance_id
+        ))
+        .tag_specifications(
+            aws_sdk_ec2::types::TagSpecification::builder()
+                .resource_type(aws_sdk_ec2::types::ResourceType::Snapshot)
+                .set_tags(Some(create_tags(
+                    instance_name,
+                    &clients.resource_prefix,
+                    custom_tags,
+                )))
+                .build(),
+        )
+        .send()
+        .await
+        .map_err(Ec2CliError::ec2)?;
+
+    snapshot
+        .snapshot_id()
+        .map(String::from)
+        .ok_or_else(|| Ec2CliError::Ec2("No snapshot ID returned".to_string(), None))
+}
+
+/// Wait for a snapshot to be registered as pending or completed
+pub async fn wait_for_snapshot_started(
+    clients: &AwsClients,
+    snapshot_id: &str,
+    timeout_secs: u64,
+) -> Result<()> {
+    let started = poll_until(
+        std::time::Duration::from_secs(timeout_secs),
+        std::time::Duration::from_secs(2),
+        || async {
+            let result = clients
+                .ec2
+                .describe_snapshots()
+                .snapshot_ids(snapshot_id)
+                .send()
+                .await
+                .map_err(Ec2CliError::ec2)?;
+
+            match result.snapshots().first().and_then(|s| s.state()) {
+                Some(SnapshotState::Pending | SnapshotState::Completed) => Ok(true),
+                Some(SnapshotState::Error) => Err(Ec2CliError::Ec2(
+                    format!("Snapshot {} failed", snapshot_id),
+                    None,
+                )),
+                _ => Ok(false),
+            }
+        },
+    )
+    .await?;
+
+    if !started {
+        return Err(Ec2CliError::Timeout(format!(
+            "Snapshot {} did not start within {} seconds",
+            snapshot_id, timeout_secs
+        )));
+    }
+    Ok(())
+}
+
+/// An ec2-cli managed EBS snapshot
+#[derive(Debug, Clone)]
+pub struct ManagedSnapshot {
+    pub snapshot_id: String,
+    /// Value of the ec2-cli:name tag, if present
+    pub name: Option<String>,
+    pub volume_size_gb: Option<i32>,
+    pub state: String,
+    pub started_at: Option<String>,
+}
+
+/// List snapshots owned by this account and tagged as managed by ec2-cli under the
+/// configured resource prefix
+pub async fn list_managed_snapshots(clients: &AwsClients) -> Result<Vec<ManagedSnapshot>> {
+    let mut snapshots = Vec::new();
+    let mut next_token = None;
+
+    loop {
+        let result = clients
+            .ec2
+            .describe_snapshots()
+            .owner_ids("self")
+            .filters(
+                Filter::builder()
+                    .name(format!("tag:{}", MANAGED_TAG_KEY))
+                    .values(MANAGED_TAG_VALUE)
+                    .build(),
+            )
+            .filters(
+                Filter::builder()
+                    .name(format!("tag:{}", DEPLOYMENT_TAG_KEY))
+                    .values(&clients.resource_prefix)
+                    .build(),
+            )
+            .set_next_token(next_token)
+            .send()
+            .await
+            .map_err(Ec2CliError::ec2)?;
+
+        for snapshot in result.snapshots() {
+            let Some(snapshot_id) = snapshot.snapshot_id() else {
+                continue;
+            };
+
+            snapshots.push(ManagedSnapshot {
+                snapshot_id: snapshot_id.to_string(),
+                name: snapshot
+                    .tags()
+                    .iter()
+                    .find(|t| t.key() == Some(NAME_TAG_KEY))
+                    .and_then(|t| t.value())
+                    .map(String::from),
+                volume_size_gb: snapshot.volume_size(),
+                state: snapshot
+                    .state()
+                    .map(|s| s.as_str().to_string())
+                    .unwrap_or_else(|| "unknown".to_string()),
+                started_at: snapshot.start_time().map(|t| t.to_string()),
+            });
+        }
+
+        next_token = result.next_token().map(String::from);
+        if next_token.is_none() {
+            break;
+        }
+    }
+
+    Ok(snapshots)
+}
+
+/// Marker files the user data script creates as setup progresses
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SetupMarker {
+    /// The bare repo exists, so `push` works (written before package installation)
+    GitReady,
+    /// Every setup step has finished
+    Complete,
+}
+
+impl SetupMarker {
+    /// Path of the marker file for `username`
+    pub fn path(self, username: &str) -> String {
+        let file = match self {
+            SetupMarker::GitReady => GIT_READY_MARKER,
+            SetupMarker::Complete => READY_MARKER,
+        };
+        format!("/home/{}/{}", username, file)
+    }
 
-        if let Some(key_path) = ssh_key_path {
-            cmd.arg("-i").arg(key_path);
+    fn description(self) -> &'static str {
+        match self {
+            SetupMarker::GitReady => "Git repo setup",
+            SetupMarker::Complete => "Instance setup",
         }
+    }
+}
+
+/// Interval between checks for a setup marker
+const MARKER_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(2);
+
+/// Wait for a setup marker file to exist on the instance, checking over SSH through SSM
+pub async fn wait_for_marker(
+    instance_id: &str,
+    username: &str,
+    ssh_key_path: Option<&str>,
+    marker: SetupMarker,
+    timeout_secs: u64,
+) -> Result<()> {
+    let target = format!("{}@{}", username, instance_id);
+    let marker_check = format!("test -f {}", marker.path(username));
+    let proxy_command = ssm_proxy_command();
+
+    wait_for_marker_with(
+        marker,
+        std::time::Duration::from_secs(timeout_secs),
+        MARKER_POLL_INTERVAL,
+        || {
+            let mut cmd = tokio::process::Command::new("ssh");
 
-        cmd.arg("-o")
-            .arg(format!("ProxyCommand={}", SSM_PROXY_COMMAND))
-            .arg("-o")
-            .arg("StrictHostKeyChecking=no")
-            .arg("-o")
-            .arg("UserKnownHostsFile=/dev/null")
-            .arg("-o")
-            .arg("ConnectTimeout=5")
-            .arg("-o")
-            .arg("BatchMode=yes")
-            .arg("-o")
-            .arg("LogLevel=ERROR")
-            .arg(&target)
-            .arg(&marker_check);
-
-        // Suppress output
-        cmd.stdout(std::process::Stdio::null());
-        cmd.stderr(std::process::Stdio::null());
-
-        match cmd.status().await {
-            Ok(status) if status.success() => return Ok(()),
-            _ => {
-                // File doesn't exist yet or SSH failed, wait and retry
-                tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
+            if let Some(key_path) = ssh_key_path {
+                cmd.arg("-i").arg(key_path);
             }
+
+            cmd.arg("-o")
+                .arg(format!("ProxyCommand={}", proxy_command))
+                .arg("-o")
+                .arg("StrictHostKeyChecking=no")
+                .arg("-o")
+                .arg("UserKnownHostsFile=/dev/null")
+                .arg("-o")
+                .arg("ConnectTimeout=5")
+                .arg("-o")
+                .arg("BatchMode=yes")
+                .arg("-o")
+                .arg("LogLevel=ERROR")
+                .arg(&target)
+                .arg(&marker_check);
+
+            // Suppress output
+            cmd.stdout(std::process::Stdio::null());
+            cmd.stderr(std::process::Stdio::null());
+
+            // A missing file and a failed connection both mean "not yet"
+            async move { Ok(cmd.status().await.is_ok_and(|status| status.success())) }
+        },
+    )
+    .await
+}
+
+/// Poll `marker_exists` until it reports the marker, failing with a timeout error
+async fn wait_for_marker_with<F, Fut>(
+    marker: SetupMarker,
+    timeout: std::time::Duration,
+    interval: std::time::Duration,
+    marker_exists: F,
+) -> Result<()>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<bool>>,
+{
+    if poll_until(timeout, interval, marker_exists).await? {
+        Ok(())
+    } else {
+        Err(Ec2CliError::Timeout(format!(
+            "{} did not complete within {} seconds",
+            marker.description(),
+            timeout.as_secs()
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    #[test]
+    fn test_security_group_name() {
+        assert_eq!(
+            security_group_name("ec2-cli", "dev", "1a2b3c4d"),
+            "ec2-cli-dev-1a2b3c4d"
+        );
+        assert_eq!(
+            security_group_name("team-a", "dev", "1a2b3c4d"),
+            "team-a-dev-1a2b3c4d"
+        );
+    }
+
+    fn managed(id: &str, name: &str, state: InstanceStateName) -> ManagedInstance {
+        ManagedInstance {
+            instance_id: id.to_string(),
+            name: Some(name.to_string()),
+            state,
+            security_group_id: None,
+            owner: None,
+            vpc_id: None,
+            subnet_id: None,
         }
     }
+
+    #[test]
+    fn test_decode_console_output() {
+        // "[  OK  ] Reached target Cloud-init\n" followed by a stray 0xff byte
+        let encoded = "WyAgT0sgIF0gUmVhY2hlZCB0YXJnZXQgQ2xvdWQtaW5pdAr/";
+        assert_eq!(
+            decode_console_output(Some(encoded)).unwrap().as_deref(),
+            Some("[  OK  ] Reached target Cloud-init\n\u{fffd}")
+        );
+
+        assert!(decode_console_output(None).unwrap().is_none());
+        assert!(decode_console_output(Some("")).unwrap().is_none());
+        assert!(matches!(
+            decode_console_output(Some("not base64!")),
+            Err(Ec2CliError::Ec2(_, _))
+        ));
+    }
+
+    #[test]
+    fn test_find_by_name_tag_detects_conflicts_across_regions() {
+        let east = [
+            managed("i-1", "dev", InstanceStateName::Running),
+            managed("i-2", "build", InstanceStateName::Running),
+        ];
+        let west = [
+            managed("i-3", "dev", InstanceStateName::Stopped),
+            managed("i-4", "build", InstanceStateName::Terminated),
+        ];
+        let found = || {
+            east.iter()
+                .map(|i| ("us-east-1", i))
+                .chain(west.iter().map(|i| ("us-west-2", i)))
+        };
+
+        // Terminated instances don't count as candidates
+        let (region, instance) = find_by_name_tag("build", found()).unwrap().unwrap();
+        assert_eq!(
+            (region, instance.instance_id.as_str()),
+            ("us-east-1", "i-2")
+        );
+        assert!(find_by_name_tag("missing", found()).unwrap().is_none());
+
+        let err = find_by_name_tag("dev", found()).unwrap_err();
+        assert!(matches!(
+            err,
+            Ec2CliError::AmbiguousInstanceName(ref msg)
+                if msg.ends_with("i-1 (us-east-1), i-3 (us-west-2)")
+        ));
+    }
+
+    fn capacity_error() -> Ec2CliError {
+        Ec2CliError::Ec2(
+            "InsufficientInstanceCapacity: There is no Spot capacity available".to_string(),
+            None,
+        )
+    }
+
+    #[tokio::test]
+    async fn test_spot_failure_falls_back_to_on_demand() {
+        let tried = RefCell::new(Vec::new());
+        let result = launch_with_fallback(LaunchMode::attempts(true), |mode| {
+            tried.borrow_mut().push(mode);
+            async move {
+                match mode {
+                    LaunchMode::Spot => Err(capacity_error()),
+                    LaunchMode::OnDemand => Ok("i-123".to_string()),
+                }
+            }
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(result, ("i-123".to_string(), LaunchMode::OnDemand));
+        assert_eq!(
+            *tried.borrow(),
+            vec![LaunchMode::Spot, LaunchMode::OnDemand]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_spot_success_does_not_fall_back() {
+        let result = launch_with_fallback(LaunchMode::attempts(true), |mode| async move {
+            Ok(format!("i-{}", mode))
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(result, ("i-spot".to_string(), LaunchMode::Spot));
+    }
+
+    #[tokio::test]
+    async fn test_other_spot_errors_are_not_retried() {
+        let tried = RefCell::new(Vec::new());
+        let result = launch_with_fallback(LaunchMode::attempts(true), |mode| {
+            tried.borrow_mut().push(mode);
+            async {
+                Err(Ec2CliError::Ec2(
+                    "UnauthorizedOperation: denied".to_string(),
+                    None,
+                ))
+            }
+        })
+        .await;
+
+        assert!(result.is_err());
+        assert_eq!(*tried.borrow(), vec![LaunchMode::Spot]);
+    }
+
+    #[test]
+    fn test_instance_profile_not_ready_detection() {
+        assert!(is_instance_profile_not_ready(&Ec2CliError::Ec2(
+            "InvalidParameterValue: Invalid IAM Instance Profile name".to_string(),
+            None
+        )));
+        assert!(!is_instance_profile_not_ready(&capacity_error()));
+    }
+
+    #[test]
+    fn test_explain_launch_error() {
+        let explain_as = |message: &str, mode| {
+            explain_launch_error(
+                Ec2CliError::Ec2(message.to_string(), None),
+                "g5.xlarge",
+                mode,
+                "us-west-2",
+            )
+            .to_string()
+        };
+        let explain = |message: &str| explain_as(message, LaunchMode::OnDemand);
+
+        let vcpu_limit = "VcpuLimitExceeded: You have requested more vCPU capacity than your current vCPU limit of 0 allows";
+        let message = explain(vcpu_limit);
+        assert!(message.starts_with("EC2 quota exceeded: launching g5.xlarge (on-demand)"));
+        assert!(message.contains("'Running On-Demand G and VT instances' quota"));
+        // Spot launches count against the Spot quota instead
+        let message = explain_as(vcpu_limit, LaunchMode::Spot);
+        assert!(message.starts_with("EC2 quota exceeded: launching g5.xlarge (spot)"));
+        assert!(message.contains("'All G and VT Spot Instance Requests' quota"));
+        assert!(message.contains(
+            "https://us-west-2.console.aws.amazon.com/servicequotas/home/services/ec2/quotas"
+        ));
+
+        let message =
+            explain("InstanceLimitExceeded: Your quota allows for 0 more running instance(s)");
+        assert!(message.contains("can't run more g5.xlarge instances in us-west-2"));
+        assert!(message.contains("servicequotas"));
+
+        let message = explain("InvalidAMIID.NotFound: The image id '[ami-123]' does not exist");
+        assert!(message.contains("isn't available in us-west-2"));
+        assert!(message.contains("--refresh-ami"));
+        // The original error stays in the message
+        assert!(message.contains("ami-123"));
+
+        let message = explain("Unsupported: Your requested instance type (g5.xlarge) is not supported in your requested Availability Zone (us-west-2d)");
+        assert!(message.contains("g5.xlarge isn't offered"));
+        assert!(message.contains("us-west-2d"));
+
+        // Anything else, and other kinds of error, pass through
+        assert_eq!(
+            explain("UnauthorizedOperation: denied"),
+            "AWS EC2 error: UnauthorizedOperation: denied"
+        );
+        assert!(matches!(
+            explain_launch_error(
+                Ec2CliError::Cancelled,
+                "t3.micro",
+                LaunchMode::OnDemand,
+                "us-east-1"
+            ),
+            Ec2CliError::Cancelled
+        ));
+    }
+
+    #[test]
+    fn test_vcpu_quota() {
+        let on_demand = |instance_type| vcpu_quota(instance_type, LaunchMode::OnDemand);
+        assert!(on_demand("t3.large").contains("Standard"));
+        assert!(on_demand("m7g.xlarge").contains("Standard"));
+        assert!(on_demand("g6.2xlarge").contains("G and VT"));
+        assert!(on_demand("vt1.3xlarge").contains("G and VT"));
+        assert!(on_demand("p4d.24xlarge").contains(" P "));
+        assert!(on_demand("inf2.xlarge").contains(" Inf "));
+        assert!(on_demand("trn1.2xlarge").contains(" Trn "));
+        assert!(on_demand("x2idn.16xlarge").contains(" X "));
+
+        let spot = |instance_type| vcpu_quota(instance_type, LaunchMode::Spot);
+        assert_eq!(
+            spot("t3.large"),
+            "All Standard (A, C, D, H, I, M, R, T, Z) Spot Instance Requests"
+        );
+        assert_eq!(spot("g6.2xlarge"), "All G and VT Spot Instance Requests");
+        assert_eq!(spot("p5.48xlarge"), "All P5 Spot Instance Requests");
+        assert_eq!(
+            spot("p4d.24xlarge"),
+            "All P4, P3 and P2 Spot Instance Requests"
+        );
+        assert_eq!(spot("x2idn.16xlarge"), "All X Spot Instance Requests");
+    }
+
+    #[tokio::test]
+    async fn test_on_demand_only_is_not_retried() {
+        let tried = RefCell::new(Vec::new());
+        let result = launch_with_fallback(LaunchMode::attempts(false), |mode| {
+            tried.borrow_mut().push(mode);
+            async { Err(capacity_error()) }
+        })
+        .await;
+
+        assert!(result.is_err());
+        assert_eq!(*tried.borrow(), vec![LaunchMode::OnDemand]);
+    }
+
+    fn image(id: &str, name: &str, created: &str) -> Image {
+        Image::builder()
+            .image_id(id)
+            .name(name)
+            .creation_date(created)
+            .build()
+    }
+
+    #[test]
+    fn test_ami_name_patterns() {
+        let patterns = ami_name_patterns("ubuntu-24.04", "arm64").unwrap();
+        assert_eq!(
+            patterns,
+            vec![
+                "ubuntu/images/hvm-ssd-gp3/ubuntu-noble-24.04-arm64-server-*",
+                "ubuntu/images/hvm-ssd/ubuntu-noble-24.04-arm64-server-*",
+            ]
+        );
+        assert_eq!(
+            ami_name_patterns("ubuntu-22.04", "amd64").unwrap()[0],
+            "ubuntu/images/hvm-ssd/ubuntu-jammy-22.04-amd64-server-*"
+        );
+        assert!(ami_name_patterns("debian-12", "amd64").is_err());
+    }
+
+    #[test]
+    fn test_matches_name_pattern() {
+        let pattern = "ubuntu/images/hvm-ssd-gp3/ubuntu-noble-24.04-amd64-server-*";
+        assert!(matches_name_pattern(
+            "ubuntu/images/hvm-ssd-gp3/ubuntu-noble-24.04-amd64-server-20240423",
+            pattern
+        ));
+        assert!(!matches_name_pattern(
+            "ubuntu/images/hvm-ssd/ubuntu-noble-24.04-amd64-server-20240423",
+            pattern
+        ));
+        assert!(matches_name_pattern("a-x-b-y-c", "a-*-b-*-c"));
+        assert!(!matches_name_pattern("a-x-c", "a-*-b-*"));
+        assert!(matches_name_pattern("exact", "exact"));
+        assert!(!matches_name_pattern("exactly", "exact"));
+    }
+
+    #[test]
+    fn test_select_latest_image_prefers_primary_pattern() {
+        let patterns = ami_name_patterns("ubuntu-24.04", "amd64").unwrap();
+        let images = vec![
+            image(
+                "ami-old",
+                "ubuntu/images/hvm-ssd-gp3/ubuntu-noble-24.04-amd64-server-20240423",
+                "2024-04-23T00:00:00.000Z",
+            ),
+            image(
+                "ami-new",
+                "ubuntu/images/hvm-ssd-gp3/ubuntu-noble-24.04-amd64-server-20240801",
+                "2024-08-01T00:00:00.000Z",
+            ),
+            // Newer, but only matches the fallback pattern
+            image(
+                "ami-fallback",
+                "ubuntu/images/hvm-ssd/ubuntu-noble-24.04-amd64-server-20240901",
+                "2024-09-01T00:00:00.000Z",
+            ),
+        ];
+
+        let (id, pattern) = select_latest_image(&images, &patterns).unwrap();
+        assert_eq!(id, "ami-new");
+        assert_eq!(pattern, patterns[0]);
+    }
+
+    #[test]
+    fn test_select_latest_image_uses_fallback_pattern() {
+        let patterns = ami_name_patterns("ubuntu-22.04", "amd64").unwrap();
+        let images = vec![image(
+            "ami-gp3",
+            "ubuntu/images/hvm-ssd-gp3/ubuntu-jammy-22.04-amd64-server-20250101",
+            "2025-01-01T00:00:00.000Z",
+        )];
+
+        let (id, pattern) = select_latest_image(&images, &patterns).unwrap();
+        assert_eq!(id, "ami-gp3");
+        assert_eq!(pattern, patterns[1]);
+
+        assert!(select_latest_image(&[], &patterns).is_none());
+    }
+
+    #[test]
+    fn test_root_block_device_kms_key() {
+        let mut root_volume = RootVolumeConfig::default();
+        let ebs = root_block_device(&root_volume, true).ebs().unwrap().clone();
+        assert_eq!(ebs.encrypted(), Some(true));
+        assert_eq!(ebs.kms_key_id(), None);
+
+        root_volume.kms_key_id = Some("alias/ebs-dev".to_string());
+        let mapping = root_block_device(&root_volume, true);
+        assert_eq!(mapping.device_name(), Some("/dev/sda1"));
+        let ebs = mapping.ebs().unwrap();
+        assert_eq!(ebs.encrypted(), Some(true));
+        assert_eq!(ebs.kms_key_id(), Some("alias/ebs-dev"));
+        assert_eq!(ebs.volume_size(), Some(30));
+
+        // A key is meaningless without encryption, so it isn't sent
+        let ebs = root_block_device(&root_volume, false)
+            .ebs()
+            .unwrap()
+            .clone();
+        assert_eq!(ebs.encrypted(), Some(false));
+        assert_eq!(ebs.kms_key_id(), None);
+    }
+
+    #[test]
+    fn test_root_volume_id() {
+        use aws_sdk_ec2::types::{EbsInstanceBlockDevice, InstanceBlockDeviceMapping};
+
+        let mapping = |device: &str, volume: &str| {
+            InstanceBlockDeviceMapping::builder()
+                .device_name(device)
+                .ebs(EbsInstanceBlockDevice::builder().volume_id(volume).build())
+                .build()
+        };
+        let instance = Instance::builder()
+            .root_device_name("/dev/sda1")
+            .block_device_mappings(mapping("/dev/sdf", "vol-data"))
+            .block_device_mappings(mapping("/dev/sda1", "vol-root"))
+            .build();
+        assert_eq!(root_volume_id(&instance), Some("vol-root".to_string()));
+
+        // Without a root device name there's nothing to match
+        let no_root = Instance::builder()
+            .block_device_mappings(mapping("/dev/sda1", "vol-root"))
+            .build();
+        assert_eq!(root_volume_id(&no_root), None);
+    }
+
+    #[test]
+    fn test_is_owned_by() {
+        let instance = |owner: Option<&str>| ManagedInstance {
+            instance_id: "i-1".to_string(),
+            name: Some("dev".to_string()),
+            state: InstanceStateName::Running,
+            security_group_id: None,
+            owner: owner.map(String::from),
+            vpc_id: None,
+            subnet_id: None,
+        };
+
+        assert!(instance(Some("alice")).is_owned_by(Some("alice")));
+        assert!(!instance(Some("bob")).is_owned_by(Some("alice")));
+        // Untagged instances don't belong to anyone when ownership is enforced
+        assert!(!instance(None).is_owned_by(Some("alice")));
+        assert!(instance(None).is_owned_by(None));
+        assert!(instance(Some("bob")).is_owned_by(None));
+    }
+
+    #[test]
+    fn test_setup_marker_paths() {
+        assert_eq!(
+            SetupMarker::GitReady.path("ubuntu"),
+            "/home/ubuntu/.ec2-cli-git-ready"
+        );
+        assert_eq!(
+            SetupMarker::Complete.path("ubuntu"),
+            "/home/ubuntu/.ec2-cli-ready"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_marker_until_present() {
+        let checks = RefCell::new(0);
+        wait_for_marker_with(
+            SetupMarker::GitReady,
+            std::time::Duration::from_secs(5),
+            std::time::Duration::from_millis(1),
+            || {
+                *checks.borrow_mut() += 1;
+                let present = *checks.borrow() >= 3;
+                async move { Ok(present) }
+            },
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(*checks.borrow(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_marker_times_out() {
+        let err = wait_for_marker_with(
+            SetupMarker::Complete,
+            std::time::Duration::from_millis(10),
+            std::time::Duration::from_millis(1),
+            || async { Ok(false) },
+        )
+        .await
+        .unwrap_err();
+
+        assert!(matches!(err, Ec2CliError::Timeout(_)));
+        assert!(err.to_string().contains("Instance setup"));
+    }
 }
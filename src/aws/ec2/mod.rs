@@ -1 +1,2 @@
+pub mod ami_cache;
 pub mod instance;
@@ -1,10 +1,24 @@
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use aws_sdk_ec2::types::{Filter, RouteState, State, Subnet, VpcEndpoint};
+
 use crate::config::Settings;
+use crate::ui::info;
 use crate::{Ec2CliError, Result};
 
 use super::client::{
-    get_default_vpc, AwsClients, DEPLOYMENT_TAG_KEY, DEPLOYMENT_TAG_VALUE, MANAGED_TAG_KEY,
+    get_default_vpc, machine_hash, AwsClients, DEPLOYMENT_TAG_KEY, MANAGED_TAG_KEY,
     MANAGED_TAG_VALUE,
 };
+use super::ec2::instance::{list_managed_instances, ManagedInstance};
+use super::retry::poll_until;
+
+/// How long to wait for a new instance profile to become visible
+pub const IAM_PROPAGATION_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// How often to check whether IAM changes have propagated
+pub const IAM_POLL_INTERVAL: Duration = Duration::from_secs(2);
 
 /// Infrastructure resources for ec2-cli
 #[derive(Debug, Clone)]
@@ -14,24 +28,43 @@ pub struct Infrastructure {
     pub instance_profile_arn: String,
 }
 
+/// The network ec2-cli's instances already run in
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExistingNetwork {
+    pub vpc_id: String,
+    pub subnet_id: String,
+    /// Live managed instances in the subnet
+    pub instance_count: usize,
+}
+
 impl Infrastructure {
     /// Get or create infrastructure for ec2-cli
-    /// VPC and subnet come from settings (configured via `config init`)
+    /// VPC and subnet come from settings (configured via `config init`); the
+    /// default VPC is only used in managed network mode
     /// IAM resources are created if they don't exist
     pub async fn get_or_create(clients: &AwsClients) -> Result<Self> {
         let settings = Settings::load()?;
+        let (vpc_id, subnet_id) = settings.launch_network()?;
+        let subnet_id = subnet_id.to_string();
 
-        // Get VPC ID from settings or use default VPC
-        let vpc_id = match settings.vpc_id {
-            Some(vpc_id) => vpc_id,
+        let vpc_id = match vpc_id {
+            Some(vpc_id) => vpc_id.to_string(),
             None => get_default_vpc(clients).await?,
         };
 
-        // Get subnet ID from settings (required)
-        let subnet_id = settings.subnet_id.ok_or(Ec2CliError::SubnetNotConfigured)?;
-
         // Validate subnet exists and is in the VPC
-        validate_subnet(clients, &subnet_id, &vpc_id).await?;
+        let subnet = validate_subnet(clients, &subnet_id, &vpc_id).await?;
+
+        // Fail fast if instances in this subnet could never register with SSM
+        let connectivity = SubnetConnectivity::describe(clients, &subnet, &vpc_id).await?;
+        if !connectivity.reaches_ssm() {
+            // Some endpoints but not all usually means one was deleted since the VPC was set up
+            let missing = connectivity.missing_endpoint_services();
+            if missing.len() < SSM_ENDPOINT_SERVICES.len() {
+                return Err(Ec2CliError::SsmEndpointsMissing(vpc_id, missing.join(", ")));
+            }
+            return Err(Ec2CliError::SsmUnreachable(subnet_id));
+        }
 
         // Get or create IAM resources
         let instance_profile_arn = get_or_create_iam_resources(clients).await?;
@@ -42,10 +75,38 @@ impl Infrastructure {
             instance_profile_arn,
         })
     }
+
+    /// Find the subnet managed instances in the region were launched into, so
+    /// `config init` can offer it instead of a subnet that needs checking afresh.
+    /// With instances in several subnets, the one with the most wins.
+    pub async fn find_existing(clients: &AwsClients) -> Result<Option<ExistingNetwork>> {
+        let instances = list_managed_instances(clients).await?;
+        Ok(busiest_network(&instances))
+    }
+}
+
+/// The VPC and subnet holding the most live instances (ties go to the lowest subnet ID)
+fn busiest_network(instances: &[ManagedInstance]) -> Option<ExistingNetwork> {
+    let mut counts: BTreeMap<(&str, &str), usize> = BTreeMap::new();
+    for instance in instances.iter().filter(|i| !i.is_terminated()) {
+        if let (Some(subnet_id), Some(vpc_id)) = (&instance.subnet_id, &instance.vpc_id) {
+            *counts.entry((subnet_id, vpc_id)).or_default() += 1;
+        }
+    }
+
+    counts
+        .into_iter()
+        .rev()
+        .max_by_key(|(_, count)| *count)
+        .map(|((subnet_id, vpc_id), instance_count)| ExistingNetwork {
+            vpc_id: vpc_id.to_string(),
+            subnet_id: subnet_id.to_string(),
+            instance_count,
+        })
 }
 
 /// Validate that a subnet exists and is in the expected VPC
-async fn validate_subnet(clients: &AwsClients, subnet_id: &str, vpc_id: &str) -> Result<()> {
+async fn validate_subnet(clients: &AwsClients, subnet_id: &str, vpc_id: &str) -> Result<Subnet> {
     let subnets = clients
         .ec2
         .describe_subnets()
@@ -67,14 +128,175 @@ async fn validate_subnet(clients: &AwsClients, subnet_id: &str, vpc_id: &str) ->
         )));
     }
 
-    Ok(())
+    Ok(subnet.clone())
+}
+
+/// VPC endpoint services the SSM agent needs when there is no internet route
+const SSM_ENDPOINT_SERVICES: &[&str] = &["ssm", "ssmmessages", "ec2messages"];
+
+/// Where a subnet's default (0.0.0.0/0) route sends traffic
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DefaultRoute {
+    /// Internet gateway; only usable by instances with a public IP
+    InternetGateway,
+    /// NAT gateway, transit gateway, or other egress that works without a public IP
+    Nat,
+}
+
+/// Network facts that decide whether an instance can reach SSM
+#[derive(Debug, Clone, Default)]
+struct SubnetConnectivity {
+    default_routes: Vec<DefaultRoute>,
+    maps_public_ip: bool,
+    /// Service names of available interface endpoints in the VPC
+    endpoint_services: Vec<String>,
+}
+
+impl SubnetConnectivity {
+    async fn describe(clients: &AwsClients, subnet: &Subnet, vpc_id: &str) -> Result<Self> {
+        let subnet_id = subnet.subnet_id().unwrap_or_default();
+
+        // Subnets without an explicit association use the VPC's main route table
+        let mut tables = clients
+            .ec2
+            .describe_route_tables()
+            .filters(
+                Filter::builder()
+                    .name("association.subnet-id")
+                    .values(subnet_id)
+                    .build(),
+            )
+            .send()
+            .await
+            .map_err(Ec2CliError::ec2)?
+            .route_tables()
+            .to_vec();
+        if tables.is_empty() {
+            tables = clients
+                .ec2
+                .describe_route_tables()
+                .filters(Filter::builder().name("vpc-id").values(vpc_id).build())
+                .filters(
+                    Filter::builder()
+                        .name("association.main")
+                        .values("true")
+                        .build(),
+                )
+                .send()
+                .await
+                .map_err(Ec2CliError::ec2)?
+                .route_tables()
+                .to_vec();
+        }
+
+        let default_routes = tables
+            .iter()
+            .flat_map(|t| t.routes())
+            .filter(|r| r.destination_cidr_block() == Some("0.0.0.0/0"))
+            .filter(|r| r.state() != Some(&RouteState::Blackhole))
+            .map(|r| match r.gateway_id() {
+                Some(gw) if gw.starts_with("igw-") => DefaultRoute::InternetGateway,
+                _ => DefaultRoute::Nat,
+            })
+            .collect();
+
+        let endpoints = clients
+            .ec2
+            .describe_vpc_endpoints()
+            .filters(Filter::builder().name("vpc-id").values(vpc_id).build())
+            .send()
+            .await
+            .map_err(Ec2CliError::ec2)?;
+
+        Ok(Self {
+            default_routes,
+            maps_public_ip: subnet.map_public_ip_on_launch().unwrap_or(false),
+            endpoint_services: available_endpoint_services(endpoints.vpc_endpoints()),
+        })
+    }
+
+    /// SSM endpoint services the VPC has no available endpoint for
+    fn missing_endpoint_services(&self) -> Vec<&'static str> {
+        // Endpoint service names look like com.amazonaws.<region>.ssm
+        SSM_ENDPOINT_SERVICES
+            .iter()
+            .copied()
+            .filter(|service| {
+                !self
+                    .endpoint_services
+                    .iter()
+                    .any(|name| name.rsplit('.').next() == Some(*service))
+            })
+            .collect()
+    }
+
+    /// Whether the SSM agent on an instance in this subnet can reach the SSM service
+    fn reaches_ssm(&self) -> bool {
+        let has_internet = self.default_routes.iter().any(|route| match route {
+            DefaultRoute::InternetGateway => self.maps_public_ip,
+            DefaultRoute::Nat => true,
+        });
+
+        has_internet || self.missing_endpoint_services().is_empty()
+    }
+}
+
+/// Service names of the endpoints that are available; ones being created or
+/// deleted don't carry traffic
+fn available_endpoint_services(endpoints: &[VpcEndpoint]) -> Vec<String> {
+    endpoints
+        .iter()
+        .filter(|e| e.state() == Some(&State::Available))
+        .filter_map(|e| e.service_name().map(String::from))
+        .collect()
+}
+
+/// Names of the per-machine IAM role and instance profile under `prefix`
+fn iam_resource_names(prefix: &str, hash: &str) -> (String, String) {
+    (
+        format!("{}-instance-role-{}", prefix, hash),
+        format!("{}-instance-profile-{}", prefix, hash),
+    )
+}
+
+/// Check whether this machine's IAM role and instance profile exist, without creating them
+pub async fn iam_resources_exist(clients: &AwsClients) -> Result<bool> {
+    let (role_name, profile_name) = iam_resource_names(&clients.resource_prefix, &machine_hash());
+
+    if let Err(e) = clients.iam.get_role().role_name(&role_name).send().await {
+        let is_not_found = e
+            .as_service_error()
+            .map(|se| se.is_no_such_entity_exception())
+            .unwrap_or(false);
+        if !is_not_found {
+            return Err(Ec2CliError::iam(e));
+        }
+        return Ok(false);
+    }
+
+    if let Err(e) = clients
+        .iam
+        .get_instance_profile()
+        .instance_profile_name(&profile_name)
+        .send()
+        .await
+    {
+        let is_not_found = e
+            .as_service_error()
+            .map(|se| se.is_no_such_entity_exception())
+            .unwrap_or(false);
+        if !is_not_found {
+            return Err(Ec2CliError::iam(e));
+        }
+        return Ok(false);
+    }
+
+    Ok(true)
 }
 
 /// Get or create IAM role and instance profile for SSM
 async fn get_or_create_iam_resources(clients: &AwsClients) -> Result<String> {
-    let hash = super::client::machine_hash();
-    let role_name = format!("ec2-cli-instance-role-{}", hash);
-    let profile_name = format!("ec2-cli-instance-profile-{}", hash);
+    let (role_name, profile_name) = iam_resource_names(&clients.resource_prefix, &machine_hash());
 
     // Check if role already exists
     let role_exists = match clients.iam.get_role().role_name(&role_name).send().await {
@@ -93,7 +315,7 @@ async fn get_or_create_iam_resources(clients: &AwsClients) -> Result<String> {
     };
 
     if !role_exists {
-        println!("  Creating IAM role and instance profile...");
+        info!("  Creating IAM role and instance profile...");
 
         // Create the role
         let assume_role_policy = r#"{
@@ -120,14 +342,14 @@ async fn get_or_create_iam_resources(clients: &AwsClients) -> Result<String> {
                     .key(MANAGED_TAG_KEY)
                     .value(MANAGED_TAG_VALUE)
                     .build()
-                    .map_err(|e| Ec2CliError::Iam(e.to_string()))?,
+                    .map_err(|e| Ec2CliError::Iam(e.to_string(), Some(e.into())))?,
             )
             .tags(
                 aws_sdk_iam::types::Tag::builder()
                     .key(DEPLOYMENT_TAG_KEY)
-                    .value(DEPLOYMENT_TAG_VALUE)
+                    .value(&clients.resource_prefix)
                     .build()
-                    .map_err(|e| Ec2CliError::Iam(e.to_string()))?,
+                    .map_err(|e| Ec2CliError::Iam(e.to_string(), Some(e.into())))?,
             )
             .send()
             .await
@@ -139,12 +361,12 @@ async fn get_or_create_iam_resources(clients: &AwsClients) -> Result<String> {
             .iam
             .attach_role_policy()
             .role_name(&role_name)
-            .policy_arn("arn:aws:iam::aws:policy/AmazonSSMManagedInstanceCore")
+            .policy_arn(SSM_MANAGED_POLICY_ARN)
             .send()
             .await
             .map_err(Ec2CliError::iam)?;
     } else {
-        // Role exists - ensure managed policy is attached (migration from old inline policy)
+        // Role exists - ensure the managed policy is still attached
         ensure_managed_policy_attached(clients, &role_name).await?;
     }
 
@@ -172,9 +394,9 @@ async fn get_or_create_iam_resources(clients: &AwsClients) -> Result<String> {
 
     let profile_arn = match existing_profile {
         Some(p) => {
-            let profile = p
-                .instance_profile()
-                .ok_or_else(|| Ec2CliError::Iam("No instance profile in response".to_string()))?;
+            let profile = p.instance_profile().ok_or_else(|| {
+                Ec2CliError::Iam("No instance profile in response".to_string(), None)
+            })?;
 
             // Verify role is attached to existing profile (handles partial creation failures)
             if profile.roles().is_empty() {
@@ -187,8 +409,7 @@ async fn get_or_create_iam_resources(clients: &AwsClients) -> Result<String> {
                     .await
                     .map_err(Ec2CliError::iam)?;
 
-                // Wait for propagation
-                tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
+                wait_for_instance_profile(clients, &profile_name).await?;
             }
 
             profile.arn().to_string()
@@ -204,14 +425,14 @@ async fn get_or_create_iam_resources(clients: &AwsClients) -> Result<String> {
                         .key(MANAGED_TAG_KEY)
                         .value(MANAGED_TAG_VALUE)
                         .build()
-                        .map_err(|e| Ec2CliError::Iam(e.to_string()))?,
+                        .map_err(|e| Ec2CliError::Iam(e.to_string(), Some(e.into())))?,
                 )
                 .tags(
                     aws_sdk_iam::types::Tag::builder()
                         .key(DEPLOYMENT_TAG_KEY)
-                        .value(DEPLOYMENT_TAG_VALUE)
+                        .value(&clients.resource_prefix)
                         .build()
-                        .map_err(|e| Ec2CliError::Iam(e.to_string()))?,
+                        .map_err(|e| Ec2CliError::Iam(e.to_string(), Some(e.into())))?,
                 )
                 .send()
                 .await
@@ -227,12 +448,13 @@ async fn get_or_create_iam_resources(clients: &AwsClients) -> Result<String> {
                 .await
                 .map_err(Ec2CliError::iam)?;
 
-            // Wait a bit for propagation
-            tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
+            wait_for_instance_profile(clients, &profile_name).await?;
 
             profile
                 .instance_profile()
-                .ok_or_else(|| Ec2CliError::Iam("No instance profile in response".to_string()))?
+                .ok_or_else(|| {
+                    Ec2CliError::Iam("No instance profile in response".to_string(), None)
+                })?
                 .arn()
                 .to_string()
         }
@@ -241,47 +463,296 @@ async fn get_or_create_iam_resources(clients: &AwsClients) -> Result<String> {
     Ok(profile_arn)
 }
 
-const SSM_MANAGED_POLICY_ARN: &str = "arn:aws:iam::aws:policy/AmazonSSMManagedInstanceCore";
+/// Wait until a newly created or updated instance profile can be read back with its role.
+///
+/// IAM is eventually consistent, so EC2 may still reject the profile briefly after this
+/// returns; `launch_instance` retries for that case.
+async fn wait_for_instance_profile(clients: &AwsClients, profile_name: &str) -> Result<()> {
+    let ready = poll_until(IAM_PROPAGATION_TIMEOUT, IAM_POLL_INTERVAL, || async {
+        match clients
+            .iam
+            .get_instance_profile()
+            .instance_profile_name(profile_name)
+            .send()
+            .await
+        {
+            Ok(output) => Ok(output
+                .instance_profile()
+                .is_some_and(|profile| !profile.roles().is_empty())),
+            Err(e) => {
+                let is_not_found = e
+                    .as_service_error()
+                    .map(|se| se.is_no_such_entity_exception())
+                    .unwrap_or(false);
+                if is_not_found {
+                    Ok(false)
+                } else {
+                    Err(Ec2CliError::iam(e))
+                }
+            }
+        }
+    })
+    .await?;
+
+    if !ready {
+        return Err(Ec2CliError::Iam(
+            format!(
+                "Instance profile {} was not available after {}s",
+                profile_name,
+                IAM_PROPAGATION_TIMEOUT.as_secs()
+            ),
+            None,
+        ));
+    }
 
-/// Ensure the managed SSM policy is attached to an existing role
-/// This handles migration from the old inline policy to the managed policy
-async fn ensure_managed_policy_attached(clients: &AwsClients, role_name: &str) -> Result<()> {
-    // Check if managed policy is already attached
-    let attached_policies = clients
-        .iam
-        .list_attached_role_policies()
-        .role_name(role_name)
-        .send()
-        .await
-        .map_err(Ec2CliError::iam)?;
+    Ok(())
+}
 
-    let has_managed_policy = attached_policies
-        .attached_policies()
-        .iter()
-        .any(|p| p.policy_arn() == Some(SSM_MANAGED_POLICY_ARN));
+const SSM_MANAGED_POLICY_ARN: &str = "arn:aws:iam::aws:policy/AmazonSSMManagedInstanceCore";
 
-    if !has_managed_policy {
-        println!("  Upgrading IAM role to use managed SSM policy...");
+/// Whether the SSM managed policy is among a role's attached policy ARNs
+fn has_ssm_managed_policy<'a>(attached_arns: impl IntoIterator<Item = &'a str>) -> bool {
+    attached_arns
+        .into_iter()
+        .any(|arn| arn == SSM_MANAGED_POLICY_ARN)
+}
 
-        // Attach managed policy
-        clients
+/// Ensure the managed SSM policy is attached to an existing role.
+///
+/// Without it, instances never register with SSM and `up` times out waiting, so the
+/// policy is re-attached if it was detached (or if the role predates the managed
+/// policy and still uses the old inline one).
+async fn ensure_managed_policy_attached(clients: &AwsClients, role_name: &str) -> Result<()> {
+    // Collect every attached policy; the listing is paginated
+    let mut attached_arns = Vec::new();
+    let mut marker = None;
+    loop {
+        let page = clients
             .iam
-            .attach_role_policy()
+            .list_attached_role_policies()
             .role_name(role_name)
-            .policy_arn(SSM_MANAGED_POLICY_ARN)
+            .set_marker(marker)
             .send()
             .await
             .map_err(Ec2CliError::iam)?;
 
-        // Delete old inline policy if it exists (ignore errors - may not exist)
-        let _ = clients
-            .iam
-            .delete_role_policy()
-            .role_name(role_name)
-            .policy_name("ec2-cli-ssm-policy")
-            .send()
-            .await;
+        attached_arns.extend(
+            page.attached_policies()
+                .iter()
+                .filter_map(|p| p.policy_arn().map(String::from)),
+        );
+
+        marker = page
+            .marker()
+            .filter(|_| page.is_truncated())
+            .map(String::from);
+        if marker.is_none() {
+            break;
+        }
+    }
+
+    if has_ssm_managed_policy(attached_arns.iter().map(String::as_str)) {
+        return Ok(());
     }
 
+    eprintln!(
+        "  Warning: IAM role {} is missing the AmazonSSMManagedInstanceCore policy, re-attaching it",
+        role_name
+    );
+
+    clients
+        .iam
+        .attach_role_policy()
+        .role_name(role_name)
+        .policy_arn(SSM_MANAGED_POLICY_ARN)
+        .send()
+        .await
+        .map_err(|e| {
+            let cause = Ec2CliError::iam(e);
+            Ec2CliError::Iam(
+                format!(
+                    "IAM role {} is missing {} and it could not be re-attached ({}). \
+                 Instances using this role can't connect to SSM. Attach it with: \
+                 aws iam attach-role-policy --role-name {} --policy-arn {}",
+                    role_name, SSM_MANAGED_POLICY_ARN, cause, role_name, SSM_MANAGED_POLICY_ARN
+                ),
+                Some(Box::new(cause)),
+            )
+        })?;
+
+    // Delete old inline policy if it exists (ignore errors - may not exist)
+    let _ = clients
+        .iam
+        .delete_role_policy()
+        .role_name(role_name)
+        .policy_name("ec2-cli-ssm-policy")
+        .send()
+        .await;
+
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aws_sdk_ec2::types::InstanceStateName;
+
+    fn endpoints(services: &[&str]) -> Vec<String> {
+        services
+            .iter()
+            .map(|s| format!("com.amazonaws.us-east-1.{}", s))
+            .collect()
+    }
+
+    #[test]
+    fn test_nat_route_reaches_ssm() {
+        let connectivity = SubnetConnectivity {
+            default_routes: vec![DefaultRoute::Nat],
+            ..Default::default()
+        };
+        assert!(connectivity.reaches_ssm());
+    }
+
+    #[test]
+    fn test_internet_gateway_requires_public_ip() {
+        let mut connectivity = SubnetConnectivity {
+            default_routes: vec![DefaultRoute::InternetGateway],
+            maps_public_ip: false,
+            ..Default::default()
+        };
+        assert!(!connectivity.reaches_ssm());
+
+        connectivity.maps_public_ip = true;
+        assert!(connectivity.reaches_ssm());
+    }
+
+    #[test]
+    fn test_endpoints_without_internet_reach_ssm() {
+        let connectivity = SubnetConnectivity {
+            endpoint_services: endpoints(&["ssm", "ssmmessages", "ec2messages", "s3"]),
+            ..Default::default()
+        };
+        assert!(connectivity.reaches_ssm());
+    }
+
+    #[test]
+    fn test_partial_endpoints_do_not_reach_ssm() {
+        let connectivity = SubnetConnectivity {
+            endpoint_services: endpoints(&["ssm", "ec2messages"]),
+            ..Default::default()
+        };
+        assert!(!connectivity.reaches_ssm());
+    }
+
+    #[test]
+    fn test_missing_endpoints_detected() {
+        let endpoint = |service: &str, state: State| {
+            VpcEndpoint::builder()
+                .service_name(format!("com.amazonaws.us-east-1.{}", service))
+                .state(state)
+                .build()
+        };
+        // As returned by describe_vpc_endpoints after ssmmessages was deleted
+        let described = [
+            endpoint("ssm", State::Available),
+            endpoint("ec2messages", State::Available),
+            endpoint("s3", State::Available),
+            endpoint("ssmmessages", State::Deleting),
+        ];
+
+        let connectivity = SubnetConnectivity {
+            endpoint_services: available_endpoint_services(&described),
+            ..Default::default()
+        };
+        assert_eq!(
+            connectivity.missing_endpoint_services(),
+            vec!["ssmmessages"]
+        );
+        assert!(!connectivity.reaches_ssm());
+
+        // A VPC without endpoints is missing all of them
+        assert_eq!(
+            SubnetConnectivity::default().missing_endpoint_services(),
+            SSM_ENDPOINT_SERVICES.to_vec()
+        );
+    }
+
+    #[test]
+    fn test_isolated_subnet_does_not_reach_ssm() {
+        assert!(!SubnetConnectivity::default().reaches_ssm());
+    }
+
+    #[test]
+    fn test_has_ssm_managed_policy() {
+        assert!(has_ssm_managed_policy([
+            "arn:aws:iam::aws:policy/ReadOnlyAccess",
+            SSM_MANAGED_POLICY_ARN,
+        ]));
+        assert!(!has_ssm_managed_policy([
+            "arn:aws:iam::aws:policy/ReadOnlyAccess",
+            // The deprecated role policy doesn't count
+            "arn:aws:iam::aws:policy/service-role/AmazonEC2RoleforSSM",
+        ]));
+        assert!(!has_ssm_managed_policy([]));
+    }
+
+    #[test]
+    fn test_iam_resource_names() {
+        assert_eq!(
+            iam_resource_names("ec2-cli", "0badf00d"),
+            (
+                "ec2-cli-instance-role-0badf00d".to_string(),
+                "ec2-cli-instance-profile-0badf00d".to_string()
+            )
+        );
+
+        // Distinct prefixes never share IAM resources
+        let (role, profile) = iam_resource_names("team-a", "0badf00d");
+        assert_eq!(role, "team-a-instance-role-0badf00d");
+        assert_eq!(profile, "team-a-instance-profile-0badf00d");
+        // The longest allowed prefix still fits IAM's 64-character name limit
+        let (_, profile) = iam_resource_names(&"a".repeat(32), "0badf00d");
+        assert!(profile.len() <= 64);
+    }
+
+    fn launched(subnet_id: &str, state: InstanceStateName) -> ManagedInstance {
+        ManagedInstance {
+            instance_id: format!("i-{}", subnet_id),
+            name: None,
+            state,
+            security_group_id: None,
+            owner: None,
+            vpc_id: Some("vpc-1".to_string()),
+            subnet_id: Some(subnet_id.to_string()),
+        }
+    }
+
+    #[test]
+    fn test_busiest_network() {
+        assert_eq!(busiest_network(&[]), None);
+
+        let instances = [
+            launched("subnet-a", InstanceStateName::Running),
+            launched("subnet-b", InstanceStateName::Running),
+            launched("subnet-b", InstanceStateName::Stopped),
+            // Terminated instances no longer use their subnet
+            launched("subnet-a", InstanceStateName::Terminated),
+            launched("subnet-a", InstanceStateName::ShuttingDown),
+        ];
+        assert_eq!(
+            busiest_network(&instances),
+            Some(ExistingNetwork {
+                vpc_id: "vpc-1".to_string(),
+                subnet_id: "subnet-b".to_string(),
+                instance_count: 2,
+            })
+        );
+
+        // Ties go to the lowest subnet ID
+        let network = busiest_network(&instances[..2]).unwrap();
+        assert_eq!(network.subnet_id, "subnet-a");
+
+        // Only terminated instances leave nothing to reuse
+        assert_eq!(busiest_network(&instances[3..]), None);
+    }
+}
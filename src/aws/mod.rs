@@ -1,3 +1,5 @@
 pub mod client;
+pub mod concurrency;
 pub mod ec2;
 pub mod infrastructure;
+pub mod retry;
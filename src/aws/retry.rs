@@ -0,0 +1,138 @@
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+use crate::{Ec2CliError, Result};
+
+/// Call `check` every `interval` until it reports ready, giving up after `timeout`.
+///
+/// Returns whether `check` became ready in time. Errors from `check` are returned
+/// immediately.
+pub async fn poll_until<F, Fut>(timeout: Duration, interval: Duration, mut check: F) -> Result<bool>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<bool>>,
+{
+    let deadline = Instant::now() + timeout;
+
+    loop {
+        if check().await? {
+            return Ok(true);
+        }
+        if Instant::now() + interval > deadline {
+            return Ok(false);
+        }
+        tokio::time::sleep(interval).await;
+    }
+}
+
+/// Run `op`, retrying every `interval` while it fails with an error matching
+/// `should_retry`, until `timeout` has passed. The last result is returned.
+pub async fn retry_while<T, F, Fut, P>(
+    timeout: Duration,
+    interval: Duration,
+    should_retry: P,
+    mut op: F,
+) -> Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T>>,
+    P: Fn(&Ec2CliError) -> bool,
+{
+    let deadline = Instant::now() + timeout;
+
+    loop {
+        match op().await {
+            Err(e) if should_retry(&e) && Instant::now() + interval <= deadline => {
+                tokio::time::sleep(interval).await;
+            }
+            result => return result,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    const INTERVAL: Duration = Duration::from_millis(1);
+
+    #[tokio::test]
+    async fn test_poll_until_ready_after_attempts() {
+        let attempts = Cell::new(0);
+        let ready = poll_until(Duration::from_secs(5), INTERVAL, || {
+            attempts.set(attempts.get() + 1);
+            let n = attempts.get();
+            async move { Ok(n >= 3) }
+        })
+        .await
+        .unwrap();
+
+        assert!(ready);
+        assert_eq!(attempts.get(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_poll_until_times_out() {
+        let ready = poll_until(Duration::from_millis(20), INTERVAL, || async { Ok(false) })
+            .await
+            .unwrap();
+
+        assert!(!ready);
+    }
+
+    #[tokio::test]
+    async fn test_poll_until_returns_errors() {
+        let attempts = Cell::new(0);
+        let result = poll_until(Duration::from_secs(5), INTERVAL, || {
+            attempts.set(attempts.get() + 1);
+            async { Err(Ec2CliError::Iam("AccessDenied: denied".to_string(), None)) }
+        })
+        .await;
+
+        assert!(result.is_err());
+        assert_eq!(attempts.get(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_retry_while_retries_matching_errors() {
+        let attempts = Cell::new(0);
+        let result = retry_while(
+            Duration::from_secs(5),
+            INTERVAL,
+            |e| matches!(e, Ec2CliError::Ec2(..)),
+            || {
+                attempts.set(attempts.get() + 1);
+                let n = attempts.get();
+                async move {
+                    if n < 3 {
+                        Err(Ec2CliError::Ec2("not yet".to_string(), None))
+                    } else {
+                        Ok(n)
+                    }
+                }
+            },
+        )
+        .await;
+
+        assert_eq!(result.unwrap(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_retry_while_stops_on_other_errors() {
+        let attempts = Cell::new(0);
+        let result: Result<()> = retry_while(
+            Duration::from_secs(5),
+            INTERVAL,
+            |e| matches!(e, Ec2CliError::Ec2(..)),
+            || {
+                attempts.set(attempts.get() + 1);
+                async { Err(Ec2CliError::Iam("AccessDenied: denied".to_string(), None)) }
+            },
+        )
+        .await;
+
+        assert!(result.is_err());
+        assert_eq!(attempts.get(), 1);
+    }
+}
@@ -0,0 +1,176 @@
+use crate::error::exit_code;
+use crate::{Ec2CliError, Result};
+
+/// How one item of a batch command ended
+#[derive(Debug)]
+pub enum Outcome {
+    Succeeded,
+    Failed(Ec2CliError),
+    /// Not attempted because an earlier item failed without `--keep-going`
+    Skipped,
+}
+
+/// Per-item outcomes of a command run over several instances or profiles, in the
+/// order the items were given
+#[derive(Debug, Default)]
+pub struct BatchResult {
+    outcomes: Vec<(String, Outcome)>,
+}
+
+impl BatchResult {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record how `name` ended
+    pub fn record(&mut self, name: impl Into<String>, result: Result<()>) {
+        let outcome = match result {
+            Ok(()) => Outcome::Succeeded,
+            Err(e) => Outcome::Failed(e),
+        };
+        self.outcomes.push((name.into(), outcome));
+    }
+
+    /// Record that `name` wasn't attempted
+    pub fn skip(&mut self, name: impl Into<String>) {
+        self.outcomes.push((name.into(), Outcome::Skipped));
+    }
+
+    pub fn succeeded(&self) -> usize {
+        self.count(|outcome| matches!(outcome, Outcome::Succeeded))
+    }
+
+    pub fn failed(&self) -> usize {
+        self.count(|outcome| matches!(outcome, Outcome::Failed(_)))
+    }
+
+    pub fn skipped(&self) -> usize {
+        self.count(|outcome| matches!(outcome, Outcome::Skipped))
+    }
+
+    fn count(&self, predicate: impl Fn(&Outcome) -> bool) -> usize {
+        self.outcomes
+            .iter()
+            .filter(|(_, outcome)| predicate(outcome))
+            .count()
+    }
+
+    /// Process exit code for the batch: 0 if nothing failed, the failures' own
+    /// code if they all agree (e.g. every instance was missing), else the general one
+    pub fn exit_code(&self) -> i32 {
+        let mut codes = self
+            .outcomes
+            .iter()
+            .filter_map(|(_, outcome)| match outcome {
+                Outcome::Failed(e) => Some(e.exit_code()),
+                _ => None,
+            });
+        let Some(first) = codes.next() else {
+            return 0;
+        };
+        if codes.all(|code| code == first) {
+            first
+        } else {
+            exit_code::GENERAL
+        }
+    }
+
+    /// The summary table printed after the batch, one line per item
+    pub fn summary(&self) -> String {
+        let mut lines: Vec<String> = self
+            .outcomes
+            .iter()
+            .map(|(name, outcome)| match outcome {
+                Outcome::Succeeded => format!("ok    {}", name),
+                Outcome::Failed(e) => format!("FAIL  {}: {}", name, e),
+                Outcome::Skipped => format!("skip  {}", name),
+            })
+            .collect();
+
+        let mut totals = format!("{} of {} succeeded", self.succeeded(), self.outcomes.len());
+        if self.failed() > 0 {
+            totals.push_str(&format!(", {} failed", self.failed()));
+        }
+        if self.skipped() > 0 {
+            totals.push_str(&format!(", {} skipped", self.skipped()));
+        }
+        lines.push(String::new());
+        lines.push(format!("{}.", totals));
+        lines.join("\n")
+    }
+
+    /// Ok if every item succeeded, otherwise an error naming the failed items that
+    /// exits with [`BatchResult::exit_code`]
+    pub fn into_result(self, action: &str) -> Result<()> {
+        if self.failed() == 0 {
+            return Ok(());
+        }
+
+        let failed: Vec<&str> = self
+            .outcomes
+            .iter()
+            .filter(|(_, outcome)| matches!(outcome, Outcome::Failed(_)))
+            .map(|(name, _)| name.as_str())
+            .collect();
+        Err(Ec2CliError::BatchFailed(
+            format!(
+                "{} failed for {} of {}: {}",
+                action,
+                failed.len(),
+                self.outcomes.len(),
+                failed.join(", ")
+            ),
+            self.exit_code(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mixed() -> BatchResult {
+        let mut batch = BatchResult::new();
+        batch.record("a", Ok(()));
+        batch.record("b", Err(Ec2CliError::InstanceNotFound("b".to_string())));
+        batch.record("c", Ok(()));
+        batch.skip("d");
+        batch
+    }
+
+    #[test]
+    fn test_batch_aggregation() {
+        let batch = mixed();
+        assert_eq!(
+            (batch.succeeded(), batch.failed(), batch.skipped()),
+            (2, 1, 1)
+        );
+
+        // One line per item in the order recorded, then the totals
+        let summary = batch.summary();
+        assert!(summary.starts_with("ok    a\nFAIL  b: Instance not found: b\nok    c\nskip  d\n"));
+        assert!(summary.ends_with("2 of 4 succeeded, 1 failed, 1 skipped."));
+    }
+
+    #[test]
+    fn test_batch_exit_code() {
+        // Nothing failed
+        let mut batch = BatchResult::new();
+        batch.record("a", Ok(()));
+        assert_eq!(batch.exit_code(), 0);
+        assert!(batch.into_result("Push").is_ok());
+        assert_eq!(BatchResult::new().exit_code(), 0);
+
+        // Failures of one kind keep that kind's code
+        let batch = mixed();
+        assert_eq!(batch.exit_code(), exit_code::NOT_FOUND);
+        let err = batch.into_result("Push").unwrap_err();
+        assert_eq!(err.exit_code(), exit_code::NOT_FOUND);
+        assert_eq!(err.to_string(), "Push failed for 1 of 4: b");
+
+        // Different kinds fall back to the general code
+        let mut batch = mixed();
+        batch.record("e", Err(Ec2CliError::Timeout("e".to_string())));
+        assert_eq!(batch.exit_code(), exit_code::GENERAL);
+    }
+}
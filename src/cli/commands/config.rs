@@ -1,82 +1,57 @@
 use std::process::Command;
 
 use aws_sdk_ec2::types::Filter;
-use dialoguer::{Input, Select};
+use dialoguer::{Confirm, Input, Select};
+use serde::Serialize;
+use std::collections::HashMap;
 
-use crate::aws::client::{get_default_vpc, AwsClients};
-use crate::config::Settings;
+use crate::aws::client::{get_default_vpc, AwsClients, DEFAULT_RESOURCE_PREFIX};
+use crate::aws::infrastructure::{ExistingNetwork, Infrastructure};
+use crate::config::{NetworkMode, Settings};
+use crate::git::suggest_username;
 use crate::profile::ProfileLoader;
-use crate::ui::create_spinner;
+use crate::ssh::{DEFAULT_SSH_CONNECT_TIMEOUT_SECS, DEFAULT_SSH_KEEPALIVE_INTERVAL_SECS};
+use crate::ui::{create_spinner, info};
+use crate::user_data::DEFAULT_INIT_LOG_PATH;
 use crate::{Ec2CliError, Result};
 
-pub async fn init() -> Result<()> {
-    println!("Checking prerequisites...\n");
+/// Flags for `config init`
+#[derive(Debug, Clone, Default)]
+pub struct InitFlags {
+    /// Write settings from flags without prompting
+    pub non_interactive: bool,
+    pub region: Option<String>,
+    pub vpc: Option<String>,
+    pub subnet: Option<String>,
+    pub username: Option<String>,
+    /// Back up the config file and start from defaults
+    pub reset: bool,
+    /// Reset without asking first
+    pub force: bool,
+}
 
-    let mut all_ok = true;
+pub async fn init(flags: InitFlags) -> Result<()> {
+    let aws_default_region = check_prerequisites().await?;
 
-    // Check AWS CLI
-    print!("  AWS CLI: ");
-    match check_aws_cli() {
-        Ok(version) => println!("OK ({})", version),
-        Err(e) => {
-            println!("MISSING");
-            println!("    {}", e);
-            all_ok = false;
+    // Load existing settings, or start over from defaults
+    let mut settings = if flags.reset {
+        if !confirm_reset(&flags)? {
+            println!("Cancelled.");
+            return Ok(());
         }
-    }
-
-    // Check Session Manager Plugin
-    print!("  Session Manager Plugin: ");
-    match check_session_manager_plugin() {
-        Ok(version) => println!("OK ({})", version),
-        Err(e) => {
-            println!("MISSING");
-            println!("    {}", e);
-            println!("    Install from: https://docs.aws.amazon.com/systems-manager/latest/userguide/session-manager-working-with-install-plugin.html");
-            all_ok = false;
-        }
-    }
-
-    // Check AWS Credentials and get default region
-    print!("  AWS Credentials: ");
-    let aws_default_region = match AwsClients::new_without_settings().await {
-        Ok(clients) => {
-            println!("OK");
-            println!("    Region: {}", clients.region);
-            println!("    Account: {}", clients.account_id);
-            Some(clients.region)
-        }
-        Err(_) => {
-            println!("MISSING/INVALID");
-            println!("    Configure with: aws configure");
-            all_ok = false;
-            None
+        let (settings, backup) = Settings::reset()?;
+        if let Some(backup) = backup {
+            info!("Previous configuration saved to {}", backup.display());
         }
+        settings
+    } else {
+        Settings::load().unwrap_or_default()
     };
 
-    // Check Git
-    print!("  Git: ");
-    match check_git() {
-        Ok(version) => println!("OK ({})", version),
-        Err(e) => {
-            println!("MISSING");
-            println!("    {}", e);
-            all_ok = false;
-        }
-    }
-
-    println!();
-
-    // If prerequisites failed, stop here
-    if !all_ok {
-        return Err(Ec2CliError::Prerequisites(
-            "Some prerequisites are not met".to_string(),
-        ));
+    if flags.non_interactive {
+        return init_non_interactive(settings, &flags).await;
     }
 
-    // Load existing settings
-    let mut settings = Settings::load().unwrap_or_default();
-
     println!("Configure ec2-cli settings:\n");
 
     // Configure region
@@ -108,9 +83,101 @@ pub async fn init() -> Result<()> {
     })?;
     spinner.finish_and_clear();
 
+    // Reuse the subnet earlier instances were launched into, if the user wants to
+    let spinner = create_spinner("Looking for existing ec2-cli instances...");
+    // Best effort: without permission to list instances, fall back to the prompts
+    let existing = Infrastructure::find_existing(&clients)
+        .await
+        .unwrap_or(None);
+    spinner.finish_and_clear();
+    match existing {
+        Some(network) if confirm_reuse_network(&network)? => {
+            reuse_network(&clients, &mut settings, network).await?
+        }
+        _ => select_network(&clients, &mut settings).await?,
+    }
+
+    // Configure Username tag
+    println!();
+    if settings.has_username_tag() {
+        println!(
+            "  Username tag: {} (already configured)",
+            settings.tags.get("Username").unwrap()
+        );
+    } else {
+        let mut input =
+            Input::<String>::new().with_prompt("  Enter your username (for resource tagging)");
+        if let Some(suggested) = suggest_username() {
+            input = input.default(suggested);
+        }
+        let username: String = input
+            .interact_text()
+            .map_err(|e| Ec2CliError::Config(format!("Failed to read input: {}", e)))?;
+
+        settings.set_tag("Username", &username)?;
+    }
+
+    // Save settings
+    settings.save()?;
+
+    println!();
+    println!("Configuration saved! You can now use 'ec2-cli up' to launch an instance.");
+
+    Ok(())
+}
+
+/// Ask before `--reset` discards the current settings, unless `--force` was given
+fn confirm_reset(flags: &InitFlags) -> Result<bool> {
+    if flags.force {
+        return Ok(true);
+    }
+    if flags.non_interactive {
+        return Err(Ec2CliError::Config(
+            "--reset with --non-interactive also needs --force".to_string(),
+        ));
+    }
+
+    Confirm::new()
+        .with_prompt("Reset all ec2-cli settings? Profiles and instance state are kept")
+        .default(false)
+        .interact()
+        .map_err(|_| Ec2CliError::Cancelled)
+}
+
+/// Ask whether to use the subnet existing managed instances run in
+fn confirm_reuse_network(network: &ExistingNetwork) -> Result<bool> {
+    println!(
+        "  Found {} ec2-cli instance{} in subnet {} (VPC {})",
+        network.instance_count,
+        if network.instance_count == 1 { "" } else { "s" },
+        network.subnet_id,
+        network.vpc_id
+    );
+    Confirm::new()
+        .with_prompt("  Use this subnet? (no to pick another)")
+        .default(true)
+        .interact()
+        .map_err(|e| Ec2CliError::Config(format!("Failed to read input: {}", e)))
+}
+
+/// Configure the VPC and subnet of existing instances
+async fn reuse_network(
+    clients: &AwsClients,
+    settings: &mut Settings,
+    network: ExistingNetwork,
+) -> Result<()> {
+    let default_vpc_id = get_default_vpc(clients).await.ok();
+
+    settings.vpc_id = settings.vpc_setting(network.vpc_id, default_vpc_id.as_ref());
+    settings.subnet_id = Some(network.subnet_id);
+    Ok(())
+}
+
+/// Prompt for the VPC and a subnet in it
+async fn select_network(clients: &AwsClients, settings: &mut Settings) -> Result<()> {
     // Configure VPC
     let spinner = create_spinner("Looking up VPC...");
-    let default_vpc_id = get_default_vpc(&clients).await.ok();
+    let default_vpc_id = get_default_vpc(clients).await.ok();
     spinner.finish_and_clear();
     let current_vpc = settings.vpc_id.clone().or(default_vpc_id.clone());
 
@@ -134,21 +201,16 @@ pub async fn init() -> Result<()> {
         // Validate format before API call
         Settings::validate_vpc_id(&vpc_input)?;
         let spinner = create_spinner("Validating VPC...");
-        validate_vpc(&clients, &vpc_input).await?;
+        validate_vpc(clients, &vpc_input).await?;
         spinner.finish_and_clear();
         vpc_input
     };
 
-    // Store None if using default VPC, otherwise store the VPC ID
-    settings.vpc_id = if Some(&vpc_id) == default_vpc_id.as_ref() {
-        None
-    } else {
-        Some(vpc_id.clone())
-    };
+    settings.vpc_id = settings.vpc_setting(vpc_id.clone(), default_vpc_id.as_ref());
 
     // Configure subnet - list available subnets in the VPC
     let spinner = create_spinner("Fetching subnets...");
-    let subnets = list_subnets(&clients, &vpc_id).await?;
+    let subnets = list_subnets(clients, &vpc_id).await?;
     spinner.finish_and_clear();
     if subnets.is_empty() {
         return Err(Ec2CliError::NoSubnetsInVpc(vpc_id));
@@ -180,29 +242,258 @@ pub async fn init() -> Result<()> {
         .map_err(|e| Ec2CliError::Config(format!("Failed to read input: {}", e)))?;
 
     settings.subnet_id = Some(subnets[selection].subnet_id.clone());
+    Ok(())
+}
+
+/// Check for the AWS CLI, Session Manager plugin, credentials, and git, returning
+/// the AWS default region when credentials are valid
+async fn check_prerequisites() -> Result<Option<String>> {
+    println!("Checking prerequisites...\n");
+
+    let mut all_ok = true;
+
+    // Check AWS CLI
+    print!("  AWS CLI: ");
+    match check_aws_cli() {
+        Ok(version) => println!("OK ({})", version),
+        Err(e) => {
+            println!("MISSING");
+            println!("    {}", e);
+            all_ok = false;
+        }
+    }
+
+    // Check Session Manager Plugin
+    print!("  Session Manager Plugin: ");
+    match check_session_manager_plugin() {
+        Ok(version) => println!("OK ({})", version),
+        Err(e) => {
+            println!("MISSING");
+            println!("    {}", e);
+            println!("    Install from: https://docs.aws.amazon.com/systems-manager/latest/userguide/session-manager-working-with-install-plugin.html");
+            all_ok = false;
+        }
+    }
+
+    // Check AWS Credentials and get default region
+    print!("  AWS Credentials: ");
+    let aws_default_region = match AwsClients::new_without_settings().await {
+        Ok(clients) => {
+            println!("OK");
+            println!("    Region: {}", clients.region);
+            println!("    Account: {}", clients.account_id);
+            Some(clients.region)
+        }
+        Err(_) => {
+            println!("MISSING/INVALID");
+            println!("    Configure with: aws configure");
+            all_ok = false;
+            None
+        }
+    };
+
+    // Check Git
+    print!("  Git: ");
+    match check_git() {
+        Ok(version) => println!("OK ({})", version),
+        Err(e) => {
+            println!("MISSING");
+            println!("    {}", e);
+            all_ok = false;
+        }
+    }
 
-    // Configure Username tag
     println!();
-    if settings.has_username_tag() {
-        println!(
-            "  Username tag: {} (already configured)",
-            settings.tags.get("Username").unwrap()
-        );
-    } else {
-        let username: String = Input::new()
-            .with_prompt("  Enter your username (for resource tagging)")
-            .interact_text()
-            .map_err(|e| Ec2CliError::Config(format!("Failed to read input: {}", e)))?;
 
-        settings.set_tag("Username", &username)?;
+    // If prerequisites failed, stop here
+    if !all_ok {
+        return Err(Ec2CliError::Prerequisites(
+            "Some prerequisites are not met".to_string(),
+        ));
     }
 
-    // Save settings
+    Ok(aws_default_region)
+}
+
+/// Write settings from `config init` flags, falling back to already configured
+/// values and erroring on anything still missing
+async fn init_non_interactive(mut settings: Settings, flags: &InitFlags) -> Result<()> {
+    apply_init_flags(&mut settings, flags)?;
+    let region = settings.region.clone().unwrap_or_default();
+    let subnet_id = settings.subnet_id.clone().unwrap_or_default();
+
+    let spinner = create_spinner("Connecting to AWS...");
+    let clients = AwsClients::with_region(&region).await.map_err(|e| {
+        spinner.finish_and_clear();
+        Ec2CliError::Config(format!(
+            "Failed to connect to AWS in region '{}': {}",
+            region, e
+        ))
+    })?;
+    spinner.finish_and_clear();
+
+    // Check the VPC exists and the subnet belongs to it
+    let spinner = create_spinner("Validating VPC and subnet...");
+    let vpc_id = match settings.launch_network()?.0 {
+        Some(vpc_id) => {
+            validate_vpc(&clients, vpc_id).await?;
+            vpc_id.to_string()
+        }
+        None => get_default_vpc(&clients).await?,
+    };
+    let subnets = list_subnets(&clients, &vpc_id).await?;
+    spinner.finish_and_clear();
+    check_subnet_in_vpc(&subnets, &subnet_id, &vpc_id)?;
+
     settings.save()?;
 
-    println!();
-    println!("Configuration saved! You can now use 'ec2-cli up' to launch an instance.");
+    info!("Configuration saved:");
+    info!("  Region: {}", region);
+    info!(
+        "  VPC:    {}",
+        settings.vpc_id.as_deref().unwrap_or("(default)")
+    );
+    info!("  Subnet: {}", subnet_id);
+    info!("  Username tag: {}", settings.tags["Username"]);
+
+    Ok(())
+}
+
+/// Validate `config init` flags and merge them into settings
+fn apply_init_flags(settings: &mut Settings, flags: &InitFlags) -> Result<()> {
+    let missing = |flag: &str| {
+        Ec2CliError::Config(format!(
+            "{} is required with --non-interactive (no value is configured)",
+            flag
+        ))
+    };
+
+    let region = flags
+        .region
+        .clone()
+        .or_else(|| settings.region.clone())
+        .ok_or_else(|| missing("--region"))?;
+    Settings::validate_region(&region)?;
+
+    let subnet_id = flags
+        .subnet
+        .clone()
+        .or_else(|| settings.subnet_id.clone())
+        .ok_or_else(|| missing("--subnet"))?;
+    Settings::validate_subnet_id(&subnet_id)?;
 
+    if let Some(ref vpc_id) = flags.vpc {
+        Settings::validate_vpc_id(vpc_id)?;
+    }
+
+    match flags.username {
+        Some(ref username) => settings.set_tag("Username", username)?,
+        None if settings.has_username_tag() => {}
+        None => return Err(missing("--username")),
+    }
+
+    // Without --vpc, keep the configured VPC (or the default VPC)
+    if flags.vpc.is_some() {
+        settings.vpc_id = flags.vpc.clone();
+    }
+    settings.region = Some(region);
+    settings.subnet_id = Some(subnet_id);
+    Ok(())
+}
+
+/// A single setting written by `config set-region`, `set-vpc`, or `set-subnet`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SettingKey {
+    Region,
+    Vpc,
+    Subnet,
+}
+
+impl SettingKey {
+    fn label(self) -> &'static str {
+        match self {
+            SettingKey::Region => "Region",
+            SettingKey::Vpc => "VPC",
+            SettingKey::Subnet => "Subnet",
+        }
+    }
+}
+
+/// Set one setting after checking its format and that it exists in AWS
+pub async fn set(key: SettingKey, value: &str) -> Result<()> {
+    let mut settings = Settings::load()?;
+    let previous_region = settings.region.clone();
+
+    // Malformed values are rejected before any AWS call
+    apply_setting(&mut settings, key, value)?;
+
+    let spinner = create_spinner("Connecting to AWS...");
+    let clients = match settings.region {
+        Some(ref region) => AwsClients::with_region(region).await,
+        None => AwsClients::new_without_settings().await,
+    };
+    spinner.finish_and_clear();
+    let clients = clients?;
+
+    let spinner = create_spinner(format!("Validating {}...", key.label()));
+    match key {
+        SettingKey::Region => validate_region_enabled(&clients, value).await?,
+        SettingKey::Vpc => {
+            validate_vpc(&clients, value).await?;
+            // The configured subnet may belong to the old VPC
+            if let Some(ref subnet_id) = settings.subnet_id {
+                let subnets = list_subnets(&clients, value).await?;
+                if check_subnet_in_vpc(&subnets, subnet_id, value).is_err() {
+                    spinner.finish_and_clear();
+                    eprintln!(
+                        "Warning: Subnet {} is not in VPC {}; update it with 'ec2-cli config set-subnet'",
+                        subnet_id, value
+                    );
+                }
+            }
+        }
+        SettingKey::Subnet => {
+            let vpc_id = match settings.launch_network()?.0 {
+                Some(vpc_id) => vpc_id.to_string(),
+                None => get_default_vpc(&clients).await?,
+            };
+            let subnets = list_subnets(&clients, &vpc_id).await?;
+            check_subnet_in_vpc(&subnets, value, &vpc_id)?;
+        }
+    }
+    spinner.finish_and_clear();
+
+    if key == SettingKey::Region
+        && previous_region.as_deref() != Some(value)
+        && (settings.vpc_id.is_some() || settings.subnet_id.is_some())
+    {
+        eprintln!(
+            "Warning: The configured VPC and subnet are region-specific; \
+             update them with 'ec2-cli config set-vpc' and 'set-subnet'"
+        );
+    }
+
+    settings.save()?;
+    info!("{} set to {}", key.label(), value);
+    Ok(())
+}
+
+/// Check a value's format and write it into settings (nothing is saved)
+fn apply_setting(settings: &mut Settings, key: SettingKey, value: &str) -> Result<()> {
+    match key {
+        SettingKey::Region => {
+            Settings::validate_region(value)?;
+            settings.region = Some(value.to_string());
+        }
+        SettingKey::Vpc => {
+            Settings::validate_vpc_id(value)?;
+            settings.vpc_id = Some(value.to_string());
+        }
+        SettingKey::Subnet => {
+            Settings::validate_subnet_id(value)?;
+            settings.subnet_id = Some(value.to_string());
+        }
+    }
     Ok(())
 }
 
@@ -230,6 +521,38 @@ async fn validate_vpc(clients: &AwsClients, vpc_id: &str) -> Result<()> {
     Ok(())
 }
 
+/// Validate that a region exists and is enabled for the account
+async fn validate_region_enabled(clients: &AwsClients, region: &str) -> Result<()> {
+    let regions = clients
+        .ec2
+        .describe_regions()
+        .region_names(region)
+        .send()
+        .await
+        .map_err(Ec2CliError::ec2)?;
+
+    if regions.regions().is_empty() {
+        return Err(Ec2CliError::Config(format!(
+            "Region '{}' is not enabled for this account",
+            region
+        )));
+    }
+
+    Ok(())
+}
+
+/// Check that `subnet_id` is one of the VPC's subnets
+fn check_subnet_in_vpc(subnets: &[SubnetInfo], subnet_id: &str, vpc_id: &str) -> Result<()> {
+    if subnets.iter().any(|s| s.subnet_id == subnet_id) {
+        return Ok(());
+    }
+
+    Err(Ec2CliError::SubnetNotFound(format!(
+        "{} (not in VPC {})",
+        subnet_id, vpc_id
+    )))
+}
+
 /// List subnets in a VPC
 async fn list_subnets(clients: &AwsClients, vpc_id: &str) -> Result<Vec<SubnetInfo>> {
     let subnets = clients
@@ -251,10 +574,137 @@ async fn list_subnets(clients: &AwsClients, vpc_id: &str) -> Result<Vec<SubnetIn
         .collect())
 }
 
-pub fn show() -> Result<()> {
+/// Resolved configuration for `config show --json`
+#[derive(Serialize)]
+struct ConfigReport {
+    config_file: Option<String>,
+    region: ConfigValue,
+    vpc_id: ConfigValue,
+    subnet_id: ConfigValue,
+    network_mode: NetworkMode,
+    aws_profile: ConfigValue,
+    native_ssm_proxy: bool,
+    enforce_owner: bool,
+    resource_prefix: String,
+    init_log_path: String,
+    ssh_connect_timeout: u64,
+    ssh_keepalive_interval: u64,
+    instance_defaults: serde_json::Map<String, serde_json::Value>,
+    profile_dirs: ProfileDirs,
+    state_dir: String,
+    profiles: Vec<ProfileEntry>,
+    tags: HashMap<String, String>,
+}
+
+/// A setting value along with where it came from
+#[derive(Serialize)]
+struct ConfigValue {
+    value: Option<String>,
+    /// "configured", "default" (a fallback applies), or "unset"
+    source: &'static str,
+}
+
+impl ConfigValue {
+    /// A setting that falls back to an AWS-side default when not configured
+    fn with_default(value: Option<String>) -> Self {
+        let source = if value.is_some() {
+            "configured"
+        } else {
+            "default"
+        };
+        Self { value, source }
+    }
+
+    /// A setting that has no fallback and must be configured
+    fn required(value: Option<String>) -> Self {
+        let source = if value.is_some() {
+            "configured"
+        } else {
+            "unset"
+        };
+        Self { value, source }
+    }
+}
+
+#[derive(Serialize)]
+struct ProfileDirs {
+    global: Option<String>,
+    local: Option<String>,
+}
+
+#[derive(Serialize)]
+struct ProfileEntry {
+    name: String,
+    source: String,
+    path: Option<String>,
+}
+
+fn state_dir() -> Option<std::path::PathBuf> {
+    directories::ProjectDirs::from("", "", "ec2-cli")
+        .and_then(|dirs| dirs.state_dir().map(|d| d.to_path_buf()))
+}
+
+fn show_json(loader: &ProfileLoader, settings: Settings) -> Result<()> {
+    let profiles = loader
+        .list()?
+        .into_iter()
+        .map(|info| ProfileEntry {
+            name: info.name,
+            source: info.source.to_string(),
+            path: info.path.map(|p| p.display().to_string()),
+        })
+        .collect();
+
+    let report = ConfigReport {
+        config_file: Settings::config_path().map(|p| p.display().to_string()),
+        region: ConfigValue::with_default(settings.region),
+        vpc_id: match settings.network_mode {
+            NetworkMode::Managed => ConfigValue::with_default(settings.vpc_id),
+            NetworkMode::Byo => ConfigValue::required(settings.vpc_id),
+        },
+        subnet_id: ConfigValue::required(settings.subnet_id),
+        network_mode: settings.network_mode,
+        aws_profile: ConfigValue::with_default(settings.aws_profile),
+        native_ssm_proxy: settings.native_ssm_proxy,
+        enforce_owner: settings.enforce_owner,
+        resource_prefix: settings
+            .resource_prefix
+            .clone()
+            .unwrap_or_else(|| DEFAULT_RESOURCE_PREFIX.to_string()),
+        init_log_path: settings
+            .init_log_path
+            .clone()
+            .unwrap_or_else(|| DEFAULT_INIT_LOG_PATH.to_string()),
+        ssh_connect_timeout: settings
+            .ssh_connect_timeout
+            .unwrap_or(DEFAULT_SSH_CONNECT_TIMEOUT_SECS),
+        ssh_keepalive_interval: settings
+            .ssh_keepalive_interval
+            .unwrap_or(DEFAULT_SSH_KEEPALIVE_INTERVAL_SECS),
+        instance_defaults: settings.instance_defaults,
+        profile_dirs: ProfileDirs {
+            global: loader.global_dir().map(|p| p.display().to_string()),
+            local: loader.local_dir().map(|p| p.display().to_string()),
+        },
+        state_dir: state_dir()
+            .map(|dir| dir.display().to_string())
+            .unwrap_or_else(|| "~/.local/state/ec2-cli/".to_string()),
+        profiles,
+        tags: settings.tags,
+    };
+
+    println!("{}", serde_json::to_string_pretty(&report)?);
+    Ok(())
+}
+
+pub fn show(json: bool) -> Result<()> {
     let loader = ProfileLoader::new();
     let settings = Settings::load().unwrap_or_default();
 
+    if json {
+        return show_json(&loader, settings);
+    }
+
     println!("Configuration:");
     println!();
 
@@ -266,7 +716,13 @@ pub fn show() -> Result<()> {
     );
     println!(
         "  VPC: {}",
-        settings.vpc_id.as_deref().unwrap_or("(default VPC)")
+        settings
+            .vpc_id
+            .as_deref()
+            .unwrap_or(match settings.network_mode {
+                NetworkMode::Managed => "(default VPC)",
+                NetworkMode::Byo => "(not configured - run 'ec2-cli config set-vpc')",
+            })
     );
     println!(
         "  Subnet: {}",
@@ -275,6 +731,52 @@ pub fn show() -> Result<()> {
             .as_deref()
             .unwrap_or("(not configured - run 'ec2-cli config init')")
     );
+    println!(
+        "  AWS profile: {}",
+        settings
+            .aws_profile
+            .as_deref()
+            .unwrap_or("(from AWS_PROFILE or default)")
+    );
+    println!(
+        "  SSM proxy: {}",
+        if settings.native_ssm_proxy {
+            "native (session-manager-plugin)"
+        } else {
+            "AWS CLI"
+        }
+    );
+    if settings.network_mode == NetworkMode::Byo {
+        println!("  Network: bring your own (no default VPC fallback)");
+    }
+    if settings.enforce_owner {
+        println!("  Ownership: enforced (Username tag)");
+    }
+    println!(
+        "  Resource prefix: {}",
+        settings
+            .resource_prefix
+            .as_deref()
+            .unwrap_or(DEFAULT_RESOURCE_PREFIX)
+    );
+    if let Some(ref path) = settings.init_log_path {
+        println!("  Init log: {}", path);
+    }
+    println!(
+        "  SSH timeouts: connect {}s, keepalive every {}s",
+        settings
+            .ssh_connect_timeout
+            .unwrap_or(DEFAULT_SSH_CONNECT_TIMEOUT_SECS),
+        settings
+            .ssh_keepalive_interval
+            .unwrap_or(DEFAULT_SSH_KEEPALIVE_INTERVAL_SECS)
+    );
+    if !settings.instance_defaults.is_empty() {
+        println!(
+            "  Instance defaults: {}",
+            serde_json::Value::Object(settings.instance_defaults.clone())
+        );
+    }
 
     // Profile directories
     println!();
@@ -287,12 +789,9 @@ pub fn show() -> Result<()> {
     }
 
     // State file
-    let state_dir = directories::ProjectDirs::from("", "", "ec2-cli")
-        .and_then(|dirs| dirs.state_dir().map(|d| d.to_path_buf()));
-
     println!();
     println!("State directory:");
-    if let Some(dir) = state_dir {
+    if let Some(dir) = state_dir() {
         println!("  {}", dir.display());
     } else {
         println!("  ~/.local/state/ec2-cli/");
@@ -331,7 +830,7 @@ pub fn tags_set(key: &str, value: &str) -> Result<()> {
     let mut settings = Settings::load()?;
     settings.set_tag(key, value)?;
     settings.save()?;
-    println!("Tag '{}' set to '{}'", key, value);
+    info!("Tag '{}' set to '{}'", key, value);
     Ok(())
 }
 
@@ -359,9 +858,9 @@ pub fn tags_remove(key: &str) -> Result<()> {
 
     if settings.remove_tag(key).is_some() {
         settings.save()?;
-        println!("Tag '{}' removed", key);
+        info!("Tag '{}' removed", key);
     } else {
-        println!("Tag '{}' not found", key);
+        info!("Tag '{}' not found", key);
     }
 
     Ok(())
@@ -415,3 +914,133 @@ fn check_git() -> Result<String> {
         Err(Ec2CliError::Prerequisites("Git not working".to_string()))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn flags(region: Option<&str>, subnet: Option<&str>, username: Option<&str>) -> InitFlags {
+        InitFlags {
+            non_interactive: true,
+            region: region.map(String::from),
+            vpc: None,
+            subnet: subnet.map(String::from),
+            username: username.map(String::from),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_apply_init_flags() {
+        let mut settings = Settings::default();
+        let mut init_flags = flags(Some("eu-west-1"), Some("subnet-12345678"), Some("jdoe"));
+        init_flags.vpc = Some("vpc-abcdef12".to_string());
+
+        apply_init_flags(&mut settings, &init_flags).unwrap();
+        assert_eq!(settings.region.as_deref(), Some("eu-west-1"));
+        assert_eq!(settings.vpc_id.as_deref(), Some("vpc-abcdef12"));
+        assert_eq!(settings.subnet_id.as_deref(), Some("subnet-12345678"));
+        assert_eq!(
+            settings.tags.get("Username").map(String::as_str),
+            Some("jdoe")
+        );
+    }
+
+    #[test]
+    fn test_apply_init_flags_missing_values() {
+        let mut settings = Settings::default();
+        assert!(apply_init_flags(
+            &mut settings,
+            &flags(None, Some("subnet-12345678"), Some("jdoe"))
+        )
+        .is_err());
+        assert!(
+            apply_init_flags(&mut settings, &flags(Some("us-east-1"), None, Some("jdoe"))).is_err()
+        );
+        assert!(apply_init_flags(
+            &mut settings,
+            &flags(Some("us-east-1"), Some("subnet-12345678"), None)
+        )
+        .is_err());
+        // Nothing is written when validation fails
+        assert!(settings.region.is_none());
+    }
+
+    #[test]
+    fn test_apply_init_flags_uses_configured_values() {
+        let mut settings = Settings {
+            region: Some("us-west-2".to_string()),
+            subnet_id: Some("subnet-87654321".to_string()),
+            ..Default::default()
+        };
+        settings.set_tag("Username", "existing").unwrap();
+
+        apply_init_flags(&mut settings, &flags(None, None, None)).unwrap();
+        assert_eq!(settings.region.as_deref(), Some("us-west-2"));
+        assert_eq!(settings.subnet_id.as_deref(), Some("subnet-87654321"));
+        assert_eq!(settings.tags["Username"], "existing");
+    }
+
+    #[test]
+    fn test_apply_init_flags_rejects_invalid_values() {
+        let mut settings = Settings::default();
+        assert!(apply_init_flags(
+            &mut settings,
+            &flags(Some("nowhere"), Some("subnet-12345678"), Some("jdoe"))
+        )
+        .is_err());
+        assert!(apply_init_flags(
+            &mut settings,
+            &flags(Some("us-east-1"), Some("sn-1"), Some("jdoe"))
+        )
+        .is_err());
+
+        let mut init_flags = flags(Some("us-east-1"), Some("subnet-12345678"), Some("jdoe"));
+        init_flags.vpc = Some("vpc-1".to_string());
+        assert!(apply_init_flags(&mut settings, &init_flags).is_err());
+    }
+
+    #[test]
+    fn test_apply_setting() {
+        let mut settings = Settings::default();
+        apply_setting(&mut settings, SettingKey::Region, "eu-west-1").unwrap();
+        apply_setting(&mut settings, SettingKey::Vpc, "vpc-abcdef12").unwrap();
+        apply_setting(&mut settings, SettingKey::Subnet, "subnet-12345678").unwrap();
+
+        assert_eq!(settings.region.as_deref(), Some("eu-west-1"));
+        assert_eq!(settings.vpc_id.as_deref(), Some("vpc-abcdef12"));
+        assert_eq!(settings.subnet_id.as_deref(), Some("subnet-12345678"));
+    }
+
+    #[test]
+    fn test_apply_setting_rejects_invalid_values() {
+        let mut settings = Settings {
+            region: Some("us-west-2".to_string()),
+            vpc_id: Some("vpc-abcdef12".to_string()),
+            subnet_id: Some("subnet-12345678".to_string()),
+            ..Default::default()
+        };
+
+        assert!(apply_setting(&mut settings, SettingKey::Region, "nowhere").is_err());
+        assert!(apply_setting(&mut settings, SettingKey::Vpc, "subnet-12345678").is_err());
+        assert!(apply_setting(&mut settings, SettingKey::Subnet, "sn-1").is_err());
+
+        // Rejected values never reach settings
+        assert_eq!(settings.region.as_deref(), Some("us-west-2"));
+        assert_eq!(settings.vpc_id.as_deref(), Some("vpc-abcdef12"));
+        assert_eq!(settings.subnet_id.as_deref(), Some("subnet-12345678"));
+    }
+
+    #[test]
+    fn test_check_subnet_in_vpc() {
+        let subnets = vec![SubnetInfo {
+            subnet_id: "subnet-12345678".to_string(),
+            availability_zone: "us-east-1a".to_string(),
+            cidr_block: "10.0.0.0/24".to_string(),
+        }];
+
+        assert!(check_subnet_in_vpc(&subnets, "subnet-12345678", "vpc-1").is_ok());
+        let err = check_subnet_in_vpc(&subnets, "subnet-87654321", "vpc-1").unwrap_err();
+        assert!(matches!(err, Ec2CliError::SubnetNotFound(msg) if msg.contains("vpc-1")));
+    }
+}
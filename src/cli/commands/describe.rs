@@ -0,0 +1,29 @@
+use crate::state::{update_instance, InstanceResolver, InstanceState};
+use crate::ui::info;
+use crate::{Ec2CliError, Result};
+
+/// Set or clear the description of a tracked instance
+pub fn execute(name: String, text: String) -> Result<()> {
+    let resolved = InstanceResolver::load()?.resolve(Some(&name))?;
+    if !resolved.tracked {
+        return Err(Ec2CliError::InstanceNotFound(name));
+    }
+
+    // An empty description clears it
+    let text = text.trim();
+    let description = if text.is_empty() {
+        None
+    } else {
+        InstanceState::validate_description(text)?;
+        Some(text.to_string())
+    };
+
+    let message = match description {
+        Some(ref description) => format!("Description for '{}': {}", resolved.name, description),
+        None => format!("Description for '{}' cleared", resolved.name),
+    };
+    update_instance(&resolved.name, |entry| entry.description = description)?;
+
+    info!("{}", message);
+    Ok(())
+}
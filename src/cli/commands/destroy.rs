@@ -1,25 +1,95 @@
+use chrono::Utc;
 use dialoguer::Confirm;
 
 use crate::aws::client::AwsClients;
-use crate::aws::ec2::instance::{delete_security_group, terminate_instance, wait_for_terminated};
+use crate::aws::ec2::instance::{
+    create_root_snapshot, delete_security_group, get_instance_owner, terminate_instance,
+    wait_for_snapshot_started, wait_for_terminated,
+};
+use crate::config::Settings;
 use crate::git::{list_remotes, remove_remote};
-use crate::state::{get_instance, remove_instance as remove_instance_state, resolve_instance_name};
-use crate::ui::create_spinner;
+use crate::ssh::remove_known_host;
+use crate::state::{
+    mark_instance_terminated, remove_instance as remove_instance_state, InstanceResolver,
+    ResolvedInstance,
+};
+use crate::ui::{create_spinner, format_duration, info};
 use crate::{Ec2CliError, Result};
 
 /// Timeout for waiting for instance termination (seconds)
 const TERMINATION_TIMEOUT_SECS: u64 = 120;
 
-pub async fn execute(name: String, force: bool) -> Result<()> {
-    // Resolve instance name
-    let name = resolve_instance_name(Some(&name))?;
+/// Timeout for waiting for a root volume snapshot to start (seconds)
+const SNAPSHOT_START_TIMEOUT_SECS: u64 = 60;
 
-    // Get instance from state
-    let instance_state =
-        get_instance(&name)?.ok_or_else(|| Ec2CliError::InstanceNotFound(name.clone()))?;
+pub async fn execute(
+    name: String,
+    force: bool,
+    keep_state: bool,
+    snapshot: bool,
+    all_users: bool,
+    dry_run: bool,
+) -> Result<()> {
+    // Resolve instance by name, link, or instance ID
+    let resolved = InstanceResolver::load()?.resolve(Some(&name))?;
+
+    // Only local state and git remotes are read; nothing is checked or changed in AWS
+    if dry_run {
+        let remote_name = format!("ec2-{}", resolved.name);
+        let has_remote = list_remotes().is_ok_and(|remotes| remotes.contains(&remote_name));
+        for line in dry_run_plan(&resolved, keep_state, snapshot, has_remote) {
+            println!("{}", line);
+        }
+        println!();
+        println!("Dry run: nothing was changed.");
+        return Ok(());
+    }
+
+    let ResolvedInstance {
+        name,
+        state: instance_state,
+        linked,
+        tracked,
+    } = resolved;
+
+    // Initialize AWS clients with the correct region
+    let spinner = create_spinner("Connecting to AWS...");
+    let clients = AwsClients::with_region(&instance_state.region).await?;
+    spinner.finish_with_message("Connected to AWS");
+
+    // Credentials for another account can't see the instance; --force proceeds anyway
+    match instance_state.check_account(&clients.account_id) {
+        Err(e) if force => eprintln!("Warning: {}", e),
+        result => result?,
+    }
+
+    // With enforce_owner, only the instance's owner may destroy it (--force doesn't override)
+    let settings = Settings::load().unwrap_or_default();
+    if let Some(owner) = settings.owner_filter(all_users)? {
+        let instance_owner = get_instance_owner(&clients, &instance_state.instance_id).await?;
+        check_owner(&name, owner, instance_owner.as_deref())?;
+    }
 
     // Confirm destruction unless forced
     if !force {
+        let uptime = Utc::now() - instance_state.created_at;
+        println!("Instance '{}' ({})", name, instance_state.instance_id);
+        if let Some(ref instance_type) = instance_state.instance_type {
+            println!("  Type:   {}", instance_type);
+        }
+        if tracked {
+            println!(
+                "  Uptime: {} (launched {})",
+                format_duration(uptime),
+                instance_state.created_at.format("%Y-%m-%d %H:%M UTC")
+            );
+        } else {
+            println!("  Not tracked in local state");
+        }
+        if snapshot {
+            println!("  The root volume will be snapshotted before termination");
+        }
+
         let confirmed = Confirm::new()
             .with_prompt(format!(
                 "Are you sure you want to destroy instance '{}'?",
@@ -35,12 +105,17 @@ pub async fn execute(name: String, force: bool) -> Result<()> {
         }
     }
 
-    println!("Destroying instance '{}'...", name);
+    info!("Destroying instance '{}'...", name);
 
-    // Initialize AWS clients with the correct region
-    let spinner = create_spinner("Connecting to AWS...");
-    let clients = AwsClients::with_region(&instance_state.region).await?;
-    spinner.finish_with_message("Connected to AWS");
+    // Snapshot the root volume first; if this fails the instance is left running
+    if snapshot {
+        let spinner = create_spinner("Snapshotting root volume...");
+        let snapshot_id =
+            create_root_snapshot(&clients, &instance_state.instance_id, &name, &settings.tags)
+                .await?;
+        wait_for_snapshot_started(&clients, &snapshot_id, SNAPSHOT_START_TIMEOUT_SECS).await?;
+        spinner.finish_with_message(format!("Root volume snapshot {} started", snapshot_id));
+    }
 
     // Terminate the instance
     let spinner = create_spinner(format!(
@@ -67,51 +142,179 @@ pub async fn execute(name: String, force: bool) -> Result<()> {
         instance_state.instance_id
     ));
 
-    // Remove from state early - instance is confirmed terminated
+    // Update state early - instance is confirmed terminated
     // This makes the operation more resilient if cleanup steps fail or crash
-    remove_instance_state(&name)?;
+    if keep_state {
+        mark_instance_terminated(&name)?;
+    } else {
+        remove_instance_state(&name)?;
+    }
 
-    // Best-effort security group cleanup
-    if let Some(ref sg_id) = instance_state.security_group_id {
+    // Best-effort security group cleanup (user-supplied groups are left in place)
+    if let Some(sg_id) = instance_state.security_group_to_delete() {
         let spinner = create_spinner(format!("Deleting security group {}...", sg_id));
         match delete_security_group(&clients, sg_id).await {
             Ok(_) => {
                 spinner.finish_with_message(format!("Security group {} deleted", sg_id));
             }
             Err(e) => {
-                spinner.finish_with_message(format!(
-                    "Warning: Could not delete security group {}: {}",
-                    sg_id, e
-                ));
+                // Warnings go to stderr so they survive --quiet
+                spinner.finish_and_clear();
+                eprintln!("Warning: Could not delete security group {}: {}", sg_id, e);
             }
         }
+    } else if let Some(ref sg_id) = instance_state.security_group_id {
+        info!(
+            "  Keeping security group {} (not created by ec2-cli)",
+            sg_id
+        );
     }
 
     // Try to remove git remote if it exists
     let remote_name = format!("ec2-{}", name);
     if let Ok(remotes) = list_remotes() {
         if remotes.contains(&remote_name) {
-            println!("  Removing git remote '{}'...", remote_name);
+            info!("  Removing git remote '{}'...", remote_name);
             let _ = remove_remote(&remote_name);
         }
     }
 
-    // Remove link file if it exists and matches this instance
-    let link_file = std::env::current_dir()
-        .ok()
-        .map(|p| p.join(".ec2-cli").join("instance"));
+    // Drop any pinned host key so a reused instance ID isn't rejected later
+    if let Ok(true) = remove_known_host(&instance_state.instance_id) {
+        info!("  Removed pinned host key");
+    }
 
-    if let Some(link_path) = link_file {
-        if link_path.exists() {
-            if let Ok(linked_name) = std::fs::read_to_string(&link_path) {
-                if linked_name.trim() == name {
-                    let _ = std::fs::remove_file(&link_path);
-                    println!("  Removed directory link");
-                }
-            }
+    // Remove the current directory's link if it points at this instance
+    if linked {
+        if let Ok(cwd) = std::env::current_dir() {
+            let _ = std::fs::remove_file(cwd.join(".ec2-cli").join("instance"));
+            info!("  Removed directory link");
         }
     }
 
-    println!("Instance '{}' destroyed.", name);
+    info!("Instance '{}' destroyed.", name);
+    if keep_state {
+        info!("State entry kept; view it with 'ec2-cli list --all'.");
+    }
     Ok(())
 }
+
+/// Describe what destroying `target` would do, one line per step
+fn dry_run_plan(
+    target: &ResolvedInstance,
+    keep_state: bool,
+    snapshot: bool,
+    has_remote: bool,
+) -> Vec<String> {
+    let instance = &target.state;
+    let mut lines = vec![format!(
+        "Would destroy instance '{}' ({}, {}):",
+        target.name, instance.instance_id, instance.region
+    )];
+
+    if snapshot {
+        lines.push("  - Snapshot the root volume".to_string());
+    }
+    lines.push(format!("  - Terminate instance {}", instance.instance_id));
+
+    if let Some(sg_id) = instance.security_group_to_delete() {
+        lines.push(format!("  - Delete security group {}", sg_id));
+    } else if let Some(ref sg_id) = instance.security_group_id {
+        lines.push(format!(
+            "  - Keep security group {} (not created by ec2-cli)",
+            sg_id
+        ));
+    }
+
+    if target.tracked {
+        lines.push(if keep_state {
+            "  - Mark the state entry terminated".to_string()
+        } else {
+            "  - Remove the state entry".to_string()
+        });
+    }
+    if has_remote {
+        lines.push(format!("  - Remove git remote 'ec2-{}'", target.name));
+    }
+    lines.push("  - Remove any pinned host key".to_string());
+    if target.linked {
+        lines.push("  - Remove the directory link".to_string());
+    }
+
+    lines
+}
+
+/// Refuse to act on an instance whose Username tag isn't `owner`
+fn check_owner(name: &str, owner: &str, instance_owner: Option<&str>) -> Result<()> {
+    if instance_owner == Some(owner) {
+        return Ok(());
+    }
+
+    Err(Ec2CliError::NotInstanceOwner(format!(
+        "'{}' is tagged Username={} but you are {}. Pass --all-users to override",
+        name,
+        instance_owner.unwrap_or("(none)"),
+        owner
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::State;
+
+    fn target(linked: bool, tracked: bool) -> ResolvedInstance {
+        let mut state = State::default();
+        state.add_instance("dev", "i-1", "default", "us-east-1", "ubuntu", "sg-1", None);
+        ResolvedInstance {
+            name: "dev".to_string(),
+            state: state.instances.remove("dev").unwrap(),
+            linked,
+            tracked,
+        }
+    }
+
+    #[test]
+    fn test_dry_run_plan() {
+        let plan = dry_run_plan(&target(true, true), false, true, true);
+        assert_eq!(
+            plan,
+            vec![
+                "Would destroy instance 'dev' (i-1, us-east-1):",
+                "  - Snapshot the root volume",
+                "  - Terminate instance i-1",
+                "  - Delete security group sg-1",
+                "  - Remove the state entry",
+                "  - Remove git remote 'ec2-dev'",
+                "  - Remove any pinned host key",
+                "  - Remove the directory link",
+            ]
+        );
+
+        let mut untracked = target(false, false);
+        untracked.state.security_group_managed = false;
+        let plan = dry_run_plan(&untracked, true, false, false);
+        assert_eq!(
+            plan[1..],
+            [
+                "  - Terminate instance i-1",
+                "  - Keep security group sg-1 (not created by ec2-cli)",
+                "  - Remove any pinned host key",
+            ]
+        );
+
+        let plan = dry_run_plan(&target(false, true), true, false, false);
+        assert!(plan.contains(&"  - Mark the state entry terminated".to_string()));
+    }
+
+    #[test]
+    fn test_check_owner() {
+        assert!(check_owner("dev", "alice", Some("alice")).is_ok());
+
+        let err = check_owner("dev", "alice", Some("bob")).unwrap_err();
+        assert!(matches!(err, Ec2CliError::NotInstanceOwner(msg) if msg.contains("Username=bob")));
+
+        // Instances without a Username tag aren't anyone's
+        assert!(check_owner("dev", "alice", None).is_err());
+    }
+}
@@ -0,0 +1,127 @@
+use std::collections::BTreeMap;
+
+use aws_sdk_ec2::types::InstanceStateName;
+
+use crate::aws::client::AwsClients;
+use crate::aws::ec2::instance::get_instance_state;
+use crate::state::State;
+use crate::ui::{create_spinner, info};
+use crate::{Ec2CliError, Result};
+
+/// Remove state entries whose instances were terminated or deleted outside ec2-cli.
+/// Only AWS is read; unlike `state repair`, nothing missing from state is added.
+pub async fn execute(apply: bool) -> Result<()> {
+    let mut state = State::load()?;
+
+    // Entries kept with `destroy --keep-state` are already known to be terminated
+    let mut by_region: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+    for (name, entry) in &state.instances {
+        if entry.terminated_at.is_none() {
+            by_region.entry(&entry.region).or_default().push(name);
+        }
+    }
+
+    let mut prune = Vec::new();
+    for (region, mut names) in by_region {
+        names.sort();
+        let spinner = create_spinner(format!("Checking instances in {}...", region));
+        let clients = AwsClients::with_region(region).await?;
+
+        for name in names {
+            let entry = &state.instances[name];
+            // Another account's instances would look deleted from this one
+            if entry.check_account(&clients.account_id).is_err() {
+                spinner.suspend(|| {
+                    eprintln!(
+                        "Warning: Skipping '{}': it was launched in another AWS account",
+                        name
+                    )
+                });
+                continue;
+            }
+
+            let live = match get_instance_state(&clients, &entry.instance_id).await {
+                Ok(live) => Some(live),
+                Err(Ec2CliError::InstanceNotFound(_)) => None,
+                Err(e) => {
+                    spinner.finish_and_clear();
+                    return Err(e);
+                }
+            };
+            if should_prune(live.as_ref()) {
+                prune.push((name.to_string(), live));
+            }
+        }
+        spinner.finish_and_clear();
+    }
+
+    if prune.is_empty() {
+        info!("Every instance in state still exists.");
+        return Ok(());
+    }
+
+    for (name, live) in &prune {
+        let entry = &state.instances[name];
+        println!(
+            "- {} ({}, {}): {}",
+            name,
+            entry.instance_id,
+            entry.region,
+            if live.is_some() {
+                "terminated"
+            } else {
+                "not found"
+            }
+        );
+    }
+    println!();
+
+    if !apply {
+        info!(
+            "{} to remove. Re-run with --apply to update state.",
+            prune.len()
+        );
+        return Ok(());
+    }
+
+    for (name, _) in &prune {
+        state.remove_instance(name);
+    }
+    state.save()?;
+
+    info!(
+        "Removed {} entr{} from state.",
+        prune.len(),
+        if prune.len() == 1 { "y" } else { "ies" }
+    );
+    Ok(())
+}
+
+/// Whether a state entry should be removed given its instance's state in AWS
+/// (None when AWS no longer knows the instance)
+fn should_prune(live: Option<&InstanceStateName>) -> bool {
+    matches!(live, None | Some(InstanceStateName::Terminated))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_should_prune() {
+        // Gone, or terminated and still listed for a while
+        assert!(should_prune(None));
+        assert!(should_prune(Some(&InstanceStateName::Terminated)));
+
+        // Instances that exist, including ones on their way down or stopped
+        for live in [
+            InstanceStateName::Pending,
+            InstanceStateName::Running,
+            InstanceStateName::Stopping,
+            InstanceStateName::Stopped,
+            InstanceStateName::ShuttingDown,
+        ] {
+            assert!(!should_prune(Some(&live)), "{:?}", live);
+        }
+    }
+}
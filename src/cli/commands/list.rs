@@ -1,9 +1,189 @@
-use crate::state::list_instances;
-use crate::Result;
+use std::collections::HashMap;
 
-pub fn execute(_all: bool) -> Result<()> {
-    let instances = list_instances()?;
+use chrono::{DateTime, Utc};
+use clap::ValueEnum;
 
+use crate::state::{list_instances, InstanceState};
+use crate::{Ec2CliError, Result};
+
+/// Output format for `list`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum ListFormat {
+    /// Name, instance ID, region, creation time, and description
+    #[default]
+    Table,
+    /// Table plus instance type, root volume, private IP, and last connection
+    Wide,
+}
+
+/// Descriptions longer than this are truncated in the table
+const DESCRIPTION_WIDTH: usize = 30;
+
+/// A column in the instance table
+struct Column {
+    header: &'static str,
+    width: usize,
+    value: fn(&str, &InstanceState) -> String,
+}
+
+impl ListFormat {
+    /// Columns shown for this format, in order
+    fn columns(self) -> Vec<Column> {
+        let mut columns = vec![
+            Column {
+                header: "NAME",
+                width: 20,
+                value: |name, _| name.to_string(),
+            },
+            Column {
+                header: "INSTANCE ID",
+                width: 20,
+                value: |_, state| state.instance_id.clone(),
+            },
+            Column {
+                header: "REGION",
+                width: 15,
+                value: |_, state| state.region.clone(),
+            },
+            Column {
+                header: "CREATED",
+                width: 20,
+                value: |_, state| state.created_at.format("%Y-%m-%d %H:%M").to_string(),
+            },
+        ];
+
+        if self == ListFormat::Wide {
+            columns.extend([
+                Column {
+                    header: "TYPE",
+                    width: 12,
+                    value: |_, state| optional(state.instance_type.clone()),
+                },
+                Column {
+                    header: "VOLUME",
+                    width: 8,
+                    value: |_, state| optional(state.root_volume_gb.map(|gb| format!("{} GB", gb))),
+                },
+                Column {
+                    header: "PRIVATE IP",
+                    width: 16,
+                    value: |_, state| optional(state.private_ip.clone()),
+                },
+                Column {
+                    header: "LAST CONNECTED",
+                    width: 17,
+                    value: |_, state| {
+                        optional(
+                            state
+                                .last_connected_at
+                                .map(|t| t.format("%Y-%m-%d %H:%M").to_string()),
+                        )
+                    },
+                },
+            ]);
+        }
+
+        columns.push(Column {
+            header: "DESCRIPTION",
+            width: DESCRIPTION_WIDTH,
+            value: |_, state| {
+                state
+                    .description
+                    .as_deref()
+                    .map(|d| truncate(d, DESCRIPTION_WIDTH))
+                    .unwrap_or_default()
+            },
+        });
+
+        columns
+    }
+}
+
+/// Placeholder for values not recorded in state (e.g. instances launched by older versions)
+fn optional(value: Option<String>) -> String {
+    value.unwrap_or_else(|| "-".to_string())
+}
+
+/// Shorten `text` to at most `width` characters, marking the cut with "..."
+fn truncate(text: &str, width: usize) -> String {
+    if text.chars().count() <= width {
+        return text.to_string();
+    }
+    let kept: String = text.chars().take(width.saturating_sub(3)).collect();
+    format!("{}...", kept.trim_end())
+}
+
+/// Render the header, separator, and one row per instance, sorted by name
+fn format_table(instances: &HashMap<String, InstanceState>, format: ListFormat) -> Vec<String> {
+    let columns = format.columns();
+
+    let render = |cells: Vec<String>| -> String {
+        columns
+            .iter()
+            .zip(cells)
+            .map(|(column, cell)| format!("{:<width$}", cell, width = column.width))
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+
+    let mut lines = vec![
+        render(columns.iter().map(|c| c.header.to_string()).collect())
+            .trim_end()
+            .to_string(),
+        "-".repeat(columns.iter().map(|c| c.width).sum()),
+    ];
+
+    let mut names: Vec<_> = instances.keys().collect();
+    names.sort();
+
+    for name in names {
+        let state = &instances[name];
+        let mut row = render(columns.iter().map(|c| (c.value)(name, state)).collect());
+        if state.terminated_at.is_some() {
+            row.push_str(" (terminated)");
+        }
+        lines.push(row.trim_end().to_string());
+    }
+
+    lines
+}
+
+/// Whether an instance was created within `since` and `until` (both inclusive)
+fn created_within(
+    state: &InstanceState,
+    since: Option<DateTime<Utc>>,
+    until: Option<DateTime<Utc>>,
+) -> bool {
+    since.is_none_or(|since| state.created_at >= since)
+        && until.is_none_or(|until| state.created_at <= until)
+}
+
+pub fn execute(
+    all: bool,
+    format: ListFormat,
+    since: Option<DateTime<Utc>>,
+    until: Option<DateTime<Utc>>,
+) -> Result<()> {
+    if let (Some(since), Some(until)) = (since, until) {
+        if since > until {
+            return Err(Ec2CliError::Config(
+                "--since must be before --until".to_string(),
+            ));
+        }
+    }
+    let filtered = since.is_some() || until.is_some();
+
+    // Entries kept by `destroy --keep-state` are only shown with --all
+    let instances: HashMap<_, _> = list_instances()?
+        .into_iter()
+        .filter(|(_, state)| all || state.terminated_at.is_none())
+        .filter(|(_, state)| created_within(state, since, until))
+        .collect();
+
+    if instances.is_empty() && filtered {
+        println!("No managed instances were created in that time range.");
+        return Ok(());
+    }
     if instances.is_empty() {
         println!("No managed instances found.");
         println!();
@@ -11,20 +191,8 @@ pub fn execute(_all: bool) -> Result<()> {
         return Ok(());
     }
 
-    println!(
-        "{:<20} {:<20} {:<15} {:<20}",
-        "NAME", "INSTANCE ID", "REGION", "CREATED"
-    );
-    println!("{}", "-".repeat(75));
-
-    for (name, state) in &instances {
-        println!(
-            "{:<20} {:<20} {:<15} {:<20}",
-            name,
-            state.instance_id,
-            state.region,
-            state.created_at.format("%Y-%m-%d %H:%M")
-        );
+    for line in format_table(&instances, format) {
+        println!("{}", line);
     }
 
     println!();
@@ -32,3 +200,105 @@ pub fn execute(_all: bool) -> Result<()> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::State;
+
+    fn sample_instances() -> HashMap<String, InstanceState> {
+        let mut state = State::default();
+        state.add_instance("web", "i-2", "default", "us-east-1", "ubuntu", "sg-1", None);
+        state.add_instance("api", "i-1", "default", "eu-west-1", "ubuntu", "sg-2", None);
+
+        let api = state.instances.get_mut("api").unwrap();
+        api.instance_type = Some("t3.large".to_string());
+        api.root_volume_gb = Some(30);
+        api.private_ip = Some("10.0.1.5".to_string());
+
+        state.instances
+    }
+
+    #[test]
+    fn test_table_format_columns() {
+        let lines = format_table(&sample_instances(), ListFormat::Table);
+
+        assert_eq!(
+            lines[0],
+            format!(
+                "{:<20} {:<20} {:<15} {:<20} {}",
+                "NAME", "INSTANCE ID", "REGION", "CREATED", "DESCRIPTION"
+            )
+        );
+        assert_eq!(lines[1], "-".repeat(75 + 30));
+        // Rows are sorted by name
+        let api_prefix = format!("{:<20} {:<20} {:<15} ", "api", "i-1", "eu-west-1");
+        assert!(lines[2].starts_with(&api_prefix));
+        assert!(lines[3].starts_with("web "));
+        assert!(!lines[2].contains("t3.large"));
+    }
+
+    #[test]
+    fn test_wide_format_columns() {
+        let lines = format_table(&sample_instances(), ListFormat::Wide);
+
+        assert!(lines[0].ends_with(&format!(
+            "{:<12} {:<8} {:<16} {:<17} {}",
+            "TYPE", "VOLUME", "PRIVATE IP", "LAST CONNECTED", "DESCRIPTION"
+        )));
+        assert_eq!(lines[1], "-".repeat(75 + 12 + 8 + 16 + 17 + 30));
+        assert!(lines[2].ends_with(&format!(
+            "{:<12} {:<8} {:<16} -",
+            "t3.large", "30 GB", "10.0.1.5"
+        )));
+        // Missing details show a placeholder
+        assert!(lines[3].ends_with(&format!("{:<12} {:<8} {:<16} -", "-", "-", "-")));
+    }
+
+    #[test]
+    fn test_description_column_truncated() {
+        let mut instances = sample_instances();
+        instances.get_mut("api").unwrap().description = Some("payments API".to_string());
+        instances.get_mut("web").unwrap().description =
+            Some("frontend build box for the checkout redesign".to_string());
+
+        let lines = format_table(&instances, ListFormat::Table);
+        assert!(lines[2].ends_with(" payments API"));
+        assert!(lines[3].ends_with(" frontend build box for the..."));
+    }
+
+    #[test]
+    fn test_truncate() {
+        assert_eq!(truncate("short", 10), "short");
+        assert_eq!(truncate("exactly 10", 10), "exactly 10");
+        assert_eq!(truncate("a bit too long", 10), "a bit t...");
+        assert_eq!(truncate("ééééééééééé", 10), "ééééééé...");
+    }
+
+    #[test]
+    fn test_created_within_is_inclusive() {
+        let mut state = sample_instances().remove("api").unwrap();
+        let created = DateTime::parse_from_rfc3339("2024-05-01T12:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        state.created_at = created;
+        let second = chrono::Duration::seconds(1);
+
+        assert!(created_within(&state, None, None));
+        assert!(created_within(&state, Some(created), Some(created)));
+        assert!(created_within(&state, Some(created - second), None));
+        assert!(created_within(&state, None, Some(created + second)));
+        assert!(!created_within(&state, Some(created + second), None));
+        assert!(!created_within(&state, None, Some(created - second)));
+    }
+
+    #[test]
+    fn test_terminated_suffix() {
+        let mut instances = sample_instances();
+        instances.get_mut("web").unwrap().terminated_at = Some(chrono::Utc::now());
+
+        let lines = format_table(&instances, ListFormat::Table);
+        assert!(lines[3].ends_with(" (terminated)"));
+        assert!(!lines[2].ends_with(" (terminated)"));
+    }
+}
@@ -1,41 +1,500 @@
+use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
 
-use crate::state::{get_instance, resolve_instance_name};
+use chrono::{DateTime, Utc};
+
+use crate::aws::client::AwsClients;
+use crate::aws::ec2::instance::get_console_output;
+use crate::config::Settings;
+use crate::ssh::{run_with_session_retry, ssm_ssh_opts, SSH_CONNECTION_FAILED};
+use crate::state::{InstanceResolver, InstanceState, ResolvedInstance};
+use crate::ui::info;
+use crate::user_data::DEFAULT_INIT_LOG_PATH;
 use crate::{Ec2CliError, Result};
 
-pub fn execute(name: String, follow: bool) -> Result<()> {
-    // Resolve instance name
-    let name = resolve_instance_name(Some(&name))?;
+use super::check_instance_account;
+
+/// cloud-init's own output log, fetched alongside the init log by `--download`
+const CLOUD_INIT_OUTPUT_LOG_PATH: &str = "/var/log/cloud-init-output.log";
+
+/// SSM document that runs a single command in an interactive session
+const INTERACTIVE_COMMAND_DOCUMENT: &str = "AWS-StartInteractiveCommand";
+
+/// Lines of serial console output shown when the instance can't be reached
+const CONSOLE_TAIL_LINES: usize = 100;
+
+pub async fn execute(
+    name: Option<String>,
+    follow: bool,
+    grep: Option<String>,
+    download: Option<PathBuf>,
+) -> Result<()> {
+    if let Some(pattern) = &grep {
+        validate_grep_pattern(pattern)?;
+    }
+    // Resolve instance by name, link, or instance ID
+    let ResolvedInstance {
+        name,
+        state: instance_state,
+        ..
+    } = InstanceResolver::load()?.resolve(name.as_deref())?;
+    check_instance_account(&instance_state).await?;
+    let log_path = instance_log_path(&instance_state)?;
+
+    if let Some(dir) = download {
+        return download_logs(&name, &instance_state, &dir, log_path);
+    }
+
+    info!("Viewing logs from {}...\n", name);
 
-    // Get instance from state
-    let instance_state =
-        get_instance(&name)?.ok_or_else(|| Ec2CliError::InstanceNotFound(name.clone()))?;
+    if follow {
+        return follow_logs(&instance_state, log_path, grep.as_deref()).await;
+    }
 
     let instance_id = &instance_state.instance_id;
     let username = &instance_state.username;
 
-    // Build command to view logs
-    let cmd = if follow {
-        "tail -f /var/log/ec2-cli-init.log"
+    let mut cmd = ssh_command(&instance_state)?;
+    cmd.arg(format!("{}@{}", username, instance_id))
+        .arg(log_command(log_path, follow, grep.as_deref()))
+        .stdin(Stdio::inherit())
+        .stdout(Stdio::inherit());
+
+    // An instance that never came online can still show why in its console output
+    let status = match run_with_session_retry(&mut cmd, true) {
+        Ok((status, _)) if status.code() != Some(SSH_CONNECTION_FAILED) => status,
+        Ok(_) | Err(Ec2CliError::SshCommand(_)) => {
+            show_console_output(&instance_state, grep.is_some()).await?;
+            // The console is only a fallback; scripts should still see the failure
+            return Err(Ec2CliError::SshCommand(format!(
+                "could not connect to {} to read its logs",
+                instance_state.instance_id
+            )));
+        }
+        Err(e) => return Err(e),
+    };
+
+    if !status.success() {
+        // grep also fails when nothing matched; otherwise the log might not exist yet
+        if grep.is_some() {
+            info!("\nNote: No matching lines, or the log file doesn't exist yet.");
+        } else {
+            info!("\nNote: Log file may not exist yet if cloud-init hasn't started.");
+        }
+    }
+
+    Ok(())
+}
+
+/// Print the end of the serial console output, which often shows why boot or
+/// cloud-init failed on an instance that can't be reached over SSM
+async fn show_console_output(instance_state: &InstanceState, grep: bool) -> Result<()> {
+    eprintln!(
+        "\nCould not connect to {} over SSM; showing its console output instead.",
+        instance_state.instance_id
+    );
+
+    let clients = AwsClients::with_region(&instance_state.region).await?;
+    instance_state.check_account(&clients.account_id)?;
+
+    let Some(output) = get_console_output(&clients, &instance_state.instance_id).await? else {
+        info!("No console output yet; EC2 captures it a few minutes after boot.");
+        return Ok(());
+    };
+    if grep {
+        eprintln!("Note: --grep is not applied to console output.");
+    }
+    println!("{}", last_lines(&output, CONSOLE_TAIL_LINES));
+    Ok(())
+}
+
+/// The last `count` lines of `text`
+fn last_lines(text: &str, count: usize) -> String {
+    let lines: Vec<_> = text.lines().collect();
+    lines[lines.len().saturating_sub(count)..].join("\n")
+}
+
+/// An ssh command routed through SSM, with the instance's key if one is recorded
+fn ssh_command(instance_state: &InstanceState) -> Result<Command> {
+    let mut cmd = Command::new("ssh");
+    if let Some(ref key_path) = instance_state.ssh_key_path {
+        cmd.arg("-i").arg(key_path);
+    }
+    cmd.args(ssm_ssh_opts(false)?);
+    Ok(cmd)
+}
+
+/// Save both setup logs from the instance into `dir`
+fn download_logs(
+    name: &str,
+    instance_state: &InstanceState,
+    dir: &Path,
+    log_path: &str,
+) -> Result<()> {
+    prepare_download_dir(dir)?;
+
+    let timestamp = Utc::now();
+    let mut saved = 0;
+    for remote_path in [log_path, CLOUD_INIT_OUTPUT_LOG_PATH] {
+        let output = ssh_command(instance_state)?
+            .arg(format!(
+                "{}@{}",
+                instance_state.username, instance_state.instance_id
+            ))
+            .arg(format!("sudo cat {}", remote_path))
+            .stdin(Stdio::null())
+            .stderr(Stdio::inherit())
+            .output()
+            .map_err(|e| Ec2CliError::SshCommand(e.to_string()))?;
+
+        if !output.status.success() {
+            eprintln!(
+                "Warning: Could not read {} ({})",
+                remote_path, output.status
+            );
+            continue;
+        }
+
+        let local_path = dir.join(download_filename(name, remote_path, timestamp));
+        std::fs::write(&local_path, &output.stdout)?;
+        info!("Saved {} to {}", remote_path, local_path.display());
+        saved += 1;
+    }
+
+    if saved == 0 {
+        return Err(Ec2CliError::SshCommand(format!(
+            "no logs could be downloaded from {}",
+            name
+        )));
+    }
+    Ok(())
+}
+
+/// Create `dir` if needed and check that files can be written to it
+fn prepare_download_dir(dir: &Path) -> Result<()> {
+    let invalid =
+        |e: std::io::Error| Ec2CliError::InvalidPath(format!("'{}': {}", dir.display(), e));
+
+    std::fs::create_dir_all(dir).map_err(invalid)?;
+    let probe = dir.join(format!(".ec2-cli-write-test-{}", std::process::id()));
+    std::fs::write(&probe, b"").map_err(invalid)?;
+    let _ = std::fs::remove_file(&probe);
+    Ok(())
+}
+
+/// Local file name for a downloaded log, e.g.
+/// `dev-ec2-cli-init-20250101T120000Z.log` for `/var/log/ec2-cli-init.log`
+fn download_filename(name: &str, remote_path: &str, timestamp: DateTime<Utc>) -> String {
+    let file_name = Path::new(remote_path)
+        .file_stem()
+        .map(|stem| stem.to_string_lossy())
+        .unwrap_or_default();
+    format!(
+        "{}-{}-{}.log",
+        name,
+        file_name,
+        timestamp.format("%Y%m%dT%H%M%SZ")
+    )
+}
+
+/// The init log of an instance: the path configured when it was launched, or the
+/// default for instances recorded before the path was
+fn instance_log_path(instance: &InstanceState) -> Result<&str> {
+    match instance.init_log_path {
+        Some(ref path) => {
+            // State is a plain file, so check the path before it reaches a remote shell
+            Settings::validate_init_log_path(path)?;
+            Ok(path)
+        }
+        None => Ok(DEFAULT_INIT_LOG_PATH),
+    }
+}
+
+/// Reject patterns that grep would split or the remote shell couldn't receive intact
+fn validate_grep_pattern(pattern: &str) -> Result<()> {
+    if pattern.is_empty() {
+        return Err(Ec2CliError::InvalidPattern(
+            "pattern must not be empty".to_string(),
+        ));
+    }
+    // grep treats each line as a separate pattern, and NUL can't be passed in an argument
+    if pattern.chars().any(|c| c.is_control()) {
+        return Err(Ec2CliError::InvalidPattern(
+            "pattern must not contain newlines or control characters".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Remote command that prints (or with `follow`, streams) the log at `log_path`,
+/// optionally keeping only lines that match `grep`
+fn log_command(log_path: &str, follow: bool, grep: Option<&str>) -> String {
+    let read = if follow {
+        format!("sudo tail -F {}", log_path)
     } else {
-        "cat /var/log/ec2-cli-init.log"
+        format!("cat {}", log_path)
     };
 
-    println!("Viewing logs from {}...\n", name);
+    match grep {
+        Some(pattern) => format!(
+            "{} | grep {}-e {}",
+            read,
+            // Flush each match as it arrives instead of when the pipe buffer fills
+            if follow { "--line-buffered " } else { "" },
+            shell_escape::unix::escape(pattern.into())
+        ),
+        None => read,
+    }
+}
 
-    let status = Command::new("ssh")
-        .arg(format!("{}@{}", username, instance_id))
-        .arg(cmd)
-        .stdin(Stdio::inherit())
-        .stdout(Stdio::inherit())
-        .stderr(Stdio::inherit())
-        .status()
-        .map_err(|e| Ec2CliError::SshCommand(e.to_string()))?;
+/// Arguments to `aws` for an SSM session that follows the log, with the same AWS
+/// profile the other SSM connections use
+fn follow_session_args(
+    instance_state: &InstanceState,
+    log_path: &str,
+    grep: Option<&str>,
+    aws_profile: Option<&str>,
+) -> Vec<String> {
+    let parameters = serde_json::json!({
+        "command": [log_command(log_path, true, grep)],
+    });
 
-    if !status.success() && !follow {
-        // Log file might not exist yet
-        println!("\nNote: Log file may not exist yet if cloud-init hasn't started.");
+    let mut args: Vec<String> = ["ssm", "start-session"].map(String::from).to_vec();
+    if let Some(profile) = aws_profile {
+        args.extend(["--profile".to_string(), profile.to_string()]);
+    }
+    args.extend([
+        "--target".to_string(),
+        instance_state.instance_id.clone(),
+        "--region".to_string(),
+        instance_state.region.clone(),
+        "--document-name".to_string(),
+        INTERACTIVE_COMMAND_DOCUMENT.to_string(),
+        "--parameters".to_string(),
+        parameters.to_string(),
+    ]);
+    args
+}
+
+/// Stream the log over an SSM session until the user interrupts with Ctrl-C
+async fn follow_logs(
+    instance_state: &InstanceState,
+    log_path: &str,
+    grep: Option<&str>,
+) -> Result<()> {
+    let aws_profile = Settings::load()?.effective_aws_profile();
+    let mut child = tokio::process::Command::new("aws")
+        .args(follow_session_args(
+            instance_state,
+            log_path,
+            grep,
+            aws_profile.as_deref(),
+        ))
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| {
+            Ec2CliError::Ssm(
+                format!("Failed to start SSM session: {}", e),
+                Some(e.into()),
+            )
+        })?;
+
+    tokio::select! {
+        status = child.wait() => {
+            let status = status?;
+            if !status.success() {
+                return Err(Ec2CliError::Ssm(format!(
+                    "SSM session exited with {}",
+                    status
+                ), None));
+            }
+        }
+        _ = tokio::signal::ctrl_c() => {
+            // End the session so it doesn't linger on the instance
+            child.kill().await?;
+        }
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::profile::Profile;
+    use crate::user_data::{generate_user_data, init_log_path};
+
+    #[test]
+    fn test_last_lines() {
+        assert_eq!(last_lines("a\nb\nc\n", 2), "b\nc");
+        assert_eq!(last_lines("a\nb", 5), "a\nb");
+        assert_eq!(last_lines("", 3), "");
+    }
+
+    #[test]
+    fn test_log_command_without_grep() {
+        assert_eq!(
+            log_command(DEFAULT_INIT_LOG_PATH, false, None),
+            format!("cat {}", DEFAULT_INIT_LOG_PATH)
+        );
+        assert_eq!(
+            log_command(DEFAULT_INIT_LOG_PATH, true, None),
+            format!("sudo tail -F {}", DEFAULT_INIT_LOG_PATH)
+        );
+    }
+
+    #[test]
+    fn test_log_command_quotes_pattern() {
+        assert_eq!(
+            log_command(DEFAULT_INIT_LOG_PATH, false, Some("error")),
+            format!("cat {} | grep -e error", DEFAULT_INIT_LOG_PATH)
+        );
+        assert_eq!(
+            log_command(DEFAULT_INIT_LOG_PATH, true, Some("-v")),
+            format!(
+                "sudo tail -F {} | grep --line-buffered -e -v",
+                DEFAULT_INIT_LOG_PATH
+            )
+        );
+        // Metacharacters stay inside single quotes
+        assert_eq!(
+            log_command(DEFAULT_INIT_LOG_PATH, false, Some("fail; rm -rf $HOME")),
+            format!(
+                "cat {} | grep -e 'fail; rm -rf $HOME'",
+                DEFAULT_INIT_LOG_PATH
+            )
+        );
+        assert_eq!(
+            log_command(DEFAULT_INIT_LOG_PATH, false, Some("it's `broken`")),
+            format!(
+                "cat {} | grep -e 'it'\\''s `broken`'",
+                DEFAULT_INIT_LOG_PATH
+            )
+        );
+    }
+
+    #[test]
+    fn test_quoted_pattern_survives_shell() {
+        let pattern = "don't $(touch /tmp/x) | \\d+ \"quoted\"";
+        let quoted = shell_escape::unix::escape(pattern.into());
+        let output = Command::new("sh")
+            .arg("-c")
+            .arg(format!("printf '%s' {}", quoted))
+            .output()
+            .unwrap();
+        assert_eq!(String::from_utf8(output.stdout).unwrap(), pattern);
+    }
+
+    #[test]
+    fn test_download_filename() {
+        let timestamp = DateTime::parse_from_rfc3339("2025-01-02T03:04:05Z")
+            .unwrap()
+            .with_timezone(&Utc);
+
+        assert_eq!(
+            download_filename("dev", DEFAULT_INIT_LOG_PATH, timestamp),
+            "dev-ec2-cli-init-20250102T030405Z.log"
+        );
+        assert_eq!(
+            download_filename("i-0123456789abcdef0", CLOUD_INIT_OUTPUT_LOG_PATH, timestamp),
+            "i-0123456789abcdef0-cloud-init-output-20250102T030405Z.log"
+        );
+    }
+
+    #[test]
+    fn test_prepare_download_dir_creates_dir() {
+        let dir = std::env::temp_dir()
+            .join(format!("ec2-cli-logs-test-{}", std::process::id()))
+            .join("nested");
+
+        prepare_download_dir(&dir).unwrap();
+        assert!(dir.is_dir());
+        // The write probe is cleaned up
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+
+        std::fs::remove_dir_all(dir.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_validate_grep_pattern() {
+        assert!(validate_grep_pattern("cloud-init.*error").is_ok());
+        assert!(validate_grep_pattern("a|b").is_ok());
+        assert!(validate_grep_pattern("").is_err());
+        assert!(validate_grep_pattern("first\nsecond").is_err());
+        assert!(validate_grep_pattern("nul\0byte").is_err());
+    }
+
+    #[test]
+    fn test_reads_the_log_user_data_writes() {
+        let script_and_command = |settings: &Settings| {
+            let log_path = init_log_path(settings).unwrap();
+            let profile = Profile::default_profile();
+            let script =
+                generate_user_data(&profile, None, "ubuntu", None, None, log_path).unwrap();
+            (script, log_command(log_path, false, None))
+        };
+
+        let (script, command) = script_and_command(&Settings::default());
+        assert!(script.contains(&format!("exec > >(tee {}) 2>&1", DEFAULT_INIT_LOG_PATH)));
+        assert_eq!(command, format!("cat {}", DEFAULT_INIT_LOG_PATH));
+
+        // An override moves both
+        let settings = Settings {
+            init_log_path: Some("/var/log/setup.log".to_string()),
+            ..Default::default()
+        };
+        let (script, command) = script_and_command(&settings);
+        assert!(script.contains("exec > >(tee /var/log/setup.log) 2>&1"));
+        assert_eq!(command, "cat /var/log/setup.log");
+
+        let settings = Settings {
+            init_log_path: Some("/tmp/$(id)".to_string()),
+            ..Default::default()
+        };
+        assert!(init_log_path(&settings).is_err());
+    }
+
+    #[test]
+    fn test_instance_log_path_recorded_at_launch() {
+        let mut instance: InstanceState = serde_json::from_str(
+            r#"{"instance_id":"i-1","profile":"default","region":"us-east-1","created_at":"2024-01-01T00:00:00Z"}"#,
+        )
+        .unwrap();
+        // Instances from before the path was recorded use the default
+        assert_eq!(instance_log_path(&instance).unwrap(), DEFAULT_INIT_LOG_PATH);
+
+        instance.init_log_path = Some("/var/log/setup.log".to_string());
+        assert_eq!(instance_log_path(&instance).unwrap(), "/var/log/setup.log");
+
+        instance.init_log_path = Some("/tmp/$(id)".to_string());
+        assert!(instance_log_path(&instance).is_err());
+    }
+
+    #[test]
+    fn test_follow_session_uses_aws_profile() {
+        let instance: InstanceState = serde_json::from_str(
+            r#"{"instance_id":"i-1","profile":"default","region":"eu-west-1","created_at":"2024-01-01T00:00:00Z"}"#,
+        )
+        .unwrap();
+
+        let args = follow_session_args(&instance, DEFAULT_INIT_LOG_PATH, None, None);
+        assert_eq!(
+            args[..8],
+            [
+                "ssm",
+                "start-session",
+                "--target",
+                "i-1",
+                "--region",
+                "eu-west-1",
+                "--document-name",
+                INTERACTIVE_COMMAND_DOCUMENT
+            ]
+        );
+        assert!(!args.contains(&"--profile".to_string()));
+
+        let args = follow_session_args(&instance, DEFAULT_INIT_LOG_PATH, None, Some("work"));
+        assert_eq!(args[..4], ["ssm", "start-session", "--profile", "work"]);
+        assert!(args.last().unwrap().contains("sudo tail -F"));
+    }
+}
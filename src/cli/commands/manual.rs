@@ -5,18 +5,37 @@ NAME
     ec2-cli - Ephemeral EC2 Development Environment Manager
 
 SYNOPSIS
-    ec2-cli <command> [options]
-    ec2-cli up [-p <profile>] [-n <name>] [-l]
-    ec2-cli destroy <name> [-f]
-    ec2-cli ssh <name> [-c <command>]
-    ec2-cli scp <name> <src> <dest> [-r]
-    ec2-cli push <name> [-b <branch>]
-    ec2-cli pull <name> [-b <branch>]
+    ec2-cli [-q] [-v] [--parallel <n>] <command> [options]
+    ec2-cli up [-p <profile>] [-n <name>] [-l [--force]] [--refresh-ami]
+               [--spot-fallback] [--no-git] [-d <text>] [--ami <id>]
+               [--instance-type <type>] [--tag <key=value>]... [--wait-complete]
+               [--no-encrypt] [--json]
+    ec2-cli destroy <name> [-f] [--keep-state] [--snapshot] [--all-users]
+                    [--dry-run]
+    ec2-cli reboot <name> [-w]
+    ec2-cli scale <name> <type> [-f]
+    ec2-cli open <name> <port>
+    ec2-cli ssh [name] [-c <command> | -f <script>] [-o <file>] [--env KEY=VALUE]...
+                [--verify-host]
+    ec2-cli scp [name] <src> <dest> [-r] [-p] [--mkdir] [--verify-host]
+                [--ssm | --progress]
+    ec2-cli sync [name] <src> <dest> [--exclude <pattern>]... [--delete]
+    ec2-cli push [name] [--to <name>]... [--all] [-b <branch>] [--no-checkout] [-w]
+                 [--keep-going]
+    ec2-cli push --list-branches
+    ec2-cli pull [name] [-b <branch>]
     ec2-cli status [name]
-    ec2-cli list [-a]
-    ec2-cli logs <name> [-f]
+    ec2-cli describe <name> <text>
+    ec2-cli list [-a] [--format table|wide] [--since <time>] [--until <time>]
+    ec2-cli logs [name] [-f] [-g pattern] [--download <dir>]
     ec2-cli profile <subcommand>
     ec2-cli config <subcommand>
+    ec2-cli state repair [--apply] [--all-users]
+    ec2-cli gc [--apply]
+    ec2-cli snapshots list
+    ec2-cli whoami
+    ec2-cli version [-v]
+    ec2-cli update --check
     ec2-cli manual
 
 DESCRIPTION
@@ -52,63 +71,246 @@ GETTING STARTED
     6. When finished, destroy the instance:
        $ ec2-cli destroy mydev
 
+GLOBAL OPTIONS
+    -q, --quiet
+        Suppress progress spinners and informational messages. Errors,
+        warnings, prompts, and requested output (tables, status, JSON) are
+        still printed. Useful in scripts.
+
+    -v, --verbose
+        When a command fails, also print the underlying causes of the error
+        (for example the raw AWS SDK or I/O error behind an EC2 failure).
+
+    --parallel <n>
+        Run at most n operations at once in commands that act on several
+        instances, to stay under AWS API rate limits (default: 4). Used by
+        'push --wait' with --to or --all.
+
 COMMANDS
-    up [-p <profile>] [-n <name>] [-l]
-        Launch a new EC2 instance.
+    up [-p <profile>] [-n <name>] [-l [--force]] [--refresh-ami]
+       [--spot-fallback] [--no-git] [-d <text>] [--ami <id>]
+       [--instance-type <type>] [--tag <key=value>]... [--wait-complete]
+       [--no-encrypt] [--json]
+        Launch a new EC2 instance. If an earlier 'up' with the same name was
+        interrupted before the instance was ready, the existing instance is
+        reused and setup resumes instead of launching a duplicate. A name that
+        belongs to a ready or stopped instance is rejected.
+
+        'up' returns as soon as the git repo is ready, so you can push while
+        system packages, Docker, and Rust keep installing in the background.
 
         Options:
             -p, --profile <name>    Profile to use (default: "default")
             -n, --name <name>       Custom instance name (auto-generated if omitted)
             -l, --link              Link instance to current directory
+            --force                 With --link, replace a link to another live
+                                    instance without prompting (links to
+                                    instances no longer in state are replaced)
+            --refresh-ami           Ignore the cached AMI ID (cached for 12h)
+            --spot-fallback         Try a spot instance first, then on-demand if
+                                    spot capacity or price is unavailable
+            --no-git                Skip the bare repo and working tree setup
+                                    (for scp/interactive-only workflows)
+            -d, --description <text>
+                                    Note on what the instance is for, shown
+                                    in status and list (max 256 characters)
+            --ami <id>              Launch a specific AMI, overriding the
+                                    profile's ami settings for this launch
+            --instance-type <type>  Launch this instance type instead of the
+                                    profile's (checked against the profile's
+                                    architecture; fallback types are skipped)
+            --tag <key=value>       Add a tag to this instance and its security
+                                    group, on top of the configured tags (a
+                                    key in both uses this value). Repeatable;
+                                    not saved to settings
+            --wait-complete         Also wait for package installation to
+                                    finish (up to 30 minutes)
+            --no-encrypt            Launch with an unencrypted root volume, for
+                                    accounts that can't use EBS encryption
+                                    (asks for confirmation)
+            --json                  Print progress as JSON events on stdout,
+                                    one object per line, instead of spinners
+                                    (warnings and errors stay on stderr)
+
+        JSON events (in order; "git_ready" is skipped with --no-git and
+        "setup_complete" is only sent with --wait-complete):
+            {"event":"launched","instance_id":"i-..."}     (also when resuming)
+            {"event":"running","instance_id":"i-..."}
+            {"event":"ssm_ready","instance_id":"i-..."}
+            {"event":"git_ready","instance_id":"i-..."}
+            {"event":"setup_complete","instance_id":"i-..."}
+            {"event":"ready","name":"...","instance_id":"i-..."}
 
         Examples:
             ec2-cli up                          # Launch with defaults
             ec2-cli up -p rust-dev              # Launch with custom profile
             ec2-cli up -n myproject -l          # Named instance, linked to pwd
+            ec2-cli up --spot-fallback          # Spot if available, else on-demand
+            ec2-cli up -d "perf testing"        # Launch with a description
 
-    destroy <name> [-f]
+    destroy <name> [-f] [--keep-state] [--snapshot] [--all-users] [--dry-run]
         Terminate an instance and cleanup associated resources.
 
         Options:
-            -f, --force             Skip confirmation prompt
+            -f, --force             Skip confirmation prompt and proceed even if
+                                    the active AWS account differs from the
+                                    one the instance was launched in
+            --keep-state            Keep the state entry, marked as terminated
+            --snapshot              Snapshot the root volume before terminating
+                                    (see 'snapshots list')
+            --all-users             Skip the enforce_owner check (see FILES)
+            --dry-run               List the instance and cleanup steps that
+                                    would run, without contacting AWS
 
         Examples:
+            ec2-cli destroy mydev --dry-run     # Check the target first
             ec2-cli destroy mydev               # Interactive confirmation
             ec2-cli destroy mydev -f            # Force destroy
 
-    ssh <name> [-c <command>]
-        SSH into an instance via SSM Session Manager.
+    reboot <name> [-w]
+        Reboot an instance, e.g. after a kernel update or when it stops
+        responding. With -w, wait for the SSM agent to go offline and come
+        back online (up to 5 minutes) before returning.
+
+        Options:
+            -w, --wait              Wait until the instance is reachable again
+
+    scale <name> <type> [-f]
+        Change an instance's type, e.g. for more CPU or memory. A running
+        instance is stopped, modified, started again, and waited on until
+        SSM is back online; a stopped instance is modified and left stopped.
+        The type must match the instance's architecture (e.g. t4g for arm64).
+        Data on the EBS root volume is kept. Spot instances can't be scaled.
+        If AWS rejects the new type, the instance is started with its old one.
+
+        Options:
+            -f, --force             Don't ask before stopping the instance
+
+    open <name> <port>
+        Forward a port from an instance (e.g. a dev server on port 3000) to a
+        free local port over SSM and open http://localhost:<local port> in the
+        default browser. The session stays open until Ctrl-C. Needs the
+        session-manager-plugin; the web app should listen on the instance's
+        localhost or all interfaces.
+
+        Examples:
+            ec2-cli open mydev 3000             # Open the app on port 3000
+
+    ssh [name] [-c <command> | -f <script>] [-o <file>] [--env KEY=VALUE]...
+        [--verify-host]
+        SSH into an instance via SSM Session Manager. If no name given, uses
+        linked instance.
 
         Options:
             -c, --command <cmd>     Execute command instead of interactive shell
+            -f, --command-file <path>
+                                    Run a local script with bash on the
+                                    instance, echoing each command
+            -o, --output <file>     Write the command's stdout to a file
+            --env KEY=VALUE         Export a variable for the command
+                                    (repeatable; no shell metacharacters)
+            --verify-host           Pin and verify the instance host key
+
+        An interactive session that fails to start because the SSM agent
+        isn't connected yet (common right after 'up') is retried up to 3
+        times with a backoff; logs and scp --mkdir do the same. Commands
+        from -c or -f and scp transfers run once, so they never run twice.
 
         Examples:
             ec2-cli ssh mydev                   # Interactive shell
             ec2-cli ssh mydev -c "uname -a"    # Run single command
+            ec2-cli ssh mydev -c "env" -o env.txt  # Save command output
+            ec2-cli ssh mydev -f setup.sh      # Run a local script
+            ec2-cli ssh mydev --env RUST_LOG=debug -c "cargo run"
+            ec2-cli ssh                         # Linked instance
 
-    scp <name> <src> <dest> [-r]
+    scp [name] <src> <dest> [-r] [-p] [--mkdir] [--verify-host]
+        [--ssm | --progress]
         Copy files to/from an instance via SSM. Prefix remote paths with ":".
+        If only <src> and <dest> are given, uses linked instance.
 
         Options:
             -r, --recursive         Copy directories recursively
+            -p, --preserve          Preserve modification times and modes
+            --mkdir                 Create the remote destination directory
+                                    (the parent, or the path itself if it
+                                    ends with /) before uploading
+            --verify-host           Pin and verify the instance host key
+            --ssm                   Copy one file of up to 16 KiB with SSM
+                                    Run Command instead of scp, for when
+                                    ssh through the SSM proxy isn't
+                                    available (needs ssm:SendCommand)
+            --progress              Show elapsed time while copying, then
+                                    the size copied and the throughput
 
         Examples:
             ec2-cli scp mydev ./file.txt :/home/ubuntu/
             ec2-cli scp mydev :/home/ubuntu/file.txt ./
             ec2-cli scp mydev -r ./project :/home/ubuntu/
+            ec2-cli scp mydev --progress ./data.tar.gz :
+            ec2-cli scp mydev --mkdir ./a.csv :data/in/  # Creates ~/data/in
+            ec2-cli scp ./file.txt :/home/ubuntu/   # Linked instance
+            ec2-cli scp mydev --ssm :.bashrc ./     # Without ssh
+
+    sync [name] <src> <dest> [--exclude <pattern>]... [--ignore-file <path>]
+         [--delete] [--verify-host]
+        Copy files to/from an instance with rsync over SSM, transferring only
+        what changed. Paths work as for scp; as with rsync, a trailing / on
+        <src> copies the directory's contents. Needs rsync locally and on the
+        instance. Exclude patterns use rsync syntax (like .gitignore, but
+        without ! negation). Patterns are read from .ec2-cli-ignore in the
+        current directory if it exists, one per line (# starts a comment).
 
-    push <name> [-b <branch>]
-        Push local git repository to the instance's bare repository.
+        Options:
+            --exclude <pattern>     Skip matching files (repeatable)
+            --ignore-file <path>    Read patterns from this file instead of
+                                    ./.ec2-cli-ignore
+            --delete                Remove destination files missing from
+                                    the source
+            --verify-host           Pin and verify the instance host key
+
+        Examples:
+            ec2-cli sync mydev ./data/ :data/ --exclude '*.tmp'
+            ec2-cli sync mydev :project/target/release/ ./release/
+            ec2-cli sync ./ :project/ --exclude target/ --delete
+
+    push [name] [--to <name>]... [--all] [-b <branch>] [--no-checkout] [-w]
+         [--keep-going]
+        Push local git repository to the instance's bare repository. The
+        instance's working tree checks out the pushed branch. If no name
+        given, uses linked instance. With --to or --all, pushes to each
+        instance in turn and stops at the first failure, listing the rest
+        as skipped; with --keep-going it pushes to every instance anyway.
+        If any push failed, a summary table follows and the exit status is
+        non-zero.
 
         Options:
+            -w, --wait              Wait for the instance's git repo setup
+                                    first (for instances still launching;
+                                    several are waited on at once, see
+                                    --parallel; an instance whose repo
+                                    never appears counts as a failed push)
+            --to <name>             Also push to this instance (repeatable)
+            -a, --all               Push to every active instance in state
             -b, --branch <name>     Branch to push (default: current branch)
+            --no-checkout           Push to refs/for/<branch> and leave the
+                                    working tree alone
+            --keep-going            Carry on past failed pushes
+            --list-branches         List local branches with their commits
+                                    (* marks the current one) and exit
 
         Examples:
             ec2-cli push mydev                  # Push current branch
             ec2-cli push mydev -b feature       # Push specific branch
+            ec2-cli push mydev --no-checkout    # Push without switching branch
+            ec2-cli push --to x86 --to arm      # Push to two instances
+            ec2-cli push --all                  # Push to every instance
+            ec2-cli push --all --keep-going     # ...even if some fail
+            ec2-cli push --list-branches        # Branches you can pass to -b
 
-    pull <name> [-b <branch>]
-        Pull from the instance's bare repository to local.
+    pull [name] [-b <branch>]
+        Pull from the instance's bare repository to local. If no name given,
+        uses linked instance.
 
         Options:
             -b, --branch <name>     Branch to pull (default: current branch)
@@ -124,40 +326,140 @@ COMMANDS
             ec2-cli status mydev               # Named instance
             ec2-cli status                     # Linked instance
 
-    list [-a]
+    describe <name> <text>
+        Set the description of an instance (at most 256 characters). The
+        description is shown in status and, truncated, in list. Pass an
+        empty string to clear it.
+
+        Examples:
+            ec2-cli describe mydev "load testing the new cache"
+            ec2-cli describe mydev ""          # Clear the description
+
+    list [-a] [--format table|wide] [--since <time>] [--until <time>]
         List all managed instances.
 
         Options:
             -a, --all               Include terminated instances
+            --format <format>       table (default) or wide, which adds
+                                    instance type, root volume, private IP,
+                                    and last connection time
+            --since <time>          Only instances created at or after
+                                    <time>: a duration ago (90s, 30m, 24h,
+                                    7d, 2w) or an RFC 3339 timestamp
+                                    (2024-05-01T00:00:00Z)
+            --until <time>          Only instances created at or before
+                                    <time> (same formats)
 
         Examples:
             ec2-cli list                       # Active instances only
             ec2-cli list -a                    # Include terminated
-
-    logs <name> [-f]
-        View cloud-init logs from an instance.
+            ec2-cli list --format wide         # Show launch details
+            ec2-cli list -a --since 7d         # Created in the last week
+
+    logs [name] [-f] [-g pattern] [--download <dir>]
+        View cloud-init logs from an instance. If no name given, uses linked
+        instance. With -f, logs are streamed over an SSM session until
+        interrupted with Ctrl-C. With -g, only lines matching the pattern
+        (a grep basic regular expression) are shown. With --download, the
+        init log and cloud-init-output.log are saved into the directory
+        (created if needed) as <name>-<log>-<UTC timestamp>.log.
+
+        If the instance can't be reached over SSM (e.g. setup failed before
+        the agent started), the last 100 lines of its EC2 serial console
+        output are shown instead, which often reveal boot or cloud-init
+        errors. This needs the ec2:GetConsoleOutput permission. The command
+        still exits with an error, since the logs themselves weren't read.
 
         Options:
             -f, --follow            Follow log output (like tail -f)
+            -g, --grep <pattern>    Only show matching lines
+            --download <dir>        Save the logs to local files instead
 
         Examples:
             ec2-cli logs mydev                 # View logs
             ec2-cli logs mydev -f              # Follow logs
+            ec2-cli logs mydev -g error        # Only lines containing "error"
+            ec2-cli logs mydev --download logs # Save to ./logs/
 
     profile list
         List all available profiles.
 
-    profile show <name>
-        Display details of a specific profile.
+    profile show <name> [--resolved]
+        Display details of a specific profile. With --resolved, print the
+        whole profile as JSON with every default filled in (instance type,
+        architecture, volume IOPS and throughput, ...), i.e. exactly what
+        up would use.
 
-    profile validate <name>
-        Validate a profile's configuration.
+        Options:
+            --resolved              Print all fields with defaults as JSON
+
+    profile validate <name | --all>
+        Validate a profile's configuration. With --all, validate every
+        local, global, and built-in profile, print a pass/fail line for each
+        and a summary, and exit non-zero if any fail (useful in CI).
+
+    profile lint <name>
+        Report every problem in a profile instead of stopping at the first:
+        validation errors, instance and fallback types that are malformed or
+        don't match the architecture, repeated fallback types, a name that
+        differs from the file name, and a local profile hiding a global one.
+        Exits non-zero if any are found.
+
+    profile create <name> [--template <template>]
+        Create ~/.config/ec2-cli/profiles/<name>.json5 from the default
+        profile or a template, then offer to open it in $EDITOR. Fails if a
+        profile with that name already exists.
+
+        Templates:
+            rust      c6i.2xlarge, 60 GB, Rust with rust-src, clang, lld
+            python    python3, pip, venv, and headers (no Rust)
+            node      nodejs and npm (no Rust)
+            go        golang-go (no Rust)
+            minimal   t3.medium, 20 GB, git only (no Rust or AgentFS)
 
-    config init
-        Initialize configuration and verify prerequisites.
+        Examples:
+            ec2-cli profile create ml --template python
+
+    profile edit <name>
+        Open a profile in $EDITOR (default: vi) and validate it after saving.
+        Editing "default" without a profile file first writes the built-in
+        default to ~/.config/ec2-cli/profiles/default.json5.
+
+    config init [--non-interactive [--region <region>] [--vpc <id>]
+                [--subnet <id>] [--username <name>]] [--reset [--force]]
+        Initialize configuration and verify prerequisites. If managed
+        instances already exist in the region, offers to reuse the subnet
+        they run in (the one with the most, if several) before asking for a
+        VPC and subnet. With --non-interactive, settings come from the
+        flags (or values already configured) instead of prompts, and a
+        missing region, subnet, or username is an error. Without --vpc the
+        default VPC is used.
+        With --reset, config.json is first copied to config.json.bak and
+        configuration starts from defaults, ignoring current settings;
+        profiles and instance state are untouched. It asks first unless
+        --force is given (required with --non-interactive).
 
-    config show
-        Display current configuration settings.
+        Examples:
+            ec2-cli config init --non-interactive --region us-east-1 \
+                --subnet subnet-0123456789abcdef0 --username ci
+            ec2-cli config init --reset
+
+    config show [--json]
+        Display current configuration settings. With --json, print the
+        resolved settings and file locations as JSON.
+
+    config set-region <region>
+    config set-vpc <id>
+    config set-subnet <id>
+        Change a single setting without running 'config init'. The value is
+        checked against AWS first: the region must be enabled, the VPC must
+        exist in the configured region, and the subnet must belong to the
+        configured VPC (or the default VPC). Nothing is saved if a check
+        fails.
+
+        Examples:
+            ec2-cli config set-region eu-west-1
+            ec2-cli config set-subnet subnet-0123456789abcdef0
 
     config tags set <key> <value>
         Set a custom tag applied to all AWS resources.
@@ -168,6 +470,44 @@ COMMANDS
     config tags remove <key>
         Remove a custom tag.
 
+    snapshots list
+        List root volume snapshots kept by 'destroy --snapshot' in the
+        current region, newest first. Snapshots are tagged as managed and
+        named after their instance; delete them with the AWS CLI or console
+        when no longer needed.
+
+    state repair [--apply] [--all-users]
+        Reconcile local state with AWS. Adds managed instances that are
+        missing from state (named from their ec2-cli:name tag, or by
+        instance ID when that name is taken or shared by several live
+        instances) and drops entries whose instances are terminated or
+        gone. Without --apply, only prints the changes. With enforce_owner
+        set, only instances tagged with your Username are added unless
+        --all-users is given.
+
+    gc [--apply]
+        Remove state entries whose instances were terminated or deleted
+        outside ec2-cli, checking each instance in its own region. Unlike
+        'state repair', nothing is added and only instances in state are
+        looked up. Entries kept with 'destroy --keep-state' and entries
+        from another AWS account are left alone. Without --apply, only
+        prints the entries that would be removed.
+
+    whoami
+        Show the AWS account, caller ARN, and region in use, plus a count of
+        managed instances and whether the IAM resources exist.
+
+    version [-v]
+        Print the ec2-cli version. With -v, also print the git commit, build
+        target, platform, and AWS SDK version, for including in bug reports.
+        The global --version flag still prints the version alone.
+
+    update --check
+        Ask GitHub for the latest release and, if it's newer than this one,
+        print the command to upgrade with. Installing is not done for you.
+        Set "update_check": true in config.json to also check after other
+        commands, at most once a day (see FILES).
+
     completions <shell>
         Generate shell completions (bash, zsh, fish).
 
@@ -181,14 +521,59 @@ COMMANDS
 FILES
     ~/.config/ec2-cli/config.json
         Global configuration file containing custom tags, region override,
-        VPC/subnet settings.
+        VPC/subnet settings. Set "aws_profile" to a profile name from
+        ~/.aws/config to always use it, regardless of AWS_PROFILE, for AWS
+        calls and the SSM sessions behind ssh, scp, and git. Set
+        "native_ssm_proxy": true to start SSM sessions without the AWS CLI
+        (session-manager-plugin is still needed).
+        Set "network_mode": "byo" to launch only into the configured VPC and
+        subnet (e.g. a corporate network with its own SSM endpoints); unlike
+        the default "managed" mode, a missing VPC is an error instead of
+        falling back to the default VPC. up still creates the security group
+        and IAM role, and checks the subnet can reach SSM either way.
+        Set "enforce_owner": true to have 'state repair' and 'destroy' only
+        act on instances whose Username tag matches yours (--all-users
+        overrides this for admins).
+        "ssh_connect_timeout" (default 30) and "ssh_keepalive_interval"
+        (default 15) set the seconds ssh, scp, and logs wait to connect and
+        between keepalives; a connection is dropped after 4 missed keepalives.
+        "resource_prefix" (default "ec2-cli") names security groups, IAM
+        roles, and Name tags, and sets the "deployment" tag; up, snapshots,
+        and state repair only look up instances and snapshots with your
+        prefix, so teams sharing an account can keep separate fleets.
+        Lowercase letters, digits, and hyphens, up to 32 characters.
+        "init_log_path" (default "/var/log/ec2-cli-init.log") moves the
+        setup log on new instances. Each instance remembers the path it was
+        launched with, so logs still finds the log on older instances.
+        "update_check": true prints a note after commands when a newer
+        release exists. GitHub is asked at most once a day, with the answer
+        cached in update_check.json next to this file; being offline only
+        skips the note.
+        "instance_defaults" is a baseline for every profile's "instance"
+        section, e.g. {"storage": {"root_volume": {"size_gb": 100}}}. Fields
+        a profile sets win, nested ones included; the built-in default
+        profile takes all of them.
+
+    ~/.config/ec2-cli/config.json.bak
+        The configuration as it was before the last 'config init --reset'.
 
     ~/.config/ec2-cli/profiles/
         Directory for global profile definitions (JSON5 format).
 
+    ~/.config/ec2-cli/known_hosts
+        Host keys pinned by --verify-host, keyed by instance ID.
+
+    ~/.config/ec2-cli/machine_id
+        Random ID created only when the hostname can't be read; with your
+        username, it stands in for the hostname in per-machine IAM names.
+
     ~/.local/state/ec2-cli/state.json
         Local state file tracking active instances.
 
+    ~/.cache/ec2-cli/ami_cache.json
+        AMI IDs resolved per region, AMI type, and architecture (12h TTL).
+        Safe to delete.
+
     .ec2-cli/profiles/
         Project-local profile directory (takes precedence over global).
 
@@ -220,24 +605,116 @@ PROFILES
                 size_gb: 30,               // 8-16384 GB
                 type: "gp3",               // gp2, gp3, io1, io2, st1, sc1
                 iops: 3000,                // For gp3/io1/io2
-                throughput: 125            // For gp3 (MB/s)
+                throughput: 125,           // For gp3 (MB/s)
+                kms_key_id: null           // KMS key ID, alias, or ARN (default key
+                                           // if omitted)
               }
             }
           },
           packages: {
             system: ["build-essential", "git"],  // apt packages
+            system_arm64: [],              // Extra apt packages for arm64 only
+            system_x86_64: [],             // Extra apt packages for x86_64 only
+            apt_repositories: [            // Added before apt-get update
+              { uri: "ppa:deadsnakes/ppa" },
+              { uri: "https://apt.example.com/ubuntu", suite: "jammy",
+                components: ["main"],
+                key_url: "https://apt.example.com/key.asc" }  // HTTPS only
+            ],
             rust: {
               enabled: true,
               channel: "stable",           // stable, beta, nightly
               components: ["rustfmt", "clippy"]
             },
-            cargo: ["cargo-watch"]         // Cargo packages to install
+            cargo: [                       // Cargo packages to install
+              "cargo-watch",
+              // Also accepts features: [...]
+              { name: "cargo-nextest", version: "0.9", locked: true },
+              // From git, optionally pinned with one of rev, tag, or branch
+              { name: "tool", git: "https://github.com/org/tool", tag: "v1.0" },
+              // From a private registry; the token is an SSM parameter
+              { name: "internal", registry: "corp",
+                index: "sparse+https://cargo.corp.example/index/",
+                token_parameter: "/corp/cargo-token" }
+            ]
           },
           environment: {
             EDITOR: "vim"                  // Environment variables
-          }
+          },
+          environment_passthrough: ["RUST_LOG"], // Local vars copied at launch
+          tools: {
+            agentfs: true                  // Install AgentFS
+          },
+          hooks: {
+            post_ready: "code --remote ssh-remote+{instance_id}", // Local command
+            on_push: "cargo build --release"  // Run on the instance after push
+          },
+          system: {
+            timezone: "Europe/Berlin",     // IANA time zone (default: UTC)
+            locale: "en_US.UTF-8",         // Generated and set as LANG
+            swap_gb: 8                     // Swapfile size (less than the root volume)
+          },
+          dotfiles: {
+            repo: "https://github.com/me/dotfiles",  // Cloned to ~/.dotfiles
+            install_command: "./install.sh"          // Run from the clone
+          },
+          docker: {
+            compose_file: "services:\n  db:\n    image: postgres:16\n",
+            run: ["-d -p 6379:6379 redis:7"]  // Arguments for `docker run`
+          },
+          user_data_extra: "",             // Raw shell run as root at the end of setup
+          allow_raw_user_data: false       // Must be true for user_data_extra to run
         }
 
+    environment_passthrough names local environment variables to export on
+    the instance as well. Each is read when 'up' runs; unset or empty ones
+    are skipped, and a set value overrides the same key in environment.
+
+    hooks.post_ready runs on your machine with `sh -c` after `up` finishes.
+    {name}, {instance_id}, and {region} are replaced with shell-quoted
+    values; the rest of the command is run as written.
+
+    hooks.on_push runs on the instance after each 'push' checks out a
+    branch, as your instance user, from ~/work/<project>, in a login shell.
+    Its output is shown by 'push'; a failure is reported but the push still
+    succeeds. It must be a single line, and shell syntax (&&, pipes,
+    quotes, $VARS) is rejected unless allow_raw_user_data is true; call a
+    script in the repo for anything more. Pushes with --no-checkout don't
+    run it.
+
+    dotfiles.repo is cloned into ~/.dotfiles as your instance user near the
+    end of setup, then install_command is run from the clone. The repo must
+    be an https:// URL the instance can reach without credentials, and
+    neither value may contain shell metacharacters. A failed clone or
+    install is logged as a warning and doesn't stop the setup.
+
+    docker.compose_file is written to ~/work/compose.yaml right after Docker
+    is installed and started with 'docker compose up -d'; it can be at most
+    8 KB, since EC2 limits the whole setup script to 16 KB. Each docker.run
+    entry is passed to 'docker run', must include -d (a foreground
+    container would keep the setup from finishing) and may not contain
+    shell metacharacters. Containers that fail to start are logged as
+    warnings.
+
+    A cargo registry token is kept in an SSM SecureString parameter named
+    by token_parameter (e.g. 'aws ssm put-parameter --type SecureString
+    --name /corp/cargo-token --value ...'). The instance reads it with its
+    own role while installing and passes it to that cargo install only, so
+    it is never in the user data or a command line. The role can decrypt
+    parameters under the default aws/ssm key; a customer managed key must
+    also allow the instance role. The AWS CLI is installed with snap for
+    this if the AMI lacks it. token_env, which put the token itself in the
+    user data, is no longer accepted.
+
+    user_data_extra is appended to the cloud-init script verbatim, after all
+    other setup and before the ready marker. It is NOT validated or escaped:
+    only use profiles whose contents you trust. It is ignored unless
+    allow_raw_user_data is true.
+
+    With architecture "arm64", the default instance types are replaced by
+    their Graviton equivalents (t3.large -> t4g.large). Explicit x86_64
+    types such as c6i.xlarge are rejected with a suggested alternative.
+
     Example: Create ~/.config/ec2-cli/profiles/rust-dev.json5
         {
           name: "rust-dev",
@@ -263,7 +740,8 @@ SECURITY
 
     Instance Security:
       - IMDSv2 required (protects against SSRF attacks)
-      - EBS volumes encrypted by default
+      - EBS volumes encrypted by default (with the profile's kms_key_id if
+        set; 'up --no-encrypt' turns this off after a confirmation)
       - No SSH keys stored or transmitted
 
     Credential Security:
@@ -296,11 +774,32 @@ ENVIRONMENT VARIABLES
         Override the default AWS region.
 
     AWS_PROFILE
-        Use a specific AWS CLI profile.
+        Use a specific AWS CLI profile. The "aws_profile" setting takes
+        precedence. Commands on an instance, ssh, scp, sync, push, pull, and
+        logs included, stop with exit status 5 when the profile is for
+        another account than the one the instance was launched in.
 
     EC2_CLI_NO_COLOR
         Disable colored output when set to any value.
 
+EXIT STATUS
+    0       Success
+    1       General error (AWS API failures, git errors, etc.)
+    2       Invalid command-line usage
+    3       Not found: instance, profile, SSH key, path, VPC, or subnet
+    4       Missing prerequisites or configuration
+    5       AWS credentials missing, invalid, or for a different account
+    6       Operation timed out
+    7       Invalid input: profile validation, paths, descriptions
+    130     Cancelled at a prompt
+
+    Commands run over several instances or profiles (push --to/--all,
+    profile validate --all) exit with the failures' shared status, or 1
+    if they failed for different reasons.
+
+    Commands that run a remote command (ssh -c) exit with that command's
+    exit status instead.
+
 EXAMPLES
     Basic workflow:
         # Launch a development instance
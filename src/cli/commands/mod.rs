@@ -1,31 +1,60 @@
 pub mod config;
+pub mod describe;
 pub mod destroy;
+pub mod gc;
 pub mod list;
 pub mod logs;
 pub mod manual;
+pub mod open;
+pub mod profile;
 pub mod pull;
 pub mod push;
+pub mod reboot;
+pub mod scale;
 pub mod scp;
+pub mod snapshots;
 pub mod ssh;
+pub mod ssm_proxy;
+pub mod state;
 pub mod status;
+pub mod sync;
 pub mod up;
+pub mod update;
+pub mod version;
+pub mod whoami;
+
+use crate::aws::client::AwsClients;
+use crate::state::InstanceState;
+use crate::ui::create_spinner;
+use crate::Result;
+
+/// Check that the active AWS credentials are for the account `instance` was launched
+/// in, before connecting to it through SSM. Entries without a recorded account pass
+/// without calling AWS.
+pub async fn check_instance_account(instance: &InstanceState) -> Result<()> {
+    if instance.account_id.is_none() {
+        return Ok(());
+    }
+    let spinner = create_spinner("Connecting to AWS...");
+    let clients = AwsClients::with_region(&instance.region).await;
+    spinner.finish_and_clear();
+    instance.check_account(&clients?.account_id)
+}
 
 /// Returns the SSH command string for use with GIT_SSH_COMMAND environment variable.
 /// This routes git SSH connections through AWS SSM Session Manager.
 ///
 /// If `ssh_key_path` is provided, adds `-i <path>` to specify the identity file.
 pub fn ssm_ssh_command(ssh_key_path: Option<&str>) -> String {
-    // Escape single quotes in path for shell safety (replace ' with '\'' which ends the
-    // quoted string, adds an escaped quote, and starts a new quoted string)
+    // GIT_SSH_COMMAND is run through the shell, so quote each argument
     let identity_flag = ssh_key_path
-        .map(|path| format!("-i '{}' ", path.replace('\'', "'\\''")))
+        .map(|path| format!("-i {} ", shell_escape::escape(path.into())))
         .unwrap_or_default();
+    let proxy_option = format!("ProxyCommand={}", crate::ssh::ssm_proxy_command());
 
     format!(
-        "ssh {}{}{}{}",
+        "ssh {}-o {} -o StrictHostKeyChecking=no -o UserKnownHostsFile=/dev/null",
         identity_flag,
-        "-o 'ProxyCommand=sh -c \"aws ssm start-session --target %h ",
-        "--document-name AWS-StartSSHSession --parameters portNumber=%p\"' ",
-        "-o StrictHostKeyChecking=no -o UserKnownHostsFile=/dev/null"
+        shell_escape::escape(proxy_option.into())
     )
 }
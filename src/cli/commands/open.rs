@@ -0,0 +1,151 @@
+use std::net::{Ipv4Addr, TcpListener, TcpStream};
+use std::process::Command;
+use std::time::Duration;
+
+use crate::aws::client::AwsClients;
+use crate::aws::retry::poll_until;
+use crate::state::{InstanceResolver, ResolvedInstance};
+use crate::ui::{create_spinner, info};
+use crate::{Ec2CliError, Result};
+
+use super::ssm_proxy::plugin_session;
+
+/// SSM document that forwards a remote port to a local one
+const PORT_FORWARD_DOCUMENT: &str = "AWS-StartPortForwardingSession";
+
+/// How long to wait for session-manager-plugin to start listening locally
+const LISTEN_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Interval between checks for the local port accepting connections
+const LISTEN_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+pub async fn execute(name: String, port: u16) -> Result<()> {
+    // Resolve instance by name, link, or instance ID
+    let ResolvedInstance {
+        name,
+        state: instance_state,
+        ..
+    } = InstanceResolver::load()?.resolve(Some(&name))?;
+
+    let spinner = create_spinner("Connecting to AWS...");
+    let clients = AwsClients::with_region(&instance_state.region).await?;
+    spinner.finish_and_clear();
+    instance_state.check_account(&clients.account_id)?;
+
+    let local_port = pick_local_port()?;
+    let mut plugin = plugin_session(
+        &clients,
+        &instance_state.instance_id,
+        PORT_FORWARD_DOCUMENT,
+        &[
+            ("portNumber", port.to_string()),
+            ("localPortNumber", local_port.to_string()),
+        ],
+    )
+    .await?
+    .spawn()
+    .map_err(|_| Ec2CliError::SessionManagerPluginNotFound)?;
+
+    // The plugin prints its own progress; wait for it to listen before opening the browser
+    let listening = poll_until(LISTEN_TIMEOUT, LISTEN_POLL_INTERVAL, || {
+        let exited = plugin.try_wait().map(|status| status.is_some());
+        async move {
+            if exited? {
+                return Err(Ec2CliError::Ssm(
+                    "Port forwarding session ended before it was ready".to_string(),
+                    None,
+                ));
+            }
+            Ok(TcpStream::connect((Ipv4Addr::LOCALHOST, local_port)).is_ok())
+        }
+    })
+    .await;
+
+    match listening {
+        Ok(true) => {}
+        result => {
+            let _ = plugin.kill();
+            let _ = plugin.wait();
+            return Err(result.err().unwrap_or_else(|| {
+                Ec2CliError::Timeout(format!(
+                    "local port {} for the session to '{}'",
+                    local_port, name
+                ))
+            }));
+        }
+    }
+
+    let url = local_url(local_port);
+    info!("Forwarding {} to port {} on '{}'", url, port, name);
+    info!("Press Ctrl-C to stop.");
+    match browser_command(&url).status() {
+        Ok(status) if status.success() => {}
+        _ => eprintln!("Warning: Could not open a browser; visit {} instead", url),
+    }
+
+    // Ctrl-C reaches the plugin too, which ends the session
+    let status = plugin.wait()?;
+    if !status.success() {
+        return Err(Ec2CliError::Ssm(
+            match status.code() {
+                Some(code) => format!("Port forwarding session exited with status {}", code),
+                None => "Port forwarding session was terminated by a signal".to_string(),
+            },
+            None,
+        ));
+    }
+    Ok(())
+}
+
+/// Ask the OS for a free local port. The listener is closed before the plugin
+/// binds the port, so another process could take it in between, but that's unlikely.
+fn pick_local_port() -> Result<u16> {
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))?;
+    Ok(listener.local_addr()?.port())
+}
+
+/// The browser URL for a forwarded local port
+fn local_url(local_port: u16) -> String {
+    format!("http://localhost:{}", local_port)
+}
+
+/// The platform's command for opening `url` in the default browser
+fn browser_command(url: &str) -> Command {
+    let mut cmd = if cfg!(target_os = "macos") {
+        Command::new("open")
+    } else if cfg!(windows) {
+        let mut cmd = Command::new("cmd");
+        cmd.args(["/C", "start", ""]);
+        cmd
+    } else {
+        Command::new("xdg-open")
+    };
+    cmd.arg(url);
+    cmd
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pick_local_port_is_free() {
+        let port = pick_local_port().unwrap();
+        assert_ne!(port, 0);
+        // Nothing holds the port once it's picked
+        TcpListener::bind((Ipv4Addr::LOCALHOST, port)).unwrap();
+    }
+
+    #[test]
+    fn test_local_url() {
+        assert_eq!(local_url(49152), "http://localhost:49152");
+
+        let url = local_url(pick_local_port().unwrap());
+        let port: u16 = url
+            .strip_prefix("http://localhost:")
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert_ne!(port, 0);
+    }
+}
@@ -0,0 +1,209 @@
+use std::io::IsTerminal;
+use std::path::Path;
+use std::process::Command;
+
+use clap::ValueEnum;
+use dialoguer::Confirm;
+
+use crate::cli::batch::BatchResult;
+use crate::profile::{Profile, ProfileLoader};
+use crate::util::expand_path;
+use crate::{Ec2CliError, Result};
+
+/// Editor used when $EDITOR is not set
+const DEFAULT_EDITOR: &str = "vi";
+
+/// Built-in starting points for `profile create --template`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ProfileTemplate {
+    /// Compute-optimized instance with Rust, clang, and lld
+    Rust,
+    /// Python 3 with pip, venv, and development headers
+    Python,
+    /// Node.js with npm
+    Node,
+    /// Go toolchain
+    Go,
+    /// Small instance with only git
+    Minimal,
+}
+
+impl ProfileTemplate {
+    fn profile(self) -> Profile {
+        match self {
+            ProfileTemplate::Rust => Profile::rust_profile(),
+            ProfileTemplate::Python => Profile::python_profile(),
+            ProfileTemplate::Node => Profile::node_profile(),
+            ProfileTemplate::Go => Profile::go_profile(),
+            ProfileTemplate::Minimal => Profile::minimal_profile(),
+        }
+    }
+}
+
+/// Create a global profile from a template (or the default profile), then offer
+/// to open it in $EDITOR for adjustments
+pub fn create(name: &str, template: Option<ProfileTemplate>) -> Result<()> {
+    let mut profile = template.map_or_else(Profile::default_profile, ProfileTemplate::profile);
+    profile.name = name.to_string();
+
+    let path = ProfileLoader::new().create(&profile)?;
+    println!("Created profile '{}' at {}", name, path.display());
+
+    if !std::io::stdin().is_terminal() {
+        return Ok(());
+    }
+    let customize = Confirm::new()
+        .with_prompt("Open it in your editor to adjust it?")
+        .default(false)
+        .interact()
+        .map_err(|_| Ec2CliError::Cancelled)?;
+    if customize {
+        edit(name)?;
+    }
+    Ok(())
+}
+
+/// Open a profile in $EDITOR and validate it once the editor exits
+pub fn edit(name: &str) -> Result<()> {
+    let loader = ProfileLoader::new();
+
+    let path = match loader.find_path(name)? {
+        Some(path) => path,
+        None if name == "default" => {
+            // The built-in default has no file; write it out so there is something to edit
+            let path = loader.materialize_default()?;
+            println!(
+                "Created {} from the built-in default profile",
+                path.display()
+            );
+            path
+        }
+        None => return Err(Ec2CliError::ProfileNotFound(name.to_string())),
+    };
+
+    loop {
+        launch_editor(&path)?;
+
+        // Edits are left on disk either way; only report problems
+        match loader
+            .load_path(&path)
+            .and_then(|profile| profile.validate())
+        {
+            Ok(()) => {
+                println!("Profile '{}' is valid ({})", name, path.display());
+                return Ok(());
+            }
+            Err(e) => {
+                eprintln!("Profile '{}' has errors: {}", name, e);
+
+                let edit_again = Confirm::new()
+                    .with_prompt("Edit again?")
+                    .default(true)
+                    .interact()
+                    .map_err(|_| Ec2CliError::Cancelled)?;
+
+                if !edit_again {
+                    eprintln!("Your changes were kept in {}", path.display());
+                    return Err(e);
+                }
+            }
+        }
+    }
+}
+
+/// Print a profile with every default filled in, i.e. exactly what `up` would use
+pub fn show_resolved(name: &str) -> Result<()> {
+    let profile = ProfileLoader::new().load(name)?;
+    println!("{}", resolved_json(&profile)?);
+    Ok(())
+}
+
+/// Loading already applies serde defaults, so serializing the loaded profile
+/// includes them; only unset optional fields are left out
+fn resolved_json(profile: &Profile) -> Result<String> {
+    Ok(serde_json::to_string_pretty(profile)?)
+}
+
+/// Print every issue found in a profile, failing if there are any
+pub fn lint(name: &str) -> Result<()> {
+    let issues = ProfileLoader::new().lint(name)?;
+    if issues.is_empty() {
+        println!("Profile '{}' has no issues.", name);
+        return Ok(());
+    }
+
+    for issue in &issues {
+        println!("- {}", issue);
+    }
+    Err(Ec2CliError::ProfileValidation(format!(
+        "{} issue(s) found in profile '{}'",
+        issues.len(),
+        name
+    )))
+}
+
+/// Validate every discoverable profile, printing a line per profile and a summary.
+///
+/// Fails if any profile doesn't parse or validate, so it can gate CI.
+pub fn validate_all() -> Result<()> {
+    // Read-only, so every profile is checked whatever fails
+    let mut batch = BatchResult::new();
+    for (info, result) in ProfileLoader::new().validate_all()? {
+        batch.record(format!("{} ({})", info.name, info.source), result);
+    }
+
+    println!("{}", batch.summary());
+    batch.into_result("Validation")
+}
+
+/// Run $EDITOR (which may include arguments, e.g. "code --wait") on a file
+fn launch_editor(path: &Path) -> Result<()> {
+    let editor = std::env::var("EDITOR")
+        .ok()
+        .filter(|e| !e.trim().is_empty())
+        .unwrap_or_else(|| DEFAULT_EDITOR.to_string());
+
+    let mut parts = editor.split_whitespace();
+    let program = parts.next().unwrap_or(DEFAULT_EDITOR);
+
+    // Allow e.g. EDITOR="~/bin/edit" (bare names are still looked up on PATH)
+    let status = Command::new(expand_path(program)?)
+        .args(parts)
+        .arg(path)
+        .status()
+        .map_err(|e| Ec2CliError::Other(format!("Failed to launch editor '{}': {}", program, e)))?;
+
+    if !status.success() {
+        return Err(Ec2CliError::Other(format!(
+            "Editor '{}' exited with {}",
+            program, status
+        )));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolved_output_includes_defaults() {
+        let profile: Profile = json5::from_str(r#"{ name: "minimal" }"#).unwrap();
+        let resolved: serde_json::Value =
+            serde_json::from_str(&resolved_json(&profile).unwrap()).unwrap();
+
+        let instance = &resolved["instance"];
+        assert!(instance["type"].is_string());
+        assert_eq!(instance["ami"]["architecture"], "x86_64");
+        let root_volume = &instance["storage"]["root_volume"];
+        assert_eq!(root_volume["type"], "gp3");
+        assert_eq!(root_volume["size_gb"], 30);
+        assert_eq!(root_volume["iops"], 3000);
+        assert_eq!(root_volume["throughput"], 125);
+
+        // It's still a valid profile, so the output can be saved and edited
+        let round_trip: Profile = serde_json::from_value(resolved).unwrap();
+        assert_eq!(round_trip, profile);
+    }
+}
@@ -1,22 +1,24 @@
 use crate::git::{add_remote, git_pull, is_git_repo, list_remotes};
-use crate::state::{get_instance, resolve_instance_name};
+use crate::state::{InstanceResolver, ResolvedInstance};
+use crate::ui::info;
 use crate::user_data::validate_project_name;
 use crate::{Ec2CliError, Result};
 
-use super::ssm_ssh_command;
+use super::{check_instance_account, ssm_ssh_command};
 
-pub fn execute(name: String, branch: Option<String>) -> Result<()> {
+pub async fn execute(name: Option<String>, branch: Option<String>) -> Result<()> {
     // Check we're in a git repo
     if !is_git_repo() {
         return Err(Ec2CliError::NotGitRepo);
     }
 
-    // Resolve instance name
-    let name = resolve_instance_name(Some(&name))?;
-
-    // Get instance from state
-    let instance_state =
-        get_instance(&name)?.ok_or_else(|| Ec2CliError::InstanceNotFound(name.clone()))?;
+    // Resolve instance by name, link, or instance ID
+    let ResolvedInstance {
+        name,
+        state: instance_state,
+        ..
+    } = InstanceResolver::load()?.resolve(name.as_deref())?;
+    check_instance_account(&instance_state).await?;
 
     let username = &instance_state.username;
 
@@ -40,15 +42,15 @@ pub fn execute(name: String, branch: Option<String>) -> Result<()> {
             "{}@{}:/home/{}/repos/{}.git",
             username, instance_state.instance_id, username, project_name
         );
-        println!("Adding remote '{}': {}", remote_name, remote_url);
+        info!("Adding remote '{}': {}", remote_name, remote_url);
         add_remote(&remote_name, &remote_url)?;
     }
 
     // Pull from remote with SSM SSH command (include identity file if available)
     let ssh_cmd = ssm_ssh_command(instance_state.ssh_key_path.as_deref());
-    println!("Pulling from {}...", remote_name);
+    info!("Pulling from {}...", remote_name);
     git_pull(&remote_name, branch.as_deref(), Some(&ssh_cmd))?;
 
-    println!("Pull complete!");
+    info!("Pull complete!");
     Ok(())
 }
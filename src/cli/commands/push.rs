@@ -1,10 +1,16 @@
-use crate::git::{add_remote, git_push, is_git_repo, list_remotes};
-use crate::state::{get_instance, resolve_instance_name};
+use crate::aws::concurrency::run_bounded;
+use crate::aws::ec2::instance::{wait_for_marker, SetupMarker};
+use crate::cli::batch::BatchResult;
+use crate::git::{
+    add_remote, git_push, is_git_repo, list_branches, list_remotes, push_refspec, Branch,
+};
+use crate::state::{InstanceResolver, ResolvedInstance};
+use crate::ui::{create_spinner, info};
 use crate::user_data::validate_project_name;
 use crate::{Ec2CliError, Result};
 use std::process::Command;
 
-use super::ssm_ssh_command;
+use super::{check_instance_account, ssm_ssh_command};
 
 /// Get the current git branch name
 fn get_current_branch() -> Result<String> {
@@ -20,20 +26,85 @@ fn get_current_branch() -> Result<String> {
     Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
 }
 
-pub fn execute(name: String, branch: Option<String>) -> Result<()> {
+/// Instances to push to, from `--all`, `--to`, or the single name/link
+fn resolve_targets(
+    name: Option<String>,
+    to: Vec<String>,
+    all: bool,
+) -> Result<Vec<ResolvedInstance>> {
+    let resolver = InstanceResolver::load()?;
+
+    if all {
+        let targets = resolver.resolve_all();
+        if targets.is_empty() {
+            return Err(Ec2CliError::InstanceNotFound(
+                "no active instances in state".to_string(),
+            ));
+        }
+        return Ok(targets);
+    }
+
+    if to.is_empty() {
+        return Ok(vec![resolver.resolve(name.as_deref())?]);
+    }
+
+    // The positional name, if given, is pushed to first
+    name.into_iter()
+        .chain(to)
+        .map(|name| resolver.resolve(Some(&name)))
+        .collect()
+}
+
+/// Run `op` for every target in order. A failure stops the rest, which are
+/// recorded as skipped, unless `keep_going` is set.
+fn for_each_target<T>(
+    targets: &[T],
+    target_name: impl Fn(&T) -> &str,
+    keep_going: bool,
+    mut op: impl FnMut(&T) -> Result<()>,
+) -> BatchResult {
+    let mut batch = BatchResult::new();
+
+    for target in targets {
+        let name = target_name(target);
+        if batch.failed() > 0 && !keep_going {
+            batch.skip(name);
+            continue;
+        }
+
+        info!("==> {}", name);
+        let result = op(target);
+        match &result {
+            Ok(()) => info!("{}: push complete", name),
+            Err(e) => eprintln!("{}: push failed: {}", name, e),
+        }
+        batch.record(name, result);
+        info!();
+    }
+
+    batch
+}
+
+/// How long `push --wait` waits for a new instance's git repo
+const GIT_READY_TIMEOUT_SECS: u64 = 600;
+
+pub async fn execute(
+    name: Option<String>,
+    to: Vec<String>,
+    all: bool,
+    branch: Option<String>,
+    checkout: bool,
+    wait: bool,
+    keep_going: bool,
+    parallel: usize,
+) -> Result<()> {
     // Check we're in a git repo
     if !is_git_repo() {
         return Err(Ec2CliError::NotGitRepo);
     }
 
-    // Resolve instance name
-    let name = resolve_instance_name(Some(&name))?;
-
-    // Get instance from state
-    let instance_state =
-        get_instance(&name)?.ok_or_else(|| Ec2CliError::InstanceNotFound(name.clone()))?;
-
-    let username = &instance_state.username;
+    // Resolve instances by name, link, or instance ID
+    let targets = resolve_targets(name, to, all)?;
 
     // Get project name from current directory
     let project_name = std::env::current_dir()?
@@ -45,8 +116,135 @@ pub fn execute(name: String, branch: Option<String>) -> Result<()> {
     // Validate project name for security
     validate_project_name(&project_name)?;
 
+    // Get branch to push (use provided branch or current branch)
+    let explicit_branch = branch.is_some();
+    let branch_to_push = match branch {
+        Some(b) => b,
+        None => get_current_branch()?,
+    };
+
+    if !checkout {
+        info!(
+            "Pushing '{}' without checkout; the instance working tree is unchanged",
+            branch_to_push
+        );
+    } else if explicit_branch {
+        info!("Instance will check out branch '{}'", branch_to_push);
+    }
+
+    // A target launched from another account, or whose repo never appeared, fails
+    // in the batch like a failed push
+    let mut failures = Vec::with_capacity(targets.len());
+    for target in &targets {
+        failures.push(check_instance_account(&target.state).await.err());
+    }
+
+    // A freshly launched instance sets up its repo before installing packages. The
+    // waits run concurrently; the pushes below stay sequential since each may add
+    // a remote to the local git config.
+    if wait {
+        let spinner = create_spinner(match targets.as_slice() {
+            [target] => format!("Waiting for git repo on {}...", target.name),
+            _ => format!("Waiting for git repos on {} instances...", targets.len()),
+        });
+        let pending: Vec<usize> = (0..targets.len())
+            .filter(|&i| failures[i].is_none())
+            .collect();
+        let instances = pending.iter().map(|&i| targets[i].state.clone()).collect();
+        let results = run_bounded(instances, parallel, |instance| async move {
+            wait_for_marker(
+                &instance.instance_id,
+                &instance.username,
+                instance.ssh_key_path.as_deref(),
+                SetupMarker::GitReady,
+                GIT_READY_TIMEOUT_SECS,
+            )
+            .await
+        })
+        .await;
+        spinner.finish_and_clear();
+        for (i, result) in pending.into_iter().zip(results) {
+            failures[i] = result.err();
+        }
+    }
+
+    if let [target] = targets.as_slice() {
+        if let Some(e) = failures[0].take() {
+            return Err(e);
+        }
+        push_to(target, &project_name, &branch_to_push, checkout)?;
+        info!("Push complete!");
+        return Ok(());
+    }
+
+    let indexed: Vec<(usize, &ResolvedInstance)> = targets.iter().enumerate().collect();
+    let batch = for_each_target(
+        &indexed,
+        |(_, target)| target.name.as_str(),
+        keep_going,
+        |(i, target)| match failures[*i].take() {
+            Some(e) => Err(e),
+            None => push_to(target, &project_name, &branch_to_push, checkout),
+        },
+    );
+
+    if batch.failed() == 0 {
+        info!("Pushed to {} instances.", targets.len());
+        return Ok(());
+    }
+    println!("{}", batch.summary());
+    batch.into_result("Push")
+}
+
+/// Print the local branches `push -b` can take, marking the checked out one
+pub fn print_branches() -> Result<()> {
+    if !is_git_repo() {
+        return Err(Ec2CliError::NotGitRepo);
+    }
+
+    let branches = list_branches()?;
+    if branches.is_empty() {
+        info!("No branches yet; commit something first.");
+        return Ok(());
+    }
+    print!("{}", format_branches(&branches));
+    Ok(())
+}
+
+/// One line per branch: marker, name padded to the longest, commit, and subject
+fn format_branches(branches: &[Branch]) -> String {
+    let width = branches
+        .iter()
+        .map(|branch| branch.name.len())
+        .max()
+        .unwrap_or(0);
+    branches
+        .iter()
+        .map(|branch| {
+            let line = format!(
+                "{} {:width$}  {} {}",
+                if branch.current { "*" } else { " " },
+                branch.name,
+                branch.commit,
+                branch.subject,
+            );
+            format!("{}\n", line.trim_end())
+        })
+        .collect()
+}
+
+/// Push `branch` to one instance, adding its remote on first use
+fn push_to(
+    target: &ResolvedInstance,
+    project_name: &str,
+    branch: &str,
+    checkout: bool,
+) -> Result<()> {
+    let instance_state = &target.state;
+    let username = &instance_state.username;
+
     // Use instance name as remote name
-    let remote_name = format!("ec2-{}", name);
+    let remote_name = format!("ec2-{}", target.name);
 
     // Check if remote already exists
     let remotes = list_remotes()?;
@@ -58,27 +256,90 @@ pub fn execute(name: String, branch: Option<String>) -> Result<()> {
             "{}@{}:/home/{}/repos/{}.git",
             username, instance_state.instance_id, username, project_name
         );
-        println!("Adding remote '{}': {}", remote_name, remote_url);
+        info!("Adding remote '{}': {}", remote_name, remote_url);
         add_remote(&remote_name, &remote_url)?;
     }
 
-    // Get branch to push (use provided branch or current branch)
-    let branch_to_push = match branch {
-        Some(b) => b,
-        None => get_current_branch()?,
-    };
-
     // Push to remote with SSM SSH command (include identity file if available)
-    // Always set upstream - it's idempotent and ensures the branch is tracked
+    // Set upstream when checking out - it's idempotent and ensures the branch is tracked.
+    // Staged refs aren't branches on the instance, so they are never tracked.
     let ssh_cmd = ssm_ssh_command(instance_state.ssh_key_path.as_deref());
-    println!("Pushing to {}...", remote_name);
-    git_push(
-        &remote_name,
-        Some(&branch_to_push),
-        true, // always set upstream
-        Some(&ssh_cmd),
-    )?;
-
-    println!("Push complete!");
-    Ok(())
+    let refspec = push_refspec(branch, checkout);
+    info!("Pushing to {}...", remote_name);
+    git_push(&remote_name, Some(&refspec), checkout, Some(&ssh_cmd))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn push_all(keep_going: bool) -> (Vec<String>, BatchResult) {
+        let mut attempted = Vec::new();
+        let batch = for_each_target(
+            &["a", "b", "c"],
+            |t| *t,
+            keep_going,
+            |t| {
+                attempted.push(t.to_string());
+                if *t == "b" {
+                    Err(Ec2CliError::Git("connection refused".to_string()))
+                } else {
+                    Ok(())
+                }
+            },
+        );
+        (attempted, batch)
+    }
+
+    #[test]
+    fn test_for_each_target_continues_after_failure() {
+        let (attempted, batch) = push_all(true);
+
+        assert_eq!(attempted, vec!["a", "b", "c"]);
+        assert_eq!((batch.succeeded(), batch.failed()), (2, 1));
+        assert!(batch
+            .summary()
+            .contains("FAIL  b: Git error: connection refused"));
+    }
+
+    #[test]
+    fn test_for_each_target_stops_without_keep_going() {
+        let (attempted, batch) = push_all(false);
+
+        assert_eq!(attempted, vec!["a", "b"]);
+        assert_eq!(
+            (batch.succeeded(), batch.failed(), batch.skipped()),
+            (1, 1, 1)
+        );
+        assert!(batch.summary().contains("skip  c"));
+    }
+
+    #[test]
+    fn test_for_each_target_all_succeed() {
+        let batch = for_each_target(&["a", "b"], |t| *t, false, |_| Ok(()));
+        assert_eq!(batch.succeeded(), 2);
+        assert!(batch.into_result("Push").is_ok());
+    }
+
+    #[test]
+    fn test_format_branches() {
+        let branch = |name: &str, commit: &str, subject: &str, current: bool| Branch {
+            name: name.to_string(),
+            commit: commit.to_string(),
+            subject: subject.to_string(),
+            current,
+        };
+        let branches = [
+            branch("feature/login", "1a2b3c4", "Add login form", false),
+            branch("main", "5d6e7f8", "Release 0.3", true),
+            branch("wip", "9a8b7c6", "", false),
+        ];
+
+        assert_eq!(
+            format_branches(&branches),
+            "  feature/login  1a2b3c4 Add login form\n\
+             * main           5d6e7f8 Release 0.3\n\
+             \x20 wip            9a8b7c6\n"
+        );
+    }
 }
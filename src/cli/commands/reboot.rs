@@ -0,0 +1,162 @@
+use std::future::Future;
+use std::time::Duration;
+
+use crate::aws::client::AwsClients;
+use crate::aws::ec2::instance::{is_ssm_online, reboot_instance, wait_for_ssm_ready};
+use crate::aws::retry::poll_until;
+use crate::state::{InstanceResolver, ResolvedInstance};
+use crate::ui::{create_spinner, info};
+use crate::Result;
+
+/// How long to watch for the SSM agent dropping offline after the reboot request
+const OFFLINE_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Interval between SSM agent status checks while waiting for it to go offline
+const OFFLINE_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Timeout for the SSM agent coming back online (seconds)
+const ONLINE_TIMEOUT_SECS: u64 = 300;
+
+pub async fn execute(name: String, wait: bool) -> Result<()> {
+    // Resolve instance by name, link, or instance ID
+    let ResolvedInstance {
+        name,
+        state: instance_state,
+        ..
+    } = InstanceResolver::load()?.resolve(Some(&name))?;
+    let instance_id = &instance_state.instance_id;
+
+    let spinner = create_spinner("Connecting to AWS...");
+    let clients = AwsClients::with_region(&instance_state.region).await?;
+    spinner.finish_and_clear();
+    instance_state.check_account(&clients.account_id)?;
+
+    let spinner = create_spinner(format!("Rebooting instance {}...", instance_id));
+    reboot_instance(&clients, instance_id).await?;
+    spinner.finish_with_message(format!("Reboot of '{}' requested", name));
+
+    if !wait {
+        info!("The instance will be reachable again once it has restarted.");
+        return Ok(());
+    }
+
+    let spinner = create_spinner("Waiting for SSM agent to go offline...");
+    let saw_offline = wait_for_restart(
+        || is_ssm_online(&clients, instance_id),
+        || {
+            spinner.set_message("Waiting for SSM agent to come back online...");
+            wait_for_ssm_ready(&clients, instance_id, ONLINE_TIMEOUT_SECS)
+        },
+        OFFLINE_TIMEOUT,
+        OFFLINE_POLL_INTERVAL,
+    )
+    .await?;
+    spinner.finish_with_message(format!("Instance '{}' is back online", name));
+
+    if !saw_offline {
+        eprintln!(
+            "Warning: The SSM agent was never seen offline; the reboot may have finished \
+             between checks"
+        );
+    }
+    Ok(())
+}
+
+/// Wait for the SSM agent to drop offline and then come back online.
+///
+/// Returns whether the agent was seen offline. A quick reboot can finish between two
+/// checks, so never seeing it offline isn't an error.
+async fn wait_for_restart<F, Fut, W, WFut>(
+    is_online: F,
+    wait_online: W,
+    offline_timeout: Duration,
+    interval: Duration,
+) -> Result<bool>
+where
+    F: Fn() -> Fut,
+    Fut: Future<Output = Result<bool>>,
+    W: FnOnce() -> WFut,
+    WFut: Future<Output = Result<()>>,
+{
+    let saw_offline = poll_until(offline_timeout, interval, || {
+        let online = is_online();
+        async move { online.await.map(|online| !online) }
+    })
+    .await?;
+
+    wait_online().await?;
+    Ok(saw_offline)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    use crate::Ec2CliError;
+
+    const INTERVAL: Duration = Duration::from_millis(1);
+
+    #[tokio::test]
+    async fn test_waits_for_offline_then_online() {
+        let events = RefCell::new(Vec::new());
+        let statuses = RefCell::new(vec![true, true, false]);
+
+        let saw_offline = wait_for_restart(
+            || {
+                let online = statuses.borrow_mut().remove(0);
+                events
+                    .borrow_mut()
+                    .push(if online { "online" } else { "offline" });
+                async move { Ok(online) }
+            },
+            || {
+                events.borrow_mut().push("wait_online");
+                async { Ok(()) }
+            },
+            Duration::from_secs(5),
+            INTERVAL,
+        )
+        .await
+        .unwrap();
+
+        assert!(saw_offline);
+        assert_eq!(
+            *events.borrow(),
+            vec!["online", "online", "offline", "wait_online"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_still_waits_for_online_when_offline_missed() {
+        let waited = RefCell::new(false);
+
+        let saw_offline = wait_for_restart(
+            || async { Ok(true) },
+            || {
+                *waited.borrow_mut() = true;
+                async { Ok(()) }
+            },
+            Duration::from_millis(20),
+            INTERVAL,
+        )
+        .await
+        .unwrap();
+
+        assert!(!saw_offline);
+        assert!(*waited.borrow());
+    }
+
+    #[tokio::test]
+    async fn test_online_timeout_is_returned() {
+        let result = wait_for_restart(
+            || async { Ok(false) },
+            || async { Err(Ec2CliError::Timeout("agent".to_string())) },
+            Duration::from_secs(5),
+            INTERVAL,
+        )
+        .await;
+
+        assert!(matches!(result, Err(Ec2CliError::Timeout(_))));
+    }
+}
@@ -0,0 +1,239 @@
+use std::future::Future;
+
+use aws_sdk_ec2::types::InstanceStateName;
+use dialoguer::Confirm;
+
+use crate::aws::client::AwsClients;
+use crate::aws::ec2::instance::{
+    get_instance_architecture, get_instance_state, modify_instance_type, start_instance,
+    stop_instance, wait_for_running, wait_for_ssm_ready, wait_for_stopped,
+};
+use crate::profile::{instance_type_arch_mismatch, is_valid_instance_type};
+use crate::state::{update_instance, InstanceResolver, ResolvedInstance};
+use crate::ui::{create_spinner, info};
+use crate::{Ec2CliError, Result};
+
+/// Timeout for the instance to stop (seconds)
+const STOP_TIMEOUT_SECS: u64 = 300;
+
+/// Timeout for the instance to reach running after the start (seconds)
+const RUNNING_TIMEOUT_SECS: u64 = 300;
+
+/// Timeout for the SSM agent to come back online after the start (seconds)
+const SSM_TIMEOUT_SECS: u64 = 600;
+
+/// A step in changing an instance's type
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ScaleStep {
+    /// Stop the instance and wait until it's stopped
+    Stop,
+    /// Change the instance type
+    Modify,
+    /// Start the instance and wait for SSM
+    Start,
+}
+
+pub async fn execute(name: String, instance_type: String, force: bool) -> Result<()> {
+    if !is_valid_instance_type(&instance_type) {
+        return Err(Ec2CliError::InvalidInstanceType(format!(
+            "'{}' (expected a type such as t3.large)",
+            instance_type
+        )));
+    }
+
+    // Resolve instance by name, link, or instance ID
+    let ResolvedInstance {
+        name,
+        state: instance_state,
+        tracked,
+        ..
+    } = InstanceResolver::load()?.resolve(Some(&name))?;
+    let instance_id = &instance_state.instance_id;
+
+    // One-time spot instances are terminated rather than stopped
+    if instance_state.spot {
+        return Err(Ec2CliError::InstanceState(format!(
+            "'{}' is a spot instance and can't be stopped to change its type",
+            name
+        )));
+    }
+
+    if instance_state.instance_type.as_deref() == Some(instance_type.as_str()) {
+        info!("Instance '{}' is already {}.", name, instance_type);
+        return Ok(());
+    }
+
+    let spinner = create_spinner("Connecting to AWS...");
+    let clients = AwsClients::with_region(&instance_state.region).await?;
+    spinner.finish_and_clear();
+    instance_state.check_account(&clients.account_id)?;
+
+    let spinner = create_spinner("Checking instance...");
+    let current = get_instance_state(&clients, instance_id).await?;
+    let architecture = get_instance_architecture(&clients, instance_id).await?;
+    spinner.finish_and_clear();
+
+    let was_running = match current {
+        InstanceStateName::Running => true,
+        InstanceStateName::Stopped => false,
+        other => {
+            return Err(Ec2CliError::InstanceState(format!(
+                "Instance {} is {:?}; it must be running or stopped to change its type",
+                instance_id, other
+            )));
+        }
+    };
+
+    if let Some(problem) = instance_type_arch_mismatch(&instance_type, &architecture) {
+        return Err(Ec2CliError::InvalidInstanceType(problem));
+    }
+
+    if was_running {
+        eprintln!(
+            "Warning: '{}' must be stopped to change its type. Running processes and SSH \
+             sessions will end; data on the EBS root volume is kept, but anything on \
+             instance-store volumes is lost.",
+            name
+        );
+        if !force {
+            let confirmed = Confirm::new()
+                .with_prompt(format!(
+                    "Stop '{}' and change its type to {}?",
+                    name, instance_type
+                ))
+                .default(false)
+                .interact()
+                .map_err(|_| Ec2CliError::Cancelled)?;
+
+            if !confirmed {
+                return Err(Ec2CliError::Cancelled);
+            }
+        }
+    }
+
+    let spinner = create_spinner("");
+    run_scale(was_running, |step| {
+        let (clients, spinner, instance_type) = (&clients, &spinner, &instance_type);
+        async move {
+            match step {
+                ScaleStep::Stop => {
+                    spinner.set_message(format!("Stopping instance {}...", instance_id));
+                    stop_instance(clients, instance_id).await?;
+                    wait_for_stopped(clients, instance_id, STOP_TIMEOUT_SECS).await
+                }
+                ScaleStep::Modify => {
+                    spinner.set_message(format!("Changing instance type to {}...", instance_type));
+                    modify_instance_type(clients, instance_id, instance_type).await
+                }
+                ScaleStep::Start => {
+                    spinner.set_message(format!("Starting instance {}...", instance_id));
+                    start_instance(clients, instance_id).await?;
+                    wait_for_running(clients, instance_id, RUNNING_TIMEOUT_SECS).await?;
+                    spinner.set_message("Waiting for SSM agent...");
+                    wait_for_ssm_ready(clients, instance_id, SSM_TIMEOUT_SECS).await
+                }
+            }
+        }
+    })
+    .await?;
+    spinner.finish_and_clear();
+
+    if tracked {
+        update_instance(&name, |entry| {
+            entry.instance_type = Some(instance_type.clone())
+        })?;
+    }
+
+    if was_running {
+        info!(
+            "Instance '{}' is now {} and back online.",
+            name, instance_type
+        );
+    } else {
+        info!(
+            "Instance '{}' is now {} (left stopped, as it was).",
+            name, instance_type
+        );
+    }
+    Ok(())
+}
+
+/// Stop (if running), change the type, then start again (if it was running).
+///
+/// The instance is started even when the type change fails, so a rejected type
+/// (e.g. no capacity, or unsupported in the zone) doesn't leave it stopped.
+async fn run_scale<F, Fut>(was_running: bool, mut step: F) -> Result<()>
+where
+    F: FnMut(ScaleStep) -> Fut,
+    Fut: Future<Output = Result<()>>,
+{
+    if was_running {
+        step(ScaleStep::Stop).await?;
+    }
+
+    let modified = step(ScaleStep::Modify).await;
+
+    if was_running {
+        let started = step(ScaleStep::Start).await;
+        modified?;
+        started
+    } else {
+        modified
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    async fn record(was_running: bool, fail: Option<ScaleStep>) -> (Result<()>, Vec<ScaleStep>) {
+        let steps = RefCell::new(Vec::new());
+        let result = run_scale(was_running, |step| {
+            steps.borrow_mut().push(step);
+            async move {
+                if Some(step) == fail {
+                    Err(Ec2CliError::Ec2(format!("{:?} failed", step), None))
+                } else {
+                    Ok(())
+                }
+            }
+        })
+        .await;
+        (result, steps.into_inner())
+    }
+
+    #[tokio::test]
+    async fn test_running_instance_is_stopped_modified_and_started() {
+        let (result, steps) = record(true, None).await;
+        assert!(result.is_ok());
+        assert_eq!(
+            steps,
+            vec![ScaleStep::Stop, ScaleStep::Modify, ScaleStep::Start]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_stopped_instance_is_only_modified() {
+        let (result, steps) = record(false, None).await;
+        assert!(result.is_ok());
+        assert_eq!(steps, vec![ScaleStep::Modify]);
+    }
+
+    #[tokio::test]
+    async fn test_failed_stop_leaves_type_unchanged() {
+        let (result, steps) = record(true, Some(ScaleStep::Stop)).await;
+        assert!(result.is_err());
+        assert_eq!(steps, vec![ScaleStep::Stop]);
+    }
+
+    #[tokio::test]
+    async fn test_failed_modify_still_restarts() {
+        let (result, steps) = record(true, Some(ScaleStep::Modify)).await;
+        assert!(matches!(result, Err(Ec2CliError::Ec2(msg, _)) if msg == "Modify failed"));
+        assert_eq!(
+            steps,
+            vec![ScaleStep::Stop, ScaleStep::Modify, ScaleStep::Start]
+        );
+    }
+}
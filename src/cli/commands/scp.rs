@@ -1,61 +1,477 @@
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::time::{Duration, Instant};
 
-use crate::ssh::SSM_PROXY_COMMAND;
-use crate::state::{get_instance, resolve_instance_name};
+use base64::Engine;
+use indicatif::{HumanBytes, HumanDuration};
+
+use crate::aws::client::AwsClients;
+use crate::aws::ec2::instance::run_remote_command;
+use crate::ssh::{
+    run_capturing_stderr, run_with_session_retry, ssm_ssh_opts, SSH_CONNECTION_FAILED,
+};
+use crate::state::{InstanceResolver, ResolvedInstance};
+use crate::ui::{create_spinner, create_timed_spinner, info};
 use crate::{Ec2CliError, Result};
 
-pub fn execute(name: String, src: String, dest: String, recursive: bool) -> Result<()> {
-    // Resolve instance name
-    let name = resolve_instance_name(Some(&name))?;
+use super::check_instance_account;
+
+/// Largest file `--ssm` copies. Its base64 form has to fit in one Run Command
+/// parameter (uploads) and in the 24,000 characters of output SSM keeps (downloads).
+const SSM_TRANSFER_MAX_BYTES: u64 = 16 * 1024;
+
+/// How long a copy over SSM may run on the instance
+const SSM_TRANSFER_TIMEOUT: Duration = Duration::from_secs(60);
 
-    // Get instance from state
-    let instance_state =
-        get_instance(&name)?.ok_or_else(|| Ec2CliError::InstanceNotFound(name.clone()))?;
+/// Exit status of the download script when the remote file is over the limit
+const SSM_FILE_TOO_LARGE: i32 = 3;
+
+pub async fn execute(
+    name: Option<String>,
+    src: String,
+    dest: String,
+    recursive: bool,
+    preserve: bool,
+    mkdir: bool,
+    verify_host: bool,
+    progress: bool,
+) -> Result<()> {
+    // Resolve instance by name, link, or instance ID
+    let ResolvedInstance {
+        name,
+        state: instance_state,
+        ..
+    } = InstanceResolver::load()?.resolve(name.as_deref())?;
+    check_instance_account(&instance_state).await?;
 
     // Parse source and destination to determine direction
     let (local_path, remote_path, is_upload) = parse_paths(&src, &dest)?;
+    let local_path = PathBuf::from(local_path);
 
-    let remote = format!(
-        "{}@{}:{}",
-        instance_state.username, instance_state.instance_id, remote_path
+    if mkdir && !is_upload {
+        return Err(Ec2CliError::InvalidPath(
+            "--mkdir only applies when copying to the instance".to_string(),
+        ));
+    }
+
+    let host = format!("{}@{}", instance_state.username, instance_state.instance_id);
+    let ssh_options = ssm_ssh_opts(verify_host)?;
+
+    // Create the destination directory first so scp doesn't fail on a missing parent
+    if let Some(dir) = remote_parent_dir(&remote_path).filter(|_| mkdir) {
+        let (status, _) = run_with_session_retry(
+            &mut build_mkdir_command(
+                instance_state.ssh_key_path.as_deref(),
+                &ssh_options,
+                &host,
+                &dir,
+            ),
+            true,
+        )?;
+
+        if !status.success() {
+            return Err(Ec2CliError::ScpTransfer(format!(
+                "could not create remote directory '{}' on instance '{}'",
+                dir, name
+            )));
+        }
+    }
+
+    let remote = format!("{}:{}", host, remote_path);
+
+    let (from, to) = if is_upload {
+        (local_path.as_os_str(), OsStr::new(&remote))
+    } else {
+        (OsStr::new(&remote), local_path.as_os_str())
+    };
+
+    let mut cmd = build_scp_command(
+        instance_state.ssh_key_path.as_deref(),
+        &ssh_options,
+        recursive,
+        preserve,
+        progress,
+        from,
+        to,
     );
 
+    // scp can't report bytes as it goes, so --progress shows elapsed time while it
+    // runs and the size and throughput at the end
+    let spinner = progress.then(|| {
+        if is_upload {
+            let size = transfer_size(&local_path)
+                .map_or_else(|_| String::new(), |bytes| format!(" {}", HumanBytes(bytes)));
+            create_timed_spinner(format!("Uploading{} to '{}'...", size, name))
+        } else {
+            create_timed_spinner(format!("Downloading from '{}'...", name))
+        }
+    });
+    let downloaded = download_target(&local_path, &remote_path);
+    let started = Instant::now();
+
+    // Capture stderr so failures can be explained; progress output stays on the terminal.
+    // The transfer itself isn't retried, so a dropped session never copies twice.
+    let result = run_capturing_stderr(&mut cmd, false);
+    if let Some(spinner) = spinner {
+        spinner.finish_and_clear();
+    }
+    let (status, stderr) = result?;
+
+    if !status.success() {
+        return Err(Ec2CliError::ScpTransfer(describe_scp_failure(
+            &name,
+            status.code(),
+            &stderr,
+        )));
+    }
+
+    // Pass through any warnings from a successful transfer
+    eprint!("{}", stderr);
+
+    if progress {
+        let copied = if is_upload { &local_path } else { &downloaded };
+        if let Ok(bytes) = transfer_size(copied) {
+            let elapsed = started.elapsed();
+            let per_second = (bytes as f64 / elapsed.as_secs_f64().max(0.001)) as u64;
+            info!(
+                "Copied {} in {} ({}/s)",
+                HumanBytes(bytes),
+                HumanDuration(elapsed),
+                HumanBytes(per_second)
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Total size in bytes of a file, or of every file under a directory
+fn transfer_size(path: &Path) -> std::io::Result<u64> {
+    let metadata = std::fs::metadata(path)?;
+    if !metadata.is_dir() {
+        return Ok(metadata.len());
+    }
+    directory_size(path)
+}
+
+/// Size of everything under `dir`. Symlinks count as the link itself and are
+/// never followed, so one pointing back up the tree can't recurse forever.
+fn directory_size(dir: &Path) -> std::io::Result<u64> {
+    let mut total = 0;
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        let metadata = std::fs::symlink_metadata(&path)?;
+        total += if metadata.is_dir() {
+            directory_size(&path)?
+        } else {
+            metadata.len()
+        };
+    }
+    Ok(total)
+}
+
+/// Where a download lands: like scp, into an existing directory under the remote
+/// file's name, otherwise at `local_path` itself
+fn download_target(local_path: &Path, remote_path: &str) -> PathBuf {
+    let file_name = remote_path.trim_end_matches('/').rsplit('/').next();
+    match file_name {
+        Some(file_name) if local_path.is_dir() && !file_name.is_empty() => {
+            local_path.join(file_name)
+        }
+        _ => local_path.to_path_buf(),
+    }
+}
+
+/// Copy a single small file with SSM Run Command, without ssh or the
+/// session-manager-plugin proxy
+pub async fn execute_ssm(
+    name: Option<String>,
+    src: String,
+    dest: String,
+    mkdir: bool,
+) -> Result<()> {
+    // Resolve instance by name, link, or instance ID
+    let ResolvedInstance {
+        name,
+        state: instance_state,
+        ..
+    } = InstanceResolver::load()?.resolve(name.as_deref())?;
+
+    let (local_path, remote_path, is_upload) = parse_paths(&src, &dest)?;
+    let local_path = PathBuf::from(local_path);
+
+    if mkdir && !is_upload {
+        return Err(Ec2CliError::InvalidPath(
+            "--mkdir only applies when copying to the instance".to_string(),
+        ));
+    }
+
+    // Check the file before connecting so an oversized upload fails fast
+    let upload = if is_upload {
+        let metadata = std::fs::metadata(&local_path)
+            .map_err(|_| Ec2CliError::PathNotFound(local_path.display().to_string()))?;
+        let file_name = local_path
+            .file_name()
+            .filter(|_| metadata.is_file())
+            .map(|n| n.to_string_lossy().into_owned())
+            .ok_or_else(|| {
+                Ec2CliError::InvalidPath(format!(
+                    "--ssm copies single files, not '{}'",
+                    local_path.display()
+                ))
+            })?;
+        check_transfer_size(&local_path.display().to_string(), metadata.len())?;
+        let encoded = encode_transfer(&std::fs::read(&local_path)?);
+        Some(upload_script(&remote_path, &file_name, &encoded, mkdir))
+    } else {
+        None
+    };
+
+    let spinner = create_spinner("Connecting to AWS...");
+    let clients = AwsClients::with_region(&instance_state.region).await?;
+    spinner.finish_and_clear();
+    instance_state.check_account(&clients.account_id)?;
+
+    let script = upload.unwrap_or_else(|| download_script(&remote_path));
+    let spinner = create_spinner("Copying over SSM...");
+    let output = run_remote_command(
+        &clients,
+        &instance_state.instance_id,
+        &as_user(&instance_state.username, &script),
+        SSM_TRANSFER_TIMEOUT,
+    )
+    .await;
+    spinner.finish_and_clear();
+    let output = output?;
+
+    if output.exit_code == SSM_FILE_TOO_LARGE && !is_upload {
+        return Err(Ec2CliError::ScpTransfer(format!(
+            "{} is larger than --ssm copies ({} bytes); use scp without --ssm",
+            remote_path, SSM_TRANSFER_MAX_BYTES
+        )));
+    }
+    if output.exit_code != 0 {
+        let detail = output
+            .stderr
+            .lines()
+            .map(str::trim)
+            .rfind(|line| !line.is_empty())
+            .unwrap_or("no error output");
+        return Err(Ec2CliError::ScpTransfer(format!(
+            "copy over SSM failed on instance '{}' ({})",
+            name, detail
+        )));
+    }
+
+    if !is_upload {
+        let bytes = decode_transfer(&output.stdout)?;
+        // Like scp, a directory destination keeps the remote file's name
+        let target = if local_path.is_dir() {
+            let file_name = remote_path.rsplit('/').next().unwrap_or(&remote_path);
+            local_path.join(file_name)
+        } else {
+            local_path
+        };
+        std::fs::write(&target, bytes)?;
+    }
+
+    Ok(())
+}
+
+/// Refuse files too large to copy with `--ssm`
+fn check_transfer_size(path: &str, size: u64) -> Result<()> {
+    if size > SSM_TRANSFER_MAX_BYTES {
+        return Err(Ec2CliError::ScpTransfer(format!(
+            "{} is {} bytes, but --ssm copies files up to {} bytes; use scp without --ssm",
+            path, size, SSM_TRANSFER_MAX_BYTES
+        )));
+    }
+    Ok(())
+}
+
+/// Encode file contents for embedding in a remote script
+fn encode_transfer(bytes: &[u8]) -> String {
+    base64::engine::general_purpose::STANDARD.encode(bytes)
+}
+
+/// Decode file contents printed by the download script
+fn decode_transfer(output: &str) -> Result<Vec<u8>> {
+    let encoded: String = output.split_whitespace().collect();
+    base64::engine::general_purpose::STANDARD
+        .decode(encoded)
+        .map_err(|e| {
+            Ec2CliError::ScpTransfer(format!(
+                "instance sent a file that isn't valid base64: {}",
+                e
+            ))
+        })
+}
+
+/// Script that writes `encoded` to `remote_path`, or into it as `file_name` when
+/// it's a directory
+fn upload_script(remote_path: &str, file_name: &str, encoded: &str, mkdir: bool) -> String {
+    let quote = |s: &str| shell_escape::unix::escape(s.into()).into_owned();
+
+    let mut script = String::from("set -e\n");
+    if let Some(dir) = remote_parent_dir(remote_path).filter(|_| mkdir) {
+        script.push_str(&format!("mkdir -p -- {}\n", quote(&dir)));
+    }
+    // An empty path (`:` or `:~`) is the home directory itself
+    let path = match home_relative(remote_path) {
+        "" => ".",
+        path => path,
+    };
+    script.push_str(&format!(
+        "dest={}\nif [ -d \"$dest\" ]; then dest=\"$dest\"/{}; fi\n",
+        quote(path),
+        quote(file_name)
+    ));
+    script.push_str(&format!(
+        "printf '%s' {} | base64 -d > \"$dest\"\n",
+        quote(encoded)
+    ));
+    script
+}
+
+/// Script that prints `remote_path` as base64, failing with `SSM_FILE_TOO_LARGE`
+/// if it's over the size limit
+fn download_script(remote_path: &str) -> String {
+    format!(
+        "set -e\n\
+         f={path}\n\
+         size=$(stat -c %s -- \"$f\")\n\
+         if [ \"$size\" -gt {max} ]; then echo \"$f is $size bytes\" >&2; exit {too_large}; fi\n\
+         base64 -w0 -- \"$f\"\n",
+        path = shell_escape::unix::escape(home_relative(remote_path).into()),
+        max = SSM_TRANSFER_MAX_BYTES,
+        too_large = SSM_FILE_TOO_LARGE,
+    )
+}
+
+/// Run commands send their script to root; run it as the instance user from
+/// their home directory instead, the way scp would
+fn as_user(username: &str, script: &str) -> String {
+    format!(
+        "runuser -l {} -c {}",
+        shell_escape::unix::escape(username.into()),
+        shell_escape::unix::escape(script.into())
+    )
+}
+
+/// Assemble the scp command line
+fn build_scp_command(
+    ssh_key_path: Option<&str>,
+    ssh_options: &[String],
+    recursive: bool,
+    preserve: bool,
+    quiet: bool,
+    from: &OsStr,
+    to: &OsStr,
+) -> Command {
     let mut cmd = Command::new("scp");
 
     // Add identity file if we have the SSH key path stored
-    if let Some(ref key_path) = instance_state.ssh_key_path {
+    if let Some(key_path) = ssh_key_path {
         cmd.arg("-i").arg(key_path);
     }
 
-    cmd.arg("-o")
-        .arg(format!("ProxyCommand={}", SSM_PROXY_COMMAND))
-        .arg("-o")
-        .arg("StrictHostKeyChecking=no")
-        .arg("-o")
-        .arg("UserKnownHostsFile=/dev/null");
+    cmd.args(ssh_options);
 
     if recursive {
         cmd.arg("-r");
     }
 
-    if is_upload {
-        cmd.arg(&local_path).arg(&remote);
-    } else {
-        cmd.arg(&remote).arg(&local_path);
+    // Keep modification times and modes
+    if preserve {
+        cmd.arg("-p");
     }
 
-    let status = cmd
-        .status()
-        .map_err(|e| Ec2CliError::ScpTransfer(format!("Failed to execute scp: {}", e)))?;
+    // Hide scp's progress meter
+    if quiet {
+        cmd.arg("-q");
+    }
 
-    if !status.success() {
-        std::process::exit(status.code().unwrap_or(1));
+    cmd.arg(from).arg(to);
+    cmd
+}
+
+/// The remote directory an upload to `remote_path` lands in: the path itself when it
+/// ends with `/`, otherwise its parent. None means the home directory, which always
+/// exists.
+fn remote_parent_dir(remote_path: &str) -> Option<String> {
+    let path = home_relative(remote_path);
+
+    let dir = if path.ends_with('/') {
+        path.trim_end_matches('/')
+    } else {
+        path.rsplit_once('/').map_or("", |(parent, _)| parent)
+    };
+
+    // An empty result is the home or root directory
+    (!dir.is_empty()).then(|| dir.to_string())
+}
+
+/// `remote_path` relative to the home directory when it starts with `~`, since
+/// relative paths are resolved from home, same as scp does with `~/`
+fn home_relative(remote_path: &str) -> &str {
+    remote_path
+        .strip_prefix("~/")
+        .or_else(|| (remote_path == "~").then_some(""))
+        .unwrap_or(remote_path)
+}
+
+/// Assemble the ssh command that creates `dir` on the instance
+fn build_mkdir_command(
+    ssh_key_path: Option<&str>,
+    ssh_options: &[String],
+    host: &str,
+    dir: &str,
+) -> Command {
+    let mut cmd = Command::new("ssh");
+
+    if let Some(key_path) = ssh_key_path {
+        cmd.arg("-i").arg(key_path);
     }
 
-    Ok(())
+    // The remote command is run by a shell, so the path is quoted
+    cmd.args(ssh_options).arg(host).arg(format!(
+        "mkdir -p -- {}",
+        shell_escape::unix::escape(dir.into())
+    ));
+    cmd
 }
 
-fn parse_paths(src: &str, dest: &str) -> Result<(String, String, bool)> {
+/// Turn a failed scp run into a specific message, keeping scp's own output for detail
+fn describe_scp_failure(name: &str, code: Option<i32>, stderr: &str) -> String {
+    let detail = stderr
+        .lines()
+        .map(str::trim)
+        .rev()
+        .find(|line| !line.is_empty())
+        .unwrap_or("no error output");
+
+    let summary = if code == Some(SSH_CONNECTION_FAILED) {
+        format!("could not connect to instance '{}'", name)
+    } else if stderr.contains("No such file or directory") {
+        "path not found".to_string()
+    } else if stderr.contains("Permission denied") {
+        "permission denied".to_string()
+    } else if stderr.contains("not a regular file") {
+        "source is a directory (use -r to copy directories)".to_string()
+    } else {
+        match code {
+            Some(code) => format!("scp exited with status {}", code),
+            None => "scp was terminated by a signal".to_string(),
+        }
+    };
+
+    format!("{} ({})", summary, detail)
+}
+
+/// Split source and destination into (local, remote, is_upload); the remote side is
+/// the one prefixed with `:`
+pub fn parse_paths(src: &str, dest: &str) -> Result<(String, String, bool)> {
     let src_is_remote = src.starts_with(':');
     let dest_is_remote = dest.starts_with(':');
 
@@ -76,3 +492,257 @@ fn parse_paths(src: &str, dest: &str) -> Result<(String, String, bool)> {
         )),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(cmd: &Command) -> Vec<String> {
+        cmd.get_args()
+            .map(|a| a.to_string_lossy().into_owned())
+            .collect()
+    }
+
+    #[test]
+    fn test_scp_command_with_preserve() {
+        let cmd = build_scp_command(
+            Some("/home/dev/.ssh/id_ed25519"),
+            &[
+                "-o".to_string(),
+                "ProxyCommand=proxy %h".to_string(),
+                "-o".to_string(),
+                "StrictHostKeyChecking=no".to_string(),
+            ],
+            true,
+            true,
+            true,
+            OsStr::new("./build"),
+            OsStr::new("ubuntu@i-123:/home/ubuntu/"),
+        );
+
+        let args = args(&cmd);
+        assert_eq!(
+            args,
+            [
+                "-i",
+                "/home/dev/.ssh/id_ed25519",
+                "-o",
+                "ProxyCommand=proxy %h",
+                "-o",
+                "StrictHostKeyChecking=no",
+                "-r",
+                "-p",
+                "-q",
+                "./build",
+                "ubuntu@i-123:/home/ubuntu/"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_scp_command_without_flags() {
+        let cmd = build_scp_command(
+            None,
+            &[],
+            false,
+            false,
+            false,
+            OsStr::new("ubuntu@i-123:/tmp/out.txt"),
+            OsStr::new("out.txt"),
+        );
+
+        assert_eq!(args(&cmd), ["ubuntu@i-123:/tmp/out.txt", "out.txt"]);
+    }
+
+    #[test]
+    fn test_transfer_encoding_round_trip() {
+        let bytes: Vec<u8> = (0..=255).cycle().take(3000).collect();
+        let encoded = encode_transfer(&bytes);
+        assert!(encoded
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "+/=".contains(c)));
+        assert_eq!(decode_transfer(&encoded).unwrap(), bytes);
+
+        // Output may be wrapped or end with a newline
+        let wrapped = format!("{}\n{}\n", &encoded[..76], &encoded[76..]);
+        assert_eq!(decode_transfer(&wrapped).unwrap(), bytes);
+        assert_eq!(decode_transfer("").unwrap(), Vec::<u8>::new());
+
+        assert!(decode_transfer("not base64!").is_err());
+    }
+
+    #[test]
+    fn test_transfer_size_limit() {
+        assert!(check_transfer_size("small.txt", SSM_TRANSFER_MAX_BYTES).is_ok());
+        let err = check_transfer_size("big.bin", SSM_TRANSFER_MAX_BYTES + 1).unwrap_err();
+        assert!(matches!(err, Ec2CliError::ScpTransfer(msg) if msg.contains("without --ssm")));
+
+        // The largest file still fits in the output SSM keeps
+        let largest = encode_transfer(&vec![0xff; SSM_TRANSFER_MAX_BYTES as usize]);
+        assert!(largest.len() < 24_000);
+    }
+
+    // The scripts target the Ubuntu instance and use GNU stat and base64 options
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_transfer_scripts_round_trip() {
+        let dir = std::env::temp_dir().join(format!("ec2-cli-ssm-scp-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let bytes: Vec<u8> = (0..=255).rev().cycle().take(1000).collect();
+        let run = |script: &str| {
+            Command::new("sh")
+                .arg("-c")
+                .arg(script)
+                .current_dir(&dir)
+                .output()
+                .unwrap()
+        };
+
+        // Upload into a new directory, then into it as a directory destination
+        let upload = upload_script("sub dir/a.bin", "local.bin", &encode_transfer(&bytes), true);
+        let uploaded = run(&upload);
+        let into_dir = run(&upload_script(
+            "sub dir/",
+            "b.bin",
+            &encode_transfer(b"x"),
+            false,
+        ));
+        let downloaded = run(&download_script("sub dir/a.bin"));
+
+        let big = dir.join("big.bin");
+        std::fs::write(&big, vec![0; SSM_TRANSFER_MAX_BYTES as usize + 1]).unwrap();
+        let too_large = run(&download_script(&big.display().to_string()));
+        let written = std::fs::read(dir.join("sub dir/a.bin"));
+        let in_dir = std::fs::read(dir.join("sub dir/b.bin"));
+        let _ = std::fs::remove_dir_all(&dir);
+
+        assert!(uploaded.status.success());
+        assert!(into_dir.status.success());
+        assert_eq!(written.unwrap(), bytes);
+        assert_eq!(in_dir.unwrap(), b"x");
+        assert!(downloaded.status.success());
+        assert_eq!(
+            decode_transfer(&String::from_utf8_lossy(&downloaded.stdout)).unwrap(),
+            bytes
+        );
+        assert_eq!(too_large.status.code(), Some(SSM_FILE_TOO_LARGE));
+    }
+
+    #[test]
+    fn test_remote_parent_dir() {
+        assert_eq!(
+            remote_parent_dir("/home/ubuntu/new/dir/file.txt").as_deref(),
+            Some("/home/ubuntu/new/dir")
+        );
+        // A trailing slash names the directory itself
+        assert_eq!(
+            remote_parent_dir("/home/ubuntu/new/").as_deref(),
+            Some("/home/ubuntu/new")
+        );
+        assert_eq!(remote_parent_dir("data/in.csv").as_deref(), Some("data"));
+        assert_eq!(remote_parent_dir("~/data/in.csv").as_deref(), Some("data"));
+
+        // Home and root always exist
+        assert_eq!(remote_parent_dir("file.txt"), None);
+        assert_eq!(remote_parent_dir("~/file.txt"), None);
+        assert_eq!(remote_parent_dir("~"), None);
+        assert_eq!(remote_parent_dir("/file.txt"), None);
+        assert_eq!(remote_parent_dir("/"), None);
+    }
+
+    #[test]
+    fn test_mkdir_command_quotes_path() {
+        let cmd = build_mkdir_command(None, &[], "ubuntu@i-123", "/home/ubuntu/my dir; rm -rf ~");
+
+        assert_eq!(
+            args(&cmd),
+            [
+                "ubuntu@i-123",
+                "mkdir -p -- '/home/ubuntu/my dir; rm -rf ~'"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_describe_scp_failure() {
+        let msg = describe_scp_failure(
+            "dev",
+            Some(1),
+            "scp: /home/ubuntu/missing.txt: No such file or directory\n",
+        );
+        assert_eq!(
+            msg,
+            "path not found (scp: /home/ubuntu/missing.txt: No such file or directory)"
+        );
+
+        let msg = describe_scp_failure("dev", Some(1), "scp: /etc/shadow: Permission denied\n");
+        assert!(msg.starts_with("permission denied"));
+
+        let msg = describe_scp_failure(
+            "dev",
+            Some(255),
+            "Connection closed by UNKNOWN port 65535\n",
+        );
+        assert!(msg.starts_with("could not connect to instance 'dev'"));
+
+        assert_eq!(
+            describe_scp_failure("dev", Some(1), ""),
+            "scp exited with status 1 (no error output)"
+        );
+    }
+
+    #[test]
+    fn test_transfer_size_of_files_and_directories() {
+        let dir = std::env::temp_dir().join(format!("ec2-cli-scp-size-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("nested/deeper")).unwrap();
+        std::fs::create_dir_all(dir.join("empty")).unwrap();
+        std::fs::write(dir.join("a.txt"), vec![0u8; 100]).unwrap();
+        std::fs::write(dir.join("nested/b.bin"), vec![0u8; 2048]).unwrap();
+        std::fs::write(dir.join("nested/deeper/c"), b"xyz").unwrap();
+
+        let file = transfer_size(&dir.join("a.txt"));
+        let nested = transfer_size(&dir.join("nested"));
+        let total = transfer_size(&dir);
+        let empty = transfer_size(&dir.join("empty"));
+        let missing = transfer_size(&dir.join("missing"));
+        let _ = std::fs::remove_dir_all(&dir);
+
+        assert_eq!(file.unwrap(), 100);
+        assert_eq!(nested.unwrap(), 2048 + 3);
+        assert_eq!(total.unwrap(), 100 + 2048 + 3);
+        assert_eq!(empty.unwrap(), 0);
+        assert!(missing.is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_transfer_size_does_not_follow_symlinks() {
+        let dir = std::env::temp_dir().join(format!("ec2-cli-scp-links-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("tree")).unwrap();
+        std::fs::write(dir.join("tree/a.txt"), vec![0u8; 100]).unwrap();
+        // A link back to the top of the tree, and one to its parent
+        std::os::unix::fs::symlink(dir.join("tree"), dir.join("tree/loop")).unwrap();
+        std::os::unix::fs::symlink("..", dir.join("tree/up")).unwrap();
+
+        let total = transfer_size(&dir.join("tree"));
+        let link_len = |name: &str| {
+            std::fs::symlink_metadata(dir.join("tree").join(name))
+                .unwrap()
+                .len()
+        };
+        let links = link_len("loop") + link_len("up");
+        let _ = std::fs::remove_dir_all(&dir);
+
+        assert_eq!(total.unwrap(), 100 + links);
+    }
+
+    #[test]
+    fn test_download_target() {
+        let dir = std::env::temp_dir();
+        assert_eq!(download_target(&dir, "/var/log/syslog"), dir.join("syslog"));
+        assert_eq!(download_target(&dir, "~/build/"), dir.join("build"));
+
+        let file = Path::new("/nonexistent-dir/out.txt");
+        assert_eq!(download_target(file, "/tmp/in.txt"), file);
+    }
+}
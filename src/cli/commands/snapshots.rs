@@ -0,0 +1,54 @@
+use crate::aws::client::AwsClients;
+use crate::aws::ec2::instance::list_managed_snapshots;
+use crate::ui::create_spinner;
+use crate::Result;
+
+/// List root volume snapshots kept by `destroy --snapshot`
+pub async fn list() -> Result<()> {
+    let spinner = create_spinner("Connecting to AWS...");
+    let clients = AwsClients::new().await?;
+    spinner.finish_and_clear();
+
+    let spinner = create_spinner("Fetching managed snapshots...");
+    let mut snapshots = list_managed_snapshots(&clients).await?;
+    spinner.finish_and_clear();
+
+    if snapshots.is_empty() {
+        println!("No managed snapshots found in {}.", clients.region);
+        println!();
+        println!("Use 'ec2-cli destroy <name> --snapshot' to keep an instance's root volume.");
+        return Ok(());
+    }
+
+    // Newest first
+    snapshots.sort_by(|a, b| b.started_at.cmp(&a.started_at));
+
+    println!(
+        "{:<24} {:<20} {:<8} {:<10} STARTED",
+        "SNAPSHOT ID", "INSTANCE", "SIZE", "STATE"
+    );
+    println!("{}", "-".repeat(85));
+
+    for snapshot in &snapshots {
+        println!(
+            "{:<24} {:<20} {:<8} {:<10} {}",
+            snapshot.snapshot_id,
+            snapshot.name.as_deref().unwrap_or("-"),
+            snapshot
+                .volume_size_gb
+                .map(|gb| format!("{} GB", gb))
+                .unwrap_or_else(|| "-".to_string()),
+            snapshot.state,
+            snapshot.started_at.as_deref().unwrap_or("-"),
+        );
+    }
+
+    println!();
+    println!(
+        "Total: {} snapshot(s) in {}",
+        snapshots.len(),
+        clients.region
+    );
+
+    Ok(())
+}
@@ -1,16 +1,36 @@
-use std::process::Command;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus, Stdio};
 
-use crate::ssh::SSM_PROXY_COMMAND;
-use crate::state::{get_instance, resolve_instance_name};
+use crate::ssh::{run_capturing_stderr, run_with_session_retry, ssm_ssh_opts};
+use crate::state::{mark_instance_connected, InstanceResolver, ResolvedInstance};
+use crate::user_data::{validate_env_key, validate_shell_safe};
 use crate::{Ec2CliError, Result};
 
-pub fn execute(name: String, command: Option<String>) -> Result<()> {
-    // Resolve instance name
-    let name = resolve_instance_name(Some(&name))?;
+use super::check_instance_account;
 
-    // Get instance from state
-    let instance_state =
-        get_instance(&name)?.ok_or_else(|| Ec2CliError::InstanceNotFound(name.clone()))?;
+pub async fn execute(
+    name: Option<String>,
+    command: Option<String>,
+    command_file: Option<PathBuf>,
+    verify_host: bool,
+    output: Option<PathBuf>,
+    env: Vec<String>,
+) -> Result<()> {
+    // A script file runs like -c, so read it before connecting
+    let command = match command_file {
+        Some(path) => Some(script_command(&read_command_file(&path)?)?),
+        None => command,
+    };
+    let command = command.map(|command| with_env(command, &env)).transpose()?;
+
+    // Resolve instance by name, link, or instance ID
+    let ResolvedInstance {
+        name,
+        state: instance_state,
+        ..
+    } = InstanceResolver::load()?.resolve(name.as_deref())?;
+    check_instance_account(&instance_state).await?;
 
     let target = format!("{}@{}", instance_state.username, instance_state.instance_id);
 
@@ -21,21 +41,20 @@ pub fn execute(name: String, command: Option<String>) -> Result<()> {
         cmd.arg("-i").arg(key_path);
     }
 
-    cmd.arg("-o")
-        .arg(format!("ProxyCommand={}", SSM_PROXY_COMMAND))
-        .arg("-o")
-        .arg("StrictHostKeyChecking=no")
-        .arg("-o")
-        .arg("UserKnownHostsFile=/dev/null")
-        .arg(&target);
+    cmd.args(ssm_ssh_opts(verify_host)?).arg(&target);
 
+    // Only an interactive shell is retried; a command might run twice
+    let retry = command.is_none();
     if let Some(remote_cmd) = command {
         cmd.arg(remote_cmd);
     }
 
-    let status = cmd
-        .status()
-        .map_err(|e| Ec2CliError::SshCommand(format!("Failed to execute ssh: {}", e)))?;
+    // Only feeds the "last connected" column, so it never stops the connection
+    if let Err(e) = mark_instance_connected(&name) {
+        eprintln!("Warning: Could not record the connection in state: {}", e);
+    }
+
+    let status = run_with_output(&mut cmd, output.as_deref(), retry)?;
 
     if !status.success() {
         std::process::exit(status.code().unwrap_or(1));
@@ -43,3 +62,164 @@ pub fn execute(name: String, command: Option<String>) -> Result<()> {
 
     Ok(())
 }
+
+/// Read a local script for --command-file
+fn read_command_file(path: &Path) -> Result<String> {
+    std::fs::read_to_string(path).map_err(|e| match e.kind() {
+        std::io::ErrorKind::NotFound => Ec2CliError::PathNotFound(path.display().to_string()),
+        _ => Ec2CliError::InvalidPath(format!("Cannot read {}: {}", path.display(), e)),
+    })
+}
+
+/// Build a remote command that runs `script` with bash, echoing each command as it runs.
+///
+/// The script is passed as a single quoted argument so the remote login shell
+/// doesn't expand or split any of it.
+fn script_command(script: &str) -> Result<String> {
+    if script.trim().is_empty() {
+        return Err(Ec2CliError::InvalidPath(
+            "Command file is empty".to_string(),
+        ));
+    }
+    Ok(format!(
+        "bash -xc {}",
+        shell_escape::unix::escape(script.into())
+    ))
+}
+
+/// Prefix a remote command with an export for each `--env` KEY=VALUE pair.
+///
+/// Exports work with any sshd, unlike SetEnv, which the server has to allow.
+fn with_env(command: String, env: &[String]) -> Result<String> {
+    let mut exports = String::new();
+    for pair in env {
+        let (key, value) = pair.split_once('=').ok_or_else(|| {
+            Ec2CliError::Config(format!("Invalid --env '{}': expected KEY=VALUE", pair))
+        })?;
+        validate_env_key(key)
+            .and_then(|()| validate_shell_safe(value, &format!("--env value for '{}'", key)))
+            .map_err(|e| match e {
+                Ec2CliError::ProfileValidation(msg) => Ec2CliError::Config(msg),
+                e => e,
+            })?;
+        exports.push_str(&format!(
+            "export {}={}; ",
+            key,
+            shell_escape::unix::escape(value.into())
+        ));
+    }
+    Ok(exports + &command)
+}
+
+/// Run a command, sending its stdout to `output` if given (stderr stays on the terminal).
+/// With `retry`, a session that fails to start right after `up` is retried.
+fn run_with_output(cmd: &mut Command, output: Option<&Path>, retry: bool) -> Result<ExitStatus> {
+    if let Some(path) = output {
+        let file = File::create(path).map_err(|e| {
+            Ec2CliError::InvalidPath(format!("Cannot create {}: {}", path.display(), e))
+        })?;
+        cmd.stdout(Stdio::from(file));
+    }
+
+    let result = if retry {
+        run_with_session_retry(cmd, true)
+    } else {
+        run_capturing_stderr(cmd, true)
+    };
+    result.map(|(status, _)| status)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_output_captures_stdout_and_exit_code() {
+        let path = std::env::temp_dir().join(format!("ec2-cli-ssh-output-{}", std::process::id()));
+
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", "echo captured; echo progress >&2; exit 3"]);
+        let status = run_with_output(&mut cmd, Some(&path), false).unwrap();
+
+        assert_eq!(status.code(), Some(3));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "captured\n");
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_script_command_quotes_script() {
+        let script = "echo 'hello world'\necho \"$HOME\"; ls *.txt\n";
+        assert_eq!(
+            script_command(script).unwrap(),
+            "bash -xc 'echo '\\''hello world'\\''\necho \"$HOME\"; ls *.txt\n'"
+        );
+        assert!(script_command(" \n").is_err());
+    }
+
+    #[test]
+    fn test_script_command_round_trips_through_shell() {
+        // The remote login shell sees the same string ssh sends
+        let script = "X='a b'\necho \"$X\" 'it'\\''s' $((1 + 2))\n";
+        let output = Command::new("sh")
+            .arg("-c")
+            .arg(script_command(script).unwrap())
+            .output()
+            .unwrap();
+
+        assert!(output.status.success());
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "a b it's 3\n");
+        // Each command is echoed to stderr
+        assert!(String::from_utf8_lossy(&output.stderr).contains("+ echo 'a b'"));
+    }
+
+    #[test]
+    fn test_read_missing_command_file() {
+        let result = read_command_file(Path::new("/nonexistent-dir/script.sh"));
+        assert!(matches!(result, Err(Ec2CliError::PathNotFound(_))));
+    }
+
+    #[test]
+    fn test_output_to_invalid_path_fails() {
+        let mut cmd = Command::new("true");
+        let result = run_with_output(&mut cmd, Some(Path::new("/nonexistent-dir/out.txt")), false);
+        assert!(matches!(result, Err(Ec2CliError::InvalidPath(_))));
+    }
+
+    #[test]
+    fn test_with_env_prepends_exports() {
+        let env = [
+            "RUST_LOG=debug".to_string(),
+            "GREETING=hello world".to_string(),
+        ];
+        let command = with_env("cargo run".to_string(), &env).unwrap();
+        assert_eq!(
+            command,
+            "export RUST_LOG=debug; export GREETING='hello world'; cargo run"
+        );
+
+        // The command sees the variables once the remote shell runs it
+        let output = Command::new("sh")
+            .arg("-c")
+            .arg(with_env("echo \"$GREETING\"".to_string(), &env).unwrap())
+            .output()
+            .unwrap();
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "hello world\n");
+
+        assert_eq!(with_env("ls".to_string(), &[]).unwrap(), "ls");
+    }
+
+    #[test]
+    fn test_with_env_rejects_malformed_pairs() {
+        for pair in [
+            "NOVALUE",
+            "=value",
+            "1KEY=value",
+            "KEY-NAME=value",
+            "KEY=$(id)",
+            "KEY=",
+        ] {
+            let result = with_env("true".to_string(), &[pair.to_string()]);
+            assert!(matches!(result, Err(Ec2CliError::Config(_))), "{}", pair);
+        }
+    }
+}
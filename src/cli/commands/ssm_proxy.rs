@@ -0,0 +1,95 @@
+use std::process::Command;
+use std::time::Duration;
+
+use crate::aws::client::AwsClients;
+use crate::aws::retry::retry_while;
+use crate::config::Settings;
+use crate::ssh::is_transient_session_error;
+use crate::{Ec2CliError, Result};
+
+/// SSM document that forwards a port for SSH
+const SSH_SESSION_DOCUMENT: &str = "AWS-StartSSHSession";
+
+/// How long to keep retrying StartSession while the agent isn't connected yet
+const START_SESSION_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Interval between StartSession attempts
+const START_SESSION_RETRY_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Act as an SSH ProxyCommand: start an SSM session with the SDK and hand the
+/// session over to session-manager-plugin, which relays stdin/stdout.
+pub async fn execute(target: String, port: u16) -> Result<()> {
+    let clients = AwsClients::new().await?;
+
+    let status = plugin_session(
+        &clients,
+        &target,
+        SSH_SESSION_DOCUMENT,
+        &[("portNumber", port.to_string())],
+    )
+    .await?
+    .status()
+    .map_err(|_| Ec2CliError::SessionManagerPluginNotFound)?;
+
+    if !status.success() {
+        std::process::exit(status.code().unwrap_or(1));
+    }
+
+    Ok(())
+}
+
+/// Start an SSM session on `target` with `document` and return the
+/// session-manager-plugin command that connects to it
+pub async fn plugin_session(
+    clients: &AwsClients,
+    target: &str,
+    document: &str,
+    parameters: &[(&str, String)],
+) -> Result<Command> {
+    let mut request = clients
+        .ssm
+        .start_session()
+        .target(target)
+        .document_name(document);
+    for (key, value) in parameters {
+        request = request.parameters(*key, vec![value.clone()]);
+    }
+    // The agent can take a moment to connect after the instance reports ready
+    let session = retry_while(
+        START_SESSION_TIMEOUT,
+        START_SESSION_RETRY_INTERVAL,
+        |e| matches!(e, Ec2CliError::Ssm(..)) && is_transient_session_error(&e.to_string()),
+        || async { request.clone().send().await.map_err(Ec2CliError::ssm) },
+    )
+    .await?;
+
+    // The plugin expects the StartSession response and request as JSON arguments
+    let response = serde_json::json!({
+        "SessionId": session.session_id(),
+        "TokenValue": session.token_value(),
+        "StreamUrl": session.stream_url(),
+    });
+    let parameters: serde_json::Map<_, _> = parameters
+        .iter()
+        .map(|(key, value)| (key.to_string(), serde_json::json!([value])))
+        .collect();
+    let request = serde_json::json!({
+        "Target": target,
+        "DocumentName": document,
+        "Parameters": parameters,
+    });
+    let aws_profile = Settings::load()
+        .unwrap_or_default()
+        .effective_aws_profile()
+        .unwrap_or_default();
+    let endpoint = format!("https://ssm.{}.amazonaws.com", clients.region);
+
+    let mut cmd = Command::new("session-manager-plugin");
+    cmd.arg(response.to_string())
+        .arg(&clients.region)
+        .arg("StartSession")
+        .arg(aws_profile)
+        .arg(request.to_string())
+        .arg(endpoint);
+    Ok(cmd)
+}
@@ -0,0 +1,331 @@
+use std::collections::{BTreeSet, HashMap};
+
+use chrono::Utc;
+
+use crate::aws::client::AwsClients;
+use crate::aws::ec2::instance::{find_by_name_tag, list_managed_instances, ManagedInstance};
+use crate::config::Settings;
+use crate::state::{InstanceState, State};
+use crate::ui::{create_spinner, info};
+use crate::Result;
+
+/// Profile recorded for instances recovered from AWS (the profile isn't tagged)
+const UNKNOWN_PROFILE: &str = "unknown";
+
+/// Changes needed to bring local state in line with AWS
+#[derive(Debug, Default)]
+struct RepairPlan {
+    /// Instances running in AWS but missing from state, keyed by the name to record
+    additions: Vec<(String, InstanceState)>,
+    /// State entries whose instances are terminated or no longer exist
+    removals: Vec<String>,
+}
+
+impl RepairPlan {
+    fn is_empty(&self) -> bool {
+        self.additions.is_empty() && self.removals.is_empty()
+    }
+}
+
+/// Reconcile local state with the managed instances that exist in AWS
+pub async fn repair(apply: bool, all_users: bool) -> Result<()> {
+    let mut state = State::load()?;
+    let settings = Settings::load().unwrap_or_default();
+    let owner = settings.owner_filter(all_users)?;
+
+    // Scan the default region plus every region referenced in state
+    let spinner = create_spinner("Connecting to AWS...");
+    let default_clients = AwsClients::new().await?;
+    spinner.finish_and_clear();
+
+    let mut regions: BTreeSet<String> =
+        state.instances.values().map(|i| i.region.clone()).collect();
+    regions.insert(default_clients.region.clone());
+
+    let mut found = HashMap::new();
+    for region in regions {
+        let spinner = create_spinner(format!("Scanning managed instances in {}...", region));
+        let clients = if region == default_clients.region {
+            None
+        } else {
+            Some(AwsClients::with_region(&region).await?)
+        };
+        let instances =
+            list_managed_instances(clients.as_ref().unwrap_or(&default_clients)).await?;
+        spinner.finish_and_clear();
+        found.insert(region, instances);
+    }
+
+    let plan = plan_repair(&state, &found, owner);
+
+    if plan.is_empty() {
+        info!("State is in sync with AWS.");
+        return Ok(());
+    }
+
+    for (name, instance) in &plan.additions {
+        println!("+ {} ({}, {})", name, instance.instance_id, instance.region);
+    }
+    for name in &plan.removals {
+        let instance = &state.instances[name];
+        println!("- {} ({}, {})", name, instance.instance_id, instance.region);
+    }
+    println!();
+
+    if !apply {
+        info!(
+            "{} to add, {} to remove. Re-run with --apply to update state.",
+            plan.additions.len(),
+            plan.removals.len()
+        );
+        return Ok(());
+    }
+
+    for name in &plan.removals {
+        state.remove_instance(name);
+    }
+    for (name, instance) in plan.additions {
+        state.instances.insert(name, instance);
+    }
+    state.save()?;
+
+    info!("State updated.");
+    Ok(())
+}
+
+/// Compare state against the instances found in each scanned region, only adding
+/// instances that belong to `owner` when given
+fn plan_repair(
+    state: &State,
+    found: &HashMap<String, Vec<ManagedInstance>>,
+    owner: Option<&str>,
+) -> RepairPlan {
+    let mut plan = RepairPlan::default();
+
+    // Entries in a scanned region whose instance is gone (entries kept with
+    // `destroy --keep-state` are already known to be terminated)
+    let mut removals: Vec<String> = state
+        .instances
+        .iter()
+        .filter(|(_, entry)| {
+            entry.terminated_at.is_none()
+                && found.get(&entry.region).is_some_and(|instances| {
+                    !instances
+                        .iter()
+                        .any(|i| i.instance_id == entry.instance_id && !i.is_terminated())
+                })
+        })
+        .map(|(name, _)| name.clone())
+        .collect();
+    removals.sort();
+
+    // Live instances that state doesn't know about
+    let mut taken: BTreeSet<String> = state
+        .instances
+        .keys()
+        .filter(|name| !removals.contains(name))
+        .cloned()
+        .collect();
+
+    let all = || {
+        found
+            .iter()
+            .flat_map(|(region, instances)| instances.iter().map(move |i| (region.as_str(), i)))
+    };
+
+    let mut regions: Vec<_> = found.keys().collect();
+    regions.sort();
+
+    for region in regions {
+        for instance in &found[region] {
+            if instance.is_terminated()
+                || !instance.is_owned_by(owner)
+                || state
+                    .instances
+                    .values()
+                    .any(|entry| entry.instance_id == instance.instance_id)
+            {
+                continue;
+            }
+
+            // Fall back to the instance ID when untagged, the name is already in use,
+            // or several live instances carry the name (recording either would make
+            // the name point at an arbitrary one)
+            let name = match instance.name {
+                Some(ref name)
+                    if !taken.contains(name) && find_by_name_tag(name, all()).is_ok() =>
+                {
+                    name.clone()
+                }
+                _ => instance.instance_id.clone(),
+            };
+            taken.insert(name.clone());
+
+            plan.additions.push((
+                name,
+                InstanceState {
+                    instance_id: instance.instance_id.clone(),
+                    profile: UNKNOWN_PROFILE.to_string(),
+                    region: region.clone(),
+                    created_at: Utc::now(),
+                    username: "ubuntu".to_string(),
+                    security_group_id: instance.security_group_id.clone(),
+                    security_group_managed: true,
+                    ssh_key_path: None,
+                    terminated_at: None,
+                    spot: false,
+                    instance_type: None,
+                    root_volume_gb: None,
+                    private_ip: None,
+                    last_connected_at: None,
+                    description: None,
+                    account_id: None,
+                    subnet_id: instance.subnet_id.clone(),
+                    availability_zone: None,
+                    init_log_path: None,
+                },
+            ));
+        }
+    }
+
+    plan.removals = removals;
+    plan
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aws_sdk_ec2::types::InstanceStateName;
+
+    fn managed(id: &str, name: Option<&str>, state: InstanceStateName) -> ManagedInstance {
+        ManagedInstance {
+            instance_id: id.to_string(),
+            name: name.map(String::from),
+            state,
+            security_group_id: Some("sg-123".to_string()),
+            owner: Some("alice".to_string()),
+            vpc_id: None,
+            subnet_id: None,
+        }
+    }
+
+    fn state_with(entries: &[(&str, &str, &str)]) -> State {
+        let mut state = State::default();
+        for (name, id, region) in entries {
+            state.add_instance(name, id, "default", region, "ubuntu", "sg-123", None);
+        }
+        state
+    }
+
+    #[test]
+    fn test_plan_in_sync() {
+        let state = state_with(&[("dev", "i-1", "us-east-1")]);
+        let found = HashMap::from([(
+            "us-east-1".to_string(),
+            vec![managed("i-1", Some("dev"), InstanceStateName::Running)],
+        )]);
+
+        assert!(plan_repair(&state, &found, None).is_empty());
+    }
+
+    #[test]
+    fn test_plan_removes_terminated_and_missing() {
+        let state = state_with(&[
+            ("gone", "i-1", "us-east-1"),
+            ("dead", "i-2", "us-east-1"),
+            ("elsewhere", "i-3", "eu-west-1"),
+        ]);
+        let found = HashMap::from([(
+            "us-east-1".to_string(),
+            vec![managed("i-2", Some("dead"), InstanceStateName::Terminated)],
+        )]);
+
+        let plan = plan_repair(&state, &found, None);
+        // Entries in regions that weren't scanned are left alone
+        assert_eq!(plan.removals, vec!["dead", "gone"]);
+        assert!(plan.additions.is_empty());
+    }
+
+    #[test]
+    fn test_plan_keeps_entries_marked_terminated() {
+        let mut state = state_with(&[("kept", "i-1", "us-east-1")]);
+        state.mark_terminated("kept");
+        let found = HashMap::from([("us-east-1".to_string(), vec![])]);
+
+        assert!(plan_repair(&state, &found, None).is_empty());
+    }
+
+    #[test]
+    fn test_plan_adds_untracked_instances() {
+        let state = state_with(&[("dev", "i-1", "us-east-1")]);
+        let found = HashMap::from([(
+            "us-east-1".to_string(),
+            vec![
+                managed("i-1", Some("dev"), InstanceStateName::Running),
+                managed("i-2", Some("build"), InstanceStateName::Pending),
+                managed("i-3", Some("dev"), InstanceStateName::Stopped),
+                managed("i-4", None, InstanceStateName::Running),
+                managed("i-5", Some("old"), InstanceStateName::Terminated),
+            ],
+        )]);
+
+        let plan = plan_repair(&state, &found, None);
+        let names: Vec<_> = plan.additions.iter().map(|(n, _)| n.as_str()).collect();
+        assert_eq!(names, vec!["build", "i-3", "i-4"]);
+        assert_eq!(plan.additions[0].1.region, "us-east-1");
+        assert_eq!(
+            plan.additions[0].1.security_group_id,
+            Some("sg-123".to_string())
+        );
+        assert!(plan.removals.is_empty());
+    }
+
+    #[test]
+    fn test_plan_names_conflicting_instances_by_id() {
+        let state = State::default();
+        let found = HashMap::from([
+            (
+                "us-east-1".to_string(),
+                vec![
+                    managed("i-1", Some("dev"), InstanceStateName::Running),
+                    managed("i-2", Some("build"), InstanceStateName::Running),
+                ],
+            ),
+            (
+                "eu-west-1".to_string(),
+                vec![
+                    managed("i-3", Some("dev"), InstanceStateName::Running),
+                    managed("i-4", Some("build"), InstanceStateName::Terminated),
+                ],
+            ),
+        ]);
+
+        let plan = plan_repair(&state, &found, None);
+        let names: Vec<_> = plan.additions.iter().map(|(n, _)| n.as_str()).collect();
+        assert_eq!(names, vec!["i-3", "i-1", "build"]);
+    }
+
+    #[test]
+    fn test_plan_only_adds_owned_instances() {
+        let state = State::default();
+        let mut theirs = managed("i-2", Some("build"), InstanceStateName::Running);
+        theirs.owner = Some("bob".to_string());
+        let mut untagged = managed("i-3", Some("ci"), InstanceStateName::Running);
+        untagged.owner = None;
+        let found = HashMap::from([(
+            "us-east-1".to_string(),
+            vec![
+                managed("i-1", Some("dev"), InstanceStateName::Running),
+                theirs,
+                untagged,
+            ],
+        )]);
+
+        let plan = plan_repair(&state, &found, Some("alice"));
+        let names: Vec<_> = plan.additions.iter().map(|(n, _)| n.as_str()).collect();
+        assert_eq!(names, vec!["dev"]);
+
+        // Without an owner filter every instance is recovered
+        assert_eq!(plan_repair(&state, &found, None).additions.len(), 3);
+    }
+}
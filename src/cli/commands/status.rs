@@ -1,31 +1,50 @@
 use crate::aws::client::AwsClients;
 use crate::aws::ec2::instance::get_instance_state;
-use crate::state::{get_instance, resolve_instance_name};
+use crate::state::InstanceResolver;
 use crate::ui::create_spinner;
-use crate::{Ec2CliError, Result};
+use crate::Result;
 
 pub async fn execute(name: Option<String>) -> Result<()> {
-    // Resolve instance name
-    let name = resolve_instance_name(name.as_deref())?;
-
-    // Get instance from state
-    let instance_state =
-        get_instance(&name)?.ok_or_else(|| Ec2CliError::InstanceNotFound(name.clone()))?;
+    // Resolve instance by name, link, or instance ID
+    let resolved = InstanceResolver::load()?.resolve(name.as_deref())?;
+    let name = &resolved.name;
+    let instance_state = &resolved.state;
 
     println!("Instance: {}", name);
     println!("  Instance ID: {}", instance_state.instance_id);
     println!("  Profile: {}", instance_state.profile);
     println!("  Region: {}", instance_state.region);
-    println!(
-        "  Created: {}",
-        instance_state.created_at.format("%Y-%m-%d %H:%M:%S UTC")
-    );
+    if let Some(ref availability_zone) = instance_state.availability_zone {
+        println!("  Availability Zone: {}", availability_zone);
+    }
+    if let Some(ref subnet_id) = instance_state.subnet_id {
+        println!("  Subnet: {}", subnet_id);
+    }
+    if let Some(ref description) = instance_state.description {
+        println!("  Description: {}", description);
+    }
+    if instance_state.spot {
+        println!("  Market: spot");
+    }
+    if resolved.tracked {
+        println!(
+            "  Created: {}",
+            instance_state.created_at.format("%Y-%m-%d %H:%M:%S UTC")
+        );
+    } else {
+        println!("  Tracked: no (not in local state)");
+    }
 
     // Get live status from AWS
     let spinner = create_spinner("Fetching instance status...");
     let clients = AwsClients::with_region(&instance_state.region).await?;
+    // Looking up an instance from another account would just report it missing
+    let live_state = match instance_state.check_account(&clients.account_id) {
+        Ok(()) => get_instance_state(&clients, &instance_state.instance_id).await,
+        Err(e) => Err(e),
+    };
 
-    match get_instance_state(&clients, &instance_state.instance_id).await {
+    match live_state {
         Ok(state) => {
             spinner.finish_and_clear();
             println!("  State: {:?}", state);
@@ -36,19 +55,8 @@ pub async fn execute(name: Option<String>) -> Result<()> {
         }
     }
 
-    // Check for directory link
-    let link_file = std::env::current_dir()
-        .ok()
-        .map(|p| p.join(".ec2-cli").join("instance"));
-
-    if let Some(link_path) = link_file {
-        if link_path.exists() {
-            if let Ok(linked_name) = std::fs::read_to_string(&link_path) {
-                if linked_name.trim() == name {
-                    println!("  Linked: yes (current directory)");
-                }
-            }
-        }
+    if resolved.linked {
+        println!("  Linked: yes (current directory)");
     }
 
     Ok(())
@@ -0,0 +1,241 @@
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::ssh::ssm_ssh_opts;
+use crate::state::{InstanceResolver, ResolvedInstance};
+use crate::{Ec2CliError, Result};
+
+use super::check_instance_account;
+use super::scp::parse_paths;
+
+/// Ignore file read from the current directory when `--ignore-file` isn't given
+const IGNORE_FILE_NAME: &str = ".ec2-cli-ignore";
+
+pub async fn execute(
+    name: Option<String>,
+    src: String,
+    dest: String,
+    excludes: Vec<String>,
+    ignore_file: Option<PathBuf>,
+    delete: bool,
+    verify_host: bool,
+) -> Result<()> {
+    // An explicit ignore file must exist; the default one is optional
+    let ignore_file =
+        ignore_file.or_else(|| Some(PathBuf::from(IGNORE_FILE_NAME)).filter(|path| path.is_file()));
+    let exclude_args = exclude_args(ignore_file.as_deref(), &excludes)?;
+
+    // Resolve instance by name, link, or instance ID
+    let ResolvedInstance {
+        name,
+        state: instance_state,
+        ..
+    } = InstanceResolver::load()?.resolve(name.as_deref())?;
+    check_instance_account(&instance_state).await?;
+
+    let (local_path, remote_path, is_upload) = parse_paths(&src, &dest)?;
+    let local_path = PathBuf::from(local_path);
+
+    let remote = format!(
+        "{}@{}:{}",
+        instance_state.username, instance_state.instance_id, remote_path
+    );
+    let (from, to) = if is_upload {
+        (local_path.as_os_str(), OsStr::new(&remote))
+    } else {
+        (OsStr::new(&remote), local_path.as_os_str())
+    };
+
+    let status = build_rsync_command(
+        instance_state.ssh_key_path.as_deref(),
+        &ssm_ssh_opts(verify_host)?,
+        &exclude_args,
+        delete,
+        from,
+        to,
+    )
+    .status()
+    .map_err(|e| match e.kind() {
+        std::io::ErrorKind::NotFound => Ec2CliError::Prerequisites(
+            "rsync not found. Install rsync to use 'ec2-cli sync'".to_string(),
+        ),
+        _ => Ec2CliError::SyncTransfer(format!("Failed to execute rsync: {}", e)),
+    })?;
+
+    if !status.success() {
+        return Err(Ec2CliError::SyncTransfer(match status.code() {
+            Some(code) => format!("rsync with instance '{}' exited with status {}", name, code),
+            None => format!("rsync with instance '{}' was terminated by a signal", name),
+        }));
+    }
+
+    Ok(())
+}
+
+/// Check an exclude pattern can be passed to rsync as-is
+fn validate_pattern(pattern: &str) -> std::result::Result<(), String> {
+    if pattern.trim().is_empty() {
+        return Err("pattern is empty".to_string());
+    }
+    if pattern.contains(['\n', '\r', '\0']) {
+        return Err(format!("'{}' contains a control character", pattern));
+    }
+    // rsync has no gitignore-style re-include, so it would be silently ignored
+    if pattern.starts_with('!') {
+        return Err(format!(
+            "'{}' uses negation, which rsync excludes don't support",
+            pattern
+        ));
+    }
+    Ok(())
+}
+
+/// Translate the ignore file and `--exclude` patterns into rsync arguments.
+///
+/// The ignore file is checked line by line (blank lines and `#` or `;` comments are skipped,
+/// as rsync does) and then passed with `--exclude-from`.
+fn exclude_args(ignore_file: Option<&Path>, excludes: &[String]) -> Result<Vec<String>> {
+    let mut args = Vec::new();
+
+    if let Some(path) = ignore_file {
+        let content = std::fs::read_to_string(path).map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => Ec2CliError::PathNotFound(path.display().to_string()),
+            _ => Ec2CliError::InvalidPath(format!("Cannot read {}: {}", path.display(), e)),
+        })?;
+
+        for (number, line) in content.lines().enumerate() {
+            let line = line.trim_end();
+            if line.trim().is_empty() || line.starts_with('#') || line.starts_with(';') {
+                continue;
+            }
+            validate_pattern(line).map_err(|problem| {
+                Ec2CliError::InvalidPattern(format!(
+                    "{} line {}: {}",
+                    path.display(),
+                    number + 1,
+                    problem
+                ))
+            })?;
+        }
+
+        args.push(format!("--exclude-from={}", path.display()));
+    }
+
+    for pattern in excludes {
+        validate_pattern(pattern).map_err(Ec2CliError::InvalidPattern)?;
+        args.push(format!("--exclude={}", pattern));
+    }
+
+    Ok(args)
+}
+
+/// Assemble the rsync command line, tunnelling over ssh through SSM
+fn build_rsync_command(
+    ssh_key_path: Option<&str>,
+    ssh_options: &[String],
+    exclude_args: &[String],
+    delete: bool,
+    from: &OsStr,
+    to: &OsStr,
+) -> Command {
+    // rsync splits -e on whitespace but honours quotes, so quote each argument
+    let mut ssh = vec!["ssh".to_string()];
+    if let Some(key_path) = ssh_key_path {
+        ssh.push("-i".to_string());
+        ssh.push(key_path.to_string());
+    }
+    ssh.extend(ssh_options.iter().cloned());
+    let ssh = ssh
+        .iter()
+        .map(|arg| shell_escape::unix::escape(arg.into()).into_owned())
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    let mut cmd = Command::new("rsync");
+    cmd.arg("-az").arg("-e").arg(ssh).args(exclude_args);
+
+    if delete {
+        cmd.arg("--delete");
+    }
+
+    cmd.arg(from).arg(to);
+    cmd
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(cmd: &Command) -> Vec<String> {
+        cmd.get_args()
+            .map(|a| a.to_string_lossy().into_owned())
+            .collect()
+    }
+
+    #[test]
+    fn test_exclude_args_from_ignore_file_and_flags() {
+        let path = std::env::temp_dir().join(format!("ec2-cli-sync-ignore-{}", std::process::id()));
+        std::fs::write(&path, "# build output\ntarget/\n\nnode_modules/\n*.log\n").unwrap();
+
+        let args = exclude_args(Some(&path), &["data/*.csv".to_string()]).unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        assert_eq!(
+            args,
+            vec![
+                format!("--exclude-from={}", path.display()),
+                "--exclude=data/*.csv".to_string(),
+            ]
+        );
+        assert!(exclude_args(None, &[]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_exclude_args_rejects_bad_patterns() {
+        let result = exclude_args(None, &["!keep.txt".to_string()]);
+        assert!(matches!(result, Err(Ec2CliError::InvalidPattern(_))));
+        let result = exclude_args(None, &[" ".to_string()]);
+        assert!(matches!(result, Err(Ec2CliError::InvalidPattern(_))));
+
+        let path = std::env::temp_dir().join(format!("ec2-cli-sync-negate-{}", std::process::id()));
+        std::fs::write(&path, "target/\n!target/keep\n").unwrap();
+        let result = exclude_args(Some(&path), &[]);
+        let _ = std::fs::remove_file(&path);
+        assert!(matches!(result, Err(Ec2CliError::InvalidPattern(msg)) if msg.contains("line 2")));
+    }
+
+    #[test]
+    fn test_exclude_args_missing_ignore_file() {
+        let result = exclude_args(Some(Path::new("/nonexistent-dir/.ec2-cli-ignore")), &[]);
+        assert!(matches!(result, Err(Ec2CliError::PathNotFound(_))));
+    }
+
+    #[test]
+    fn test_rsync_command() {
+        let cmd = build_rsync_command(
+            Some("/home/dev/.ssh/id_ed25519"),
+            &[
+                "-o".to_string(),
+                "ProxyCommand=sh -c \"proxy %h\"".to_string(),
+            ],
+            &["--exclude=target/".to_string()],
+            true,
+            OsStr::new("./project/"),
+            OsStr::new("ubuntu@i-123:project/"),
+        );
+
+        assert_eq!(
+            args(&cmd),
+            [
+                "-az",
+                "-e",
+                "ssh -i /home/dev/.ssh/id_ed25519 -o 'ProxyCommand=sh -c \"proxy %h\"'",
+                "--exclude=target/",
+                "--delete",
+                "./project/",
+                "ubuntu@i-123:project/"
+            ]
+        );
+    }
+}
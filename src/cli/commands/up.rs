@@ -1,15 +1,30 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::path::Path;
+use std::process::Command;
+
+use aws_sdk_ec2::types::InstanceStateName;
+use dialoguer::Confirm;
+use serde::Serialize;
+use uuid::Uuid;
+
 use crate::aws::client::AwsClients;
 use crate::aws::ec2::instance::{
-    create_instance_security_group, delete_security_group, launch_instance, wait_for_git_ready,
-    wait_for_running, wait_for_ssm_ready,
+    create_instance_security_group, delete_security_group, find_by_name_tag, launch_instance,
+    launch_with_fallback, list_managed_instances, validate_ami_available, wait_for_marker,
+    wait_for_running, wait_for_ssm_ready, LaunchMode, LaunchedInstance, ManagedInstance,
+    SetupMarker,
 };
 use crate::aws::infrastructure::Infrastructure;
 use crate::config::Settings;
 use crate::git::find_git_user_config;
-use crate::profile::ProfileLoader;
+use crate::profile::{
+    instance_type_arch_mismatch, is_valid_instance_type, HooksConfig, Profile, ProfileLoader,
+};
 use crate::ssh::find_ssh_public_key;
-use crate::ui::create_spinner;
-use crate::user_data::{generate_user_data, validate_project_name};
+use crate::state::{read_link, InstanceState, State};
+use crate::ui::{info, set_quiet, Progress};
+use crate::user_data::{generate_user_data, init_log_path, validate_project_name};
 use crate::{Ec2CliError, Result};
 
 /// Get the SSH username (always ubuntu for Ubuntu AMIs)
@@ -17,6 +32,219 @@ fn get_username_for_ami(_ami_type: &str) -> &'static str {
     "ubuntu"
 }
 
+/// How long `up --wait-complete` waits for package installation to finish
+const SETUP_COMPLETE_TIMEOUT_SECS: u64 = 1800;
+
+/// Attempts at a valid generated name before falling back to a generic one
+const NAME_GENERATION_ATTEMPTS: usize = 3;
+
+/// Generate a random instance name like `brave-otter`
+fn generate_instance_name() -> String {
+    generate_instance_name_with(|| petname::petname(2, "-"))
+}
+
+/// Use the first valid name from `generate`, regenerating on invalid output and
+/// falling back to a generic name with a unique suffix so unnamed instances never
+/// collide in state
+fn generate_instance_name_with(mut generate: impl FnMut() -> Option<String>) -> String {
+    (0..NAME_GENERATION_ATTEMPTS)
+        .filter_map(|_| generate())
+        .find(|name| validate_project_name(name).is_ok())
+        .unwrap_or_else(|| format!("ec2-instance-{}", &Uuid::new_v4().to_string()[..8]))
+}
+
+/// Use `ami_id` for this launch instead of the profile's AMI settings
+fn apply_ami_override(profile: &mut Profile, ami_id: Option<String>) -> Result<()> {
+    let Some(ami_id) = ami_id else {
+        return Ok(());
+    };
+
+    let valid = ami_id
+        .strip_prefix("ami-")
+        .is_some_and(|id| id.len() >= 8 && id.chars().all(|c| c.is_ascii_hexdigit()));
+    if !valid {
+        return Err(Ec2CliError::ProfileValidation(format!(
+            "Invalid AMI ID: '{}'. Expected format like 'ami-0123456789abcdef0'",
+            ami_id
+        )));
+    }
+
+    profile.instance.ami.id = Some(ami_id);
+    Ok(())
+}
+
+/// Use `instance_type` for this launch instead of the profile's type. An explicit
+/// type is launched as-is, without the profile's fallback types.
+fn apply_instance_type_override(
+    profile: &mut Profile,
+    instance_type: Option<String>,
+) -> Result<()> {
+    let Some(instance_type) = instance_type else {
+        return Ok(());
+    };
+
+    if !is_valid_instance_type(&instance_type) {
+        return Err(Ec2CliError::InvalidInstanceType(format!(
+            "'{}' (expected a type such as t3.large)",
+            instance_type
+        )));
+    }
+    if let Some(problem) =
+        instance_type_arch_mismatch(&instance_type, &profile.instance.ami.architecture)
+    {
+        return Err(Ec2CliError::InvalidInstanceType(problem));
+    }
+
+    profile.instance.instance_type = instance_type;
+    profile.instance.fallback_types.clear();
+    Ok(())
+}
+
+/// The settings' custom tags plus `tags` (`key=value`) given for this launch,
+/// which win over settings tags with the same key
+fn launch_tags(settings: &Settings, tags: &[String]) -> Result<HashMap<String, String>> {
+    // set_tag validates each tag the same way `config tags set` does
+    let mut merged = settings.clone();
+    for tag in tags {
+        let (key, value) = tag.split_once('=').ok_or_else(|| {
+            Ec2CliError::Config(format!("Invalid tag '{}': expected KEY=VALUE", tag))
+        })?;
+        merged.set_tag(key, value)?;
+    }
+    Ok(merged.tags)
+}
+
+/// What `up` should do about an instance name
+#[derive(Debug)]
+enum LaunchPlan {
+    /// Nothing usable exists under the name; launch a new instance
+    Launch,
+    /// A previous `up` launched this instance but didn't finish waiting for it
+    Resume(ManagedInstance),
+}
+
+/// Decide whether to launch or resume, given the state entry for `name` and the
+/// managed instances found in `region`
+fn plan_launch(
+    name: &str,
+    entry: Option<&InstanceState>,
+    region: &str,
+    managed: &[ManagedInstance],
+) -> Result<LaunchPlan> {
+    let matched = find_by_name_tag(name, managed.iter().map(|i| (region, i)))?;
+
+    let Some((_, existing)) = matched else {
+        // An active entry we can't check in this region may still be running
+        return match entry {
+            Some(entry) if entry.terminated_at.is_none() && entry.region != region => {
+                Err(Ec2CliError::InstanceNameExists(format!(
+                    "{} ({} in {})",
+                    name, entry.instance_id, entry.region
+                )))
+            }
+            // Stale or terminated entries are replaced
+            _ => Ok(LaunchPlan::Launch),
+        };
+    };
+
+    let tracked = entry.is_some_and(|e| e.instance_id == existing.instance_id);
+    let resumable = match existing.state {
+        InstanceStateName::Pending => true,
+        // State is only saved once setup finishes, so an untracked running instance
+        // is one whose `up` was interrupted
+        InstanceStateName::Running => !tracked,
+        _ => false,
+    };
+
+    if resumable {
+        Ok(LaunchPlan::Resume(existing.clone()))
+    } else {
+        Err(Ec2CliError::InstanceNameExists(format!(
+            "{} ({}, {})",
+            name,
+            existing.instance_id,
+            existing.state.as_str()
+        )))
+    }
+}
+
+/// Lifecycle events written by `up --json`, one object per line
+#[derive(Debug, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+enum UpEvent<'a> {
+    Launched { instance_id: &'a str },
+    Running { instance_id: &'a str },
+    SsmReady { instance_id: &'a str },
+    GitReady { instance_id: &'a str },
+    SetupComplete { instance_id: &'a str },
+    Ready { name: &'a str, instance_id: &'a str },
+}
+
+/// A wait between launching an instance and it being ready to use
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SetupStep {
+    /// The instance reaching the running state
+    Running,
+    /// The SSM agent coming online
+    SsmReady,
+    /// The bare repo and working tree being created
+    GitReady,
+}
+
+impl SetupStep {
+    fn waiting_message(self) -> &'static str {
+        match self {
+            SetupStep::Running => "Waiting for instance to start...",
+            SetupStep::SsmReady => "Waiting for SSM agent...",
+            SetupStep::GitReady => "Waiting for git repo setup...",
+        }
+    }
+
+    fn done_message(self) -> &'static str {
+        match self {
+            SetupStep::Running => "Instance running",
+            SetupStep::SsmReady => "SSM agent ready",
+            SetupStep::GitReady => "Git repo ready",
+        }
+    }
+
+    fn event(self, instance_id: &str) -> UpEvent<'_> {
+        match self {
+            SetupStep::Running => UpEvent::Running { instance_id },
+            SetupStep::SsmReady => UpEvent::SsmReady { instance_id },
+            SetupStep::GitReady => UpEvent::GitReady { instance_id },
+        }
+    }
+}
+
+/// Run each setup wait in turn, reporting it through `progress`. The git repo is
+/// only waited for when the instance has a project.
+async fn wait_until_ready<F, Fut>(
+    progress: &mut Progress,
+    instance_id: &str,
+    has_project: bool,
+    mut wait: F,
+) -> Result<()>
+where
+    F: FnMut(SetupStep) -> Fut,
+    Fut: Future<Output = Result<()>>,
+{
+    let mut steps = vec![SetupStep::Running, SetupStep::SsmReady];
+    if has_project {
+        steps.push(SetupStep::GitReady);
+    }
+
+    for step in steps {
+        progress.start(step.waiting_message());
+        if let Err(e) = wait(step).await {
+            progress.clear();
+            return Err(e);
+        }
+        progress.finish_with_event(step.done_message(), &step.event(instance_id))?;
+    }
+    Ok(())
+}
+
 /// Print a warning message when instance setup fails after launch
 fn print_cleanup_warning(name: &str, instance_id: &str, security_group_id: &str, region: &str) {
     eprintln!();
@@ -39,55 +267,92 @@ fn print_cleanup_warning(name: &str, instance_id: &str, security_group_id: &str,
     );
 }
 
+#[allow(clippy::too_many_arguments)]
 pub async fn execute(
     profile_name: Option<String>,
     instance_name: Option<String>,
     link: bool,
+    force: bool,
+    refresh_ami: bool,
+    spot_fallback: bool,
+    no_git: bool,
+    description: Option<String>,
+    ami: Option<String>,
+    instance_type: Option<String>,
+    tags: Vec<String>,
+    wait_complete: bool,
+    no_encrypt: bool,
+    json: bool,
 ) -> Result<()> {
+    // Only events go to stdout in JSON mode; warnings and errors still go to stderr
+    let mut progress = if json {
+        set_quiet(true);
+        Progress::json()
+    } else {
+        Progress::spinners()
+    };
+
     // Load profile
     let loader = ProfileLoader::new();
     let profile_name = profile_name.unwrap_or_else(|| "default".to_string());
-    let profile = loader.load(&profile_name)?;
+    let mut profile = loader.load(&profile_name)?;
     profile.validate()?;
+    let ami_override = ami.is_some();
+    apply_ami_override(&mut profile, ami)?;
+    apply_instance_type_override(&mut profile, instance_type)?;
+    let settings = Settings::load().unwrap_or_default();
+    let custom_tags = launch_tags(&settings, &tags)?;
+    if let Some(ref description) = description {
+        InstanceState::validate_description(description)?;
+    }
+    if no_encrypt {
+        confirm_no_encrypt(&profile)?;
+    }
+    if profile.user_data_extra.is_some() && !profile.allow_raw_user_data {
+        eprintln!("Warning: user_data_extra is ignored unless allow_raw_user_data is true");
+    }
 
     // Generate instance name if not provided
-    let name = instance_name
-        .unwrap_or_else(|| petname::petname(2, "-").unwrap_or_else(|| "ec2-instance".to_string()));
+    let name = instance_name.unwrap_or_else(generate_instance_name);
+
+    // Settle an existing directory link now rather than after the instance is up
+    let relink = link && confirm_relink(&std::env::current_dir()?, &name, force)?;
 
     // Determine username based on AMI type
     let username = get_username_for_ami(&profile.instance.ami.ami_type);
 
-    println!("Launching EC2 instance '{}'...", name);
-    println!("  Profile: {}", profile.name);
-    println!("  Instance type: {}", profile.instance.instance_type);
-    println!(
+    info!("Launching EC2 instance '{}'...", name);
+    info!("  Profile: {}", profile.name);
+    info!("  Instance type: {}", profile.instance.instance_type);
+    info!(
         "  AMI type: {} (user: {})",
         profile.instance.ami.ami_type, username
     );
 
     // Initialize AWS clients
-    let spinner = create_spinner("Connecting to AWS...");
+    progress.start("Connecting to AWS...");
     let clients = AwsClients::new().await?;
-    spinner.finish_with_message("Connected to AWS");
-
-    // Get or create infrastructure (VPC, subnet from config; IAM resources created if needed)
-    let spinner = create_spinner("Checking infrastructure...");
-    let infra = Infrastructure::get_or_create(&clients).await?;
-    spinner.finish_with_message("Infrastructure ready");
+    progress.finish("Connected to AWS");
 
-    // Load custom tags for security group
-    let custom_tags = Settings::load().map(|s| s.tags).unwrap_or_default();
+    // A previous `up` with this name may have been interrupted after launching
+    progress.start("Checking for an existing instance...");
+    let managed = list_managed_instances(&clients).await?;
+    let state = State::load()?;
+    let plan = plan_launch(&name, state.get_instance(&name), &clients.region, &managed)?;
+    // A resumed instance logs wherever it did when it was launched
+    let previous_log_path = state
+        .get_instance(&name)
+        .and_then(|entry| entry.init_log_path.clone());
+    progress.clear();
 
-    // Create per-instance security group
-    let spinner = create_spinner("Creating security group...");
-    let security_group_id =
-        create_instance_security_group(&clients, &infra.vpc_id, &name, &custom_tags).await?;
-    spinner.finish_with_message("Security group created");
-
-    // Get project name from current directory (for git repo setup)
-    let project_name = std::env::current_dir()
-        .ok()
-        .and_then(|p| p.file_name().map(|n| n.to_string_lossy().to_string()));
+    // Get project name from current directory (for git repo setup; none skips it)
+    let project_name = if no_git {
+        None
+    } else {
+        std::env::current_dir()
+            .ok()
+            .and_then(|p| p.file_name().map(|n| n.to_string_lossy().to_string()))
+    };
 
     // Validate project name if present
     if let Some(ref proj_name) = project_name {
@@ -109,124 +374,702 @@ pub async fn execute(
         Err(e) => return Err(e),
     };
 
-    // Detect local git user config
-    let git_user_config = find_git_user_config();
-    if let Some(ref name) = git_user_config.name {
-        println!("  Git user.name: {}", name);
-    }
-    if let Some(ref email) = git_user_config.email {
-        println!("  Git user.email: {}", email);
-    }
+    let (launched, security_group_id, spot, log_path) = match plan {
+        LaunchPlan::Resume(existing) => {
+            let security_group_id = existing.security_group_id.ok_or_else(|| {
+                Ec2CliError::InstanceState(format!(
+                    "Instance {} has no security group; destroy it and run 'up' again",
+                    existing.instance_id
+                ))
+            })?;
+            info!(
+                "  Resuming setup of existing instance {} ({})",
+                existing.instance_id,
+                existing.state.as_str()
+            );
+            progress.event(&UpEvent::Launched {
+                instance_id: &existing.instance_id,
+            })?;
+            let launched = LaunchedInstance {
+                instance_id: existing.instance_id,
+                private_ip: None,
+                subnet_id: existing.subnet_id,
+                availability_zone: None,
+            };
+            (launched, security_group_id, false, previous_log_path)
+        }
+        LaunchPlan::Launch => {
+            if ami_override {
+                if let Some(ref ami_id) = profile.instance.ami.id {
+                    progress.start(format!("Checking AMI {}...", ami_id));
+                    validate_ami_available(&clients, ami_id).await?;
+                    progress.finish(format!("Using AMI {}", ami_id));
+                }
+            }
 
-    // Generate user data
-    let git_config_ref = if git_user_config.has_config() {
-        Some(&git_user_config)
-    } else {
-        None
-    };
-    let user_data = generate_user_data(
-        &profile,
-        project_name.as_deref(),
-        username,
-        Some(&ssh_key_info.public_key),
-        git_config_ref,
-    )?;
-
-    // Launch instance (cleanup security group on failure)
-    let spinner = create_spinner("Launching instance...");
-    let instance_id = match launch_instance(
-        &clients,
-        &infra,
-        &security_group_id,
-        &profile,
-        &name,
-        &user_data,
-    )
-    .await
-    {
-        Ok(id) => {
-            spinner.finish_with_message(format!("Instance launched: {}", id));
-            id
-        }
-        Err(e) => {
-            spinner.finish_and_clear();
-            // Cleanup security group on launch failure
-            let _ = delete_security_group(&clients, &security_group_id).await;
-            return Err(e);
+            // Get or create infrastructure (VPC, subnet from config; IAM resources created if needed)
+            progress.start("Checking infrastructure...");
+            let infra = Infrastructure::get_or_create(&clients).await?;
+            progress.finish("Infrastructure ready");
+
+            let init_log_path = init_log_path(&settings)?;
+
+            // Create per-instance security group
+            progress.start("Creating security group...");
+            let security_group_id =
+                create_instance_security_group(&clients, &infra.vpc_id, &name, &custom_tags)
+                    .await?;
+            progress.finish("Security group created");
+
+            // Detect local git user config
+            let git_user_config = find_git_user_config();
+            if let Some(ref name) = git_user_config.name {
+                info!("  Git user.name: {}", name);
+            }
+            if let Some(ref email) = git_user_config.email {
+                info!("  Git user.email: {}", email);
+            }
+
+            // Generate user data
+            let git_config_ref = if git_user_config.has_config() {
+                Some(&git_user_config)
+            } else {
+                None
+            };
+            let user_data = match generate_user_data(
+                &profile,
+                project_name.as_deref(),
+                username,
+                Some(&ssh_key_info.public_key),
+                git_config_ref,
+                init_log_path,
+            ) {
+                Ok(user_data) => user_data,
+                Err(e) => {
+                    let _ = delete_security_group(&clients, &security_group_id).await;
+                    return Err(e);
+                }
+            };
+
+            // Launch instance (cleanup security group on failure)
+            progress.start("Launching instance...");
+            let launched = launch_with_fallback(LaunchMode::attempts(spot_fallback), |mode| {
+                launch_instance(
+                    &clients,
+                    &infra,
+                    &security_group_id,
+                    &profile,
+                    &name,
+                    &user_data,
+                    &custom_tags,
+                    refresh_ami,
+                    mode,
+                    !no_encrypt,
+                )
+            })
+            .await;
+            match launched {
+                Ok((launched, mode)) => {
+                    progress.finish_with_event(
+                        format!("Instance launched: {} ({})", launched.instance_id, mode),
+                        &UpEvent::Launched {
+                            instance_id: &launched.instance_id,
+                        },
+                    )?;
+                    if spot_fallback && mode == LaunchMode::OnDemand {
+                        info!("  Spot capacity unavailable, launched on-demand instead");
+                    }
+                    (
+                        launched,
+                        security_group_id,
+                        mode == LaunchMode::Spot,
+                        Some(init_log_path.to_string()),
+                    )
+                }
+                Err(e) => {
+                    progress.clear();
+                    // Cleanup security group on launch failure
+                    let _ = delete_security_group(&clients, &security_group_id).await;
+                    return Err(e);
+                }
+            }
         }
     };
 
-    // Wait for instance to be running
-    let spinner = create_spinner("Waiting for instance to start...");
-    if let Err(e) = wait_for_running(&clients, &instance_id, 300).await {
-        spinner.finish_and_clear();
-        print_cleanup_warning(&name, &instance_id, &security_group_id, &clients.region);
-        return Err(e);
-    }
-    spinner.finish_with_message("Instance running");
-
-    // Wait for SSM agent to be ready
-    let spinner = create_spinner("Waiting for SSM agent...");
-    if let Err(e) = wait_for_ssm_ready(&clients, &instance_id, 600).await {
-        spinner.finish_and_clear();
-        print_cleanup_warning(&name, &instance_id, &security_group_id, &clients.region);
-        return Err(e);
-    }
-    spinner.finish_with_message("SSM agent ready");
-
-    // Save state with username, security group ID, and SSH key path
-    let ssh_key_path_str = ssh_key_info.private_key_path.to_string_lossy();
-
-    // Wait for git repo to be ready (only if project is configured)
-    if project_name.is_some() {
-        let spinner = create_spinner("Waiting for git repo setup...");
-        if let Err(e) =
-            wait_for_git_ready(&instance_id, username, Some(&ssh_key_path_str), 300).await
-        {
-            spinner.finish_and_clear();
+    let LaunchedInstance {
+        instance_id,
+        private_ip,
+        subnet_id,
+        availability_zone,
+    } = launched;
+
+    let ssh_key_path = ssh_key_info
+        .private_key_path
+        .as_ref()
+        .map(|path| path.to_string_lossy().into_owned());
+
+    // Save state with username, security group ID, SSH key path, and launch details
+    let save_to_state = || -> Result<()> {
+        crate::state::save_instance(
+            &name,
+            &instance_id,
+            &profile.name,
+            &clients.region,
+            username,
+            &security_group_id,
+            ssh_key_path.as_deref(),
+        )?;
+        crate::state::update_instance(&name, |entry| {
+            entry.spot = spot;
+            entry.instance_type = Some(profile.instance.instance_type.clone());
+            entry.root_volume_gb = Some(profile.instance.storage.root_volume.size_gb);
+            entry.private_ip = private_ip.clone();
+            entry.subnet_id = subnet_id.clone();
+            entry.availability_zone = availability_zone.clone();
+            entry.init_log_path = log_path.clone();
+            entry.description = description.clone();
+            entry.account_id = Some(clients.account_id.clone());
+        })
+    };
+
+    // Wait for the instance to finish setting up. If interrupted with Ctrl-C, record what
+    // was launched so far so that `ec2-cli destroy` can still clean it up.
+    let setup = async {
+        let result = wait_until_ready(
+            &mut progress,
+            &instance_id,
+            project_name.is_some(),
+            |step| {
+                let (clients, instance_id) = (&clients, &instance_id);
+                let ssh_key_path = ssh_key_path.as_deref();
+                async move {
+                    match step {
+                        SetupStep::Running => wait_for_running(clients, instance_id, 300).await,
+                        SetupStep::SsmReady => wait_for_ssm_ready(clients, instance_id, 600).await,
+                        SetupStep::GitReady => {
+                            wait_for_marker(
+                                instance_id,
+                                username,
+                                ssh_key_path,
+                                SetupMarker::GitReady,
+                                300,
+                            )
+                            .await
+                        }
+                    }
+                }
+            },
+        )
+        .await;
+        if result.is_err() {
             print_cleanup_warning(&name, &instance_id, &security_group_id, &clients.region);
-            return Err(e);
         }
-        spinner.finish_with_message("Git repo ready");
+        result
+    };
+
+    tokio::select! {
+        result = setup => result?,
+        _ = tokio::signal::ctrl_c() => {
+            save_to_state()?;
+            eprintln!();
+            eprintln!("Interrupted. Instance {} ({}) was saved to state.", name, instance_id);
+            eprintln!("Run 'ec2-cli destroy {}' to clean it up.", name);
+            return Err(Ec2CliError::Cancelled);
+        }
     }
-    crate::state::save_instance(
-        &name,
-        &instance_id,
-        &profile.name,
-        &clients.region,
-        username,
-        &security_group_id,
-        Some(&ssh_key_path_str),
-    )?;
+
+    save_to_state()?;
 
     // Create link file if requested
     if link {
-        create_link_file(&name)?;
-        println!("  Linked to current directory");
+        create_link_file(&std::env::current_dir()?, &name, relink)?;
+        info!("  Linked to current directory");
     }
 
-    println!();
-    println!("Instance '{}' is ready!", name);
-    println!("  Instance ID: {}", instance_id);
-    println!("  Connect with: ec2-cli ssh {}", name);
+    // Packages install after the git repo is ready; optionally wait for them too.
+    // The instance is already in state, so a timeout here leaves it usable.
+    if wait_complete {
+        progress.start("Waiting for package installation...");
+        let result = wait_for_marker(
+            &instance_id,
+            username,
+            ssh_key_path.as_deref(),
+            SetupMarker::Complete,
+            SETUP_COMPLETE_TIMEOUT_SECS,
+        )
+        .await;
+        progress.clear();
+        if let Err(e) = result {
+            eprintln!("Check progress with 'ec2-cli logs {} -f'", name);
+            return Err(e);
+        }
+        progress.event(&UpEvent::SetupComplete {
+            instance_id: &instance_id,
+        })?;
+        info!("  Setup complete");
+    }
+
+    progress.event(&UpEvent::Ready {
+        name: &name,
+        instance_id: &instance_id,
+    })?;
+    info!();
+    info!("Instance '{}' is ready!", name);
+    info!("  Instance ID: {}", instance_id);
+    info!("  Connect with: ec2-cli ssh {}", name);
+
+    if !wait_complete {
+        info!(
+            "  Packages are still installing; follow with: ec2-cli logs {} -f",
+            name
+        );
+    }
 
     if let Some(ref proj) = project_name {
-        println!("  Push code with: ec2-cli push {}", name);
-        println!(
+        info!("  Push code with: ec2-cli push {}", name);
+        info!(
             "  Git remote: {}@{}:/home/{}/repos/{}.git",
             username, instance_id, username, proj
         );
     }
 
+    // Run the profile's local post-ready hook; failures don't undo the launch
+    if let Some(ref hook) = profile.hooks.post_ready {
+        let command = HooksConfig::render(hook, &name, &instance_id, &clients.region);
+        info!();
+        info!("Running post_ready hook...");
+        let mut hook_command = Command::new("sh");
+        hook_command.arg("-c").arg(&command);
+        // Keep stdout to the event stream in JSON mode
+        if json {
+            hook_command.stdout(std::io::stderr());
+        }
+        match hook_command.status() {
+            Ok(status) if status.success() => {}
+            Ok(status) => eprintln!("Warning: post_ready hook exited with {}", status),
+            Err(e) => eprintln!("Warning: Failed to run post_ready hook: {}", e),
+        }
+    }
+
     Ok(())
 }
 
-fn create_link_file(name: &str) -> Result<()> {
-    let link_dir = std::env::current_dir()?.join(".ec2-cli");
-    std::fs::create_dir_all(&link_dir)?;
+/// Ask before launching with an unencrypted root volume (`--no-encrypt`)
+fn confirm_no_encrypt(profile: &Profile) -> Result<()> {
+    if profile.instance.storage.root_volume.kms_key_id.is_some() {
+        return Err(Ec2CliError::ProfileValidation(format!(
+            "--no-encrypt conflicts with the kms_key_id set in profile '{}'",
+            profile.name
+        )));
+    }
+
+    let confirmed = Confirm::new()
+        .with_prompt("Launch with an UNENCRYPTED root volume?")
+        .default(false)
+        .interact()
+        .map_err(|_| Ec2CliError::Cancelled)?;
+    if !confirmed {
+        return Err(Ec2CliError::Cancelled);
+    }
+    Ok(())
+}
+
+/// Whether `up --link` may replace the link in `dir`. Links to instances that are gone
+/// from state are stale and replaced; a link to a live instance needs --force or a yes
+/// at the prompt.
+fn confirm_relink(dir: &Path, name: &str, force: bool) -> Result<bool> {
+    let existing = match read_link(dir)? {
+        Some(existing) if existing != name => existing,
+        _ => return Ok(false),
+    };
+    if force {
+        return Ok(true);
+    }
+
+    let live = State::load()?
+        .get_instance(&existing)
+        .is_some_and(|instance| instance.terminated_at.is_none());
+    if !live {
+        info!("  Replacing stale link to '{}'", existing);
+        return Ok(true);
+    }
+
+    // Without a terminal to prompt on, --force is required
+    let confirmed = Confirm::new()
+        .with_prompt(format!(
+            "This directory is linked to '{}'. Link it to '{}' instead?",
+            existing, name
+        ))
+        .default(false)
+        .interact()
+        .map_err(|_| Ec2CliError::LinkExists(existing))?;
+    if !confirmed {
+        return Err(Ec2CliError::Cancelled);
+    }
+    Ok(true)
+}
+
+/// Link `dir` to instance `name`, refusing to replace a link to another instance
+/// unless `overwrite` is set
+fn create_link_file(dir: &Path, name: &str, overwrite: bool) -> Result<()> {
+    let link_dir = dir.join(".ec2-cli");
+    if link_dir.is_symlink() {
+        return Err(Ec2CliError::InvalidPath(
+            "Link directory cannot be a symlink".to_string(),
+        ));
+    }
+
+    if let Some(existing) = read_link(dir)? {
+        if existing != name && !overwrite {
+            return Err(Ec2CliError::LinkExists(existing));
+        }
+    }
 
-    let link_file = link_dir.join("instance");
-    std::fs::write(&link_file, name)?;
+    std::fs::create_dir_all(&link_dir)?;
+    std::fs::write(link_dir.join("instance"), name)?;
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::io::Write;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn test_create_link_file_overwrite_guard() {
+        let dir = std::env::temp_dir().join(format!("ec2-cli-link-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        create_link_file(&dir, "old", false).unwrap();
+        assert_eq!(read_link(&dir).unwrap().as_deref(), Some("old"));
+
+        // Relinking the same instance is fine; another instance needs overwrite
+        create_link_file(&dir, "old", false).unwrap();
+        let err = create_link_file(&dir, "new", false).unwrap_err();
+        assert!(matches!(err, Ec2CliError::LinkExists(name) if name == "old"));
+        assert_eq!(read_link(&dir).unwrap().as_deref(), Some("old"));
+
+        create_link_file(&dir, "new", true).unwrap();
+        assert_eq!(read_link(&dir).unwrap().as_deref(), Some("new"));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_create_link_file_rejects_symlinked_dir() {
+        let dir = std::env::temp_dir().join(format!("ec2-cli-link-sym-{}", std::process::id()));
+        let target = dir.join("elsewhere");
+        std::fs::create_dir_all(&target).unwrap();
+        std::os::unix::fs::symlink(&target, dir.join(".ec2-cli")).unwrap();
+
+        assert!(create_link_file(&dir, "dev", true).is_err());
+        assert!(!target.join("instance").exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_ami_override_takes_precedence() {
+        let mut profile = Profile::default_profile();
+        profile.instance.ami.id = Some("ami-11111111".to_string());
+
+        apply_ami_override(&mut profile, Some("ami-0123456789abcdef0".to_string())).unwrap();
+        assert_eq!(
+            profile.instance.ami.id.as_deref(),
+            Some("ami-0123456789abcdef0")
+        );
+
+        // Without an override the profile's AMI is kept
+        apply_ami_override(&mut profile, None).unwrap();
+        assert_eq!(
+            profile.instance.ami.id.as_deref(),
+            Some("ami-0123456789abcdef0")
+        );
+    }
+
+    #[test]
+    fn test_ami_override_replaces_type_lookup() {
+        let mut profile = Profile::default_profile();
+        assert!(profile.instance.ami.id.is_none());
+
+        apply_ami_override(&mut profile, Some("ami-12345678".to_string())).unwrap();
+        assert_eq!(profile.instance.ami.id.as_deref(), Some("ami-12345678"));
+
+        assert!(apply_ami_override(&mut profile, Some("ubuntu-24.04".to_string())).is_err());
+        assert!(apply_ami_override(&mut profile, Some("ami-xyz".to_string())).is_err());
+    }
+
+    #[test]
+    fn test_launch_tags_merge_with_settings() {
+        let mut settings = Settings::default();
+        settings.set_tag("Username", "alice").unwrap();
+        settings.set_tag("Team", "infra").unwrap();
+
+        let tags = launch_tags(
+            &settings,
+            &["Ticket=OPS-123".to_string(), "Team=ml".to_string()],
+        )
+        .unwrap();
+        assert_eq!(tags.len(), 3);
+        assert_eq!(tags["Username"], "alice");
+        assert_eq!(tags["Ticket"], "OPS-123");
+        // A launch tag wins over the settings tag with the same key
+        assert_eq!(tags["Team"], "ml");
+        // Settings themselves are unchanged
+        assert_eq!(settings.tags["Team"], "infra");
+
+        // Values may be empty or contain '='
+        let tags = launch_tags(&settings, &["Empty=".to_string(), "Expr=a=b".to_string()]).unwrap();
+        assert_eq!(tags["Empty"], "");
+        assert_eq!(tags["Expr"], "a=b");
+
+        assert_eq!(launch_tags(&settings, &[]).unwrap(), settings.tags);
+    }
+
+    #[test]
+    fn test_launch_tags_rejects_invalid() {
+        let settings = Settings::default();
+        for tag in [
+            "Ticket",
+            "=value",
+            "aws:x=1",
+            "Name=mine",
+            "ec2-cli:managed=false",
+        ] {
+            assert!(
+                launch_tags(&settings, &[tag.to_string()]).is_err(),
+                "{}",
+                tag
+            );
+        }
+    }
+
+    #[test]
+    fn test_instance_type_override_takes_precedence() {
+        let mut profile = Profile::default_profile();
+        assert!(!profile.instance.fallback_types.is_empty());
+
+        apply_instance_type_override(&mut profile, Some("c5.2xlarge".to_string())).unwrap();
+        assert_eq!(profile.instance.instance_type, "c5.2xlarge");
+        assert!(profile.instance.fallback_types.is_empty());
+
+        // Without an override the profile's type is kept
+        let mut profile = Profile::default_profile();
+        let expected = profile.instance.clone();
+        apply_instance_type_override(&mut profile, None).unwrap();
+        assert_eq!(profile.instance, expected);
+    }
+
+    #[test]
+    fn test_instance_type_override_checks_architecture() {
+        let mut profile = Profile::default_profile();
+        let result = apply_instance_type_override(&mut profile, Some("m7g.large".to_string()));
+        assert!(matches!(result, Err(Ec2CliError::InvalidInstanceType(_))));
+
+        profile.instance.ami.architecture = "arm64".to_string();
+        let result = apply_instance_type_override(&mut profile, Some("m5.large".to_string()));
+        assert!(
+            matches!(result, Err(Ec2CliError::InvalidInstanceType(msg)) if msg.contains("m6g.large"))
+        );
+        apply_instance_type_override(&mut profile, Some("m7g.large".to_string())).unwrap();
+        assert_eq!(profile.instance.instance_type, "m7g.large");
+
+        assert!(apply_instance_type_override(&mut profile, Some("large".to_string())).is_err());
+    }
+
+    #[test]
+    fn test_generated_name_used_when_valid() {
+        assert_eq!(
+            generate_instance_name_with(|| Some("brave-otter".to_string())),
+            "brave-otter"
+        );
+    }
+
+    #[test]
+    fn test_invalid_generated_name_regenerated() {
+        let mut names = vec!["brave-otter", "-bad name"].into_iter().rev();
+        let name = generate_instance_name_with(|| names.next().map(String::from));
+        assert_eq!(name, "brave-otter");
+    }
+
+    #[test]
+    fn test_fallback_names_are_unique() {
+        let first = generate_instance_name_with(|| None);
+        let second = generate_instance_name_with(|| None);
+
+        assert!(first.starts_with("ec2-instance-"));
+        assert!(validate_project_name(&first).is_ok());
+        assert_ne!(first, second);
+
+        // Persistently invalid output also falls back
+        let fallback = generate_instance_name_with(|| Some("not valid!".to_string()));
+        assert!(fallback.starts_with("ec2-instance-"));
+    }
+
+    fn managed(id: &str, name: &str, state: InstanceStateName) -> ManagedInstance {
+        ManagedInstance {
+            instance_id: id.to_string(),
+            name: Some(name.to_string()),
+            state,
+            security_group_id: Some("sg-1".to_string()),
+            owner: None,
+            vpc_id: None,
+            subnet_id: None,
+        }
+    }
+
+    fn entry(id: &str, region: &str) -> InstanceState {
+        let mut state = State::default();
+        state.add_instance("dev", id, "default", region, "ubuntu", "sg-1", None);
+        state.instances.remove("dev").unwrap()
+    }
+
+    #[test]
+    fn test_plan_launch_without_existing_instance() {
+        let other = [managed("i-2", "other", InstanceStateName::Running)];
+        assert!(matches!(
+            plan_launch("dev", None, "us-east-1", &other).unwrap(),
+            LaunchPlan::Launch
+        ));
+
+        // Terminated instances and stale entries don't block a new launch
+        let gone = [managed("i-1", "dev", InstanceStateName::Terminated)];
+        let stale = entry("i-1", "us-east-1");
+        assert!(matches!(
+            plan_launch("dev", Some(&stale), "us-east-1", &gone).unwrap(),
+            LaunchPlan::Launch
+        ));
+
+        // An entry in another region can't be checked, so it isn't replaced
+        let elsewhere = entry("i-1", "eu-west-1");
+        assert!(plan_launch("dev", Some(&elsewhere), "us-east-1", &[]).is_err());
+    }
+
+    #[test]
+    fn test_plan_launch_resumes_interrupted_launch() {
+        // Interrupted before state was saved
+        for state in [InstanceStateName::Pending, InstanceStateName::Running] {
+            let found = [managed("i-1", "dev", state)];
+            match plan_launch("dev", None, "us-east-1", &found).unwrap() {
+                LaunchPlan::Resume(instance) => assert_eq!(instance.instance_id, "i-1"),
+                plan => panic!("expected resume, got {:?}", plan),
+            }
+        }
+
+        // Saved to state but still starting
+        let found = [managed("i-1", "dev", InstanceStateName::Pending)];
+        let tracked = entry("i-1", "us-east-1");
+        assert!(matches!(
+            plan_launch("dev", Some(&tracked), "us-east-1", &found).unwrap(),
+            LaunchPlan::Resume(_)
+        ));
+    }
+
+    #[test]
+    fn test_plan_launch_rejects_name_in_use() {
+        let tracked = entry("i-1", "us-east-1");
+
+        let running = [managed("i-1", "dev", InstanceStateName::Running)];
+        let err = plan_launch("dev", Some(&tracked), "us-east-1", &running).unwrap_err();
+        assert!(matches!(err, Ec2CliError::InstanceNameExists(_)));
+
+        let stopped = [managed("i-1", "dev", InstanceStateName::Stopped)];
+        assert!(plan_launch("dev", None, "us-east-1", &stopped).is_err());
+
+        let duplicates = [
+            managed("i-1", "dev", InstanceStateName::Pending),
+            managed("i-2", "dev", InstanceStateName::Running),
+        ];
+        let err = plan_launch("dev", None, "us-east-1", &duplicates).unwrap_err();
+        assert!(matches!(err, Ec2CliError::AmbiguousInstanceName(_)));
+        assert!(err.to_string().contains("i-1 (us-east-1), i-2 (us-east-1)"));
+    }
+
+    /// A writer that keeps what `Progress` writes so it can be checked
+    #[derive(Clone, Default)]
+    struct Captured(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Captured {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Captured {
+        fn lines(&self) -> Vec<String> {
+            String::from_utf8(self.0.lock().unwrap().clone())
+                .unwrap()
+                .lines()
+                .map(String::from)
+                .collect()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_json_events_emitted_in_order() {
+        let out = Captured::default();
+        let mut progress = Progress::json_to(out.clone());
+        let waits = RefCell::new(Vec::new());
+
+        progress
+            .event(&UpEvent::Launched {
+                instance_id: "i-123",
+            })
+            .unwrap();
+        wait_until_ready(&mut progress, "i-123", true, |step| {
+            waits.borrow_mut().push(step);
+            async { Ok(()) }
+        })
+        .await
+        .unwrap();
+        progress
+            .event(&UpEvent::Ready {
+                name: "dev",
+                instance_id: "i-123",
+            })
+            .unwrap();
+
+        assert_eq!(
+            waits.into_inner(),
+            vec![SetupStep::Running, SetupStep::SsmReady, SetupStep::GitReady]
+        );
+        assert_eq!(
+            out.lines(),
+            vec![
+                r#"{"event":"launched","instance_id":"i-123"}"#,
+                r#"{"event":"running","instance_id":"i-123"}"#,
+                r#"{"event":"ssm_ready","instance_id":"i-123"}"#,
+                r#"{"event":"git_ready","instance_id":"i-123"}"#,
+                r#"{"event":"ready","name":"dev","instance_id":"i-123"}"#,
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_failed_wait_stops_the_event_stream() {
+        let out = Captured::default();
+        let mut progress = Progress::json_to(out.clone());
+
+        let result = wait_until_ready(&mut progress, "i-123", false, |step| async move {
+            match step {
+                SetupStep::SsmReady => Err(Ec2CliError::Timeout("SSM agent".to_string())),
+                _ => Ok(()),
+            }
+        })
+        .await;
+
+        assert!(matches!(result, Err(Ec2CliError::Timeout(_))));
+        assert_eq!(
+            out.lines(),
+            vec![r#"{"event":"running","instance_id":"i-123"}"#]
+        );
+    }
+}
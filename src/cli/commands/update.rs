@@ -0,0 +1,203 @@
+use std::path::PathBuf;
+use std::process::Command;
+
+use chrono::{DateTime, Duration, Utc};
+use semver::Version;
+use serde::{Deserialize, Serialize};
+
+use crate::config::Settings;
+use crate::ui::is_quiet;
+use crate::{Ec2CliError, Result};
+
+/// GitHub API endpoint for the newest published release
+const LATEST_RELEASE_URL: &str = "https://api.github.com/repos/pedrocarlo/ec2-cli/releases/latest";
+
+/// Where new releases are installed from
+const INSTALL_COMMAND: &str = "cargo install --git https://github.com/pedrocarlo/ec2-cli";
+
+/// File in the config directory caching the last release check
+const UPDATE_CHECK_FILE: &str = "update_check.json";
+
+/// Hours between background checks when `update_check` is enabled
+const CHECK_INTERVAL_HOURS: i64 = 24;
+
+/// Seconds to wait for GitHub; the background check gives up sooner
+const CHECK_TIMEOUT_SECS: u32 = 10;
+const BACKGROUND_CHECK_TIMEOUT_SECS: u32 = 3;
+
+/// The result of the last release check
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct UpdateCheck {
+    checked_at: DateTime<Utc>,
+    /// None if GitHub couldn't be asked (offline, rate limited, no releases yet)
+    #[serde(default)]
+    latest_version: Option<String>,
+}
+
+/// Check GitHub for a newer release and print how to upgrade
+pub fn execute() -> Result<()> {
+    let latest = fetch_latest_version(CHECK_TIMEOUT_SECS).ok_or_else(|| {
+        Ec2CliError::Other("Could not get the latest release from GitHub".to_string())
+    })?;
+    save_check(Some(&latest));
+
+    let current = env!("CARGO_PKG_VERSION");
+    match newer_version(current, &latest) {
+        Some(latest) => {
+            println!("ec2-cli {} is available (you have {}).", latest, current);
+            println!("Upgrade with: {}", INSTALL_COMMAND);
+        }
+        None => println!("ec2-cli {} is up to date.", current),
+    }
+    Ok(())
+}
+
+/// With `update_check` enabled, print an upgrade hint when a newer release exists.
+/// GitHub is asked at most once a day, failed attempts included; any failure is
+/// ignored, so this never breaks or noticeably slows the command that ran.
+pub fn notify_if_outdated() {
+    if is_quiet() || !Settings::load().is_ok_and(|settings| settings.update_check) {
+        return;
+    }
+
+    let latest = match load_check() {
+        Some(check) if is_fresh(&check, Utc::now()) => check.latest_version,
+        _ => {
+            let latest = fetch_latest_version(BACKGROUND_CHECK_TIMEOUT_SECS);
+            save_check(latest.as_deref());
+            latest
+        }
+    };
+    let Some(latest) = latest else {
+        return;
+    };
+
+    if let Some(latest) = newer_version(env!("CARGO_PKG_VERSION"), &latest) {
+        eprintln!(
+            "Note: ec2-cli {} is available; upgrade with: {}",
+            latest, INSTALL_COMMAND
+        );
+    }
+}
+
+/// The latest release's version if it's newer than `current`. Tags may have a
+/// leading "v"; anything that isn't semver is never reported as newer.
+fn newer_version(current: &str, latest: &str) -> Option<Version> {
+    let parse = |version: &str| Version::parse(version.trim().trim_start_matches('v')).ok();
+    let (current, latest) = (parse(current)?, parse(latest)?);
+    (latest > current).then_some(latest)
+}
+
+/// Whether a cached check is recent enough to skip asking GitHub again
+fn is_fresh(check: &UpdateCheck, now: DateTime<Utc>) -> bool {
+    let age = now - check.checked_at;
+    age >= Duration::zero() && age < Duration::hours(CHECK_INTERVAL_HOURS)
+}
+
+/// Ask GitHub for the latest release tag with curl, or None if that fails for any
+/// reason (offline, rate limited, curl missing)
+fn fetch_latest_version(timeout_secs: u32) -> Option<String> {
+    let output = Command::new("curl")
+        .args(["-fsSL", "--max-time", &timeout_secs.to_string()])
+        .args(["-H", "Accept: application/vnd.github+json"])
+        .arg(LATEST_RELEASE_URL)
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+
+    let release: serde_json::Value = serde_json::from_slice(&output.stdout).ok()?;
+    release["tag_name"].as_str().map(String::from)
+}
+
+fn check_path() -> Option<PathBuf> {
+    Settings::config_path().map(|path| path.with_file_name(UPDATE_CHECK_FILE))
+}
+
+fn load_check() -> Option<UpdateCheck> {
+    let content = std::fs::read_to_string(check_path()?).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+/// Cache a check result, None for a check that failed, so neither is repeated
+/// until the interval passes. Failing to write it only means asking GitHub again
+fn save_check(latest_version: Option<&str>) {
+    let check = UpdateCheck {
+        checked_at: Utc::now(),
+        latest_version: latest_version.map(String::from),
+    };
+    if let (Some(path), Ok(content)) = (check_path(), serde_json::to_string_pretty(&check)) {
+        let _ = path
+            .parent()
+            .map(std::fs::create_dir_all)
+            .transpose()
+            .and_then(|_| std::fs::write(&path, content));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_newer_version() {
+        assert_eq!(
+            newer_version("0.1.0", "v0.2.0"),
+            Some(Version::new(0, 2, 0))
+        );
+        assert_eq!(
+            newer_version("0.1.9", "0.1.10"),
+            Some(Version::new(0, 1, 10))
+        );
+        assert_eq!(newer_version("0.2.0", "v0.2.0"), None);
+        assert_eq!(newer_version("0.3.0", "v0.2.0"), None);
+
+        // A pre-release sorts before its release
+        assert_eq!(newer_version("0.2.0", "v0.2.0-rc.1"), None);
+        assert!(newer_version("0.2.0-rc.1", "v0.2.0").is_some());
+
+        // Tags that aren't versions are ignored
+        assert_eq!(newer_version("0.1.0", "nightly"), None);
+        assert_eq!(newer_version("0.1.0", ""), None);
+    }
+
+    #[test]
+    fn test_cached_check_freshness() {
+        let now = Utc::now();
+        let check = |hours_ago: i64| UpdateCheck {
+            checked_at: now - Duration::hours(hours_ago),
+            latest_version: Some("v0.2.0".to_string()),
+        };
+
+        assert!(is_fresh(&check(0), now));
+        assert!(is_fresh(&check(CHECK_INTERVAL_HOURS - 1), now));
+        assert!(!is_fresh(&check(CHECK_INTERVAL_HOURS), now));
+        // A check "from the future" (clock changes) is redone
+        assert!(!is_fresh(&check(-1), now));
+    }
+
+    #[test]
+    fn test_failed_check_is_cached() {
+        let now = Utc::now();
+        let failed = |hours_ago: i64| UpdateCheck {
+            checked_at: now - Duration::hours(hours_ago),
+            latest_version: None,
+        };
+
+        // A failed check waits out the interval like a successful one
+        assert!(is_fresh(&failed(1), now));
+        assert!(!is_fresh(&failed(CHECK_INTERVAL_HOURS), now));
+
+        let saved = serde_json::to_string(&failed(1)).unwrap();
+        let loaded: UpdateCheck = serde_json::from_str(&saved).unwrap();
+        assert_eq!(loaded, failed(1));
+
+        // Files written before failures were cached still load
+        let old: UpdateCheck = serde_json::from_str(
+            r#"{"checked_at":"2024-01-01T00:00:00Z","latest_version":"v0.2.0"}"#,
+        )
+        .unwrap();
+        assert_eq!(old.latest_version.as_deref(), Some("v0.2.0"));
+    }
+}
@@ -0,0 +1,20 @@
+use aws_config::BehaviorVersion;
+
+/// Print the version, or with `verbose` the build details useful in bug reports
+pub fn execute(verbose: bool) {
+    println!("ec2-cli {}", env!("CARGO_PKG_VERSION"));
+
+    if !verbose {
+        return;
+    }
+
+    println!("  Commit: {}", env!("EC2_CLI_GIT_COMMIT"));
+    println!("  Target: {}", env!("EC2_CLI_TARGET"));
+    println!(
+        "  Platform: {} ({})",
+        std::env::consts::OS,
+        std::env::consts::ARCH
+    );
+    println!("  AWS SDK: aws-sdk-ec2 {}", env!("EC2_CLI_AWS_SDK_VERSION"));
+    println!("  AWS behavior version: {:?}", BehaviorVersion::latest());
+}
@@ -0,0 +1,46 @@
+use crate::aws::client::AwsClients;
+use crate::aws::ec2::instance::list_managed_instances;
+use crate::aws::infrastructure::iam_resources_exist;
+use crate::state::list_instances;
+use crate::ui::create_spinner;
+use crate::Result;
+
+/// Print the resolved AWS identity and a summary of managed resources
+pub async fn execute() -> Result<()> {
+    let spinner = create_spinner("Connecting to AWS...");
+    let clients = AwsClients::new().await?;
+    spinner.finish_and_clear();
+
+    println!("AWS identity:");
+    println!("  Account: {}", clients.account_id);
+    println!("  ARN: {}", clients.caller_arn);
+    println!("  Region: {}", clients.region);
+
+    let spinner = create_spinner("Checking managed resources...");
+    let managed = list_managed_instances(&clients).await?;
+    let infra_exists = iam_resources_exist(&clients).await?;
+    spinner.finish_and_clear();
+
+    let active = managed.iter().filter(|i| !i.is_terminated()).count();
+    let tracked = list_instances()?
+        .values()
+        .filter(|i| i.region == clients.region)
+        .count();
+
+    println!();
+    println!("Managed resources in {}:", clients.region);
+    println!(
+        "  Instances: {} active ({} in local state)",
+        active, tracked
+    );
+    println!(
+        "  IAM role and instance profile: {}",
+        if infra_exists {
+            "present"
+        } else {
+            "not created yet (created on first 'ec2-cli up')"
+        }
+    );
+
+    Ok(())
+}
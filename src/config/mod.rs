@@ -1,3 +1,3 @@
 mod settings;
 
-pub use settings::Settings;
+pub use settings::{NetworkMode, Settings};
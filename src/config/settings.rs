@@ -2,13 +2,42 @@ use directories::ProjectDirs;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::io::Write;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 #[cfg(unix)]
 use std::os::unix::fs::OpenOptionsExt;
 
+use crate::aws::client::{OWNER_TAG_KEY, RESERVED_TAG_KEYS};
 use crate::{Ec2CliError, Result};
 
+/// Maximum number of tags AWS allows on a single resource
+const AWS_MAX_TAGS: usize = 50;
+
+/// Custom tags that fit alongside the tags ec2-cli always sets
+const MAX_CUSTOM_TAGS: usize = AWS_MAX_TAGS - RESERVED_TAG_KEYS.len();
+
+/// Longest resource prefix that keeps IAM role and instance profile names
+/// within IAM's 64-character limit
+const MAX_RESOURCE_PREFIX_LEN: usize = 32;
+
+/// Where `up` gets the network it launches into
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NetworkMode {
+    /// The configured VPC, or the account's default VPC when none is set
+    #[default]
+    Managed,
+    /// Only the configured VPC and subnet (bring your own network), e.g. a
+    /// corporate VPC with its own SSM endpoints; nothing falls back to defaults
+    Byo,
+}
+
+impl NetworkMode {
+    fn is_managed(&self) -> bool {
+        *self == NetworkMode::Managed
+    }
+}
+
 /// Global settings for ec2-cli
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct Settings {
@@ -28,6 +57,55 @@ pub struct Settings {
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub subnet_id: Option<String>,
 
+    /// Whether the VPC may fall back to the default VPC ("managed") or must be
+    /// configured ("byo")
+    #[serde(default, skip_serializing_if = "NetworkMode::is_managed")]
+    pub network_mode: NetworkMode,
+
+    /// Named AWS CLI profile to use for credentials and default region, regardless
+    /// of AWS_PROFILE (None = the default provider chain)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub aws_profile: Option<String>,
+
+    /// Start SSM sessions with the AWS SDK and session-manager-plugin directly,
+    /// instead of going through `aws ssm start-session`
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub native_ssm_proxy: bool,
+
+    /// Seconds ssh and scp wait for a connection, including SSM session setup
+    /// (None = 30)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ssh_connect_timeout: Option<u64>,
+
+    /// Seconds between keepalive probes on ssh and scp connections; a connection
+    /// is dropped after several unanswered probes (None = 15)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ssh_keepalive_interval: Option<u64>,
+
+    /// Prefix for the names and `deployment` tag of managed resources, so teams
+    /// sharing an account can keep separate fleets (None = "ec2-cli")
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resource_prefix: Option<String>,
+
+    /// Where the user data script on new instances writes its log, and so where
+    /// `logs` reads it (None = /var/log/ec2-cli-init.log)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub init_log_path: Option<String>,
+
+    /// Restrict bulk operations (`state repair`) and `destroy` to instances whose
+    /// Username tag matches the configured one, unless `--all-users` is passed
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub enforce_owner: bool,
+
+    /// Baseline for every profile's `instance` section (e.g. a volume size);
+    /// fields a profile sets itself win
+    #[serde(default, skip_serializing_if = "serde_json::Map::is_empty")]
+    pub instance_defaults: serde_json::Map<String, serde_json::Value>,
+
+    /// Check GitHub once a day, after commands, for a newer ec2-cli release
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub update_check: bool,
+
     /// Whether the manual hint has been shown
     #[serde(default, skip_serializing_if = "std::ops::Not::not")]
     pub manual_hint_shown: bool,
@@ -39,6 +117,15 @@ impl Settings {
         ProjectDirs::from("", "", "ec2-cli").map(|dirs| dirs.config_dir().join("config.json"))
     }
 
+    /// The AWS profile credentials come from: the `aws_profile` setting, else
+    /// AWS_PROFILE (None = the default profile)
+    pub fn effective_aws_profile(&self) -> Option<String> {
+        self.aws_profile
+            .clone()
+            .or_else(|| std::env::var("AWS_PROFILE").ok())
+            .filter(|profile| !profile.is_empty())
+    }
+
     /// Load settings from the config file
     pub fn load() -> Result<Self> {
         let path = Self::config_path()
@@ -55,6 +142,28 @@ impl Settings {
         Ok(settings)
     }
 
+    /// Back up the config file to `config.json.bak` and return default settings to
+    /// configure from scratch, with the backup's path if there was a file to back up.
+    /// Profiles and state live in other files and are left alone.
+    pub fn reset() -> Result<(Self, Option<PathBuf>)> {
+        let path = Self::config_path()
+            .ok_or_else(|| Ec2CliError::Config("Cannot determine config directory".to_string()))?;
+        Self::reset_at(&path)
+    }
+
+    fn reset_at(path: &Path) -> Result<(Self, Option<PathBuf>)> {
+        if !path.exists() {
+            return Ok((Self::default(), None));
+        }
+
+        // A copy keeps the 0600 permissions of the original
+        let mut backup = path.as_os_str().to_owned();
+        backup.push(".bak");
+        let backup = PathBuf::from(backup);
+        std::fs::copy(path, &backup)?;
+        Ok((Self::default(), Some(backup)))
+    }
+
     /// Save settings to the config file with restricted permissions (0600)
     pub fn save(&self) -> Result<()> {
         let path = Self::config_path()
@@ -131,10 +240,24 @@ impl Settings {
         Ok(())
     }
 
-    /// Set a tag (validates key and value)
+    /// Set a tag (validates key and value, and that the tag fits within AWS's limit)
     pub fn set_tag(&mut self, key: &str, value: &str) -> Result<()> {
         Self::validate_tag_key(key)?;
         Self::validate_tag_value(value)?;
+        if RESERVED_TAG_KEYS.contains(&key) {
+            return Err(Ec2CliError::Config(format!(
+                "Tag key '{}' is reserved by ec2-cli",
+                key
+            )));
+        }
+        if !self.tags.contains_key(key) && self.tags.len() >= MAX_CUSTOM_TAGS {
+            return Err(Ec2CliError::Config(format!(
+                "Cannot add more than {} custom tags (AWS allows {} tags per resource and ec2-cli reserves {})",
+                MAX_CUSTOM_TAGS,
+                AWS_MAX_TAGS,
+                RESERVED_TAG_KEYS.len()
+            )));
+        }
         self.tags.insert(key.to_string(), value.to_string());
         Ok(())
     }
@@ -146,7 +269,26 @@ impl Settings {
 
     /// Check if Username tag is configured
     pub fn has_username_tag(&self) -> bool {
-        self.tags.contains_key("Username")
+        self.tags.contains_key(OWNER_TAG_KEY)
+    }
+
+    /// The owner to restrict instances to, or None when ownership isn't enforced
+    /// (or `all_users` overrides it)
+    pub fn owner_filter(&self, all_users: bool) -> Result<Option<&str>> {
+        if !self.enforce_owner || all_users {
+            return Ok(None);
+        }
+
+        self.tags
+            .get(OWNER_TAG_KEY)
+            .map(|owner| Some(owner.as_str()))
+            .ok_or_else(|| {
+                Ec2CliError::Config(
+                    "enforce_owner is enabled but no Username tag is configured. \
+                     Run 'ec2-cli config tags set Username <name>'"
+                        .to_string(),
+                )
+            })
     }
 
     /// Mark the manual hint as shown and persist to config
@@ -196,6 +338,107 @@ impl Settings {
         }
         Ok(())
     }
+
+    /// Validate a resource prefix: lowercase letters, digits, and hyphens, starting
+    /// with a letter and not ending with a hyphen, so it is safe in every AWS name
+    pub fn validate_resource_prefix(prefix: &str) -> Result<()> {
+        let invalid = |reason: &str| {
+            Err(Ec2CliError::Config(format!(
+                "Invalid resource_prefix '{}': {}",
+                prefix, reason
+            )))
+        };
+
+        if prefix.is_empty() || prefix.len() > MAX_RESOURCE_PREFIX_LEN {
+            return invalid(&format!(
+                "must be 1 to {} characters",
+                MAX_RESOURCE_PREFIX_LEN
+            ));
+        }
+        if !prefix
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+        {
+            return invalid("only lowercase letters, digits, and hyphens are allowed");
+        }
+        if !prefix.starts_with(|c: char| c.is_ascii_lowercase()) || prefix.ends_with('-') {
+            return invalid("must start with a letter and not end with a hyphen");
+        }
+        // Security group names starting with sg- are rejected by EC2
+        if prefix.starts_with("sg-") || prefix.starts_with("aws") {
+            return invalid("must not start with 'sg-' or 'aws'");
+        }
+        Ok(())
+    }
+
+    /// Validate an init log path: absolute, made of letters, digits, `/`, `.`, `_`,
+    /// and `-` (it is used unquoted in shell commands), without `..` components
+    pub fn validate_init_log_path(path: &str) -> Result<()> {
+        let invalid = |reason: &str| {
+            Err(Ec2CliError::Config(format!(
+                "Invalid init_log_path '{}': {}",
+                path, reason
+            )))
+        };
+
+        if !path.starts_with('/') || path.ends_with('/') {
+            return invalid("must be an absolute path to a file");
+        }
+        if !path
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '/' | '.' | '_' | '-'))
+        {
+            return invalid("only letters, digits, '/', '.', '_', and '-' are allowed");
+        }
+        if path.split('/').any(|part| part == "..") {
+            return invalid("must not contain '..'");
+        }
+        Ok(())
+    }
+
+    /// The VPC and subnet to launch into. The VPC is None when the default VPC
+    /// should be used, which bring-your-own network mode never allows.
+    pub fn launch_network(&self) -> Result<(Option<&str>, &str)> {
+        let subnet_id = self
+            .subnet_id
+            .as_deref()
+            .ok_or(Ec2CliError::SubnetNotConfigured)?;
+
+        match (self.network_mode, self.vpc_id.as_deref()) {
+            (NetworkMode::Byo, None) => Err(Ec2CliError::Config(
+                "network_mode is \"byo\" but no VPC is configured; set it with 'ec2-cli config set-vpc'"
+                    .to_string(),
+            )),
+            (_, vpc_id) => Ok((vpc_id, subnet_id)),
+        }
+    }
+
+    /// The value to store as `vpc_id` for a chosen VPC: None for the default VPC,
+    /// except in bring-your-own mode, which only launches into a configured one
+    pub fn vpc_setting(&self, vpc_id: String, default_vpc_id: Option<&String>) -> Option<String> {
+        if self.network_mode == NetworkMode::Managed && Some(&vpc_id) == default_vpc_id {
+            None
+        } else {
+            Some(vpc_id)
+        }
+    }
+
+    /// Validate subnet ID format (subnet-xxxxxxxx or subnet-xxxxxxxxxxxxxxxxx)
+    pub fn validate_subnet_id(subnet_id: &str) -> Result<()> {
+        let suffix = subnet_id.strip_prefix("subnet-").ok_or_else(|| {
+            Ec2CliError::Config(format!(
+                "Invalid subnet ID format: '{}'. Must start with 'subnet-'",
+                subnet_id
+            ))
+        })?;
+        if suffix.len() < 8 || !suffix.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(Ec2CliError::Config(format!(
+                "Invalid subnet ID format: '{}'. Expected format like 'subnet-12345678'",
+                subnet_id
+            )));
+        }
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -237,6 +480,80 @@ mod tests {
         assert_eq!(settings.tags.get("Username"), Some(&"testuser".to_string()));
     }
 
+    #[test]
+    fn test_validate_resource_prefix() {
+        for prefix in ["ec2-cli", "team-a", "ml2", "a"] {
+            assert!(
+                Settings::validate_resource_prefix(prefix).is_ok(),
+                "{}",
+                prefix
+            );
+        }
+        for prefix in [
+            "",
+            "Team-A",
+            "team_a",
+            "team a",
+            "-team",
+            "team-",
+            "2team",
+            "sg-team",
+            "aws-team",
+            "a".repeat(33).as_str(),
+        ] {
+            assert!(
+                Settings::validate_resource_prefix(prefix).is_err(),
+                "{}",
+                prefix
+            );
+        }
+    }
+
+    #[test]
+    fn test_validate_init_log_path() {
+        for path in [
+            "/var/log/ec2-cli-init.log",
+            "/home/ubuntu/init_log.txt",
+            "/x",
+        ] {
+            assert!(Settings::validate_init_log_path(path).is_ok(), "{}", path);
+        }
+        for path in [
+            "",
+            "init.log",
+            "/var/log/",
+            "/var/log/../../etc/passwd",
+            "/var/log/my log",
+            "/var/log/$(id).log",
+            "~/init.log",
+        ] {
+            assert!(Settings::validate_init_log_path(path).is_err(), "{}", path);
+        }
+    }
+
+    #[test]
+    fn test_set_tag_rejects_reserved_keys() {
+        let mut settings = Settings::default();
+        for key in ["ec2-cli:managed", "ec2-cli:name", "Name", "deployment"] {
+            assert!(settings.set_tag(key, "value").is_err());
+        }
+        assert!(settings.tags.is_empty());
+    }
+
+    #[test]
+    fn test_set_tag_count_limit() {
+        let mut settings = Settings::default();
+        for i in 0..MAX_CUSTOM_TAGS {
+            settings.set_tag(&format!("tag-{}", i), "value").unwrap();
+        }
+        assert_eq!(MAX_CUSTOM_TAGS, 46);
+        assert!(settings.set_tag("one-too-many", "value").is_err());
+
+        // Updating an existing tag doesn't count against the limit
+        assert!(settings.set_tag("tag-0", "updated").is_ok());
+        assert_eq!(settings.tags.get("tag-0"), Some(&"updated".to_string()));
+    }
+
     #[test]
     fn test_remove_tag() {
         let mut settings = Settings::default();
@@ -248,6 +565,15 @@ mod tests {
         assert!(!settings.tags.contains_key("Username"));
     }
 
+    #[test]
+    fn test_validate_subnet_id() {
+        assert!(Settings::validate_subnet_id("subnet-12345678").is_ok());
+        assert!(Settings::validate_subnet_id("subnet-0123456789abcdef0").is_ok());
+        assert!(Settings::validate_subnet_id("vpc-12345678").is_err());
+        assert!(Settings::validate_subnet_id("subnet-123").is_err());
+        assert!(Settings::validate_subnet_id("subnet-1234567g").is_err());
+    }
+
     #[test]
     fn test_has_username_tag() {
         let mut settings = Settings::default();
@@ -257,4 +583,121 @@ mod tests {
             .insert("Username".to_string(), "testuser".to_string());
         assert!(settings.has_username_tag());
     }
+
+    #[test]
+    fn test_owner_filter() {
+        let mut settings = Settings::default();
+        assert_eq!(settings.owner_filter(false).unwrap(), None);
+
+        // Enforcing ownership needs a Username tag to compare against
+        settings.enforce_owner = true;
+        assert!(settings.owner_filter(false).is_err());
+        assert_eq!(settings.owner_filter(true).unwrap(), None);
+
+        settings
+            .tags
+            .insert("Username".to_string(), "alice".to_string());
+        assert_eq!(settings.owner_filter(false).unwrap(), Some("alice"));
+        assert_eq!(settings.owner_filter(true).unwrap(), None);
+    }
+
+    #[test]
+    fn test_launch_network_by_mode() {
+        let mut settings = Settings {
+            subnet_id: Some("subnet-12345678".to_string()),
+            ..Default::default()
+        };
+
+        // Managed mode falls back to the default VPC
+        assert_eq!(
+            settings.launch_network().unwrap(),
+            (None, "subnet-12345678")
+        );
+
+        // Bring-your-own mode never does
+        settings.network_mode = NetworkMode::Byo;
+        assert!(matches!(
+            settings.launch_network(),
+            Err(Ec2CliError::Config(msg)) if msg.contains("byo")
+        ));
+        settings.vpc_id = Some("vpc-12345678".to_string());
+        assert_eq!(
+            settings.launch_network().unwrap(),
+            (Some("vpc-12345678"), "subnet-12345678")
+        );
+
+        // A subnet is needed either way
+        settings.subnet_id = None;
+        assert!(matches!(
+            settings.launch_network(),
+            Err(Ec2CliError::SubnetNotConfigured)
+        ));
+    }
+
+    #[test]
+    fn test_vpc_setting_keeps_byo_vpc_explicit() {
+        let default_vpc = "vpc-default1".to_string();
+        let mut settings = Settings::default();
+        assert_eq!(
+            settings.vpc_setting(default_vpc.clone(), Some(&default_vpc)),
+            None
+        );
+        assert_eq!(
+            settings.vpc_setting("vpc-other123".to_string(), Some(&default_vpc)),
+            Some("vpc-other123".to_string())
+        );
+
+        settings.network_mode = NetworkMode::Byo;
+        assert_eq!(
+            settings.vpc_setting(default_vpc.clone(), Some(&default_vpc)),
+            Some(default_vpc)
+        );
+    }
+
+    #[test]
+    fn test_network_mode_serialization() {
+        let settings: Settings = serde_json::from_str("{}").unwrap();
+        assert_eq!(settings.network_mode, NetworkMode::Managed);
+        assert!(!serde_json::to_string(&settings)
+            .unwrap()
+            .contains("network_mode"));
+
+        let settings: Settings = serde_json::from_str(r#"{"network_mode": "byo"}"#).unwrap();
+        assert_eq!(settings.network_mode, NetworkMode::Byo);
+        assert!(serde_json::from_str::<Settings>(r#"{"network_mode": "custom"}"#).is_err());
+    }
+
+    #[test]
+    fn test_reset_backs_up_config() {
+        let dir = std::env::temp_dir().join(format!("ec2-cli-reset-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("config.json");
+        let _ = std::fs::remove_file(&path);
+
+        // Nothing to back up on a fresh install
+        let (settings, backup) = Settings::reset_at(&path).unwrap();
+        assert!(backup.is_none());
+        assert!(settings.region.is_none());
+
+        let configured = Settings {
+            region: Some("eu-west-1".to_string()),
+            subnet_id: Some("subnet-0123456789abcdef0".to_string()),
+            ..Default::default()
+        };
+        let content = serde_json::to_string_pretty(&configured).unwrap();
+        std::fs::write(&path, &content).unwrap();
+
+        let (settings, backup) = Settings::reset_at(&path).unwrap();
+        let backup = backup.unwrap();
+        assert_eq!(backup, dir.join("config.json.bak"));
+        assert_eq!(std::fs::read_to_string(&backup).unwrap(), content);
+        // The config itself is only replaced when init saves
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), content);
+        assert_eq!(
+            serde_json::to_value(&settings).unwrap(),
+            serde_json::to_value(Settings::default()).unwrap()
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
 }
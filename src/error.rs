@@ -1,19 +1,22 @@
 use thiserror::Error;
 
+/// The underlying cause carried by AWS error variants
+pub type BoxError = Box<dyn std::error::Error + Send + Sync + 'static>;
+
 #[derive(Error, Debug)]
 pub enum Ec2CliError {
-    // AWS Errors
+    // AWS Errors (the message is summarized; the original error, if any, is the source)
     #[error("AWS SDK error: {0}")]
-    AwsSdk(String),
+    AwsSdk(String, #[source] Option<BoxError>),
 
     #[error("AWS EC2 error: {0}")]
-    Ec2(String),
+    Ec2(String, #[source] Option<BoxError>),
 
     #[error("AWS SSM error: {0}")]
-    Ssm(String),
+    Ssm(String, #[source] Option<BoxError>),
 
     #[error("AWS IAM error: {0}")]
-    Iam(String),
+    Iam(String, #[source] Option<BoxError>),
 
     #[error("AWS credentials not found or invalid: {0}")]
     AwsCredentials(String),
@@ -24,6 +27,9 @@ pub enum Ec2CliError {
     #[error("Resource already exists: {0}")]
     ResourceAlreadyExists(String),
 
+    #[error("EC2 quota exceeded: {0}")]
+    QuotaExceeded(String),
+
     // Profile Errors
     #[error("Profile not found: {0}")]
     ProfileNotFound(String),
@@ -38,15 +44,36 @@ pub enum Ec2CliError {
     #[error("Instance not found: {0}")]
     InstanceNotFound(String),
 
+    #[error("No instance name given and no instance is linked to this directory (use `ec2-cli up --link` or pass a name)")]
+    NoInstanceSpecified,
+
     #[error("Instance name already in use: {0}")]
     InstanceNameExists(String),
 
+    #[error("Instance name is ambiguous: {0}")]
+    AmbiguousInstanceName(String),
+
+    #[error("This directory is already linked to instance '{0}' (pass --force to relink)")]
+    LinkExists(String),
+
     #[error("Instance not ready: {0}")]
     InstanceNotReady(String),
 
     #[error("Instance in unexpected state: {0}")]
     InstanceState(String),
 
+    #[error("Invalid instance description: {0}")]
+    InvalidDescription(String),
+
+    #[error("Invalid instance type: {0}")]
+    InvalidInstanceType(String),
+
+    #[error("AWS account mismatch: {0}")]
+    AccountMismatch(String),
+
+    #[error("Instance belongs to another user: {0}")]
+    NotInstanceOwner(String),
+
     // State Errors
     #[error("State file error: {0}")]
     StateFile(String),
@@ -74,6 +101,9 @@ pub enum Ec2CliError {
     #[error("SCP transfer failed: {0}")]
     ScpTransfer(String),
 
+    #[error("Sync failed: {0}")]
+    SyncTransfer(String),
+
     #[error("SSH public key not found. Checked: {0}")]
     SshKeyNotFound(String),
 
@@ -87,6 +117,9 @@ pub enum Ec2CliError {
     #[error("Path not found: {0}")]
     PathNotFound(String),
 
+    #[error("Invalid grep pattern: {0}")]
+    InvalidPattern(String),
+
     // Config Errors
     #[error("Configuration error: {0}")]
     Config(String),
@@ -109,6 +142,12 @@ pub enum Ec2CliError {
     #[error("Subnet must be configured. Run 'ec2-cli config init' first.")]
     SubnetNotConfigured,
 
+    #[error("Subnet {0} cannot reach SSM. Instances need a NAT gateway route, an internet gateway route with public IPs on launch, or VPC endpoints for ssm, ssmmessages, and ec2messages.")]
+    SsmUnreachable(String),
+
+    #[error("VPC {0} is missing SSM endpoints for: {1}. Instances in subnets without a NAT or internet route can't reach SSM until the missing interface endpoints are recreated.")]
+    SsmEndpointsMissing(String, String),
+
     // File/IO Errors
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
@@ -124,6 +163,10 @@ pub enum Ec2CliError {
     #[error("Operation cancelled by user")]
     Cancelled,
 
+    // Batch commands (the message names the failed items; the code is the batch's)
+    #[error("{0}")]
+    BatchFailed(String, i32),
+
     // Generic
     #[error("{0}")]
     Other(String),
@@ -156,34 +199,176 @@ macro_rules! format_sdk_error {
     }};
 }
 
+/// Render an error for the terminal; with `verbose`, each underlying cause follows
+/// on its own line
+pub fn format_error(err: &anyhow::Error, verbose: bool) -> String {
+    let mut output = err.to_string();
+    if verbose {
+        for cause in err.chain().skip(1) {
+            output.push_str(&format!("\n  Caused by: {}", cause));
+        }
+    }
+    output
+}
+
+/// Exit code for an error returned from a command, which may wrap an `Ec2CliError`
+pub fn exit_code_for(err: &anyhow::Error) -> i32 {
+    err.downcast_ref::<Ec2CliError>()
+        .map_or(exit_code::GENERAL, Ec2CliError::exit_code)
+}
+
+/// Process exit codes, so scripts can branch on the kind of failure.
+///
+/// 2 is left to clap, which uses it for command-line usage errors.
+pub mod exit_code {
+    /// Any error without a more specific code
+    pub const GENERAL: i32 = 1;
+    /// An instance, profile, AWS resource, or path doesn't exist
+    pub const NOT_FOUND: i32 = 3;
+    /// A prerequisite or required configuration is missing
+    pub const PREREQUISITES: i32 = 4;
+    /// AWS credentials are missing, invalid, or for the wrong account
+    pub const AWS_CREDENTIALS: i32 = 5;
+    /// An operation timed out
+    pub const TIMEOUT: i32 = 6;
+    /// A profile, path, or other input failed validation
+    pub const INVALID_INPUT: i32 = 7;
+    /// The user cancelled at a prompt
+    pub const CANCELLED: i32 = 130;
+}
+
 impl Ec2CliError {
-    pub fn aws_sdk(err: impl std::fmt::Display) -> Self {
-        Ec2CliError::AwsSdk(err.to_string())
+    /// The process exit code for this error
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Ec2CliError::ResourceNotFound(_)
+            | Ec2CliError::ProfileNotFound(_)
+            | Ec2CliError::InstanceNotFound(_)
+            | Ec2CliError::NoInstanceSpecified
+            | Ec2CliError::SshKeyNotFound(_)
+            | Ec2CliError::PathNotFound(_)
+            | Ec2CliError::VpcNotFound(_)
+            | Ec2CliError::SubnetNotFound(_)
+            | Ec2CliError::NoSubnetsInVpc(_)
+            | Ec2CliError::NoDefaultVpc => exit_code::NOT_FOUND,
+            Ec2CliError::Prerequisites(_)
+            | Ec2CliError::SessionManagerPluginNotFound
+            | Ec2CliError::SubnetNotConfigured
+            | Ec2CliError::Config(_) => exit_code::PREREQUISITES,
+            Ec2CliError::AwsCredentials(_) | Ec2CliError::AccountMismatch(_) => {
+                exit_code::AWS_CREDENTIALS
+            }
+            Ec2CliError::Timeout(_) => exit_code::TIMEOUT,
+            Ec2CliError::ProfileInvalid(_)
+            | Ec2CliError::ProfileValidation(_)
+            | Ec2CliError::InvalidDescription(_)
+            | Ec2CliError::InvalidInstanceType(_)
+            | Ec2CliError::InvalidPath(_)
+            | Ec2CliError::InvalidPattern(_)
+            | Ec2CliError::SshKeyInvalid(_) => exit_code::INVALID_INPUT,
+            Ec2CliError::Cancelled => exit_code::CANCELLED,
+            Ec2CliError::BatchFailed(_, code) => *code,
+            _ => exit_code::GENERAL,
+        }
+    }
+
+    pub fn aws_sdk(err: impl std::error::Error + Send + Sync + 'static) -> Self {
+        Ec2CliError::AwsSdk(err.to_string(), Some(Box::new(err)))
     }
 
     pub fn ec2<E, R>(err: aws_sdk_ec2::error::SdkError<E, R>) -> Self
     where
-        E: std::fmt::Debug + aws_smithy_types::error::metadata::ProvideErrorMetadata,
-        R: std::fmt::Debug,
+        E: std::error::Error
+            + aws_smithy_types::error::metadata::ProvideErrorMetadata
+            + Send
+            + Sync
+            + 'static,
+        R: std::fmt::Debug + Send + Sync + 'static,
     {
-        Ec2CliError::Ec2(format_sdk_error!(aws_sdk_ec2, err))
+        Ec2CliError::Ec2(format_sdk_error!(aws_sdk_ec2, err), Some(Box::new(err)))
     }
 
     pub fn ssm<E, R>(err: aws_sdk_ssm::error::SdkError<E, R>) -> Self
     where
-        E: std::fmt::Debug + aws_smithy_types::error::metadata::ProvideErrorMetadata,
-        R: std::fmt::Debug,
+        E: std::error::Error
+            + aws_smithy_types::error::metadata::ProvideErrorMetadata
+            + Send
+            + Sync
+            + 'static,
+        R: std::fmt::Debug + Send + Sync + 'static,
     {
-        Ec2CliError::Ssm(format_sdk_error!(aws_sdk_ssm, err))
+        Ec2CliError::Ssm(format_sdk_error!(aws_sdk_ssm, err), Some(Box::new(err)))
     }
 
     pub fn iam<E, R>(err: aws_sdk_iam::error::SdkError<E, R>) -> Self
     where
-        E: std::fmt::Debug + aws_smithy_types::error::metadata::ProvideErrorMetadata,
-        R: std::fmt::Debug,
+        E: std::error::Error
+            + aws_smithy_types::error::metadata::ProvideErrorMetadata
+            + Send
+            + Sync
+            + 'static,
+        R: std::fmt::Debug + Send + Sync + 'static,
     {
-        Ec2CliError::Iam(format_sdk_error!(aws_sdk_iam, err))
+        Ec2CliError::Iam(format_sdk_error!(aws_sdk_iam, err), Some(Box::new(err)))
     }
 }
 
 pub type Result<T> = std::result::Result<T, Ec2CliError>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exit_codes() {
+        assert_eq!(
+            Ec2CliError::InstanceNotFound("dev".to_string()).exit_code(),
+            exit_code::NOT_FOUND
+        );
+        assert_eq!(
+            Ec2CliError::Prerequisites("missing".to_string()).exit_code(),
+            exit_code::PREREQUISITES
+        );
+        assert_eq!(
+            Ec2CliError::AwsCredentials("expired".to_string()).exit_code(),
+            exit_code::AWS_CREDENTIALS
+        );
+        assert_eq!(
+            Ec2CliError::Timeout("waiting".to_string()).exit_code(),
+            exit_code::TIMEOUT
+        );
+        assert_eq!(
+            Ec2CliError::Ec2("boom".to_string(), None).exit_code(),
+            exit_code::GENERAL
+        );
+    }
+
+    #[test]
+    fn test_exit_code_from_anyhow() {
+        let err: anyhow::Error = Ec2CliError::Cancelled.into();
+        assert_eq!(exit_code_for(&err), exit_code::CANCELLED);
+
+        let err = anyhow::anyhow!("not an ec2-cli error");
+        assert_eq!(exit_code_for(&err), exit_code::GENERAL);
+    }
+
+    #[test]
+    fn test_source_chain_printed_when_verbose() {
+        let cause = std::io::Error::new(std::io::ErrorKind::ConnectionReset, "connection reset");
+        let err: anyhow::Error = Ec2CliError::Ec2(
+            "RequestError: dispatch failed".to_string(),
+            Some(cause.into()),
+        )
+        .into();
+
+        assert!(std::error::Error::source(err.downcast_ref::<Ec2CliError>().unwrap()).is_some());
+        assert_eq!(
+            format_error(&err, false),
+            "AWS EC2 error: RequestError: dispatch failed"
+        );
+        assert_eq!(
+            format_error(&err, true),
+            "AWS EC2 error: RequestError: dispatch failed\n  Caused by: connection reset"
+        );
+    }
+}
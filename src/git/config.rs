@@ -23,14 +23,58 @@ pub fn find_git_user_config() -> GitUserConfig {
     }
 }
 
+/// Suggest a username for resource tagging.
+///
+/// Tries the local part of the global git email (`git config --global user.email`),
+/// then the OS username (`$USER`, falling back to `whoami`).
+pub fn suggest_username() -> Option<String> {
+    suggest_username_from(run_git_config(&["--global", "--get", "user.email"]), || {
+        std::env::var("USER")
+            .ok()
+            .filter(|s| !s.trim().is_empty())
+            .or_else(whoami)
+    })
+}
+
+fn suggest_username_from(
+    email: Option<String>,
+    os_username: impl FnOnce() -> Option<String>,
+) -> Option<String> {
+    email
+        .and_then(|email| {
+            email
+                .split('@')
+                .next()
+                .map(|local| local.trim().to_string())
+                .filter(|local| !local.is_empty())
+        })
+        .or_else(|| os_username().map(|user| user.trim().to_string()))
+        .filter(|user| !user.is_empty())
+}
+
+fn whoami() -> Option<String> {
+    Command::new("whoami")
+        .output()
+        .ok()
+        .filter(|o| o.status.success())
+        .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_string())
+        .filter(|s| !s.is_empty())
+}
+
 /// Get a single git config value by key.
 /// Uses git's normal config resolution order (local -> global -> system) to find
 /// the effective value. This ensures we pick up config from XDG locations,
 /// include files, and system-wide settings.
 /// Returns None if git is not installed, the key is not set, or the value is empty.
 fn get_git_config_value(key: &str) -> Option<String> {
+    run_git_config(&["--get", key])
+}
+
+/// Run `git config` with the given arguments and return its trimmed output, if any
+fn run_git_config(args: &[&str]) -> Option<String> {
     Command::new("git")
-        .args(["config", "--get", key])
+        .arg("config")
+        .args(args)
         .output()
         .ok()
         .filter(|o| o.status.success())
@@ -84,4 +128,27 @@ mod tests {
         };
         assert!(config.has_config());
     }
+
+    #[test]
+    fn test_suggest_username_prefers_git_email_local_part() {
+        let suggestion = suggest_username_from(Some("jdoe@example.com".to_string()), || {
+            Some("john".to_string())
+        });
+        assert_eq!(suggestion, Some("jdoe".to_string()));
+    }
+
+    #[test]
+    fn test_suggest_username_falls_back_to_os_user() {
+        let fallback = || Some("john".to_string());
+        assert_eq!(
+            suggest_username_from(None, fallback),
+            Some("john".to_string())
+        );
+        // An email with an empty local part isn't useful
+        assert_eq!(
+            suggest_username_from(Some("@example.com".to_string()), fallback),
+            Some("john".to_string())
+        );
+        assert_eq!(suggest_username_from(None, || None), None);
+    }
 }
@@ -1,5 +1,8 @@
 pub mod config;
 pub mod operations;
 
-pub use config::{find_git_user_config, GitUserConfig};
-pub use operations::{add_remote, git_pull, git_push, is_git_repo, list_remotes, remove_remote};
+pub use config::{find_git_user_config, suggest_username, GitUserConfig};
+pub use operations::{
+    add_remote, git_pull, git_push, is_git_repo, list_branches, list_remotes, push_refspec,
+    remove_remote, Branch, NO_CHECKOUT_REF_PREFIX,
+};
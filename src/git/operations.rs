@@ -2,14 +2,30 @@ use std::process::{Command, Stdio};
 
 use crate::{Ec2CliError, Result};
 
-/// Push to a remote via git subprocess
+/// Ref namespace for pushes that the instance's post-receive hook leaves alone,
+/// so the working tree is not checked out
+pub const NO_CHECKOUT_REF_PREFIX: &str = "refs/for/";
+
+/// Build the refspec for pushing `branch` to an instance.
 ///
 /// Uses explicit refspec format (`branch:branch`) to bypass `push.default=simple`
 /// upstream check, which would otherwise fail when the local branch has no
-/// tracking branch configured.
+/// tracking branch configured. Without `checkout`, the branch is pushed under
+/// `NO_CHECKOUT_REF_PREFIX` instead of `refs/heads/`.
+pub fn push_refspec(branch: &str, checkout: bool) -> String {
+    if checkout {
+        format!("{}:{}", branch, branch)
+    } else {
+        format!("{}:{}{}", branch, NO_CHECKOUT_REF_PREFIX, branch)
+    }
+}
+
+/// Push to a remote via git subprocess
+///
+/// `refspec` is typically built with `push_refspec`.
 pub fn git_push(
     remote: &str,
-    branch: Option<&str>,
+    refspec: Option<&str>,
     set_upstream: bool,
     ssh_command: Option<&str>,
 ) -> Result<()> {
@@ -22,10 +38,8 @@ pub fn git_push(
 
     cmd.arg(remote);
 
-    // Use explicit refspec format to avoid "no upstream branch" errors
-    // when push.default=simple is set
-    if let Some(b) = branch {
-        cmd.arg(format!("{}:{}", b, b));
+    if let Some(r) = refspec {
+        cmd.arg(r);
     }
 
     if let Some(ssh_cmd) = ssh_command {
@@ -103,6 +117,55 @@ pub fn list_remotes() -> Result<Vec<String>> {
     Ok(remotes)
 }
 
+/// A local branch and the commit it points to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Branch {
+    pub name: String,
+    /// Abbreviated commit hash
+    pub commit: String,
+    /// Subject line of the commit
+    pub subject: String,
+    /// Whether this is the checked out branch
+    pub current: bool,
+}
+
+/// `git for-each-ref` format parsed by `parse_branches`: one tab-separated line
+/// per branch, the subject last since it may contain tabs itself
+const BRANCH_FORMAT: &str =
+    "%(HEAD)%09%(refname:short)%09%(objectname:short)%09%(contents:subject)";
+
+/// List local branches, sorted by name
+pub fn list_branches() -> Result<Vec<Branch>> {
+    let output = Command::new("git")
+        .args(["for-each-ref", "--format", BRANCH_FORMAT, "refs/heads/"])
+        .output()
+        .map_err(|e| Ec2CliError::Git(e.to_string()))?;
+
+    if !output.status.success() {
+        return Err(Ec2CliError::Git("Failed to list branches".to_string()));
+    }
+
+    Ok(parse_branches(&String::from_utf8_lossy(&output.stdout)))
+}
+
+fn parse_branches(output: &str) -> Vec<Branch> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.splitn(4, '\t');
+            let current = fields.next()? == "*";
+            let name = fields.next().filter(|name| !name.is_empty())?;
+            let commit = fields.next()?;
+            Some(Branch {
+                name: name.to_string(),
+                commit: commit.to_string(),
+                subject: fields.next().unwrap_or_default().to_string(),
+                current,
+            })
+        })
+        .collect()
+}
+
 /// Add a remote using git command
 pub fn add_remote(name: &str, url: &str) -> Result<()> {
     let status = Command::new("git")
@@ -133,3 +196,57 @@ pub fn remove_remote(name: &str) -> Result<()> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_push_refspec_checkout() {
+        assert_eq!(push_refspec("main", true), "main:main");
+        assert_eq!(push_refspec("feature/x", true), "feature/x:feature/x");
+    }
+
+    #[test]
+    fn test_push_refspec_no_checkout() {
+        assert_eq!(push_refspec("main", false), "main:refs/for/main");
+        assert_eq!(
+            push_refspec("feature/x", false),
+            "feature/x:refs/for/feature/x"
+        );
+    }
+
+    #[test]
+    fn test_parse_branches() {
+        let output = " \tfeature/x\t1a2b3c4\tAdd x\n\
+                      *\tmain\t5d6e7f8\tFix\ttabbed subject\n\
+                      \x20\twip\t9a8b7c6\t\n";
+        assert_eq!(
+            parse_branches(output),
+            vec![
+                Branch {
+                    name: "feature/x".to_string(),
+                    commit: "1a2b3c4".to_string(),
+                    subject: "Add x".to_string(),
+                    current: false,
+                },
+                Branch {
+                    name: "main".to_string(),
+                    commit: "5d6e7f8".to_string(),
+                    subject: "Fix\ttabbed subject".to_string(),
+                    current: true,
+                },
+                Branch {
+                    name: "wip".to_string(),
+                    commit: "9a8b7c6".to_string(),
+                    subject: String::new(),
+                    current: false,
+                },
+            ]
+        );
+
+        // An empty repository has no branches; malformed lines are skipped
+        assert!(parse_branches("").is_empty());
+        assert!(parse_branches("garbage\n").is_empty());
+    }
+}
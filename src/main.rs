@@ -1,10 +1,18 @@
-use clap::{CommandFactory, Parser, Subcommand};
+use std::path::PathBuf;
+
+use chrono::{DateTime, Utc};
+use clap::{ArgGroup, CommandFactory, Parser, Subcommand};
 use clap_complete::engine::ArgValueCompleter;
 use clap_complete::CompleteEnv;
 use dialoguer::console::Style;
 
+use aws::concurrency::DEFAULT_PARALLEL;
+use cli::commands::config::{InitFlags, SettingKey};
+use cli::commands::list::ListFormat;
+use cli::commands::profile::ProfileTemplate;
 use cli::completions::{InstanceCompleter, ProfileCompleter, ShellType};
 use config::Settings;
+use ui::parse_time;
 
 mod aws;
 mod cli;
@@ -16,6 +24,7 @@ mod ssh;
 mod state;
 mod ui;
 mod user_data;
+mod util;
 
 pub use error::{Ec2CliError, Result};
 pub use profile::{Profile, ProfileLoader};
@@ -26,6 +35,25 @@ pub use profile::{Profile, ProfileLoader};
 #[command(version)]
 #[command(propagate_version = true)]
 struct Cli {
+    /// Suppress progress and informational output (errors, warnings, and
+    /// requested output such as tables are still printed)
+    #[arg(short, long, global = true)]
+    quiet: bool,
+
+    /// Show the underlying cause of errors, and extra details where supported
+    #[arg(short, long, global = true)]
+    verbose: bool,
+
+    /// Most operations to run at once in commands that act on several instances
+    #[arg(
+        long,
+        global = true,
+        value_name = "N",
+        default_value_t = DEFAULT_PARALLEL,
+        value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..)
+    )]
+    parallel: usize,
+
     #[command(subcommand)]
     command: Commands,
 }
@@ -45,6 +73,51 @@ enum Commands {
         /// Link instance to current directory
         #[arg(short, long)]
         link: bool,
+
+        /// Replace a link to another live instance without prompting
+        #[arg(long, requires = "link")]
+        force: bool,
+
+        /// Ignore the cached AMI ID and look up the latest image
+        #[arg(long)]
+        refresh_ami: bool,
+
+        /// Try a spot instance first, falling back to on-demand if spot capacity is unavailable
+        #[arg(long)]
+        spot_fallback: bool,
+
+        /// Skip creating the bare repo and working tree on the instance
+        #[arg(long)]
+        no_git: bool,
+
+        /// Note on what the instance is for (shown in status and list)
+        #[arg(short, long)]
+        description: Option<String>,
+
+        /// Launch this AMI ID instead of the profile's AMI
+        #[arg(long)]
+        ami: Option<String>,
+
+        /// Launch this instance type instead of the profile's type
+        #[arg(long)]
+        instance_type: Option<String>,
+
+        /// Extra tag for this instance and its security group (repeatable)
+        #[arg(long = "tag", value_name = "KEY=VALUE")]
+        tags: Vec<String>,
+
+        /// Also wait for package installation to finish (by default `up` returns
+        /// once the git repo is ready and packages keep installing)
+        #[arg(long)]
+        wait_complete: bool,
+
+        /// Launch with an unencrypted root volume (asks for confirmation)
+        #[arg(long)]
+        no_encrypt: bool,
+
+        /// Print progress as JSON events, one object per line, instead of spinners
+        #[arg(long)]
+        json: bool,
     },
 
     /// Terminate instance and cleanup resources
@@ -53,55 +126,198 @@ enum Commands {
         #[arg(add = ArgValueCompleter::new(InstanceCompleter))]
         name: String,
 
-        /// Skip confirmation prompt
+        /// Skip confirmation prompt and the AWS account check
         #[arg(short, long)]
         force: bool,
+
+        /// Keep the state entry (marked terminated) for later reference
+        #[arg(long)]
+        keep_state: bool,
+
+        /// Snapshot the root volume before terminating
+        #[arg(long)]
+        snapshot: bool,
+
+        /// Skip the ownership check when enforce_owner is enabled
+        #[arg(long)]
+        all_users: bool,
+
+        /// Show what would be destroyed and cleaned up without changing anything
+        #[arg(long)]
+        dry_run: bool,
     },
 
-    /// SSH into instance via SSM Session Manager
-    Ssh {
+    /// Reboot an instance
+    Reboot {
         /// Instance name
         #[arg(add = ArgValueCompleter::new(InstanceCompleter))]
         name: String,
 
-        /// Command to execute
-        #[arg(short = 'c', long)]
-        command: Option<String>,
+        /// Wait for the SSM agent to go offline and come back online
+        #[arg(short, long)]
+        wait: bool,
     },
 
-    /// Copy files to/from EC2 instance via SSM
-    Scp {
+    /// Forward a port from an instance and open it in the browser
+    Open {
         /// Instance name
         #[arg(add = ArgValueCompleter::new(InstanceCompleter))]
         name: String,
 
-        /// Source path (prefix with : for remote)
-        src: String,
+        /// Remote port the web app listens on (1-65535)
+        #[arg(value_parser = clap::value_parser!(u16).range(1..))]
+        port: u16,
+    },
+
+    /// Change an instance's type (stops and restarts a running instance)
+    Scale {
+        /// Instance name
+        #[arg(add = ArgValueCompleter::new(InstanceCompleter))]
+        name: String,
+
+        /// New instance type (e.g. t3.xlarge)
+        instance_type: String,
+
+        /// Skip the confirmation prompt before stopping the instance
+        #[arg(short, long)]
+        force: bool,
+    },
+
+    /// SSH into instance via SSM Session Manager
+    #[command(group(ArgGroup::new("remote_command").args(["command", "command_file"])))]
+    Ssh {
+        /// Instance name (optional if linked)
+        #[arg(add = ArgValueCompleter::new(InstanceCompleter))]
+        name: Option<String>,
 
-        /// Destination path (prefix with : for remote)
-        dest: String,
+        /// Command to execute
+        #[arg(short = 'c', long)]
+        command: Option<String>,
+
+        /// Run a local script file on the instance, echoing each command
+        #[arg(short = 'f', long)]
+        command_file: Option<PathBuf>,
+
+        /// Write the command's stdout to a file (requires --command or --command-file)
+        #[arg(short = 'o', long, requires = "remote_command")]
+        output: Option<PathBuf>,
+
+        /// Verify the host key against the managed known_hosts file (pinned on first connect)
+        #[arg(long)]
+        verify_host: bool,
+
+        /// Set an environment variable for the command (repeatable)
+        #[arg(long = "env", value_name = "KEY=VALUE", requires = "remote_command")]
+        env: Vec<String>,
+    },
+
+    /// Copy files to/from EC2 instance via SSM
+    Scp {
+        /// Instance name (optional if linked), then source and destination paths
+        /// (prefix with : for remote)
+        #[arg(
+            num_args = 2..=3,
+            required = true,
+            value_names = ["NAME", "SRC", "DEST"],
+            add = ArgValueCompleter::new(InstanceCompleter)
+        )]
+        args: Vec<String>,
 
         /// Copy directories recursively
         #[arg(short, long)]
         recursive: bool,
+
+        /// Preserve modification times and modes
+        #[arg(short, long)]
+        preserve: bool,
+
+        /// Create the remote destination directory first (uploads only)
+        #[arg(long)]
+        mkdir: bool,
+
+        /// Verify the host key against the managed known_hosts file (pinned on first connect)
+        #[arg(long)]
+        verify_host: bool,
+
+        /// Copy one small file (up to 16 KiB) with SSM Run Command instead of scp,
+        /// without ssh or the SSM session proxy
+        #[arg(long, conflicts_with_all = ["recursive", "preserve", "verify_host"])]
+        ssm: bool,
+
+        /// Show elapsed time while copying, then the size and throughput
+        #[arg(long, conflicts_with = "ssm")]
+        progress: bool,
+    },
+
+    /// Sync files to/from instance with rsync
+    Sync {
+        /// Instance name (optional if linked), then source and destination paths
+        /// (prefix with : for remote)
+        #[arg(
+            num_args = 2..=3,
+            required = true,
+            value_names = ["NAME", "SRC", "DEST"],
+            add = ArgValueCompleter::new(InstanceCompleter)
+        )]
+        args: Vec<String>,
+
+        /// Skip files matching this rsync pattern (repeatable)
+        #[arg(long, value_name = "PATTERN")]
+        exclude: Vec<String>,
+
+        /// Read exclude patterns from this file instead of ./.ec2-cli-ignore
+        #[arg(long, value_name = "PATH")]
+        ignore_file: Option<PathBuf>,
+
+        /// Delete destination files that don't exist in the source
+        #[arg(long)]
+        delete: bool,
+
+        /// Verify the host key against the managed known_hosts file (pinned on first connect)
+        #[arg(long)]
+        verify_host: bool,
     },
 
     /// Push code to EC2 bare repo
     Push {
-        /// Instance name
+        /// Instance name (optional if linked)
         #[arg(add = ArgValueCompleter::new(InstanceCompleter))]
-        name: String,
+        name: Option<String>,
+
+        /// Also push to this instance (repeatable)
+        #[arg(long, value_name = "NAME", add = ArgValueCompleter::new(InstanceCompleter))]
+        to: Vec<String>,
+
+        /// Push to every active instance in state
+        #[arg(short, long, conflicts_with_all = ["name", "to"])]
+        all: bool,
 
         /// Branch to push
         #[arg(short, long)]
         branch: Option<String>,
+
+        /// Push to refs/for/<branch> without changing the instance's checked out branch
+        #[arg(long)]
+        no_checkout: bool,
+
+        /// Wait for the instance's git repo setup to finish before pushing
+        #[arg(short, long)]
+        wait: bool,
+
+        /// With --to or --all, push to the rest after a push fails instead of stopping
+        #[arg(long)]
+        keep_going: bool,
+
+        /// List local branches and the commits they point to, then exit without pushing
+        #[arg(long, conflicts_with_all = ["name", "to", "all", "branch", "wait"])]
+        list_branches: bool,
     },
 
     /// Pull from EC2 bare repo
     Pull {
-        /// Instance name
+        /// Instance name (optional if linked)
         #[arg(add = ArgValueCompleter::new(InstanceCompleter))]
-        name: String,
+        name: Option<String>,
 
         /// Branch to pull
         #[arg(short, long)]
@@ -115,11 +331,33 @@ enum Commands {
         name: Option<String>,
     },
 
+    /// Set the description of an instance (empty text clears it)
+    Describe {
+        /// Instance name
+        #[arg(add = ArgValueCompleter::new(InstanceCompleter))]
+        name: String,
+
+        /// Description text (at most 256 characters)
+        text: String,
+    },
+
     /// List managed instances
     List {
         /// Show all instances including terminated
         #[arg(short, long)]
         all: bool,
+
+        /// Output format (wide adds type, volume, private IP, and last connection)
+        #[arg(long, value_enum, default_value_t = ListFormat::Table)]
+        format: ListFormat,
+
+        /// Only instances created since this time (e.g. 24h, 7d, or 2024-05-01T00:00:00Z)
+        #[arg(long, value_name = "TIME", value_parser = parse_time)]
+        since: Option<DateTime<Utc>>,
+
+        /// Only instances created up to this time (same formats as --since)
+        #[arg(long, value_name = "TIME", value_parser = parse_time)]
+        until: Option<DateTime<Utc>>,
     },
 
     /// Manage EC2 profiles
@@ -134,15 +372,57 @@ enum Commands {
         command: ConfigCommands,
     },
 
+    /// Inspect and repair local instance state
+    State {
+        #[command(subcommand)]
+        command: StateCommands,
+    },
+
+    /// Remove state entries for instances terminated or deleted outside ec2-cli
+    Gc {
+        /// Remove the entries (otherwise only list them)
+        #[arg(long)]
+        apply: bool,
+    },
+
+    /// Manage root volume snapshots kept by `destroy --snapshot`
+    Snapshots {
+        #[command(subcommand)]
+        command: SnapshotsCommands,
+    },
+
     /// View cloud-init logs from instance
     Logs {
-        /// Instance name
+        /// Instance name (optional if linked)
         #[arg(add = ArgValueCompleter::new(InstanceCompleter))]
-        name: String,
+        name: Option<String>,
 
         /// Follow log output
         #[arg(short, long)]
         follow: bool,
+
+        /// Only show lines matching this pattern (grep basic regex)
+        #[arg(short, long)]
+        grep: Option<String>,
+
+        /// Save the init and cloud-init output logs into this directory
+        #[arg(long, value_name = "DIR", conflicts_with_all = ["follow", "grep"])]
+        download: Option<PathBuf>,
+    },
+
+    /// Show the AWS identity in use and a summary of managed resources
+    Whoami,
+
+    /// Show version information
+    ///
+    /// With --verbose, includes the git commit, target, platform, and AWS SDK details
+    Version,
+
+    /// Check for a newer ec2-cli release
+    Update {
+        /// Only check and print how to upgrade (installing isn't supported)
+        #[arg(long, required = true)]
+        check: bool,
     },
 
     /// Generate shell completions
@@ -154,6 +434,16 @@ enum Commands {
 
     /// Display comprehensive user manual
     Manual,
+
+    /// SSH ProxyCommand that starts an SSM session without the AWS CLI
+    #[command(hide = true)]
+    SsmProxy {
+        /// Instance ID
+        target: String,
+
+        /// Remote port
+        port: u16,
+    },
 }
 
 #[derive(Subcommand)]
@@ -166,10 +456,45 @@ enum ProfileCommands {
         /// Profile name
         #[arg(add = ArgValueCompleter::new(ProfileCompleter))]
         name: String,
+
+        /// Print every field, with defaults filled in, as JSON
+        #[arg(long)]
+        resolved: bool,
     },
 
-    /// Validate a profile
+    /// Validate a profile, or every profile with --all
     Validate {
+        /// Profile name
+        #[arg(
+            add = ArgValueCompleter::new(ProfileCompleter),
+            required_unless_present = "all"
+        )]
+        name: Option<String>,
+
+        /// Validate every local, global, and built-in profile
+        #[arg(long, conflicts_with = "name")]
+        all: bool,
+    },
+
+    /// Report every issue in a profile, including checks validate leaves to launch time
+    Lint {
+        /// Profile name
+        #[arg(add = ArgValueCompleter::new(ProfileCompleter))]
+        name: String,
+    },
+
+    /// Create a global profile, optionally from a template for a common stack
+    Create {
+        /// Profile name
+        name: String,
+
+        /// Start from a built-in template instead of the default profile
+        #[arg(long, value_enum)]
+        template: Option<ProfileTemplate>,
+    },
+
+    /// Open a profile in $EDITOR and validate it on save
+    Edit {
         /// Profile name
         #[arg(add = ArgValueCompleter::new(ProfileCompleter))]
         name: String,
@@ -179,10 +504,60 @@ enum ProfileCommands {
 #[derive(Subcommand)]
 enum ConfigCommands {
     /// Initialize configuration and check prerequisites
-    Init,
+    Init {
+        /// Write settings from flags without prompting (for CI and automation)
+        #[arg(long)]
+        non_interactive: bool,
+
+        /// AWS region (required unless already configured)
+        #[arg(long, requires = "non_interactive")]
+        region: Option<String>,
+
+        /// VPC ID (default VPC if omitted)
+        #[arg(long, requires = "non_interactive")]
+        vpc: Option<String>,
+
+        /// Subnet ID in the VPC (required unless already configured)
+        #[arg(long, requires = "non_interactive")]
+        subnet: Option<String>,
+
+        /// Username tag (required unless already configured)
+        #[arg(long, requires = "non_interactive")]
+        username: Option<String>,
+
+        /// Back up config.json to config.json.bak and configure from defaults
+        #[arg(long)]
+        reset: bool,
+
+        /// Reset without asking for confirmation
+        #[arg(long, requires = "reset")]
+        force: bool,
+    },
 
     /// Show current configuration
-    Show,
+    Show {
+        /// Output as JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Set the AWS region after checking it is enabled
+    SetRegion {
+        /// Region name (e.g., us-east-1)
+        region: String,
+    },
+
+    /// Set the VPC after checking it exists in the configured region
+    SetVpc {
+        /// VPC ID (e.g., vpc-0123456789abcdef0)
+        id: String,
+    },
+
+    /// Set the subnet after checking it belongs to the configured VPC
+    SetSubnet {
+        /// Subnet ID (e.g., subnet-0123456789abcdef0)
+        id: String,
+    },
 
     /// Manage custom resource tags
     Tags {
@@ -191,6 +566,26 @@ enum ConfigCommands {
     },
 }
 
+#[derive(Subcommand)]
+enum SnapshotsCommands {
+    /// List managed snapshots in the current region
+    List,
+}
+
+#[derive(Subcommand)]
+enum StateCommands {
+    /// Reconcile local state with managed instances in AWS
+    Repair {
+        /// Write the changes (otherwise only show what would change)
+        #[arg(long)]
+        apply: bool,
+
+        /// Recover instances launched by other users when enforce_owner is enabled
+        #[arg(long)]
+        all_users: bool,
+    },
+}
+
 #[derive(Subcommand)]
 enum TagsCommands {
     /// Set a custom tag (applied to all AWS resources)
@@ -213,6 +608,9 @@ enum TagsCommands {
 
 /// Show a one-time hint about the manual command
 fn maybe_show_manual_hint() {
+    if ui::is_quiet() {
+        return;
+    }
     if let Ok(mut settings) = Settings::load() {
         if !settings.manual_hint_shown {
             let cyan = Style::new().cyan();
@@ -227,47 +625,198 @@ fn maybe_show_manual_hint() {
 }
 
 #[tokio::main]
-async fn main() -> anyhow::Result<()> {
+async fn main() {
     // Handle shell completion callbacks (when COMPLETE env var is set)
     CompleteEnv::with_factory(Cli::command).complete();
 
     let cli = Cli::parse();
+    ui::set_quiet(cli.quiet);
 
+    // Machine-facing commands keep their output (and timing) to themselves
+    let notify_update = !matches!(
+        cli.command,
+        Commands::SsmProxy { .. } | Commands::Completions { .. } | Commands::Update { .. }
+    );
+
+    // Exit with a code that reflects the kind of failure (see `error::exit_code`)
+    let verbose = cli.verbose;
+    if let Err(e) = run(cli).await {
+        eprintln!("Error: {}", error::format_error(&e, verbose));
+        std::process::exit(error::exit_code_for(&e));
+    }
+
+    if notify_update {
+        cli::commands::update::notify_if_outdated();
+    }
+}
+
+async fn run(cli: Cli) -> anyhow::Result<()> {
     match cli.command {
         Commands::Up {
             profile,
             name,
             link,
+            force,
+            refresh_ami,
+            spot_fallback,
+            no_git,
+            description,
+            ami,
+            instance_type,
+            tags,
+            wait_complete,
+            no_encrypt,
+            json,
         } => {
-            cli::commands::up::execute(profile, name, link).await?;
+            cli::commands::up::execute(
+                profile,
+                name,
+                link,
+                force,
+                refresh_ami,
+                spot_fallback,
+                no_git,
+                description,
+                ami,
+                instance_type,
+                tags,
+                wait_complete,
+                no_encrypt,
+                json,
+            )
+            .await?;
             maybe_show_manual_hint();
             Ok(())
         }
-        Commands::Destroy { name, force } => {
-            cli::commands::destroy::execute(name, force).await?;
+        Commands::Destroy {
+            name,
+            force,
+            keep_state,
+            snapshot,
+            all_users,
+            dry_run,
+        } => {
+            cli::commands::destroy::execute(name, force, keep_state, snapshot, all_users, dry_run)
+                .await?;
             maybe_show_manual_hint();
             Ok(())
         }
-        Commands::Ssh { name, command } => {
-            cli::commands::ssh::execute(name, command)?;
+        Commands::Reboot { name, wait } => {
+            cli::commands::reboot::execute(name, wait).await?;
+            Ok(())
+        }
+        Commands::Open { name, port } => {
+            cli::commands::open::execute(name, port).await?;
+            Ok(())
+        }
+        Commands::Scale {
+            name,
+            instance_type,
+            force,
+        } => {
+            cli::commands::scale::execute(name, instance_type, force).await?;
+            Ok(())
+        }
+        Commands::Ssh {
+            name,
+            command,
+            command_file,
+            verify_host,
+            output,
+            env,
+        } => {
+            cli::commands::ssh::execute(name, command, command_file, verify_host, output, env)
+                .await?;
             maybe_show_manual_hint();
             Ok(())
         }
         Commands::Scp {
-            name,
-            src,
-            dest,
+            args,
             recursive,
+            preserve,
+            mkdir,
+            verify_host,
+            ssm,
+            progress,
+        } => {
+            // The instance name is optional, so src and dest are always the last two
+            let mut args = args;
+            let dest = args.pop().expect("clap requires at least two paths");
+            let src = args.pop().expect("clap requires at least two paths");
+            let name = args.pop();
+            if ssm {
+                cli::commands::scp::execute_ssm(name, src, dest, mkdir).await?;
+            } else {
+                cli::commands::scp::execute(
+                    name,
+                    src,
+                    dest,
+                    recursive,
+                    preserve,
+                    mkdir,
+                    verify_host,
+                    progress,
+                )
+                .await?;
+            }
+            Ok(())
+        }
+        Commands::Sync {
+            args,
+            exclude,
+            ignore_file,
+            delete,
+            verify_host,
         } => {
-            cli::commands::scp::execute(name, src, dest, recursive)?;
+            // The instance name is optional, so src and dest are always the last two
+            let mut args = args;
+            let dest = args.pop().expect("clap requires at least two paths");
+            let src = args.pop().expect("clap requires at least two paths");
+            let name = args.pop();
+            cli::commands::sync::execute(
+                name,
+                src,
+                dest,
+                exclude,
+                ignore_file,
+                delete,
+                verify_host,
+            )
+            .await?;
             Ok(())
         }
-        Commands::Push { name, branch } => {
-            cli::commands::push::execute(name, branch)?;
+        Commands::Push {
+            list_branches: true,
+            ..
+        } => {
+            cli::commands::push::print_branches()?;
+            Ok(())
+        }
+        Commands::Push {
+            name,
+            to,
+            all,
+            branch,
+            no_checkout,
+            wait,
+            keep_going,
+            list_branches: false,
+        } => {
+            cli::commands::push::execute(
+                name,
+                to,
+                all,
+                branch,
+                !no_checkout,
+                wait,
+                keep_going,
+                cli.parallel,
+            )
+            .await?;
             Ok(())
         }
         Commands::Pull { name, branch } => {
-            cli::commands::pull::execute(name, branch)?;
+            cli::commands::pull::execute(name, branch).await?;
             Ok(())
         }
         Commands::Status { name } => {
@@ -275,8 +824,17 @@ async fn main() -> anyhow::Result<()> {
             maybe_show_manual_hint();
             Ok(())
         }
-        Commands::List { all } => {
-            cli::commands::list::execute(all)?;
+        Commands::Describe { name, text } => {
+            cli::commands::describe::execute(name, text)?;
+            Ok(())
+        }
+        Commands::List {
+            all,
+            format,
+            since,
+            until,
+        } => {
+            cli::commands::list::execute(all, format, since, until)?;
             maybe_show_manual_hint();
             Ok(())
         }
@@ -300,7 +858,14 @@ async fn main() -> anyhow::Result<()> {
                 }
                 Ok(())
             }
-            ProfileCommands::Show { name } => {
+            ProfileCommands::Show {
+                name,
+                resolved: true,
+            } => {
+                cli::commands::profile::show_resolved(&name)?;
+                Ok(())
+            }
+            ProfileCommands::Show { name, .. } => {
                 let loader = ProfileLoader::new();
                 let profile = loader.load(&name)?;
 
@@ -330,6 +895,21 @@ async fn main() -> anyhow::Result<()> {
                 if !profile.packages.system.is_empty() {
                     println!("  System: {:?}", profile.packages.system);
                 }
+                if !profile.packages.system_arm64.is_empty() {
+                    println!("  System (arm64): {:?}", profile.packages.system_arm64);
+                }
+                if !profile.packages.system_x86_64.is_empty() {
+                    println!("  System (x86_64): {:?}", profile.packages.system_x86_64);
+                }
+                if !profile.packages.apt_repositories.is_empty() {
+                    let uris: Vec<_> = profile
+                        .packages
+                        .apt_repositories
+                        .iter()
+                        .map(|r| r.uri.as_str())
+                        .collect();
+                    println!("  Apt repositories: {:?}", uris);
+                }
                 if profile.packages.rust.enabled {
                     println!(
                         "  Rust: {} ({:?})",
@@ -337,7 +917,8 @@ async fn main() -> anyhow::Result<()> {
                     );
                 }
                 if !profile.packages.cargo.is_empty() {
-                    println!("  Cargo: {:?}", profile.packages.cargo);
+                    let names: Vec<_> = profile.packages.cargo.iter().map(|p| p.name()).collect();
+                    println!("  Cargo: {:?}", names);
                 }
                 if !profile.environment.is_empty() {
                     println!();
@@ -346,9 +927,22 @@ async fn main() -> anyhow::Result<()> {
                         println!("  {}={}", key, value);
                     }
                 }
+                if let Some(ref extra) = profile.user_data_extra {
+                    println!();
+                    if profile.allow_raw_user_data {
+                        println!("User data extra (runs as root):");
+                    } else {
+                        println!("User data extra (ignored, allow_raw_user_data is false):");
+                    }
+                    for line in extra.lines() {
+                        println!("  {}", line);
+                    }
+                }
                 Ok(())
             }
-            ProfileCommands::Validate { name } => {
+            ProfileCommands::Validate {
+                name: Some(name), ..
+            } => {
                 let loader = ProfileLoader::new();
                 let profile = loader.load(&name)?;
 
@@ -363,14 +957,60 @@ async fn main() -> anyhow::Result<()> {
                     }
                 }
             }
+            // clap only allows omitting the name with --all
+            ProfileCommands::Validate { name: None, .. } => {
+                cli::commands::profile::validate_all()?;
+                Ok(())
+            }
+            ProfileCommands::Lint { name } => {
+                cli::commands::profile::lint(&name)?;
+                Ok(())
+            }
+            ProfileCommands::Create { name, template } => {
+                cli::commands::profile::create(&name, template)?;
+                Ok(())
+            }
+            ProfileCommands::Edit { name } => {
+                cli::commands::profile::edit(&name)?;
+                Ok(())
+            }
         },
         Commands::Config { command } => match command {
-            ConfigCommands::Init => {
-                cli::commands::config::init().await?;
+            ConfigCommands::Init {
+                non_interactive,
+                region,
+                vpc,
+                subnet,
+                username,
+                reset,
+                force,
+            } => {
+                cli::commands::config::init(InitFlags {
+                    non_interactive,
+                    region,
+                    vpc,
+                    subnet,
+                    username,
+                    reset,
+                    force,
+                })
+                .await?;
                 Ok(())
             }
-            ConfigCommands::Show => {
-                cli::commands::config::show()?;
+            ConfigCommands::Show { json } => {
+                cli::commands::config::show(json)?;
+                Ok(())
+            }
+            ConfigCommands::SetRegion { region } => {
+                cli::commands::config::set(SettingKey::Region, &region).await?;
+                Ok(())
+            }
+            ConfigCommands::SetVpc { id } => {
+                cli::commands::config::set(SettingKey::Vpc, &id).await?;
+                Ok(())
+            }
+            ConfigCommands::SetSubnet { id } => {
+                cli::commands::config::set(SettingKey::Subnet, &id).await?;
                 Ok(())
             }
             ConfigCommands::Tags { command } => match command {
@@ -388,8 +1028,41 @@ async fn main() -> anyhow::Result<()> {
                 }
             },
         },
-        Commands::Logs { name, follow } => {
-            cli::commands::logs::execute(name, follow)?;
+        Commands::State { command } => match command {
+            StateCommands::Repair { apply, all_users } => {
+                cli::commands::state::repair(apply, all_users).await?;
+                Ok(())
+            }
+        },
+        Commands::Gc { apply } => {
+            cli::commands::gc::execute(apply).await?;
+            Ok(())
+        }
+        Commands::Snapshots { command } => match command {
+            SnapshotsCommands::List => {
+                cli::commands::snapshots::list().await?;
+                Ok(())
+            }
+        },
+        Commands::Logs {
+            name,
+            follow,
+            grep,
+            download,
+        } => {
+            cli::commands::logs::execute(name, follow, grep, download).await?;
+            Ok(())
+        }
+        Commands::Whoami => {
+            cli::commands::whoami::execute().await?;
+            Ok(())
+        }
+        Commands::Version => {
+            cli::commands::version::execute(cli.verbose);
+            Ok(())
+        }
+        Commands::Update { check: _ } => {
+            cli::commands::update::execute()?;
             Ok(())
         }
         Commands::Completions { shell } => {
@@ -408,5 +1081,269 @@ async fn main() -> anyhow::Result<()> {
             cli::commands::manual::execute();
             Ok(())
         }
+        Commands::SsmProxy { target, port } => {
+            cli::commands::ssm_proxy::execute(target, port).await?;
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cli_definition() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn test_reboot_arguments() {
+        let cli = Cli::try_parse_from(["ec2-cli", "reboot", "dev", "--wait"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Commands::Reboot { ref name, wait: true } if name == "dev"
+        ));
+
+        let cli = Cli::try_parse_from(["ec2-cli", "reboot", "dev"]).unwrap();
+        assert!(matches!(cli.command, Commands::Reboot { wait: false, .. }));
+
+        // The instance name is required
+        assert!(Cli::try_parse_from(["ec2-cli", "reboot"]).is_err());
+    }
+
+    #[test]
+    fn test_parallel_argument() {
+        let cli = Cli::try_parse_from(["ec2-cli", "push", "--all"]).unwrap();
+        assert_eq!(cli.parallel, DEFAULT_PARALLEL);
+
+        // Global, so it's accepted after the subcommand too
+        let cli = Cli::try_parse_from(["ec2-cli", "push", "--all", "--parallel", "8"]).unwrap();
+        assert_eq!(cli.parallel, 8);
+
+        assert!(Cli::try_parse_from(["ec2-cli", "--parallel", "0", "push"]).is_err());
+    }
+
+    #[test]
+    fn test_push_keep_going_argument() {
+        let cli = Cli::try_parse_from(["ec2-cli", "push", "--all"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Commands::Push {
+                keep_going: false,
+                ..
+            }
+        ));
+
+        let cli =
+            Cli::try_parse_from(["ec2-cli", "push", "--to", "a", "--to", "b", "--keep-going"])
+                .unwrap();
+        assert!(matches!(
+            cli.command,
+            Commands::Push {
+                keep_going: true,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_push_list_branches_argument() {
+        let cli = Cli::try_parse_from(["ec2-cli", "push", "--list-branches"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Commands::Push {
+                list_branches: true,
+                ..
+            }
+        ));
+
+        // Listing never pushes, so push targets are rejected
+        assert!(Cli::try_parse_from(["ec2-cli", "push", "dev", "--list-branches"]).is_err());
+        assert!(Cli::try_parse_from(["ec2-cli", "push", "--all", "--list-branches"]).is_err());
+    }
+
+    #[test]
+    fn test_config_init_reset_arguments() {
+        let cli = Cli::try_parse_from(["ec2-cli", "config", "init", "--reset", "--force"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Commands::Config {
+                command: ConfigCommands::Init {
+                    reset: true,
+                    force: true,
+                    ..
+                }
+            }
+        ));
+
+        // --force only skips the reset confirmation
+        assert!(Cli::try_parse_from(["ec2-cli", "config", "init", "--force"]).is_err());
+    }
+
+    #[test]
+    fn test_open_arguments() {
+        let cli = Cli::try_parse_from(["ec2-cli", "open", "dev", "8080"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Commands::Open { ref name, port: 8080 } if name == "dev"
+        ));
+
+        // The remote port must be 1-65535
+        assert!(Cli::try_parse_from(["ec2-cli", "open", "dev", "0"]).is_err());
+        assert!(Cli::try_parse_from(["ec2-cli", "open", "dev", "65536"]).is_err());
+        assert!(Cli::try_parse_from(["ec2-cli", "open", "dev"]).is_err());
+    }
+
+    #[test]
+    fn test_profile_show_arguments() {
+        let cli = Cli::try_parse_from(["ec2-cli", "profile", "show", "dev", "--resolved"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Commands::Profile {
+                command: ProfileCommands::Show { resolved: true, .. }
+            }
+        ));
+
+        let cli = Cli::try_parse_from(["ec2-cli", "profile", "show", "dev"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Commands::Profile {
+                command: ProfileCommands::Show {
+                    resolved: false,
+                    ..
+                }
+            }
+        ));
+    }
+
+    #[test]
+    fn test_profile_validate_arguments() {
+        let cli = Cli::try_parse_from(["ec2-cli", "profile", "validate", "--all"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Commands::Profile {
+                command: ProfileCommands::Validate {
+                    name: None,
+                    all: true
+                }
+            }
+        ));
+
+        assert!(Cli::try_parse_from(["ec2-cli", "profile", "validate"]).is_err());
+        assert!(Cli::try_parse_from(["ec2-cli", "profile", "validate", "dev", "--all"]).is_err());
+    }
+
+    #[test]
+    fn test_profile_create_arguments() {
+        let cli =
+            Cli::try_parse_from(["ec2-cli", "profile", "create", "ml", "--template", "python"])
+                .unwrap();
+        assert!(matches!(
+            cli.command,
+            Commands::Profile {
+                command: ProfileCommands::Create {
+                    template: Some(ProfileTemplate::Python),
+                    ..
+                }
+            }
+        ));
+
+        let cli = Cli::try_parse_from(["ec2-cli", "profile", "create", "ml"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Commands::Profile {
+                command: ProfileCommands::Create { template: None, .. }
+            }
+        ));
+
+        assert!(
+            Cli::try_parse_from(["ec2-cli", "profile", "create", "ml", "--template", "java"])
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_scale_arguments() {
+        let cli = Cli::try_parse_from(["ec2-cli", "scale", "dev", "t3.xlarge", "-f"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Commands::Scale { ref name, ref instance_type, force: true }
+                if name == "dev" && instance_type == "t3.xlarge"
+        ));
+
+        // The new type is required
+        assert!(Cli::try_parse_from(["ec2-cli", "scale", "dev"]).is_err());
+    }
+
+    #[test]
+    fn test_up_tag_arguments() {
+        let cli =
+            Cli::try_parse_from(["ec2-cli", "up", "--tag", "Ticket=OPS-1", "--tag", "Team=ml"])
+                .unwrap();
+        assert!(matches!(
+            cli.command,
+            Commands::Up { ref tags, .. } if tags == &["Ticket=OPS-1", "Team=ml"]
+        ));
+
+        let cli = Cli::try_parse_from(["ec2-cli", "up"]).unwrap();
+        assert!(matches!(cli.command, Commands::Up { ref tags, .. } if tags.is_empty()));
+    }
+
+    #[test]
+    fn test_gc_arguments() {
+        let cli = Cli::try_parse_from(["ec2-cli", "gc"]).unwrap();
+        assert!(matches!(cli.command, Commands::Gc { apply: false }));
+
+        let cli = Cli::try_parse_from(["ec2-cli", "gc", "--apply"]).unwrap();
+        assert!(matches!(cli.command, Commands::Gc { apply: true }));
+    }
+
+    #[test]
+    fn test_ssh_env_arguments() {
+        let cli = Cli::try_parse_from([
+            "ec2-cli", "ssh", "dev", "-c", "env", "--env", "A=1", "--env", "B=2",
+        ])
+        .unwrap();
+        assert!(matches!(
+            cli.command,
+            Commands::Ssh { ref env, .. } if env == &["A=1", "B=2"]
+        ));
+
+        // Only a command gets the variables, not an interactive shell
+        assert!(Cli::try_parse_from(["ec2-cli", "ssh", "dev", "--env", "A=1"]).is_err());
+    }
+
+    #[test]
+    fn test_list_time_arguments() {
+        let cli = Cli::try_parse_from([
+            "ec2-cli",
+            "list",
+            "--all",
+            "--since",
+            "24h",
+            "--until",
+            "2030-01-01T00:00:00Z",
+        ])
+        .unwrap();
+        assert!(matches!(
+            cli.command,
+            Commands::List {
+                since: Some(_),
+                until: Some(_),
+                ..
+            }
+        ));
+
+        assert!(Cli::try_parse_from(["ec2-cli", "list", "--since", "yesterday"]).is_err());
+    }
+
+    #[test]
+    fn test_update_arguments() {
+        let cli = Cli::try_parse_from(["ec2-cli", "update", "--check"]).unwrap();
+        assert!(matches!(cli.command, Commands::Update { check: true }));
+
+        // Installing updates isn't supported, so --check is required
+        assert!(Cli::try_parse_from(["ec2-cli", "update"]).is_err());
     }
 }
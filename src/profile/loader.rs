@@ -1,5 +1,7 @@
+use crate::config::Settings;
 use crate::{Ec2CliError, Result};
 use directories::ProjectDirs;
+use serde_json::{Map, Value};
 use std::path::{Path, PathBuf};
 
 use super::schema::Profile;
@@ -34,6 +36,8 @@ pub struct ProfileLoader {
     global_dir: Option<PathBuf>,
     /// Local profiles directory: .ec2-cli/profiles/
     local_dir: Option<PathBuf>,
+    /// `instance_defaults` from settings, layered under every loaded profile
+    instance_defaults: Map<String, Value>,
 }
 
 impl ProfileLoader {
@@ -45,9 +49,15 @@ impl ProfileLoader {
             .ok()
             .map(|d| d.join(".ec2-cli").join("profiles"));
 
+        // Unreadable settings are reported by the commands that need them
+        let instance_defaults = Settings::load()
+            .map(|settings| settings.instance_defaults)
+            .unwrap_or_default();
+
         Self {
             global_dir,
             local_dir,
+            instance_defaults,
         }
     }
 
@@ -55,6 +65,9 @@ impl ProfileLoader {
     /// 1. Local project profiles (.ec2-cli/profiles/)
     /// 2. Global profiles (~/.config/ec2-cli/profiles/)
     /// 3. Built-in default profile
+    ///
+    /// Settings' `instance_defaults` fill in whatever the profile's `instance`
+    /// section leaves out.
     pub fn load(&self, name: &str) -> Result<Profile> {
         // Validate profile name to prevent path traversal attacks
         validate_profile_name(name)?;
@@ -73,33 +86,127 @@ impl ProfileLoader {
             }
         }
 
-        // Fall back to built-in default
+        // Fall back to built-in default, whose instance section is all defaults
         if name == "default" {
-            return Ok(Profile::default_profile());
+            return self.with_instance_defaults(Profile::default_profile());
         }
 
         Err(Ec2CliError::ProfileNotFound(name.to_string()))
     }
 
     fn try_load_from_dir(&self, dir: &Path, name: &str) -> Result<Option<Profile>> {
-        // Try .json5 first, then .json
-        for ext in ["json5", "json"] {
-            let path = dir.join(format!("{}.{}", name, ext));
-            if path.exists() {
-                let content = std::fs::read_to_string(&path)?;
-                let profile: Profile = json5::from_str(&content).map_err(|e| {
-                    Ec2CliError::ProfileInvalid(format!(
-                        "Failed to parse {}: {}",
-                        path.display(),
-                        e
-                    ))
-                })?;
-                return Ok(Some(profile));
+        match find_in_dir(dir, name) {
+            Some(path) => self.load_path(&path).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Parse a profile file with `instance_defaults` layered under it
+    pub(crate) fn load_path(&self, path: &Path) -> Result<Profile> {
+        if self.instance_defaults.is_empty() {
+            return Self::load_file(path);
+        }
+
+        // Merge before deserializing, while fields the profile omits are still missing
+        let content = std::fs::read_to_string(path)?;
+        let mut value: Value = json5::from_str(&content).map_err(|e| {
+            Ec2CliError::ProfileInvalid(format!("Failed to parse {}: {}", path.display(), e))
+        })?;
+        if let Some(profile) = value.as_object_mut() {
+            let instance = profile
+                .entry("instance")
+                .or_insert_with(|| Value::Object(Map::new()));
+            let mut merged = Value::Object(self.instance_defaults.clone());
+            merge_values(&mut merged, instance);
+            *instance = merged;
+        }
+
+        let mut profile: Profile = serde_json::from_value(value).map_err(|e| {
+            Ec2CliError::ProfileInvalid(format!(
+                "Failed to parse {} with instance_defaults from config.json: {}",
+                path.display(),
+                e
+            ))
+        })?;
+        profile.apply_architecture_defaults();
+        Ok(profile)
+    }
+
+    /// Apply `instance_defaults` to a profile that has no file, overriding its
+    /// built-in instance settings
+    fn with_instance_defaults(&self, mut profile: Profile) -> Result<Profile> {
+        if self.instance_defaults.is_empty() {
+            return Ok(profile);
+        }
+
+        let mut instance = serde_json::to_value(&profile.instance)?;
+        merge_values(
+            &mut instance,
+            &Value::Object(self.instance_defaults.clone()),
+        );
+        profile.instance = serde_json::from_value(instance).map_err(|e| {
+            Ec2CliError::Config(format!("Invalid instance_defaults in config.json: {}", e))
+        })?;
+        profile.apply_architecture_defaults();
+        Ok(profile)
+    }
+
+    /// Parse a profile file without validating it
+    pub fn load_file(path: &Path) -> Result<Profile> {
+        let content = std::fs::read_to_string(path)?;
+        let mut profile: Profile = json5::from_str(&content).map_err(|e| {
+            Ec2CliError::ProfileInvalid(format!("Failed to parse {}: {}", path.display(), e))
+        })?;
+        profile.apply_architecture_defaults();
+        Ok(profile)
+    }
+
+    /// Resolve the file backing a profile, using the same precedence as `load`.
+    /// Returns None for the built-in default and for profiles that don't exist.
+    pub fn find_path(&self, name: &str) -> Result<Option<PathBuf>> {
+        validate_profile_name(name)?;
+
+        for dir in [&self.local_dir, &self.global_dir].into_iter().flatten() {
+            if let Some(path) = find_in_dir(dir, name) {
+                return Ok(Some(path));
             }
         }
+
         Ok(None)
     }
 
+    /// Write the built-in default profile to the global profiles directory so it can be edited
+    pub fn materialize_default(&self) -> Result<PathBuf> {
+        self.write_global(&Profile::default_profile())
+    }
+
+    /// Save a new profile to the global profiles directory. Fails if a local or
+    /// global profile with the same name already exists.
+    pub fn create(&self, profile: &Profile) -> Result<PathBuf> {
+        if let Some(path) = self.find_path(&profile.name)? {
+            return Err(Ec2CliError::ResourceAlreadyExists(format!(
+                "Profile '{}' ({})",
+                profile.name,
+                path.display()
+            )));
+        }
+        self.write_global(profile)
+    }
+
+    fn write_global(&self, profile: &Profile) -> Result<PathBuf> {
+        let global_dir = self
+            .global_dir
+            .as_ref()
+            .ok_or_else(|| Ec2CliError::Config("Cannot determine config directory".to_string()))?;
+        std::fs::create_dir_all(global_dir)?;
+
+        let path = global_dir.join(format!("{}.json5", profile.name));
+        let content = serde_json::to_string_pretty(profile)?;
+        std::fs::write(&path, content)?;
+
+        Ok(path)
+    }
+
     /// List all available profiles
     pub fn list(&self) -> Result<Vec<ProfileInfo>> {
         let mut profiles = Vec::new();
@@ -156,6 +263,56 @@ impl ProfileLoader {
         Ok(profiles)
     }
 
+    /// Load and validate every profile `list` finds, in the same order, with
+    /// `instance_defaults` applied as `load` does
+    pub fn validate_all(&self) -> Result<Vec<(ProfileInfo, Result<()>)>> {
+        Ok(self
+            .list()?
+            .into_iter()
+            .map(|info| {
+                let profile = match info.path {
+                    Some(ref path) => self.load_path(path),
+                    None => self.with_instance_defaults(Profile::default_profile()),
+                };
+                let result = profile.and_then(|profile| profile.validate());
+                (info, result)
+            })
+            .collect())
+    }
+
+    /// Load a profile and collect every issue `Profile::lint` finds, plus ones that
+    /// need the other profile files: a local profile hiding a global one of the
+    /// same name. Fails only if the profile can't be found or parsed.
+    pub fn lint(&self, name: &str) -> Result<Vec<String>> {
+        let profile = self.load(name)?;
+        let mut issues = profile.lint();
+
+        if profile.name != name {
+            issues.push(format!(
+                "name: '{}' doesn't match the file name; the profile is loaded as '{}'",
+                profile.name, name
+            ));
+        }
+
+        let local = self
+            .local_dir
+            .as_deref()
+            .and_then(|dir| find_in_dir(dir, name));
+        let global = self
+            .global_dir
+            .as_deref()
+            .and_then(|dir| find_in_dir(dir, name));
+        if let (Some(local), Some(global)) = (local, global) {
+            issues.push(format!(
+                "{} is hidden by the local profile {}",
+                global.display(),
+                local.display()
+            ));
+        }
+
+        Ok(issues)
+    }
+
     /// Get the global profiles directory path
     pub fn global_dir(&self) -> Option<&PathBuf> {
         self.global_dir.as_ref()
@@ -167,12 +324,38 @@ impl ProfileLoader {
     }
 }
 
+/// Merge `overlay` into `base`: objects merge key by key, and any other value in
+/// `overlay` replaces the one in `base`
+fn merge_values(base: &mut Value, overlay: &Value) {
+    match (base, overlay) {
+        (Value::Object(base), Value::Object(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(key) {
+                    Some(existing) => merge_values(existing, value),
+                    None => {
+                        base.insert(key.clone(), value.clone());
+                    }
+                }
+            }
+        }
+        (base, overlay) => *base = overlay.clone(),
+    }
+}
+
 impl Default for ProfileLoader {
     fn default() -> Self {
         Self::new()
     }
 }
 
+/// Find a profile file in a directory, trying .json5 first, then .json
+fn find_in_dir(dir: &Path, name: &str) -> Option<PathBuf> {
+    ["json5", "json"]
+        .iter()
+        .map(|ext| dir.join(format!("{}.{}", name, ext)))
+        .find(|path| path.exists())
+}
+
 fn extract_profile_name(path: &Path) -> Option<String> {
     let ext = path.extension()?.to_str()?;
     if ext != "json" && ext != "json5" {
@@ -241,4 +424,214 @@ mod tests {
         assert!(validate_profile_name("my_profile").is_ok());
         assert!(validate_profile_name("MyProfile123").is_ok());
     }
+
+    #[test]
+    fn test_create() {
+        let dir = std::env::temp_dir().join(format!("ec2-cli-create-{}", std::process::id()));
+        let loader = ProfileLoader {
+            global_dir: Some(dir.clone()),
+            local_dir: None,
+            instance_defaults: Map::new(),
+        };
+
+        let mut profile = Profile::python_profile();
+        profile.name = "ml".to_string();
+        let path = loader.create(&profile).unwrap();
+        let saved = ProfileLoader::load_file(&path);
+        let again = loader.create(&profile);
+        profile.name = "../ml".to_string();
+        let traversal = loader.create(&profile);
+        let _ = std::fs::remove_dir_all(&dir);
+
+        assert_eq!(path, dir.join("ml.json5"));
+        assert_eq!(saved.unwrap().packages, Profile::python_profile().packages);
+        assert!(matches!(again, Err(Ec2CliError::ResourceAlreadyExists(_))));
+        assert!(matches!(traversal, Err(Ec2CliError::ProfileInvalid(_))));
+    }
+
+    #[test]
+    fn test_validate_all() {
+        let dir = std::env::temp_dir().join(format!("ec2-cli-validate-all-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("good.json5"), r#"{ name: "good" }"#).unwrap();
+        std::fs::write(
+            dir.join("bad.json5"),
+            r#"{ name: "bad", instance: { storage: { root_volume: { size_gb: 4 } } } }"#,
+        )
+        .unwrap();
+        std::fs::write(dir.join("broken.json"), "{ not json").unwrap();
+        std::fs::write(dir.join("notes.txt"), "ignored").unwrap();
+
+        let loader = ProfileLoader {
+            global_dir: Some(dir.clone()),
+            local_dir: None,
+            instance_defaults: Map::new(),
+        };
+        let results = loader.validate_all().unwrap();
+        let _ = std::fs::remove_dir_all(&dir);
+
+        let summary: Vec<_> = results
+            .iter()
+            .map(|(info, result)| (info.name.as_str(), result.is_ok()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("bad", false),
+                ("broken", false),
+                ("default", true),
+                ("good", true)
+            ]
+        );
+        assert!(matches!(results[1].1, Err(Ec2CliError::ProfileInvalid(_))));
+    }
+
+    #[test]
+    fn test_lint_reports_every_issue() {
+        let dir = std::env::temp_dir().join(format!("ec2-cli-lint-{}", std::process::id()));
+        let (local_dir, global_dir) = (dir.join("local"), dir.join("global"));
+        std::fs::create_dir_all(&local_dir).unwrap();
+        std::fs::create_dir_all(&global_dir).unwrap();
+        std::fs::write(
+            local_dir.join("dev.json5"),
+            r#"{
+                name: "dev",
+                instance: {
+                    type: "t3.large",
+                    fallback_types: ["t3.xlarge", "T3 MEDIUM", "t3.xlarge"],
+                    storage: { root_volume: { size_gb: 4 } },
+                },
+            }"#,
+        )
+        .unwrap();
+        std::fs::write(global_dir.join("dev.json5"), r#"{ name: "dev" }"#).unwrap();
+        std::fs::write(global_dir.join("clean.json5"), r#"{ name: "clean" }"#).unwrap();
+        std::fs::write(global_dir.join("renamed.json5"), r#"{ name: "other" }"#).unwrap();
+
+        let loader = ProfileLoader {
+            global_dir: Some(global_dir.clone()),
+            local_dir: Some(local_dir.clone()),
+            instance_defaults: Map::new(),
+        };
+        let dev = loader.lint("dev");
+        let clean = loader.lint("clean");
+        let renamed = loader.lint("renamed");
+        let missing = loader.lint("missing");
+        let _ = std::fs::remove_dir_all(&dir);
+
+        let dev = dev.unwrap();
+        assert_eq!(dev.len(), 4, "{:?}", dev);
+        assert!(dev[0].contains("at least 8 GB"));
+        assert!(dev[1].contains("'T3 MEDIUM' is not a valid instance type"));
+        assert!(dev[2].contains("'t3.xlarge' is listed more than once"));
+        assert!(dev[3].contains("is hidden by the local profile"));
+
+        assert_eq!(clean.unwrap(), Vec::<String>::new());
+        assert_eq!(renamed.unwrap().len(), 1);
+        assert!(matches!(missing, Err(Ec2CliError::ProfileNotFound(_))));
+    }
+
+    #[test]
+    fn test_instance_defaults_layered_under_profiles() {
+        let dir = std::env::temp_dir().join(format!("ec2-cli-defaults-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("bare.json5"),
+            r#"{ name: "bare", instance: { type: "c7i.xlarge" } }"#,
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("sized.json5"),
+            r#"{ name: "sized", instance: { storage: { root_volume: { size_gb: 50 } } } }"#,
+        )
+        .unwrap();
+        std::fs::write(dir.join("empty.json5"), r#"{ name: "empty" }"#).unwrap();
+
+        let defaults = serde_json::json!({
+            "type": "m7i.large",
+            "storage": { "root_volume": { "size_gb": 200, "type": "gp2" } },
+        });
+        let loader = ProfileLoader {
+            global_dir: Some(dir.clone()),
+            local_dir: None,
+            instance_defaults: defaults.as_object().unwrap().clone(),
+        };
+        let bare = loader.load("bare").unwrap();
+        let sized = loader.load("sized").unwrap();
+        let empty = loader.load("empty").unwrap();
+        let builtin = loader.load("default").unwrap();
+        let _ = std::fs::remove_dir_all(&dir);
+
+        // Omitted fields come from the defaults, nested ones included
+        assert_eq!(bare.instance.instance_type, "c7i.xlarge");
+        assert_eq!(bare.instance.storage.root_volume.size_gb, 200);
+        assert_eq!(bare.instance.storage.root_volume.volume_type, "gp2");
+
+        // An explicit value wins; its siblings still get the defaults
+        assert_eq!(sized.instance.instance_type, "m7i.large");
+        assert_eq!(sized.instance.storage.root_volume.size_gb, 50);
+        assert_eq!(sized.instance.storage.root_volume.volume_type, "gp2");
+
+        assert_eq!(empty.instance.instance_type, "m7i.large");
+        assert_eq!(empty.instance.storage.root_volume.size_gb, 200);
+        // Fields neither sets keep the schema defaults
+        assert_eq!(empty.instance.storage.root_volume.iops, Some(3000));
+
+        // The built-in default has no file, so the defaults replace its settings
+        assert_eq!(builtin.instance.instance_type, "m7i.large");
+        assert_eq!(builtin.instance.storage.root_volume.size_gb, 200);
+        assert_eq!(builtin.instance.fallback_types, vec!["t3.medium"]);
+    }
+
+    #[test]
+    fn test_validate_all_applies_instance_defaults() {
+        let dir =
+            std::env::temp_dir().join(format!("ec2-cli-validate-defaults-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("graviton.json5"),
+            r#"{ name: "graviton", instance: { ami: { architecture: "arm64" } } }"#,
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("pinned.json5"),
+            r#"{ name: "pinned", instance: { type: "m7i.large" } }"#,
+        )
+        .unwrap();
+
+        // An x86 type default doesn't fit a profile that only sets arm64
+        let defaults = serde_json::json!({ "type": "c6i.large" });
+        let loader = ProfileLoader {
+            global_dir: Some(dir.clone()),
+            local_dir: None,
+            instance_defaults: defaults.as_object().unwrap().clone(),
+        };
+        let results = loader.validate_all().unwrap();
+        let loaded = loader.load("graviton").and_then(|p| p.validate());
+        let _ = std::fs::remove_dir_all(&dir);
+
+        let summary: Vec<_> = results
+            .iter()
+            .map(|(info, result)| (info.name.as_str(), result.is_ok()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![("default", true), ("graviton", false), ("pinned", true)]
+        );
+        // The same answer `load` gives
+        assert!(loaded.is_err());
+    }
+
+    #[test]
+    fn test_merge_values() {
+        let mut base = serde_json::json!({ "a": 1, "b": { "c": 2, "d": 3 } });
+        merge_values(
+            &mut base,
+            &serde_json::json!({ "b": { "c": 20, "e": 5 }, "f": [1] }),
+        );
+        assert_eq!(
+            base,
+            serde_json::json!({ "a": 1, "b": { "c": 20, "d": 3, "e": 5 }, "f": [1] })
+        );
+    }
 }
@@ -2,4 +2,7 @@ mod loader;
 mod schema;
 
 pub use loader::ProfileLoader;
-pub use schema::Profile;
+pub use schema::{
+    instance_type_arch_mismatch, is_valid_instance_type, AmiConfig, AptRepo, CargoPackage,
+    CargoPackageSpec, DockerConfig, DotfilesConfig, HooksConfig, Profile, RootVolumeConfig,
+};
@@ -1,7 +1,7 @@
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Profile {
     pub name: String,
     #[serde(default)]
@@ -10,9 +10,32 @@ pub struct Profile {
     pub packages: PackageConfig,
     #[serde(default)]
     pub environment: HashMap<String, String>,
+    /// Local environment variables copied into `environment` at launch when set
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub environment_passthrough: Vec<String>,
+    #[serde(default)]
+    pub tools: ToolsConfig,
+    #[serde(default)]
+    pub hooks: HooksConfig,
+    #[serde(default)]
+    pub system: SystemConfig,
+    /// Dotfiles repository cloned into the instance user's home
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dotfiles: Option<DotfilesConfig>,
+    /// Containers started once Docker is installed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub docker: Option<DockerConfig>,
+    /// Raw shell appended to the cloud-init script, run as root before the ready marker.
+    /// Ignored unless `allow_raw_user_data` is set; it is not validated or escaped, so
+    /// the profile author is responsible for its contents.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user_data_extra: Option<String>,
+    /// Acknowledge that `user_data_extra` is run verbatim
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub allow_raw_user_data: bool,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct InstanceConfig {
     #[serde(rename = "type", default = "default_instance_type")]
     pub instance_type: String,
@@ -39,7 +62,62 @@ fn default_instance_type() -> String {
     "t3.large".to_string()
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// x86_64 instance families and their Graviton (arm64) equivalents
+const GRAVITON_FAMILIES: &[(&str, &str)] = &[
+    ("t2", "t4g"),
+    ("t3", "t4g"),
+    ("t3a", "t4g"),
+    ("m5", "m6g"),
+    ("m6i", "m7g"),
+    ("c5", "c6g"),
+    ("c6i", "c7g"),
+    ("r5", "r6g"),
+    ("r6i", "r7g"),
+];
+
+/// Get the Graviton equivalent of an x86_64 instance type (e.g. t3.large -> t4g.large)
+fn graviton_equivalent(instance_type: &str) -> Option<String> {
+    let (family, size) = instance_type.split_once('.')?;
+    GRAVITON_FAMILIES
+        .iter()
+        .find(|(x86, _)| *x86 == family)
+        .map(|(_, arm)| format!("{}.{}", arm, size))
+}
+
+/// Check an instance type name has the `family.size` form (e.g. t3.large, u-6tb1.metal)
+pub fn is_valid_instance_type(instance_type: &str) -> bool {
+    let valid_part = |part: &str| {
+        !part.is_empty()
+            && part
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+    };
+    instance_type
+        .split_once('.')
+        .is_some_and(|(family, size)| valid_part(family) && valid_part(size))
+}
+
+/// Why an instance type can't run on `architecture`, if it can't
+pub fn instance_type_arch_mismatch(instance_type: &str, architecture: &str) -> Option<String> {
+    match architecture {
+        "arm64" => graviton_equivalent(instance_type).map(|arm| {
+            format!(
+                "Instance type {} is not available for arm64. Try {} instead",
+                instance_type, arm
+            )
+        }),
+        "x86_64" => {
+            let family = instance_type.split_once('.')?.0;
+            GRAVITON_FAMILIES
+                .iter()
+                .any(|(_, arm)| *arm == family)
+                .then(|| format!("Instance type {} is arm64 only", instance_type))
+        }
+        _ => None,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct AmiConfig {
     #[serde(rename = "type", default = "default_ami_type")]
     pub ami_type: String,
@@ -67,13 +145,13 @@ fn default_architecture() -> String {
     "x86_64".to_string()
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default)]
 pub struct StorageConfig {
     #[serde(default)]
     pub root_volume: RootVolumeConfig,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct RootVolumeConfig {
     #[serde(default = "default_volume_size")]
     pub size_gb: u32,
@@ -81,8 +159,12 @@ pub struct RootVolumeConfig {
     pub volume_type: String,
     #[serde(default = "default_iops")]
     pub iops: Option<u32>,
-    #[serde(default)]
+    #[serde(default = "default_throughput")]
     pub throughput: Option<u32>,
+    /// KMS key (ID, alias, or ARN) to encrypt the volume with instead of the
+    /// account's default EBS key
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kms_key_id: Option<String>,
 }
 
 impl Default for RootVolumeConfig {
@@ -90,8 +172,9 @@ impl Default for RootVolumeConfig {
         Self {
             size_gb: default_volume_size(),
             volume_type: default_volume_type(),
-            iops: Some(default_iops().unwrap()),
-            throughput: Some(125),
+            iops: default_iops(),
+            throughput: default_throughput(),
+            kms_key_id: None,
         }
     }
 }
@@ -108,17 +191,120 @@ fn default_iops() -> Option<u32> {
     Some(3000)
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+fn default_throughput() -> Option<u32> {
+    Some(125)
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default)]
 pub struct PackageConfig {
     #[serde(default)]
     pub system: Vec<String>,
+    /// Extra system packages installed only on arm64 instances
+    #[serde(default)]
+    pub system_arm64: Vec<String>,
+    /// Extra system packages installed only on x86_64 instances
+    #[serde(default)]
+    pub system_x86_64: Vec<String>,
+    /// Extra apt repositories added before the package index is updated
+    #[serde(default)]
+    pub apt_repositories: Vec<AptRepo>,
     #[serde(default)]
     pub rust: RustConfig,
     #[serde(default)]
-    pub cargo: Vec<String>,
+    pub cargo: Vec<CargoPackage>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// An apt repository: a PPA (`uri: "ppa:owner/name"`) or a `deb` source line
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AptRepo {
+    pub uri: String,
+    /// Distribution, e.g. "jammy" (not used for PPAs)
+    #[serde(default)]
+    pub suite: String,
+    #[serde(default)]
+    pub components: Vec<String>,
+    /// HTTPS URL of the repository's signing key
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key_url: Option<String>,
+}
+
+/// A cargo package to install: either a plain crate name or a detailed spec
+// Profiles hold a handful of these, so the spec isn't worth boxing
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum CargoPackage {
+    Name(String),
+    Spec(CargoPackageSpec),
+}
+
+impl CargoPackage {
+    /// The crate name
+    pub fn name(&self) -> &str {
+        match self {
+            CargoPackage::Name(name) => name,
+            CargoPackage::Spec(spec) => &spec.name,
+        }
+    }
+}
+
+impl From<&str> for CargoPackage {
+    fn from(name: &str) -> Self {
+        CargoPackage::Name(name.to_string())
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CargoPackageSpec {
+    pub name: String,
+    /// Version requirement passed to `--version`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub features: Vec<String>,
+    /// Pass `--locked` to use the crate's Cargo.lock
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub locked: bool,
+    /// Install from a git repository URL instead of crates.io
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub git: Option<String>,
+    /// Commit to install from `git`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rev: Option<String>,
+    /// Tag to install from `git`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+    /// Branch to install from `git`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub branch: Option<String>,
+    /// Install from this alternative registry instead of crates.io
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub registry: Option<String>,
+    /// Index URL for `registry`, if it isn't configured on the instance already
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub index: Option<String>,
+    /// SSM SecureString parameter holding the token for `registry`. The instance
+    /// reads it while installing, so the token is never part of the user data.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token_parameter: Option<String>,
+    /// No longer supported: it put the token in the user data. Kept so profiles
+    /// that still set it get an error pointing at `token_parameter`.
+    #[serde(default, skip_serializing)]
+    pub token_env: Option<String>,
+}
+
+impl PackageConfig {
+    /// System packages for an architecture: the shared list followed by its extras
+    pub fn system_for_arch<'a>(&'a self, architecture: &str) -> impl Iterator<Item = &'a String> {
+        let extras = match architecture {
+            "arm64" => &self.system_arm64,
+            _ => &self.system_x86_64,
+        };
+        self.system.iter().chain(extras.iter())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct RustConfig {
     #[serde(default = "default_true")]
     pub enabled: bool,
@@ -138,6 +324,205 @@ impl Default for RustConfig {
     }
 }
 
+/// Optional tools installed on the instance
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ToolsConfig {
+    /// Install AgentFS (also relaxes AppArmor's unprivileged user namespace restriction)
+    #[serde(default = "default_true")]
+    pub agentfs: bool,
+}
+
+impl Default for ToolsConfig {
+    fn default() -> Self {
+        Self { agentfs: true }
+    }
+}
+
+/// Local commands run by ec2-cli at points in the instance lifecycle
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct HooksConfig {
+    /// Shell command run locally once `up` finishes. Supports the placeholders
+    /// `{name}`, `{instance_id}`, and `{region}`, which are substituted shell-quoted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub post_ready: Option<String>,
+    /// Shell command run on the instance after each `push` checks out a branch, as the
+    /// instance user in the project's work tree (e.g. `cargo build --release`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub on_push: Option<String>,
+}
+
+impl HooksConfig {
+    /// Substitute instance details into a hook command, quoting each value for `sh`
+    pub fn render(command: &str, name: &str, instance_id: &str, region: &str) -> String {
+        let placeholders = [
+            ("{name}", name),
+            ("{instance_id}", instance_id),
+            ("{region}", region),
+        ];
+
+        // Single pass, so substituted values are never themselves expanded
+        let mut rendered = String::with_capacity(command.len());
+        let mut rest = command;
+        while let Some(start) = rest.find('{') {
+            rendered.push_str(&rest[..start]);
+            rest = &rest[start..];
+
+            match placeholders.iter().find(|(p, _)| rest.starts_with(p)) {
+                Some((placeholder, value)) => {
+                    rendered.push_str(&shell_escape::escape((*value).into()));
+                    rest = &rest[placeholder.len()..];
+                }
+                None => {
+                    rendered.push('{');
+                    rest = &rest[1..];
+                }
+            }
+        }
+        rendered.push_str(rest);
+
+        rendered
+    }
+}
+
+/// A dotfiles repository to set up for the instance user
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DotfilesConfig {
+    /// HTTPS URL of the repository, cloned into `~/.dotfiles`
+    pub repo: String,
+    /// Command run from the clone afterwards, e.g. `./install.sh`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub install_command: Option<String>,
+}
+
+/// Containers to start on the instance, e.g. databases for local development
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DockerConfig {
+    /// Contents of a compose file written to `~/work/compose.yaml` and started with
+    /// `docker compose up -d`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub compose_file: Option<String>,
+    /// Arguments for `docker run`, one container each, e.g. `-d -p 6379:6379 redis:7`;
+    /// each must include `-d` so the setup script doesn't block on the container
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub run: Vec<String>,
+}
+
+/// Operating system settings applied early in the cloud-init script
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SystemConfig {
+    /// IANA time zone name, e.g. `Europe/Berlin` (the AMI default is UTC)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timezone: Option<String>,
+    /// Locale to generate and make the default, e.g. `en_US.UTF-8`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub locale: Option<String>,
+    /// Size of a swapfile to create on the root volume, in GiB
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub swap_gb: Option<u32>,
+}
+
+impl SystemConfig {
+    /// Check that the timezone and locale look like valid names. Both end up in
+    /// shell commands, so this also guarantees they are shell-safe.
+    pub fn validate(&self) -> crate::Result<()> {
+        if let Some(ref timezone) = self.timezone {
+            if !is_valid_timezone(timezone) {
+                return Err(crate::Ec2CliError::ProfileValidation(format!(
+                    "Invalid timezone: '{}'. Use an IANA name such as UTC or America/New_York",
+                    timezone
+                )));
+            }
+        }
+
+        if let Some(ref locale) = self.locale {
+            if !is_valid_locale(locale) {
+                return Err(crate::Ec2CliError::ProfileValidation(format!(
+                    "Invalid locale: '{}'. Use the form language_COUNTRY.charset, e.g. en_US.UTF-8",
+                    locale
+                )));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// IANA zone names: `/`-separated parts starting with a letter, made of letters,
+/// digits, `_`, `-`, and `+` (e.g. `America/Argentina/Buenos_Aires`, `Etc/GMT+3`)
+fn is_valid_timezone(timezone: &str) -> bool {
+    timezone.len() <= 64
+        && timezone.split('/').all(|part| {
+            part.starts_with(|c: char| c.is_ascii_alphabetic())
+                && part
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '+'))
+        })
+}
+
+/// glibc locale names: `ll_CC`, optionally followed by `.charset` and `@modifier`
+/// (e.g. `en_US.UTF-8`, `de_DE.UTF-8@euro`)
+fn is_valid_locale(locale: &str) -> bool {
+    let (rest, modifier) = match locale.split_once('@') {
+        Some((rest, modifier)) => (rest, Some(modifier)),
+        None => (locale, None),
+    };
+    let (name, charset) = match rest.split_once('.') {
+        Some((name, charset)) => (name, Some(charset)),
+        None => (rest, None),
+    };
+    let Some((language, country)) = name.split_once('_') else {
+        return false;
+    };
+
+    (2..=3).contains(&language.len())
+        && language.chars().all(|c| c.is_ascii_lowercase())
+        && country.len() == 2
+        && country.chars().all(|c| c.is_ascii_uppercase())
+        && charset.is_none_or(|c| {
+            !c.is_empty() && c.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        })
+        && modifier.is_none_or(|m| !m.is_empty() && m.chars().all(|c| c.is_ascii_alphanumeric()))
+}
+
+/// KMS key references EBS accepts: a key ID (`1234abcd-...` or `mrk-...`), an
+/// `alias/name`, or the ARN of either
+fn is_valid_kms_key_id(key: &str) -> bool {
+    let is_key_id = |id: &str| match id.strip_prefix("mrk-") {
+        Some(hex) => hex.len() == 32 && hex.chars().all(|c| c.is_ascii_hexdigit()),
+        None => {
+            let parts: Vec<&str> = id.split('-').collect();
+            parts.iter().map(|p| p.len()).eq([8, 4, 4, 4, 12])
+                && parts
+                    .iter()
+                    .all(|p| p.chars().all(|c| c.is_ascii_hexdigit()))
+        }
+    };
+    let is_alias = |alias: &str| {
+        alias.strip_prefix("alias/").is_some_and(|name| {
+            !name.is_empty()
+                && name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '/' | '_' | '-'))
+        })
+    };
+
+    let Some(arn) = key.strip_prefix("arn:") else {
+        return is_key_id(key) || is_alias(key);
+    };
+
+    // arn:<partition>:kms:<region>:<account>:key/<id> or :alias/<name>
+    let parts: Vec<&str> = arn.splitn(5, ':').collect();
+    let [partition, service, region, account, resource] = parts[..] else {
+        return false;
+    };
+    partition.starts_with("aws")
+        && service == "kms"
+        && !region.is_empty()
+        && account.len() == 12
+        && account.chars().all(|c| c.is_ascii_digit())
+        && (resource.strip_prefix("key/").is_some_and(is_key_id) || is_alias(resource))
+}
+
 fn default_true() -> bool {
     true
 }
@@ -158,74 +543,683 @@ impl Profile {
                     "pkg-config".to_string(),
                     "git".to_string(),
                 ],
+                system_arm64: vec![],
+                system_x86_64: vec![],
+                apt_repositories: vec![],
                 rust: RustConfig::default(),
                 cargo: vec![],
             },
             environment: HashMap::new(),
+            environment_passthrough: vec![],
+            tools: ToolsConfig::default(),
+            hooks: HooksConfig::default(),
+            system: SystemConfig::default(),
+            dotfiles: None,
+            docker: None,
+            user_data_extra: None,
+            allow_raw_user_data: false,
         }
     }
 
+    /// Template for Rust work: a compute-optimized instance with room for `target/`
+    pub fn rust_profile() -> Self {
+        let mut profile = Self::default_profile();
+        profile.name = "rust".to_string();
+        profile.instance.instance_type = "c6i.2xlarge".to_string();
+        profile.instance.fallback_types = vec!["c5.2xlarge".to_string()];
+        profile.instance.storage.root_volume.size_gb = 60;
+        profile
+            .packages
+            .system
+            .extend(["clang", "lld"].map(String::from));
+        profile
+            .packages
+            .rust
+            .components
+            .push("rust-src".to_string());
+        profile
+    }
+
+    /// Template for Python work, with pip, venv, and headers for native extensions
+    pub fn python_profile() -> Self {
+        Self::toolchain_profile(
+            "python",
+            &["python3", "python3-pip", "python3-venv", "python3-dev"],
+        )
+    }
+
+    /// Template for Node.js work, with npm
+    pub fn node_profile() -> Self {
+        Self::toolchain_profile("node", &["nodejs", "npm"])
+    }
+
+    /// Template for Go work
+    pub fn go_profile() -> Self {
+        Self::toolchain_profile("go", &["golang-go"])
+    }
+
+    /// Template with only git: a small instance and no toolchains or extra tools
+    pub fn minimal_profile() -> Self {
+        let mut profile = Self::default_profile();
+        profile.name = "minimal".to_string();
+        profile.instance.instance_type = "t3.medium".to_string();
+        profile.instance.fallback_types = vec!["t3.small".to_string()];
+        profile.instance.storage.root_volume.size_gb = 20;
+        profile.packages.system = vec!["git".to_string()];
+        profile.packages.rust.enabled = false;
+        profile.tools.agentfs = false;
+        profile
+    }
+
+    /// The default profile with Rust swapped for another language's system packages
+    fn toolchain_profile(name: &str, packages: &[&str]) -> Self {
+        let mut profile = Self::default_profile();
+        profile.name = name.to_string();
+        profile
+            .packages
+            .system
+            .extend(packages.iter().map(|p| p.to_string()));
+        profile.packages.rust.enabled = false;
+        profile
+    }
+
+    /// The raw user data script to run, if it has been explicitly allowed
+    pub fn raw_user_data(&self) -> Option<&str> {
+        self.user_data_extra
+            .as_deref()
+            .filter(|_| self.allow_raw_user_data)
+    }
+
+    /// Swap the default x86_64 instance types for Graviton equivalents on arm64 profiles.
+    /// Explicitly chosen types are left alone and caught by `validate`.
+    pub fn apply_architecture_defaults(&mut self) {
+        if self.instance.ami.architecture != "arm64" {
+            return;
+        }
+
+        let defaults = InstanceConfig::default();
+        if self.instance.instance_type == defaults.instance_type {
+            if let Some(arm) = graviton_equivalent(&self.instance.instance_type) {
+                self.instance.instance_type = arm;
+            }
+        }
+        if self.instance.fallback_types == defaults.fallback_types {
+            self.instance.fallback_types = defaults
+                .fallback_types
+                .iter()
+                .map(|t| graviton_equivalent(t).unwrap_or_else(|| t.clone()))
+                .collect();
+        }
+    }
+
+    /// Check the profile, failing with the first problem found
     pub fn validate(&self) -> crate::Result<()> {
+        match self.validation_errors().into_iter().next() {
+            Some(problem) => Err(crate::Ec2CliError::ProfileValidation(problem)),
+            None => Ok(()),
+        }
+    }
+
+    /// Every problem `validate` checks for, in the order it checks them
+    fn validation_errors(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+
         if self.name.is_empty() {
-            return Err(crate::Ec2CliError::ProfileValidation(
-                "Profile name cannot be empty".to_string(),
-            ));
+            errors.push("Profile name cannot be empty".to_string());
         }
 
         if self.instance.instance_type.is_empty() {
-            return Err(crate::Ec2CliError::ProfileValidation(
-                "Instance type cannot be empty".to_string(),
-            ));
+            errors.push("Instance type cannot be empty".to_string());
         }
 
         if self.instance.storage.root_volume.size_gb < 8 {
-            return Err(crate::Ec2CliError::ProfileValidation(
-                "Root volume size must be at least 8 GB".to_string(),
-            ));
+            errors.push("Root volume size must be at least 8 GB".to_string());
         }
 
         if self.instance.storage.root_volume.size_gb > 16384 {
-            return Err(crate::Ec2CliError::ProfileValidation(
-                "Root volume size cannot exceed 16384 GB".to_string(),
-            ));
+            errors.push("Root volume size cannot exceed 16384 GB".to_string());
         }
 
         let valid_volume_types = ["gp2", "gp3", "io1", "io2", "st1", "sc1"];
         if !valid_volume_types.contains(&self.instance.storage.root_volume.volume_type.as_str()) {
-            return Err(crate::Ec2CliError::ProfileValidation(format!(
+            errors.push(format!(
                 "Invalid volume type: {}. Valid types: {:?}",
                 self.instance.storage.root_volume.volume_type, valid_volume_types
-            )));
+            ));
+        }
+
+        if let Some(ref key) = self.instance.storage.root_volume.kms_key_id {
+            if !is_valid_kms_key_id(key) {
+                errors.push(format!(
+                    "Invalid kms_key_id: '{}'. Use a key ID, alias/name, or key or alias ARN",
+                    key
+                ));
+            }
         }
 
         let valid_architectures = ["x86_64", "arm64"];
         if !valid_architectures.contains(&self.instance.ami.architecture.as_str()) {
-            return Err(crate::Ec2CliError::ProfileValidation(format!(
+            errors.push(format!(
                 "Invalid architecture: {}. Valid: {:?}",
                 self.instance.ami.architecture, valid_architectures
-            )));
+            ));
+        }
+
+        // Only the first mismatch; `lint` names the field of every one
+        let mismatch = std::iter::once(&self.instance.instance_type)
+            .chain(self.instance.fallback_types.iter())
+            .find_map(|t| instance_type_arch_mismatch(t, &self.instance.ami.architecture));
+        errors.extend(mismatch);
+
+        if let Some(ref command) = self.hooks.on_push {
+            if command.trim().is_empty() {
+                errors.push("hooks.on_push cannot be empty".to_string());
+            } else if command.contains(['\n', '\r', '\0']) {
+                // The command is embedded in the hook script as a single quoted line
+                errors.push(
+                    "hooks.on_push must be a single line (call a script for several commands)"
+                        .to_string(),
+                );
+            } else if !self.allow_raw_user_data {
+                // Shell syntax runs unchecked in the push hook, so it needs the same
+                // opt-in as user_data_extra
+                if let Err(e) = crate::user_data::validate_shell_safe(
+                    command,
+                    "hooks.on_push (set allow_raw_user_data to allow shell syntax)",
+                ) {
+                    errors.push(validation_message(e));
+                }
+            }
         }
 
         let valid_ami_types = ["ubuntu-22.04", "ubuntu-24.04"];
         if self.instance.ami.id.is_none()
             && !valid_ami_types.contains(&self.instance.ami.ami_type.as_str())
         {
-            return Err(crate::Ec2CliError::ProfileValidation(format!(
+            errors.push(format!(
                 "Invalid AMI type: {}. Valid: {:?}",
                 self.instance.ami.ami_type, valid_ami_types
-            )));
+            ));
         }
 
         let valid_rust_channels = ["stable", "beta", "nightly"];
         if self.packages.rust.enabled
             && !valid_rust_channels.contains(&self.packages.rust.channel.as_str())
         {
-            return Err(crate::Ec2CliError::ProfileValidation(format!(
+            errors.push(format!(
                 "Invalid Rust channel: {}. Valid: {:?}",
                 self.packages.rust.channel, valid_rust_channels
-            )));
+            ));
         }
 
-        Ok(())
+        if let Err(e) = self.system.validate() {
+            errors.push(validation_message(e));
+        }
+
+        // The swapfile lives on the root volume, which also needs room for the system
+        if let Some(swap_gb) = self.system.swap_gb {
+            let root_gb = self.instance.storage.root_volume.size_gb;
+            if swap_gb == 0 || swap_gb >= root_gb {
+                errors.push(format!(
+                    "system.swap_gb must be between 1 and {} (less than the {} GB root volume)",
+                    root_gb.saturating_sub(1),
+                    root_gb
+                ));
+            }
+        }
+
+        errors
+    }
+
+    /// Every problem `validate` checks for, plus checks it leaves to launch
+    /// time: the format of each instance type (fallbacks are only tried when the
+    /// primary type is unavailable) and repeated fallbacks. Empty if none.
+    pub fn lint(&self) -> Vec<String> {
+        let mut issues = self.validation_errors();
+
+        let instance = &self.instance;
+        let types = std::iter::once(("instance.type", &instance.instance_type)).chain(
+            instance
+                .fallback_types
+                .iter()
+                .map(|t| ("instance.fallback_types", t)),
+        );
+        let mut seen = Vec::new();
+        for (field, instance_type) in types {
+            if !is_valid_instance_type(instance_type) {
+                issues.push(format!(
+                    "{}: '{}' is not a valid instance type (expected e.g. t3.large)",
+                    field, instance_type
+                ));
+            } else if let Some(problem) =
+                instance_type_arch_mismatch(instance_type, &instance.ami.architecture)
+            {
+                // `validate` already reported the first mismatch
+                if !issues.contains(&problem) {
+                    issues.push(format!("{}: {}", field, problem));
+                }
+            }
+            if seen.contains(&instance_type) {
+                issues.push(format!(
+                    "{}: '{}' is listed more than once",
+                    field, instance_type
+                ));
+            }
+            seen.push(instance_type);
+        }
+        issues
+    }
+}
+
+/// The message of a validation error, without the "Profile validation" prefix
+fn validation_message(e: crate::Ec2CliError) -> String {
+    match e {
+        crate::Ec2CliError::ProfileValidation(msg) => msg,
+        e => e.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_minimal_profile_defaults() {
+        let profile: Profile = json5::from_str(r#"{ "name": "x" }"#).unwrap();
+
+        assert_eq!(profile.name, "x");
+        assert_eq!(profile.instance.instance_type, "t3.large");
+        assert_eq!(profile.instance.fallback_types, vec!["t3.medium"]);
+        assert_eq!(profile.instance.ami.ami_type, "ubuntu-24.04");
+        assert_eq!(profile.instance.ami.architecture, "x86_64");
+        assert_eq!(profile.instance.ami.id, None);
+
+        let root_volume = &profile.instance.storage.root_volume;
+        assert_eq!(root_volume.size_gb, 30);
+        assert_eq!(root_volume.volume_type, "gp3");
+        assert_eq!(root_volume.iops, Some(3000));
+        assert_eq!(root_volume.throughput, Some(125));
+
+        assert!(profile.packages.system.is_empty());
+        assert!(profile.packages.rust.enabled);
+        assert_eq!(profile.packages.rust.channel, "stable");
+        assert_eq!(profile.packages.rust.components, vec!["rustfmt", "clippy"]);
+        assert!(profile.packages.cargo.is_empty());
+        assert!(profile.environment.is_empty());
+        assert!(profile.tools.agentfs);
+
+        profile.validate().unwrap();
+    }
+
+    #[test]
+    fn test_partial_root_volume_uses_field_defaults() {
+        let profile: Profile =
+            json5::from_str(r#"{ name: "x", instance: { storage: { root_volume: {} } } }"#)
+                .unwrap();
+
+        assert_eq!(
+            profile.instance.storage.root_volume,
+            RootVolumeConfig::default()
+        );
+    }
+
+    #[test]
+    fn test_arm64_substitutes_default_instance_types() {
+        let mut profile: Profile =
+            json5::from_str(r#"{ name: "x", instance: { ami: { architecture: "arm64" } } }"#)
+                .unwrap();
+        profile.apply_architecture_defaults();
+
+        assert_eq!(profile.instance.instance_type, "t4g.large");
+        profile.validate().unwrap();
+
+        let mut profile = Profile::default_profile();
+        profile.instance.ami.architecture = "arm64".to_string();
+        profile.apply_architecture_defaults();
+
+        assert_eq!(profile.instance.instance_type, "t4g.large");
+        assert_eq!(profile.instance.fallback_types, vec!["t4g.medium"]);
+        profile.validate().unwrap();
+    }
+
+    #[test]
+    fn test_arm64_rejects_explicit_x86_instance_type() {
+        let mut profile = Profile::default_profile();
+        profile.instance.instance_type = "c5.xlarge".to_string();
+        profile.instance.ami.architecture = "arm64".to_string();
+        profile.apply_architecture_defaults();
+
+        assert_eq!(profile.instance.instance_type, "c5.xlarge");
+        let err = profile.validate().unwrap_err().to_string();
+        assert!(err.contains("c6g.xlarge"));
+    }
+
+    #[test]
+    fn test_graviton_equivalent() {
+        assert_eq!(
+            graviton_equivalent("t3.large").as_deref(),
+            Some("t4g.large")
+        );
+        assert_eq!(
+            graviton_equivalent("m5.2xlarge").as_deref(),
+            Some("m6g.2xlarge")
+        );
+        assert_eq!(graviton_equivalent("t4g.large"), None);
+        assert_eq!(graviton_equivalent("t3"), None);
+    }
+
+    #[test]
+    fn test_instance_type_arch_mismatch() {
+        assert_eq!(instance_type_arch_mismatch("t3.large", "x86_64"), None);
+        assert_eq!(instance_type_arch_mismatch("t4g.large", "arm64"), None);
+        assert!(instance_type_arch_mismatch("t3.large", "arm64")
+            .unwrap()
+            .contains("Try t4g.large"));
+        assert!(instance_type_arch_mismatch("m6g.xlarge", "x86_64").is_some());
+        // Families we don't know about are left for AWS to reject
+        assert_eq!(instance_type_arch_mismatch("x2idn.large", "arm64"), None);
+    }
+
+    #[test]
+    fn test_lint_reports_each_architecture_mismatch_once() {
+        let mut profile = Profile::default_profile();
+        profile.instance.ami.architecture = "arm64".to_string();
+        profile.instance.instance_type = "t4g.large".to_string();
+        profile.instance.fallback_types = vec!["c6i.large".to_string(), "m6i.large".to_string()];
+
+        let issues = profile.lint();
+        assert_eq!(issues.len(), 2, "{:?}", issues);
+        assert!(issues[0].starts_with("Instance type c6i.large is not available for arm64"));
+        assert!(issues[1].starts_with("instance.fallback_types: Instance type m6i.large"));
+
+        assert!(Profile::default_profile().lint().is_empty());
+    }
+
+    #[test]
+    fn test_lint_reports_every_validation_error() {
+        let mut profile = Profile::default_profile();
+        profile.instance.storage.root_volume.size_gb = 4;
+        profile.packages.rust.channel = "nightly-2020".to_string();
+
+        // validate stops at the first problem, lint lists both
+        let err = profile.validate().unwrap_err();
+        assert!(err.to_string().contains("at least 8 GB"));
+        let issues = profile.lint();
+        assert_eq!(issues.len(), 2, "{:?}", issues);
+        assert_eq!(issues[0], "Root volume size must be at least 8 GB");
+        assert!(issues[1].starts_with("Invalid Rust channel: nightly-2020"));
+    }
+
+    #[test]
+    fn test_is_valid_instance_type() {
+        assert!(is_valid_instance_type("t3.large"));
+        assert!(is_valid_instance_type("m7g.2xlarge"));
+        assert!(is_valid_instance_type("u-6tb1.metal"));
+        assert!(!is_valid_instance_type("t3"));
+        assert!(!is_valid_instance_type("T3.large"));
+        assert!(!is_valid_instance_type("t3.large; rm"));
+        assert!(!is_valid_instance_type(".large"));
+    }
+
+    #[test]
+    fn test_cargo_packages_accept_names_and_specs() {
+        let profile: Profile = json5::from_str(
+            r#"{
+                name: "x",
+                packages: {
+                    cargo: [
+                        "cargo-watch",
+                        { name: "cargo-nextest", version: "0.9", locked: true },
+                        { name: "tool", features: ["a", "b"], git: "https://github.com/o/tool" },
+                    ],
+                },
+            }"#,
+        )
+        .unwrap();
+
+        let cargo = &profile.packages.cargo;
+        assert_eq!(cargo[0], CargoPackage::from("cargo-watch"));
+        assert_eq!(
+            cargo[1],
+            CargoPackage::Spec(CargoPackageSpec {
+                name: "cargo-nextest".to_string(),
+                version: Some("0.9".to_string()),
+                locked: true,
+                ..Default::default()
+            })
+        );
+        assert_eq!(cargo[2].name(), "tool");
+    }
+
+    #[test]
+    fn test_hook_placeholder_substitution() {
+        let rendered = HooksConfig::render(
+            "notify {name} {instance_id} --region {region} {unknown}",
+            "dev",
+            "i-0123456789abcdef0",
+            "us-east-1",
+        );
+        assert_eq!(
+            rendered,
+            "notify dev i-0123456789abcdef0 --region us-east-1 {unknown}"
+        );
+    }
+
+    #[test]
+    fn test_hook_values_are_quoted() {
+        let rendered = HooksConfig::render("echo {name}", "x; rm -rf ~", "i-1", "r");
+        assert_eq!(rendered, "echo 'x; rm -rf ~'");
+
+        // Placeholders inside substituted values are left alone
+        let rendered = HooksConfig::render("echo {name}", "{region}", "i-1", "us-east-1");
+        assert_eq!(rendered, "echo '{region}'");
+    }
+
+    #[test]
+    fn test_on_push_must_be_single_line() {
+        let mut profile = Profile::default_profile();
+        profile.hooks.on_push = Some("cargo build --release".to_string());
+        profile.validate().unwrap();
+
+        profile.hooks.on_push = Some("cargo build\nHOOKEOF".to_string());
+        assert!(profile.validate().is_err());
+        profile.hooks.on_push = Some("  ".to_string());
+        assert!(profile.validate().is_err());
+    }
+
+    #[test]
+    fn test_on_push_shell_syntax_needs_raw_opt_in() {
+        let mut profile = Profile::default_profile();
+        for command in [
+            "cargo build --release && ./restart.sh",
+            "make; rm -rf /tmp/x",
+            "echo 'built'",
+            "curl $URL | sh",
+            "make > /dev/null",
+        ] {
+            profile.hooks.on_push = Some(command.to_string());
+            assert!(
+                matches!(profile.validate(), Err(crate::Ec2CliError::ProfileValidation(ref msg)) if msg.contains("allow_raw_user_data")),
+                "{}",
+                command
+            );
+        }
+
+        // Opting in allows shell syntax, but still only on one line
+        profile.allow_raw_user_data = true;
+        profile.hooks.on_push = Some("cargo build --release && ./restart.sh".to_string());
+        profile.validate().unwrap();
+        profile.hooks.on_push = Some("make\nHOOKEOF".to_string());
+        assert!(profile.validate().is_err());
+    }
+
+    #[test]
+    fn test_system_config() {
+        let profile: Profile = json5::from_str(
+            r#"{ name: "x", system: { timezone: "Europe/Berlin", locale: "de_DE.UTF-8" } }"#,
+        )
+        .unwrap();
+        assert_eq!(profile.system.timezone.as_deref(), Some("Europe/Berlin"));
+        assert_eq!(profile.system.locale.as_deref(), Some("de_DE.UTF-8"));
+        profile.validate().unwrap();
+
+        let mut profile = Profile::default_profile();
+        profile.system.timezone = Some("Europe/Berlin; reboot".to_string());
+        let err = profile.validate().unwrap_err().to_string();
+        assert!(err.contains("Invalid timezone"));
+    }
+
+    #[test]
+    fn test_swap_must_fit_on_root_volume() {
+        let mut profile = Profile::default_profile();
+        profile.instance.storage.root_volume.size_gb = 30;
+
+        profile.system.swap_gb = Some(8);
+        profile.validate().unwrap();
+
+        profile.system.swap_gb = Some(30);
+        assert!(profile.validate().is_err());
+        profile.system.swap_gb = Some(64);
+        let err = profile.validate().unwrap_err().to_string();
+        assert!(err.contains("swap_gb"));
+        profile.system.swap_gb = Some(0);
+        assert!(profile.validate().is_err());
+    }
+
+    #[test]
+    fn test_timezone_format() {
+        for timezone in [
+            "UTC",
+            "America/New_York",
+            "America/Argentina/Buenos_Aires",
+            "Etc/GMT+3",
+        ] {
+            assert!(is_valid_timezone(timezone), "{}", timezone);
+        }
+        for timezone in [
+            "",
+            "/etc/passwd",
+            "America/",
+            "../UTC",
+            "UTC $(id)",
+            "Europe/Berlin\n",
+        ] {
+            assert!(!is_valid_timezone(timezone), "{}", timezone);
+        }
+    }
+
+    #[test]
+    fn test_locale_format() {
+        for locale in ["en_US", "en_US.UTF-8", "ast_ES.UTF-8", "de_DE.UTF-8@euro"] {
+            assert!(is_valid_locale(locale), "{}", locale);
+        }
+        for locale in [
+            "",
+            "en",
+            "EN_us.UTF-8",
+            "en_US.",
+            "en_US@",
+            "en_US.UTF-8;id",
+            "en_US UTF-8",
+        ] {
+            assert!(!is_valid_locale(locale), "{}", locale);
+        }
+    }
+
+    #[test]
+    fn test_kms_key_id_format() {
+        for key in [
+            "1234abcd-12ab-34cd-56ef-1234567890ab",
+            "mrk-1234abcd12ab34cd56ef1234567890ab",
+            "alias/ebs-dev",
+            "arn:aws:kms:us-east-1:111122223333:key/1234abcd-12ab-34cd-56ef-1234567890ab",
+            "arn:aws-us-gov:kms:us-gov-west-1:111122223333:alias/team/ebs",
+        ] {
+            assert!(is_valid_kms_key_id(key), "{}", key);
+        }
+        for key in [
+            "",
+            "alias/",
+            "my-key",
+            "1234abcd-12ab-34cd-56ef",
+            "arn:aws:kms:us-east-1:1111:key/1234abcd-12ab-34cd-56ef-1234567890ab",
+            "arn:aws:s3:us-east-1:111122223333:key/1234abcd-12ab-34cd-56ef-1234567890ab",
+            "alias/ebs; reboot",
+        ] {
+            assert!(!is_valid_kms_key_id(key), "{}", key);
+        }
+
+        let mut profile = Profile::default_profile();
+        profile.instance.storage.root_volume.kms_key_id = Some("not-a-key".to_string());
+        assert!(profile.validate().is_err());
+    }
+
+    #[test]
+    fn test_templates_validate() {
+        let templates = [
+            Profile::rust_profile(),
+            Profile::python_profile(),
+            Profile::node_profile(),
+            Profile::go_profile(),
+            Profile::minimal_profile(),
+        ];
+        for profile in templates {
+            profile.validate().unwrap();
+
+            // Templates survive being written out and read back as a profile file
+            let json = serde_json::to_string(&profile).unwrap();
+            let parsed: Profile = json5::from_str(&json).unwrap();
+            assert_eq!(parsed, profile);
+        }
+    }
+
+    #[test]
+    fn test_template_packages() {
+        let system = |profile: &Profile| profile.packages.system.clone();
+        let base = system(&Profile::default_profile());
+        let with = |extra: &[&str]| {
+            let mut packages = base.clone();
+            packages.extend(extra.iter().map(|p| p.to_string()));
+            packages
+        };
+
+        let rust = Profile::rust_profile();
+        assert_eq!(system(&rust), with(&["clang", "lld"]));
+        assert!(rust.packages.rust.enabled);
+        assert!(rust
+            .packages
+            .rust
+            .components
+            .contains(&"rust-src".to_string()));
+
+        let python = Profile::python_profile();
+        assert_eq!(
+            system(&python),
+            with(&["python3", "python3-pip", "python3-venv", "python3-dev"])
+        );
+        assert!(!python.packages.rust.enabled);
+
+        let node = Profile::node_profile();
+        assert_eq!(system(&node), with(&["nodejs", "npm"]));
+        assert!(!node.packages.rust.enabled);
+
+        let go = Profile::go_profile();
+        assert_eq!(system(&go), with(&["golang-go"]));
+        assert!(!go.packages.rust.enabled);
+
+        let minimal = Profile::minimal_profile();
+        assert_eq!(system(&minimal), vec!["git"]);
+        assert!(!minimal.packages.rust.enabled);
+        assert!(!minimal.tools.agentfs);
+        assert_eq!(minimal.instance.instance_type, "t3.medium");
+    }
+
+    #[test]
+    fn test_default_profile_round_trip() {
+        let profile = Profile::default_profile();
+        let json = serde_json::to_string(&profile).unwrap();
+        let parsed: Profile = json5::from_str(&json).unwrap();
+
+        assert_eq!(parsed, profile);
     }
 }
@@ -1,4 +1,4 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use crate::{Ec2CliError, Result};
 
@@ -10,8 +10,8 @@ const STANDARD_KEY_NAMES: &[&str] = &["id_ed25519", "id_rsa", "id_ecdsa"];
 pub struct SshKeyInfo {
     /// The public key content (for injection into authorized_keys)
     pub public_key: String,
-    /// Path to the private key file (for -i flag in SSH commands)
-    pub private_key_path: PathBuf,
+    /// Path to the matching private key file (for -i flag in SSH commands), if one exists
+    pub private_key_path: Option<PathBuf>,
 }
 
 /// Find and load the user's SSH public key.
@@ -22,7 +22,8 @@ pub struct SshKeyInfo {
 /// 3. `~/.ssh/id_rsa.pub` (legacy but common)
 /// 4. `~/.ssh/id_ecdsa.pub` (ECDSA keys)
 ///
-/// Returns both the public key content and the path to the private key.
+/// Returns the public key content and, when it exists alongside the public key,
+/// the path to the private key.
 pub fn find_ssh_public_key() -> Result<SshKeyInfo> {
     let mut checked_paths = Vec::new();
 
@@ -31,12 +32,11 @@ pub fn find_ssh_public_key() -> Result<SshKeyInfo> {
         let local_key_path = cwd.join(".ec2-cli").join("ssh_public_key");
         match try_load_key(&local_key_path) {
             Ok(key) => {
-                // For project-local keys, derive private key path by removing .pub
-                // or use the same path if it doesn't end in .pub (user provides private key path separately)
-                let private_key_path = derive_private_key_path(&local_key_path);
+                // The project-local key has no .pub suffix, so there's no private key
+                // to pair it with; SSH falls back to the agent and its default keys
                 return Ok(SshKeyInfo {
                     public_key: key,
-                    private_key_path,
+                    private_key_path: existing_private_key_path(&local_key_path),
                 });
             }
             Err(LoadKeyError::NotFound) => {
@@ -61,11 +61,9 @@ pub fn find_ssh_public_key() -> Result<SshKeyInfo> {
             let pub_path = home.join(".ssh").join(format!("{}.pub", key_name));
             match try_load_key(&pub_path) {
                 Ok(key) => {
-                    // Private key is the same path without .pub extension
-                    let private_key_path = home.join(".ssh").join(*key_name);
                     return Ok(SshKeyInfo {
                         public_key: key,
-                        private_key_path,
+                        private_key_path: existing_private_key_path(&pub_path),
                     });
                 }
                 Err(LoadKeyError::NotFound) => {
@@ -88,28 +86,21 @@ pub fn find_ssh_public_key() -> Result<SshKeyInfo> {
     Err(Ec2CliError::SshKeyNotFound(checked_paths.join(", ")))
 }
 
-/// Derive the private key path from a public key path.
-/// If the path ends in .pub, strip it. Otherwise, assume a separate private key config.
-fn derive_private_key_path(pub_key_path: &std::path::Path) -> PathBuf {
-    let path_str = pub_key_path.to_string_lossy();
-    if let Some(stripped) = path_str.strip_suffix(".pub") {
-        PathBuf::from(stripped)
-    } else {
-        // For project-local keys without .pub extension, check for common private key locations
-        // First try the same directory with common private key names
-        if let Some(parent) = pub_key_path.parent() {
-            for key_name in STANDARD_KEY_NAMES {
-                let candidate = parent.join(key_name);
-                if candidate.exists() {
-                    return candidate;
-                }
-            }
-        }
-        // Fall back to default ssh key
-        home_dir()
-            .map(|h| h.join(".ssh").join("id_ed25519"))
-            .unwrap_or_else(|| PathBuf::from("~/.ssh/id_ed25519"))
-    }
+/// Map a public key path to its private key path by stripping the `.pub` extension.
+///
+/// Returns `None` for paths without a `.pub` extension, since there's no
+/// conventional private key name to derive.
+pub fn public_to_private_key_path(pub_key_path: &Path) -> Option<PathBuf> {
+    let file_name = pub_key_path.file_name()?.to_str()?;
+    let stem = file_name
+        .strip_suffix(".pub")
+        .filter(|stem| !stem.is_empty())?;
+    Some(pub_key_path.with_file_name(stem))
+}
+
+/// The private key matching `pub_key_path`, if it exists on disk
+fn existing_private_key_path(pub_key_path: &Path) -> Option<PathBuf> {
+    public_to_private_key_path(pub_key_path).filter(|path| path.is_file())
 }
 
 /// Internal error type for key loading
@@ -254,6 +245,52 @@ mod tests {
         assert!(validate_ssh_key_format(key).is_err());
     }
 
+    #[test]
+    fn test_public_to_private_key_path() {
+        assert_eq!(
+            public_to_private_key_path(Path::new("/home/dev/.ssh/id_ed25519.pub")),
+            Some(PathBuf::from("/home/dev/.ssh/id_ed25519"))
+        );
+        assert_eq!(
+            public_to_private_key_path(Path::new("keys/deploy.key.pub")),
+            Some(PathBuf::from("keys/deploy.key"))
+        );
+    }
+
+    #[test]
+    fn test_public_to_private_key_path_without_pub_extension() {
+        assert_eq!(
+            public_to_private_key_path(Path::new("/work/.ec2-cli/ssh_public_key")),
+            None
+        );
+        assert_eq!(
+            public_to_private_key_path(Path::new("/home/dev/.pub")),
+            None
+        );
+        // Only the file name is considered
+        assert_eq!(
+            public_to_private_key_path(Path::new("/home/dev.pub/key")),
+            None
+        );
+    }
+
+    #[test]
+    fn test_existing_private_key_path_requires_file() {
+        let dir = std::env::temp_dir().join(format!("ec2-cli-key-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let pub_path = dir.join("id_ed25519.pub");
+
+        assert_eq!(existing_private_key_path(&pub_path), None);
+
+        std::fs::write(dir.join("id_ed25519"), "private").unwrap();
+        assert_eq!(
+            existing_private_key_path(&pub_path),
+            Some(dir.join("id_ed25519"))
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
     #[test]
     fn test_invalid_base64_characters() {
         let key = "ssh-rsa AAAAB3NzaC1yc2!@#$%^&*()EAAAADAQABAAABgQDKJv9EJa0VR5n5x5X5x5X5x5X5x5X5x5X5x5X5x5X5x5X5x5X5 user@host";
@@ -0,0 +1,110 @@
+use directories::ProjectDirs;
+use std::path::PathBuf;
+
+use crate::{Ec2CliError, Result};
+
+/// Get the path to the managed known_hosts file.
+///
+/// Entries are keyed by instance ID, since SSH connections target the
+/// instance ID as the hostname and route through the SSM proxy.
+pub fn known_hosts_path() -> Option<PathBuf> {
+    ProjectDirs::from("", "", "ec2-cli").map(|dirs| dirs.config_dir().join("known_hosts"))
+}
+
+/// Build the `-o` options controlling host key verification.
+///
+/// Without `verify_host`, host keys are neither checked nor recorded, which suits
+/// short-lived instances. With `verify_host`, the key is pinned in the managed
+/// known_hosts file on first connect and enforced on subsequent connects.
+pub fn host_key_options(verify_host: bool) -> Result<Vec<String>> {
+    if !verify_host {
+        return Ok(vec![
+            "-o".to_string(),
+            "StrictHostKeyChecking=no".to_string(),
+            "-o".to_string(),
+            "UserKnownHostsFile=/dev/null".to_string(),
+        ]);
+    }
+
+    let path = known_hosts_path()
+        .ok_or_else(|| Ec2CliError::Config("Cannot determine config directory".to_string()))?;
+
+    // Ensure directory exists so ssh can create the file on first connect
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    Ok(vec![
+        "-o".to_string(),
+        "StrictHostKeyChecking=accept-new".to_string(),
+        "-o".to_string(),
+        format!("UserKnownHostsFile={}", path.display()),
+        "-o".to_string(),
+        "HashKnownHosts=no".to_string(),
+    ])
+}
+
+/// Remove any pinned host keys for an instance from the managed known_hosts file.
+///
+/// Returns true if an entry was removed.
+pub fn remove_host(instance_id: &str) -> Result<bool> {
+    let path = match known_hosts_path() {
+        Some(path) if path.exists() => path,
+        _ => return Ok(false),
+    };
+
+    let content = std::fs::read_to_string(&path)?;
+    let filtered = filter_host_entries(&content, instance_id);
+
+    if filtered == content {
+        return Ok(false);
+    }
+
+    std::fs::write(&path, filtered)?;
+    Ok(true)
+}
+
+/// Return `content` with all lines for `instance_id` removed.
+fn filter_host_entries(content: &str, instance_id: &str) -> String {
+    content
+        .lines()
+        .filter(|line| {
+            let hosts = line.split_whitespace().next().unwrap_or("");
+            !hosts.split(',').any(|host| host == instance_id)
+        })
+        .map(|line| format!("{}\n", line))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_permissive_options() {
+        let opts = host_key_options(false).unwrap();
+        assert!(opts.contains(&"StrictHostKeyChecking=no".to_string()));
+        assert!(opts.contains(&"UserKnownHostsFile=/dev/null".to_string()));
+    }
+
+    #[test]
+    fn test_filter_host_entries() {
+        let content = "i-0abc ssh-ed25519 AAAA1\ni-0def ssh-ed25519 AAAA2\ni-0abc ecdsa-sha2-nistp256 AAAA3\n";
+        assert_eq!(
+            filter_host_entries(content, "i-0abc"),
+            "i-0def ssh-ed25519 AAAA2\n"
+        );
+    }
+
+    #[test]
+    fn test_filter_host_entries_no_prefix_match() {
+        let content = "i-0abcd ssh-ed25519 AAAA1\n";
+        assert_eq!(filter_host_entries(content, "i-0abc"), content);
+    }
+
+    #[test]
+    fn test_filter_host_entries_comma_separated() {
+        let content = "i-0abc,other ssh-ed25519 AAAA1\n";
+        assert_eq!(filter_host_entries(content, "i-0abc"), "");
+    }
+}
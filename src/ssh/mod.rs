@@ -1,7 +1,148 @@
 mod key_loader;
+mod known_hosts;
+mod retry;
+
+use crate::config::Settings;
+use crate::Result;
 
 pub use key_loader::find_ssh_public_key;
+use known_hosts::host_key_options;
+pub use known_hosts::remove_host as remove_known_host;
+pub use retry::{
+    is_transient_session_error, run_capturing_stderr, run_with_session_retry, SSH_CONNECTION_FAILED,
+};
 
 /// SSM proxy command for SSH connections through Session Manager
 pub const SSM_PROXY_COMMAND: &str =
     "sh -c \"aws ssm start-session --target %h --document-name AWS-StartSSHSession --parameters portNumber=%p\"";
+
+/// Seconds to wait for an SSH connection when `ssh_connect_timeout` isn't set.
+/// Starting the SSM session takes a few seconds, so this is longer than a direct connection needs
+pub const DEFAULT_SSH_CONNECT_TIMEOUT_SECS: u64 = 30;
+
+/// Seconds between keepalive probes when `ssh_keepalive_interval` isn't set
+pub const DEFAULT_SSH_KEEPALIVE_INTERVAL_SECS: u64 = 15;
+
+/// Unanswered keepalive probes before ssh drops the connection
+const SSH_KEEPALIVE_COUNT_MAX: u32 = 4;
+
+/// Get the SSH ProxyCommand used to reach instances.
+///
+/// Uses the AWS CLI by default, with the configured `aws_profile` if any. With
+/// `native_ssm_proxy` enabled in settings, routes through `ec2-cli ssm-proxy`, which
+/// only needs the session-manager-plugin and reads `aws_profile` itself.
+pub fn ssm_proxy_command() -> String {
+    proxy_command(&Settings::load().unwrap_or_default())
+}
+
+fn proxy_command(settings: &Settings) -> String {
+    if settings.native_ssm_proxy {
+        if let Ok(exe) = std::env::current_exe() {
+            return format!(
+                "{} ssm-proxy %h %p",
+                shell_escape::escape(exe.to_string_lossy())
+            );
+        }
+    }
+
+    match settings.aws_profile {
+        Some(ref profile) => {
+            let command = format!(
+                "aws ssm start-session --profile {} --target %h \
+                 --document-name AWS-StartSSHSession --parameters portNumber=%p",
+                shell_escape::escape(profile.into())
+            );
+            format!("sh -c {}", shell_escape::escape(command.into()))
+        }
+        None => SSM_PROXY_COMMAND.to_string(),
+    }
+}
+
+/// Build the `-o` options shared by ssh and scp connections to instances: the SSM
+/// ProxyCommand, connect timeout, keepalives, and host key handling
+pub fn ssm_ssh_opts(verify_host: bool) -> Result<Vec<String>> {
+    let settings = Settings::load().unwrap_or_default();
+    let mut opts = connection_options(&proxy_command(&settings), &settings);
+    opts.extend(host_key_options(verify_host)?);
+    Ok(opts)
+}
+
+/// ProxyCommand, timeout, and keepalive options, so a dropped SSM session fails
+/// instead of hanging
+fn connection_options(proxy_command: &str, settings: &Settings) -> Vec<String> {
+    let connect_timeout = settings
+        .ssh_connect_timeout
+        .unwrap_or(DEFAULT_SSH_CONNECT_TIMEOUT_SECS);
+    let keepalive_interval = settings
+        .ssh_keepalive_interval
+        .unwrap_or(DEFAULT_SSH_KEEPALIVE_INTERVAL_SECS);
+
+    [
+        format!("ProxyCommand={}", proxy_command),
+        format!("ConnectTimeout={}", connect_timeout),
+        format!("ServerAliveInterval={}", keepalive_interval),
+        format!("ServerAliveCountMax={}", SSH_KEEPALIVE_COUNT_MAX),
+    ]
+    .into_iter()
+    .flat_map(|option| ["-o".to_string(), option])
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_connection_options_defaults() {
+        let opts = connection_options("proxy %h", &Settings::default());
+        assert_eq!(
+            opts,
+            [
+                "-o",
+                "ProxyCommand=proxy %h",
+                "-o",
+                "ConnectTimeout=30",
+                "-o",
+                "ServerAliveInterval=15",
+                "-o",
+                "ServerAliveCountMax=4",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_proxy_command_uses_aws_profile() {
+        assert_eq!(proxy_command(&Settings::default()), SSM_PROXY_COMMAND);
+
+        let settings = Settings {
+            aws_profile: Some("work".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            proxy_command(&settings),
+            "sh -c 'aws ssm start-session --profile work --target %h \
+             --document-name AWS-StartSSHSession --parameters portNumber=%p'"
+        );
+
+        // Names the shell would split are quoted
+        let settings = Settings {
+            aws_profile: Some("my work".to_string()),
+            ..Default::default()
+        };
+        assert!(proxy_command(&settings).contains("--profile '\\''my work'\\''"));
+    }
+
+    #[test]
+    fn test_connection_options_from_settings() {
+        let settings = Settings {
+            ssh_connect_timeout: Some(60),
+            ssh_keepalive_interval: Some(5),
+            ..Default::default()
+        };
+        let opts = connection_options("proxy %h", &settings);
+        assert!(opts.contains(&"ConnectTimeout=60".to_string()));
+        assert!(opts.contains(&"ServerAliveInterval=5".to_string()));
+        // Every value is preceded by -o
+        assert!(opts.chunks(2).all(|pair| pair[0] == "-o"));
+    }
+}
@@ -0,0 +1,224 @@
+use std::io::{Read, Write};
+use std::process::{Command, ExitStatus, Stdio};
+use std::time::Duration;
+
+use crate::{Ec2CliError, Result};
+
+/// Exit status ssh (and so scp) uses when the connection itself fails
+pub const SSH_CONNECTION_FAILED: i32 = 255;
+
+/// Times a connection is attempted when the SSM session keeps failing to start
+const SESSION_ATTEMPTS: u32 = 3;
+
+/// Wait before the first retry, doubled for each retry after it
+const SESSION_RETRY_BACKOFF: Duration = Duration::from_secs(2);
+
+/// Output from ssh, the SSM proxy, or the StartSession API when a session failed to
+/// start for a reason that usually clears up on its own, e.g. an agent that has only
+/// just registered after `up`. Each one is printed before authentication, so nothing
+/// has run on the instance yet.
+const TRANSIENT_SESSION_ERRORS: &[&str] = &[
+    "TargetNotConnected",
+    "Cannot perform start session",
+    "Connection closed by UNKNOWN port 65535",
+    "kex_exchange_identification:",
+    "ssh_exchange_identification:",
+    "Connection timed out during banner exchange",
+];
+
+/// Whether an error message looks like an SSM session that didn't start but
+/// might on a retry
+pub fn is_transient_session_error(message: &str) -> bool {
+    TRANSIENT_SESSION_ERRORS
+        .iter()
+        .any(|pattern| message.contains(pattern))
+}
+
+/// Whether ssh exiting with `code` and printing `stderr` should be retried. Only
+/// connection failures count; a remote command's own exit status never does.
+fn should_retry(code: Option<i32>, stderr: &str) -> bool {
+    code == Some(SSH_CONNECTION_FAILED) && is_transient_session_error(stderr)
+}
+
+/// Run an ssh or scp command, retrying with a backoff while the SSM session fails
+/// to start for a transient reason.
+///
+/// Only use this for commands that are safe to start again: an interactive shell,
+/// or one of our own reads and `mkdir -p`s. A user's command or a file transfer
+/// goes through [`run_capturing_stderr`], since a session that drops partway
+/// through must not run it twice.
+///
+/// stderr is captured (and shown as it arrives when `echo_stderr` is set) and
+/// returned with the exit status of the last attempt. Gives up with
+/// `Ec2CliError::SshCommand` if the session never starts.
+pub fn run_with_session_retry(
+    cmd: &mut Command,
+    echo_stderr: bool,
+) -> Result<(ExitStatus, String)> {
+    retry_session(SESSION_ATTEMPTS, SESSION_RETRY_BACKOFF, || {
+        run_capturing_stderr(cmd, echo_stderr)
+    })
+}
+
+fn retry_session(
+    attempts: u32,
+    backoff: Duration,
+    mut attempt: impl FnMut() -> Result<(ExitStatus, String)>,
+) -> Result<(ExitStatus, String)> {
+    let mut delay = backoff;
+
+    for remaining in (0..attempts).rev() {
+        let (status, stderr) = attempt()?;
+        if !should_retry(status.code(), &stderr) {
+            return Ok((status, stderr));
+        }
+        if remaining == 0 {
+            return Err(Ec2CliError::SshCommand(format!(
+                "SSM session failed to start after {} attempts: {}",
+                attempts,
+                last_line(&stderr)
+            )));
+        }
+
+        eprintln!(
+            "SSM session failed to start; retrying in {}s...",
+            delay.as_secs()
+        );
+        std::thread::sleep(delay);
+        delay *= 2;
+    }
+
+    unreachable!("at least one attempt is made")
+}
+
+/// Run `cmd` once with stderr piped, copying it to our stderr as it arrives if
+/// `echo`. Returns the exit status and the captured stderr.
+pub fn run_capturing_stderr(cmd: &mut Command, echo: bool) -> Result<(ExitStatus, String)> {
+    let program = cmd.get_program().to_string_lossy().into_owned();
+    let mut child = cmd
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| Ec2CliError::SshCommand(format!("Failed to execute {}: {}", program, e)))?;
+
+    let mut captured = Vec::new();
+    if let Some(mut pipe) = child.stderr.take() {
+        let mut buf = [0u8; 4096];
+        loop {
+            match pipe.read(&mut buf) {
+                Ok(0) | Err(_) => break,
+                Ok(n) => {
+                    if echo {
+                        let _ = std::io::stderr().write_all(&buf[..n]);
+                    }
+                    captured.extend_from_slice(&buf[..n]);
+                }
+            }
+        }
+    }
+
+    let status = child
+        .wait()
+        .map_err(|e| Ec2CliError::SshCommand(format!("Failed to wait for {}: {}", program, e)))?;
+    Ok((status, String::from_utf8_lossy(&captured).into_owned()))
+}
+
+/// The last non-empty line of command output, for error messages
+fn last_line(output: &str) -> &str {
+    output
+        .lines()
+        .map(str::trim)
+        .rev()
+        .find(|line| !line.is_empty())
+        .unwrap_or("no error output")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    #[test]
+    fn test_retry_classification() {
+        // Session startup failures right after `up`
+        for stderr in [
+            "An error occurred (TargetNotConnected) when calling the StartSession operation: i-1 is not connected.\n",
+            "kex_exchange_identification: Connection closed by remote host\nConnection closed by UNKNOWN port 65535\n",
+            "ssh_exchange_identification: read: Connection reset by peer\n",
+            "Connection timed out during banner exchange\n",
+        ] {
+            assert!(should_retry(Some(SSH_CONNECTION_FAILED), stderr), "{}", stderr);
+        }
+
+        // Failures a retry won't fix
+        assert!(!should_retry(
+            Some(SSH_CONNECTION_FAILED),
+            "ubuntu@i-1: Permission denied (publickey).\n"
+        ));
+        assert!(!should_retry(
+            Some(SSH_CONNECTION_FAILED),
+            "Host key verification failed.\n"
+        ));
+        // A session dropped after it started may already have run something
+        assert!(!should_retry(
+            Some(SSH_CONNECTION_FAILED),
+            "client_loop: send disconnect: Connection reset by peer\n"
+        ));
+        assert!(!should_retry(
+            Some(SSH_CONNECTION_FAILED),
+            "Connection to i-1 closed by remote host.\n"
+        ));
+        // The remote command failing, even with a transient-looking message
+        assert!(!should_retry(Some(1), "TargetNotConnected\n"));
+        assert!(!should_retry(None, "TargetNotConnected"));
+    }
+
+    fn exit(code: i32) -> ExitStatus {
+        Command::new("sh")
+            .arg("-c")
+            .arg(format!("exit {}", code))
+            .status()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_retry_session_until_started() {
+        let attempts = Cell::new(0);
+        let (status, _) = retry_session(3, Duration::ZERO, || {
+            attempts.set(attempts.get() + 1);
+            if attempts.get() < 2 {
+                Ok((exit(255), "TargetNotConnected".to_string()))
+            } else {
+                Ok((exit(0), String::new()))
+            }
+        })
+        .unwrap();
+
+        assert!(status.success());
+        assert_eq!(attempts.get(), 2);
+    }
+
+    #[test]
+    fn test_retry_session_gives_up() {
+        let attempts = Cell::new(0);
+        let result = retry_session(3, Duration::ZERO, || {
+            attempts.set(attempts.get() + 1);
+            Ok((
+                exit(255),
+                "Connection closed by UNKNOWN port 65535\n".to_string(),
+            ))
+        });
+
+        assert!(matches!(result, Err(Ec2CliError::SshCommand(msg)) if msg.contains("3 attempts")));
+        assert_eq!(attempts.get(), 3);
+
+        // Other failures are returned without retrying
+        attempts.set(0);
+        let (status, _) = retry_session(3, Duration::ZERO, || {
+            attempts.set(attempts.get() + 1);
+            Ok((exit(255), "Permission denied (publickey).".to_string()))
+        })
+        .unwrap();
+        assert_eq!(status.code(), Some(255));
+        assert_eq!(attempts.get(), 1);
+    }
+}
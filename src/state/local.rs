@@ -3,13 +3,17 @@ use directories::ProjectDirs;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::io::Write;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 #[cfg(unix)]
 use std::os::unix::fs::OpenOptionsExt;
 
+use crate::config::Settings;
 use crate::{Ec2CliError, Result};
 
+/// Maximum length of an instance description, in characters
+pub const MAX_DESCRIPTION_LEN: usize = 256;
+
 /// State file structure
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct State {
@@ -29,15 +33,107 @@ pub struct InstanceState {
     /// Security group ID for cleanup on termination
     #[serde(default)]
     pub security_group_id: Option<String>,
+    /// Whether ec2-cli created the security group (and so deletes it on destroy)
+    /// rather than the user supplying an existing one
+    #[serde(default = "default_true")]
+    pub security_group_managed: bool,
     /// Path to the SSH private key used for this instance
     #[serde(default)]
     pub ssh_key_path: Option<String>,
+    /// When the instance was terminated, for entries kept with `destroy --keep-state`
+    #[serde(default)]
+    pub terminated_at: Option<DateTime<Utc>>,
+    /// Whether the instance was launched as a spot instance
+    #[serde(default)]
+    pub spot: bool,
+    /// Instance type the instance was launched with
+    #[serde(default)]
+    pub instance_type: Option<String>,
+    /// Root volume size in GB
+    #[serde(default)]
+    pub root_volume_gb: Option<u32>,
+    /// Private IP address assigned at launch
+    #[serde(default)]
+    pub private_ip: Option<String>,
+    /// When `ec2-cli ssh` last connected to the instance
+    #[serde(default)]
+    pub last_connected_at: Option<DateTime<Utc>>,
+    /// Free-form note on what the instance is for
+    #[serde(default)]
+    pub description: Option<String>,
+    /// AWS account the instance was launched in
+    #[serde(default)]
+    pub account_id: Option<String>,
+    /// Subnet the instance was launched into
+    #[serde(default)]
+    pub subnet_id: Option<String>,
+    /// Availability zone the instance was placed in (EBS volumes must match it)
+    #[serde(default)]
+    pub availability_zone: Option<String>,
+    /// Where the user data script logs its output, as configured at launch
+    #[serde(default)]
+    pub init_log_path: Option<String>,
 }
 
 fn default_username() -> String {
     "ubuntu".to_string()
 }
 
+fn default_true() -> bool {
+    true
+}
+
+impl InstanceState {
+    /// The security group `destroy` should delete: only one ec2-cli created
+    pub fn security_group_to_delete(&self) -> Option<&str> {
+        self.security_group_id
+            .as_deref()
+            .filter(|_| self.security_group_managed)
+    }
+
+    /// Validate a description: a single line of at most `MAX_DESCRIPTION_LEN` characters
+    pub fn validate_description(description: &str) -> Result<()> {
+        if description.trim().is_empty() {
+            return Err(Ec2CliError::InvalidDescription(
+                "description cannot be empty".to_string(),
+            ));
+        }
+        let len = description.chars().count();
+        if len > MAX_DESCRIPTION_LEN {
+            return Err(Ec2CliError::InvalidDescription(format!(
+                "description is {} characters, the maximum is {}",
+                len, MAX_DESCRIPTION_LEN
+            )));
+        }
+        if description.chars().any(|c| c.is_control()) {
+            return Err(Ec2CliError::InvalidDescription(
+                "description must be a single line without control characters".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Check that the current credentials are for the account the instance was
+    /// launched in. Entries without a recorded account always pass.
+    pub fn check_account(&self, current_account_id: &str) -> Result<()> {
+        match self.account_id {
+            Some(ref account_id) if account_id != current_account_id => {
+                let profile = Settings::load()
+                    .unwrap_or_default()
+                    .effective_aws_profile()
+                    .unwrap_or_else(|| "default".to_string());
+                Err(Ec2CliError::AccountMismatch(format!(
+                    "instance {} was launched in account {}, but the active AWS profile '{}' \
+                     is for account {}. Switch to a profile for the launching account with \
+                     the aws_profile setting or AWS_PROFILE",
+                    self.instance_id, account_id, profile, current_account_id
+                )))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
 impl State {
     /// Load state from file
     pub fn load() -> Result<Self> {
@@ -107,7 +203,19 @@ impl State {
                 created_at: Utc::now(),
                 username: username.to_string(),
                 security_group_id: Some(security_group_id.to_string()),
+                security_group_managed: true,
                 ssh_key_path: ssh_key_path.map(String::from),
+                terminated_at: None,
+                spot: false,
+                instance_type: None,
+                root_volume_gb: None,
+                private_ip: None,
+                last_connected_at: None,
+                description: None,
+                account_id: None,
+                subnet_id: None,
+                availability_zone: None,
+                init_log_path: None,
             },
         );
     }
@@ -121,6 +229,30 @@ impl State {
     pub fn get_instance(&self, name: &str) -> Option<&InstanceState> {
         self.instances.get(name)
     }
+
+    /// Mark an instance as terminated while keeping its entry.
+    /// Returns false if the instance isn't in state.
+    pub fn mark_terminated(&mut self, name: &str) -> bool {
+        match self.instances.get_mut(name) {
+            Some(instance) => {
+                instance.terminated_at = Some(Utc::now());
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Record that `ec2-cli ssh` connected to an instance.
+    /// Returns false if the instance isn't in state.
+    pub fn mark_connected(&mut self, name: &str) -> bool {
+        match self.instances.get_mut(name) {
+            Some(instance) => {
+                instance.last_connected_at = Some(Utc::now());
+                true
+            }
+            None => false,
+        }
+    }
 }
 
 /// Get the path to the state file
@@ -164,6 +296,16 @@ pub fn save_instance(
     state.save()
 }
 
+/// Update an existing instance entry in place (convenience function)
+pub fn update_instance(name: &str, update: impl FnOnce(&mut InstanceState)) -> Result<()> {
+    let mut state = State::load()?;
+    if let Some(instance) = state.instances.get_mut(name) {
+        update(instance);
+        state.save()?;
+    }
+    Ok(())
+}
+
 /// Remove an instance from state (convenience function)
 pub fn remove_instance(name: &str) -> Result<Option<InstanceState>> {
     let mut state = State::load()?;
@@ -172,10 +314,22 @@ pub fn remove_instance(name: &str) -> Result<Option<InstanceState>> {
     Ok(removed)
 }
 
-/// Get instance state by name (convenience function)
-pub fn get_instance(name: &str) -> Result<Option<InstanceState>> {
-    let state = State::load()?;
-    Ok(state.get_instance(name).cloned())
+/// Mark an instance as terminated, keeping its entry (convenience function)
+pub fn mark_instance_terminated(name: &str) -> Result<()> {
+    let mut state = State::load()?;
+    if state.mark_terminated(name) {
+        state.save()?;
+    }
+    Ok(())
+}
+
+/// Record a connection to an instance (convenience function)
+pub fn mark_instance_connected(name: &str) -> Result<()> {
+    let mut state = State::load()?;
+    if state.mark_connected(name) {
+        state.save()?;
+    }
+    Ok(())
 }
 
 /// List all instances (convenience function)
@@ -185,9 +339,14 @@ pub fn list_instances() -> Result<HashMap<String, InstanceState>> {
 }
 
 /// Get linked instance name from current directory
-/// Uses atomic read to avoid TOCTOU race conditions
 pub fn get_linked_instance() -> Result<Option<String>> {
-    let link_file = std::env::current_dir()?.join(".ec2-cli").join("instance");
+    read_link(&std::env::current_dir()?)
+}
+
+/// Get the instance name linked to `dir`
+/// Uses atomic read to avoid TOCTOU race conditions
+pub fn read_link(dir: &Path) -> Result<Option<String>> {
+    let link_file = dir.join(".ec2-cli").join("instance");
 
     // Check for symlink attack
     if link_file.is_symlink() {
@@ -211,19 +370,6 @@ pub fn get_linked_instance() -> Result<Option<String>> {
     }
 }
 
-/// Resolve instance name - use provided name or fall back to linked instance
-pub fn resolve_instance_name(name: Option<&str>) -> Result<String> {
-    if let Some(n) = name {
-        return Ok(n.to_string());
-    }
-
-    get_linked_instance()?.ok_or_else(|| {
-        Ec2CliError::InstanceNotFound(
-            "No instance name provided and no linked instance found".to_string(),
-        )
-    })
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -263,6 +409,95 @@ mod tests {
         assert!(state.get_instance("test-instance").is_none());
     }
 
+    #[test]
+    fn test_mark_terminated() {
+        let mut state = State::default();
+        state.add_instance(
+            "kept",
+            "i-123",
+            "default",
+            "us-east-1",
+            "ubuntu",
+            "sg-abc",
+            None,
+        );
+        assert!(state.get_instance("kept").unwrap().terminated_at.is_none());
+
+        assert!(state.mark_terminated("kept"));
+        assert!(state.get_instance("kept").unwrap().terminated_at.is_some());
+        assert!(!state.mark_terminated("missing"));
+    }
+
+    #[test]
+    fn test_terminated_at_defaults_to_none() {
+        let json = r#"{"instances":{"old":{"instance_id":"i-1","profile":"default","region":"us-east-1","created_at":"2024-01-01T00:00:00Z"}}}"#;
+        let state: State = serde_json::from_str(json).unwrap();
+        assert!(state.get_instance("old").unwrap().terminated_at.is_none());
+        assert!(!state.get_instance("old").unwrap().spot);
+        assert!(state.get_instance("old").unwrap().description.is_none());
+        assert!(state.get_instance("old").unwrap().security_group_managed);
+    }
+
+    #[test]
+    fn test_security_group_to_delete() {
+        let mut state = State::default();
+        state.add_instance(
+            "dev",
+            "i-123",
+            "default",
+            "us-east-1",
+            "ubuntu",
+            "sg-abc",
+            None,
+        );
+        let instance = state.instances.get_mut("dev").unwrap();
+        assert_eq!(instance.security_group_to_delete(), Some("sg-abc"));
+
+        // A user-supplied security group is left alone
+        instance.security_group_managed = false;
+        assert_eq!(instance.security_group_to_delete(), None);
+
+        instance.security_group_managed = true;
+        instance.security_group_id = None;
+        assert_eq!(instance.security_group_to_delete(), None);
+    }
+
+    #[test]
+    fn test_check_account() {
+        let mut state = State::default();
+        state.add_instance(
+            "dev",
+            "i-123",
+            "default",
+            "us-east-1",
+            "ubuntu",
+            "sg-abc",
+            None,
+        );
+        let instance = state.instances.get_mut("dev").unwrap();
+
+        // Entries from before accounts were recorded can't be checked
+        assert!(instance.check_account("111111111111").is_ok());
+
+        instance.account_id = Some("111111111111".to_string());
+        assert!(instance.check_account("111111111111").is_ok());
+        let err = instance.check_account("222222222222").unwrap_err();
+        assert!(matches!(err, Ec2CliError::AccountMismatch(ref msg)
+            if msg.contains("111111111111") && msg.contains("222222222222")));
+    }
+
+    #[test]
+    fn test_validate_description() {
+        assert!(InstanceState::validate_description("rust builds for PR #42").is_ok());
+        assert!(InstanceState::validate_description(&"x".repeat(MAX_DESCRIPTION_LEN)).is_ok());
+        // Length is counted in characters, not bytes
+        assert!(InstanceState::validate_description(&"é".repeat(MAX_DESCRIPTION_LEN)).is_ok());
+
+        assert!(InstanceState::validate_description(&"x".repeat(MAX_DESCRIPTION_LEN + 1)).is_err());
+        assert!(InstanceState::validate_description("  ").is_err());
+        assert!(InstanceState::validate_description("two\nlines").is_err());
+    }
+
     #[test]
     fn test_state_with_ubuntu_user() {
         let mut state = State::default();
@@ -280,4 +515,36 @@ mod tests {
         assert_eq!(instance.username, "ubuntu");
         assert_eq!(instance.ssh_key_path, None);
     }
+
+    #[test]
+    fn test_placement_round_trip() {
+        let mut state = State::default();
+        state.add_instance(
+            "dev",
+            "i-123",
+            "default",
+            "us-east-1",
+            "ubuntu",
+            "sg-abc",
+            None,
+        );
+        let entry = state.instances.get_mut("dev").unwrap();
+        entry.subnet_id = Some("subnet-0abc".to_string());
+        entry.availability_zone = Some("us-east-1b".to_string());
+        entry.init_log_path = Some("/var/log/setup.log".to_string());
+
+        let loaded: State = serde_json::from_str(&serde_json::to_string(&state).unwrap()).unwrap();
+        let entry = loaded.get_instance("dev").unwrap();
+        assert_eq!(entry.subnet_id.as_deref(), Some("subnet-0abc"));
+        assert_eq!(entry.availability_zone.as_deref(), Some("us-east-1b"));
+        assert_eq!(entry.init_log_path.as_deref(), Some("/var/log/setup.log"));
+
+        // Entries saved before placement was recorded still load
+        let json = r#"{"instances":{"old":{"instance_id":"i-1","profile":"default","region":"us-east-1","created_at":"2024-01-01T00:00:00Z"}}}"#;
+        let state: State = serde_json::from_str(json).unwrap();
+        let entry = state.get_instance("old").unwrap();
+        assert!(entry.subnet_id.is_none());
+        assert!(entry.availability_zone.is_none());
+        assert!(entry.init_log_path.is_none());
+    }
 }
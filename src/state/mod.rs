@@ -1,5 +1,8 @@
 mod local;
+mod resolver;
 
 pub use local::{
-    get_instance, list_instances, remove_instance, resolve_instance_name, save_instance, State,
+    list_instances, mark_instance_connected, mark_instance_terminated, read_link, remove_instance,
+    save_instance, update_instance, InstanceState, State,
 };
+pub use resolver::{InstanceResolver, ResolvedInstance};
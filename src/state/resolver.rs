@@ -0,0 +1,247 @@
+use chrono::Utc;
+
+use crate::config::Settings;
+use crate::{Ec2CliError, Result};
+
+use super::local::{get_linked_instance, InstanceState, State};
+
+/// An instance identified by name, link, or raw instance ID
+#[derive(Debug, Clone)]
+pub struct ResolvedInstance {
+    /// State name, or the instance ID for untracked instances
+    pub name: String,
+    pub state: InstanceState,
+    /// Whether the current directory is linked to this instance
+    pub linked: bool,
+    /// False for a raw instance ID that isn't recorded in state
+    pub tracked: bool,
+}
+
+/// Maps an optional name, the directory link, or a raw instance ID to an instance
+#[derive(Debug)]
+pub struct InstanceResolver {
+    state: State,
+    linked: Option<String>,
+    default_region: Option<String>,
+}
+
+impl InstanceResolver {
+    /// Load state, the current directory's link, and the default region
+    pub fn load() -> Result<Self> {
+        let default_region = Settings::load()
+            .ok()
+            .and_then(|s| s.region)
+            .or_else(|| std::env::var("AWS_REGION").ok())
+            .or_else(|| std::env::var("AWS_DEFAULT_REGION").ok());
+
+        Ok(Self {
+            state: State::load()?,
+            linked: get_linked_instance()?,
+            default_region,
+        })
+    }
+
+    /// Resolve `name` (a state name or `i-...` instance ID), falling back to the linked
+    /// instance when omitted
+    pub fn resolve(&self, name: Option<&str>) -> Result<ResolvedInstance> {
+        let name = match name {
+            Some(name) => name,
+            None => self
+                .linked
+                .as_deref()
+                .ok_or(Ec2CliError::NoInstanceSpecified)?,
+        };
+
+        if let Some(state) = self.state.get_instance(name) {
+            return Ok(self.resolved(name, state.clone(), true));
+        }
+
+        if !is_instance_id(name) {
+            return Err(Ec2CliError::InstanceNotFound(name.to_string()));
+        }
+
+        // An instance ID may belong to a tracked instance under another name
+        if let Some((tracked_name, state)) = self
+            .state
+            .instances
+            .iter()
+            .find(|(_, state)| state.instance_id == name)
+        {
+            return Ok(self.resolved(tracked_name, state.clone(), true));
+        }
+
+        let region = self.default_region.clone().ok_or_else(|| {
+            Ec2CliError::Config(format!(
+                "Instance {} is not in state and no default region is set. \
+                 Set AWS_REGION or run 'ec2-cli config init'",
+                name
+            ))
+        })?;
+
+        let state = InstanceState {
+            instance_id: name.to_string(),
+            profile: "unknown".to_string(),
+            region,
+            created_at: Utc::now(),
+            username: "ubuntu".to_string(),
+            security_group_id: None,
+            security_group_managed: true,
+            ssh_key_path: None,
+            terminated_at: None,
+            spot: false,
+            instance_type: None,
+            root_volume_gb: None,
+            private_ip: None,
+            last_connected_at: None,
+            description: None,
+            account_id: None,
+            subnet_id: None,
+            availability_zone: None,
+            init_log_path: None,
+        };
+        Ok(self.resolved(name, state, false))
+    }
+
+    /// Every tracked instance that hasn't been terminated, sorted by name
+    pub fn resolve_all(&self) -> Vec<ResolvedInstance> {
+        let mut names: Vec<_> = self
+            .state
+            .instances
+            .iter()
+            .filter(|(_, state)| state.terminated_at.is_none())
+            .map(|(name, _)| name)
+            .collect();
+        names.sort();
+
+        names
+            .into_iter()
+            .map(|name| self.resolved(name, self.state.instances[name].clone(), true))
+            .collect()
+    }
+
+    fn resolved(&self, name: &str, state: InstanceState, tracked: bool) -> ResolvedInstance {
+        ResolvedInstance {
+            name: name.to_string(),
+            linked: self.linked.as_deref() == Some(name),
+            state,
+            tracked,
+        }
+    }
+}
+
+/// Check for an EC2 instance ID (`i-` followed by 8 or 17 hex digits)
+fn is_instance_id(s: &str) -> bool {
+    s.strip_prefix("i-").is_some_and(|id| {
+        (id.len() == 8 || id.len() == 17) && id.chars().all(|c| c.is_ascii_hexdigit())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resolver(linked: Option<&str>, default_region: Option<&str>) -> InstanceResolver {
+        let mut state = State::default();
+        state.add_instance(
+            "dev",
+            "i-0123456789abcdef0",
+            "default",
+            "us-west-2",
+            "ubuntu",
+            "sg-1",
+            None,
+        );
+        state.add_instance(
+            "build",
+            "i-12345678",
+            "default",
+            "eu-west-1",
+            "ubuntu",
+            "sg-2",
+            None,
+        );
+
+        InstanceResolver {
+            state,
+            linked: linked.map(String::from),
+            default_region: default_region.map(String::from),
+        }
+    }
+
+    #[test]
+    fn test_resolve_by_name() {
+        let resolved = resolver(None, None).resolve(Some("dev")).unwrap();
+        assert_eq!(resolved.name, "dev");
+        assert_eq!(resolved.state.instance_id, "i-0123456789abcdef0");
+        assert!(resolved.tracked);
+        assert!(!resolved.linked);
+
+        let err = resolver(None, None).resolve(Some("missing")).unwrap_err();
+        assert!(matches!(err, Ec2CliError::InstanceNotFound(name) if name == "missing"));
+    }
+
+    #[test]
+    fn test_resolve_from_link() {
+        let resolved = resolver(Some("build"), None).resolve(None).unwrap();
+        assert_eq!(resolved.name, "build");
+        assert!(resolved.linked);
+
+        // An explicit name for the linked instance is still reported as linked
+        let resolved = resolver(Some("build"), None)
+            .resolve(Some("build"))
+            .unwrap();
+        assert!(resolved.linked);
+
+        let err = resolver(None, None).resolve(None).unwrap_err();
+        assert!(matches!(err, Ec2CliError::NoInstanceSpecified));
+    }
+
+    #[test]
+    fn test_resolve_tracked_instance_id() {
+        let resolved = resolver(None, None)
+            .resolve(Some("i-0123456789abcdef0"))
+            .unwrap();
+        assert_eq!(resolved.name, "dev");
+        assert_eq!(resolved.state.region, "us-west-2");
+        assert!(resolved.tracked);
+    }
+
+    #[test]
+    fn test_resolve_untracked_instance_id() {
+        let resolved = resolver(None, Some("us-east-1"))
+            .resolve(Some("i-0fedcba9876543210"))
+            .unwrap();
+        assert_eq!(resolved.name, "i-0fedcba9876543210");
+        assert_eq!(resolved.state.instance_id, "i-0fedcba9876543210");
+        assert_eq!(resolved.state.region, "us-east-1");
+        assert!(!resolved.tracked);
+
+        // Without a default region there is nowhere to look
+        assert!(resolver(None, None)
+            .resolve(Some("i-0fedcba9876543210"))
+            .is_err());
+    }
+
+    #[test]
+    fn test_resolve_all_skips_terminated() {
+        let mut resolver = resolver(Some("dev"), None);
+        resolver
+            .state
+            .add_instance("old", "i-3", "default", "us-east-1", "ubuntu", "sg-3", None);
+        resolver.state.mark_terminated("old");
+
+        let all = resolver.resolve_all();
+        let names: Vec<_> = all.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(names, vec!["build", "dev"]);
+        assert!(all[1].linked);
+    }
+
+    #[test]
+    fn test_is_instance_id() {
+        assert!(is_instance_id("i-12345678"));
+        assert!(is_instance_id("i-0123456789abcdef0"));
+        assert!(!is_instance_id("i-123"));
+        assert!(!is_instance_id("i-0123456789abcdefg"));
+        assert!(!is_instance_id("dev"));
+    }
+}
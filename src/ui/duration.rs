@@ -0,0 +1,113 @@
+use chrono::{DateTime, Duration, Utc};
+
+/// Format a duration compactly using its two largest units, e.g. "2d 3h" or "45m"
+pub fn format_duration(duration: Duration) -> String {
+    let minutes = duration.num_minutes().max(0);
+    let (days, hours, minutes) = (minutes / 1440, minutes / 60 % 24, minutes % 60);
+
+    if days > 0 {
+        format!("{}d {}h", days, hours)
+    } else if hours > 0 {
+        format!("{}h {}m", hours, minutes)
+    } else {
+        format!("{}m", minutes)
+    }
+}
+
+/// Parse a duration such as "90s", "30m", "24h", "7d", or "2w"
+pub fn parse_duration(value: &str) -> Option<Duration> {
+    let split = value.find(|c: char| !c.is_ascii_digit())?;
+    let (count, unit) = value.split_at(split);
+    let count: i64 = count.parse().ok()?;
+    let seconds_per_unit = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 3600,
+        "d" => 86_400,
+        "w" => 604_800,
+        _ => return None,
+    };
+    Duration::try_seconds(count.checked_mul(seconds_per_unit)?)
+}
+
+/// Parse a point in time for options like `--since`: a duration before now (see
+/// `parse_duration`) or an RFC 3339 timestamp such as "2024-05-01T12:00:00Z".
+/// The error is worded for clap to show.
+pub fn parse_time(value: &str) -> std::result::Result<DateTime<Utc>, String> {
+    parse_time_at(value, Utc::now())
+}
+
+fn parse_time_at(value: &str, now: DateTime<Utc>) -> std::result::Result<DateTime<Utc>, String> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(value) {
+        return Ok(time.with_timezone(&Utc));
+    }
+    parse_duration(value)
+        .and_then(|ago| now.checked_sub_signed(ago))
+        .ok_or_else(|| {
+            "expected a duration such as 24h or 7d, or an RFC 3339 timestamp such as \
+             2024-05-01T12:00:00Z"
+                .to_string()
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(Duration::seconds(30)), "0m");
+        assert_eq!(format_duration(Duration::minutes(45)), "45m");
+        assert_eq!(format_duration(Duration::minutes(192)), "3h 12m");
+        assert_eq!(format_duration(Duration::hours(51)), "2d 3h");
+    }
+
+    #[test]
+    fn test_format_negative_duration() {
+        // Clock skew between machines shouldn't produce a negative uptime
+        assert_eq!(format_duration(Duration::minutes(-5)), "0m");
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("90s"), Some(Duration::seconds(90)));
+        assert_eq!(parse_duration("30m"), Some(Duration::minutes(30)));
+        assert_eq!(parse_duration("24h"), Some(Duration::hours(24)));
+        assert_eq!(parse_duration("7d"), Some(Duration::days(7)));
+        assert_eq!(parse_duration("2w"), Some(Duration::weeks(2)));
+        assert_eq!(parse_duration("0h"), Some(Duration::zero()));
+
+        for invalid in [
+            "",
+            "24",
+            "h",
+            "-1h",
+            "1.5h",
+            "24 h",
+            "24hours",
+            "1y",
+            "99999999999999w",
+        ] {
+            assert_eq!(parse_duration(invalid), None, "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_parse_time() {
+        let now = DateTime::parse_from_rfc3339("2024-05-10T12:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+
+        assert_eq!(
+            parse_time_at("24h", now).unwrap(),
+            now - Duration::hours(24)
+        );
+        // Offsets are converted to UTC
+        assert_eq!(
+            parse_time_at("2024-05-01T14:00:00+02:00", now).unwrap(),
+            DateTime::parse_from_rfc3339("2024-05-01T12:00:00Z").unwrap()
+        );
+        assert!(parse_time_at("yesterday", now).is_err());
+        assert!(parse_time_at("2024-05-01", now).is_err());
+    }
+}
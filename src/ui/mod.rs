@@ -1,3 +1,10 @@
+mod duration;
+mod output;
+mod progress;
 mod spinner;
 
-pub use spinner::create_spinner;
+pub use duration::{format_duration, parse_time};
+pub(crate) use output::info;
+pub use output::{is_quiet, set_quiet};
+pub use progress::Progress;
+pub use spinner::{create_spinner, create_timed_spinner};
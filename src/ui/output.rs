@@ -0,0 +1,27 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Set by the global `--quiet` flag
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+/// Suppress informational output for the rest of the process
+pub fn set_quiet(quiet: bool) {
+    QUIET.store(quiet, Ordering::Relaxed);
+}
+
+pub fn is_quiet() -> bool {
+    QUIET.load(Ordering::Relaxed)
+}
+
+/// `println!` for progress and informational messages, silenced by `--quiet`.
+///
+/// Use plain `println!` for output the user asked for (tables, status, JSON)
+/// and `eprintln!` for warnings and errors, which are always shown.
+macro_rules! info {
+    ($($arg:tt)*) => {
+        if !$crate::ui::is_quiet() {
+            println!($($arg)*);
+        }
+    };
+}
+
+pub(crate) use info;
@@ -0,0 +1,92 @@
+use std::io::Write;
+
+use indicatif::ProgressBar;
+use serde::Serialize;
+
+use super::create_spinner;
+use crate::Result;
+
+/// Where progress is reported
+enum Sink {
+    /// A spinner for the step in progress, for a person at a terminal
+    Spinner(Option<ProgressBar>),
+    /// One JSON object per line, for programs driving the CLI
+    Json(Box<dyn Write + Send>),
+}
+
+/// Progress of a multi-step command, shown as spinners or written as JSON events.
+///
+/// Spinners show the step messages; the JSON stream only carries the events passed
+/// to [`Progress::finish_with_event`].
+pub struct Progress {
+    sink: Sink,
+}
+
+impl Progress {
+    /// Report progress with spinners (hidden under `--quiet`)
+    pub fn spinners() -> Self {
+        Self {
+            sink: Sink::Spinner(None),
+        }
+    }
+
+    /// Report progress as JSON events on stdout
+    pub fn json() -> Self {
+        Self::json_to(std::io::stdout())
+    }
+
+    /// Report progress as JSON events written to `writer`
+    pub fn json_to(writer: impl Write + Send + 'static) -> Self {
+        Self {
+            sink: Sink::Json(Box::new(writer)),
+        }
+    }
+
+    /// Start a step, replacing any spinner still showing
+    pub fn start(&mut self, message: impl Into<String>) {
+        if let Sink::Spinner(current) = &mut self.sink {
+            if let Some(spinner) = current.take() {
+                spinner.finish_and_clear();
+            }
+            *current = Some(create_spinner(message));
+        }
+    }
+
+    /// Finish the current step, leaving `message` on screen
+    pub fn finish(&mut self, message: impl Into<String>) {
+        if let Sink::Spinner(current) = &mut self.sink {
+            if let Some(spinner) = current.take() {
+                spinner.finish_with_message(message.into());
+            }
+        }
+    }
+
+    /// Finish the current step and report `event`
+    pub fn finish_with_event(
+        &mut self,
+        message: impl Into<String>,
+        event: &impl Serialize,
+    ) -> Result<()> {
+        self.finish(message);
+        self.event(event)
+    }
+
+    /// Report `event` on its own; spinners have nothing to show for it
+    pub fn event(&mut self, event: &impl Serialize) -> Result<()> {
+        if let Sink::Json(out) = &mut self.sink {
+            serde_json::to_writer(&mut *out, event)?;
+            writeln!(out)?;
+            out.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Finish the current step without a message, e.g. when it failed
+    pub fn clear(&mut self) {
+        if let Sink::Spinner(current) = &mut self.sink {
+            if let Some(spinner) = current.take() {
+                spinner.finish_and_clear();
+            }
+        }
+    }
+}
@@ -1,13 +1,25 @@
 use indicatif::{ProgressBar, ProgressStyle};
 use std::time::Duration;
 
+use super::is_quiet;
+
+/// Start a spinner with `message`; under `--quiet` the spinner is hidden
 pub fn create_spinner(message: impl Into<String>) -> ProgressBar {
+    spinner_with_template(message, "{spinner:.green} {msg}")
+}
+
+/// Like `create_spinner`, also showing how long the step has been running
+pub fn create_timed_spinner(message: impl Into<String>) -> ProgressBar {
+    spinner_with_template(message, "{spinner:.green} {msg} [{elapsed}]")
+}
+
+fn spinner_with_template(message: impl Into<String>, template: &str) -> ProgressBar {
+    if is_quiet() {
+        return ProgressBar::hidden();
+    }
+
     let spinner = ProgressBar::new_spinner();
-    spinner.set_style(
-        ProgressStyle::default_spinner()
-            .template("{spinner:.green} {msg}")
-            .unwrap(),
-    );
+    spinner.set_style(ProgressStyle::default_spinner().template(template).unwrap());
     spinner.set_message(message.into());
     spinner.enable_steady_tick(Duration::from_millis(100));
     spinner
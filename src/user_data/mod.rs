@@ -1,3 +1,28 @@
 mod generator;
 
-pub use generator::{generate_user_data, validate_project_name};
+use crate::config::Settings;
+use crate::Result;
+
+pub use generator::{
+    generate_user_data, validate_env_key, validate_project_name, validate_shell_safe,
+};
+
+/// Where the user data script logs its output when `init_log_path` isn't set
+pub const DEFAULT_INIT_LOG_PATH: &str = "/var/log/ec2-cli-init.log";
+
+/// Marker file in the user's home created once the bare repo accepts pushes
+pub const GIT_READY_MARKER: &str = ".ec2-cli-git-ready";
+
+/// Marker file in the user's home created when every setup step has finished
+pub const READY_MARKER: &str = ".ec2-cli-ready";
+
+/// The init log path configured in settings, or the default
+pub fn init_log_path(settings: &Settings) -> Result<&str> {
+    match settings.init_log_path {
+        Some(ref path) => {
+            Settings::validate_init_log_path(path)?;
+            Ok(path)
+        }
+        None => Ok(DEFAULT_INIT_LOG_PATH),
+    }
+}
@@ -0,0 +1,70 @@
+use std::path::{Path, PathBuf};
+
+use crate::{Ec2CliError, Result};
+
+/// Expand a leading `~` in a path that comes from configuration, e.g. `$EDITOR`.
+///
+/// Paths given on the command line were already expanded by the shell and are
+/// used as they are. `$` is never expanded, and `~user` forms are rejected rather
+/// than resolved.
+pub fn expand_path(path: &str) -> Result<PathBuf> {
+    let home = directories::BaseDirs::new().map(|dirs| dirs.home_dir().to_path_buf());
+    expand_path_with(path, home.as_deref())
+}
+
+fn expand_path_with(path: &str, home: Option<&Path>) -> Result<PathBuf> {
+    let Some(rest) = path.strip_prefix('~') else {
+        return Ok(PathBuf::from(path));
+    };
+
+    if !rest.is_empty() && !rest.starts_with('/') {
+        return Err(Ec2CliError::InvalidPath(format!(
+            "'{}': ~user paths are not supported, use an absolute path",
+            path
+        )));
+    }
+
+    let home = home.ok_or_else(|| {
+        Ec2CliError::InvalidPath(format!("'{}': cannot determine home directory", path))
+    })?;
+
+    Ok(home.join(rest.trim_start_matches('/')))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expand(path: &str) -> Result<PathBuf> {
+        expand_path_with(path, Some(Path::new("/home/dev")))
+    }
+
+    #[test]
+    fn test_expand_tilde() {
+        assert_eq!(expand("~").unwrap(), PathBuf::from("/home/dev"));
+        assert_eq!(
+            expand("~/.ssh/id_ed25519").unwrap(),
+            PathBuf::from("/home/dev/.ssh/id_ed25519")
+        );
+        // Only a leading ~ is special
+        assert_eq!(expand("a/~/b").unwrap(), PathBuf::from("a/~/b"));
+    }
+
+    #[test]
+    fn test_expand_tilde_user_rejected() {
+        assert!(expand("~root/.ssh").is_err());
+    }
+
+    #[test]
+    fn test_env_vars_not_expanded() {
+        assert_eq!(expand("$HOME/src").unwrap(), PathBuf::from("$HOME/src"));
+        assert_eq!(expand("${UNSET}/x").unwrap(), PathBuf::from("${UNSET}/x"));
+    }
+
+    #[test]
+    fn test_absolute_and_relative_paths_untouched() {
+        assert_eq!(expand("/etc/hosts").unwrap(), PathBuf::from("/etc/hosts"));
+        assert_eq!(expand("./file.txt").unwrap(), PathBuf::from("./file.txt"));
+        assert_eq!(expand("price$").unwrap(), PathBuf::from("price$"));
+    }
+}